@@ -11,40 +11,48 @@
 
 use std::time::Duration;
 
+#[cfg(feature = "async")]
+use futures_util::StreamExt;
 use reqwest::header::CONTENT_TYPE;
+#[cfg(feature = "async")]
+use tokio::io::{AsyncWrite, AsyncWriteExt};
 
 use crate::{client::Client, core::session::URLPart};
 
 impl Client {
     #[cfg(feature = "async")]
     pub async fn download(&self, blob_id: &str) -> crate::Result<Vec<u8>> {
-        let account_id = self.default_account_id();
-        let mut download_url = String::with_capacity(
-            self.session().download_url().len() + account_id.len() + blob_id.len(),
-        );
+        self.download_response(blob_id)
+            .await?
+            .bytes()
+            .await
+            .map(|bytes| bytes.to_vec())
+            .map_err(|err| err.into())
+    }
 
-        for part in self.download_url() {
-            match part {
-                URLPart::Value(value) => {
-                    download_url.push_str(value);
-                }
-                URLPart::Parameter(param) => match param {
-                    super::URLParameter::AccountId => {
-                        download_url.push_str(account_id);
-                    }
-                    super::URLParameter::BlobId => {
-                        download_url.push_str(blob_id);
-                    }
-                    super::URLParameter::Name => {
-                        download_url.push_str("none");
-                    }
-                    super::URLParameter::Type => {
-                        download_url.push_str("application/octet-stream");
-                    }
-                },
-            }
+    /// Download a blob into `writer` as it arrives, rather than buffering it in memory.
+    ///
+    /// Returns the number of bytes written. The writer is flushed, but not shut down.
+    #[cfg(feature = "async")]
+    pub async fn download_to(
+        &self,
+        blob_id: &str,
+        mut writer: impl AsyncWrite + Unpin,
+    ) -> crate::Result<u64> {
+        let mut stream = self.download_response(blob_id).await?.bytes_stream();
+        let mut written = 0;
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            writer.write_all(&chunk).await?;
+            written += chunk.len() as u64;
         }
+        writer.flush().await?;
+        Ok(written)
+    }
 
+    #[cfg(feature = "async")]
+    async fn download_response(&self, blob_id: &str) -> crate::Result<reqwest::Response> {
+        let download_url = self.blob_download_url(blob_id);
         let mut headers = self.headers().clone();
         headers.remove(CONTENT_TYPE);
 
@@ -58,15 +66,31 @@ impl Client {
                 .send()
                 .await?,
         )
-        .await?
-        .bytes()
         .await
-        .map(|bytes| bytes.to_vec())
-        .map_err(|err| err.into())
     }
 
     #[cfg(feature = "blocking")]
     pub fn download(&self, blob_id: &str) -> crate::Result<Vec<u8>> {
+        let download_url = self.blob_download_url(blob_id);
+
+        let mut headers = self.headers().clone();
+        headers.remove(CONTENT_TYPE);
+
+        Client::handle_error(
+            reqwest::blocking::Client::builder()
+                .timeout(Duration::from_millis(self.timeout()))
+                .redirect(self.redirect_policy())
+                .default_headers(headers)
+                .build()?
+                .get(download_url)
+                .send()?,
+        )?
+        .bytes()
+        .map(|bytes| bytes.to_vec())
+        .map_err(|err| err.into())
+    }
+
+    fn blob_download_url(&self, blob_id: &str) -> String {
         let account_id = self.default_account_id();
         let mut download_url = String::with_capacity(
             self.session().download_url().len() + account_id.len() + blob_id.len(),
@@ -94,20 +118,6 @@ impl Client {
             }
         }
 
-        let mut headers = self.headers().clone();
-        headers.remove(CONTENT_TYPE);
-
-        Client::handle_error(
-            reqwest::blocking::Client::builder()
-                .timeout(Duration::from_millis(self.timeout()))
-                .redirect(self.redirect_policy())
-                .default_headers(headers)
-                .build()?
-                .get(download_url)
-                .send()?,
-        )?
-        .bytes()
-        .map(|bytes| bytes.to_vec())
-        .map_err(|err| err.into())
+        download_url
     }
 }
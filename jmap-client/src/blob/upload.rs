@@ -9,13 +9,23 @@
  * except according to those terms.
  */
 
+#[cfg(feature = "async")]
+use std::path::Path;
 use std::time::Duration;
 
+#[cfg(feature = "async")]
+use reqwest::header::CONTENT_LENGTH;
 use reqwest::header::CONTENT_TYPE;
 use serde::Deserialize;
+#[cfg(feature = "async")]
+use tokio::io::{AsyncRead, AsyncReadExt};
 
 use crate::{client::Client, core::session::URLPart};
 
+/// How much of a streamed upload to read at a time.
+#[cfg(feature = "async")]
+const UPLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
 #[derive(Debug, Deserialize)]
 pub struct UploadResponse {
     #[serde(rename = "accountId")]
@@ -39,22 +49,73 @@ impl Client {
         blob: Vec<u8>,
         content_type: Option<&str>,
     ) -> crate::Result<UploadResponse> {
-        let account_id = account_id.unwrap_or_else(|| self.default_account_id());
-        let mut upload_url =
-            String::with_capacity(self.session().upload_url().len() + account_id.len());
+        let upload_url = self.blob_upload_url(account_id);
+
+        // Uploading a blob again at worst leaves an unused copy behind, so it's safe to retry.
+        let body = self
+            .with_retries(
+                true,
+                |_| false,
+                || self.post_upload(&upload_url, &blob, content_type),
+            )
+            .await?;
+        serde_json::from_slice::<UploadResponse>(&body)
+        .map_err(|err| err.into())
+    }
 
-        for part in self.upload_url() {
-            match part {
-                URLPart::Value(value) => {
-                    upload_url.push_str(value);
-                }
-                URLPart::Parameter(param) => {
-                    if let super::URLParameter::AccountId = param {
-                        upload_url.push_str(account_id);
-                    }
-                }
-            }
+    #[cfg(feature = "async")]
+    async fn post_upload(
+        &self,
+        upload_url: &str,
+        blob: &[u8],
+        content_type: Option<&str>,
+    ) -> (crate::Result<Vec<u8>>, Option<Duration>) {
+        let mut retry_after = None;
+        let result = async {
+            let response = reqwest::Client::builder()
+                .timeout(Duration::from_millis(self.timeout()))
+                .redirect(self.redirect_policy())
+                .default_headers(self.headers().clone())
+                .build()?
+                .post(upload_url)
+                .header(
+                    CONTENT_TYPE,
+                    content_type.unwrap_or("application/octet-stream"),
+                )
+                .body(blob.to_vec())
+                .send()
+                .await?;
+            retry_after = crate::retry::retry_after(response.headers());
+            Ok::<_, crate::Error>(Client::handle_error(response).await?.bytes().await?.to_vec())
         }
+        .await;
+        (result, retry_after)
+    }
+
+    /// Upload `length` bytes from `reader` as they're read, rather than buffering them in
+    /// memory.
+    ///
+    /// Unlike [`Client::upload`], failed uploads aren't retried, since the reader can't be
+    /// rewound. The client's timeout applies to the whole upload.
+    #[cfg(feature = "async")]
+    pub async fn upload_from(
+        &self,
+        account_id: Option<&str>,
+        reader: impl AsyncRead + Send + Sync + Unpin + 'static,
+        length: u64,
+        content_type: Option<&str>,
+    ) -> crate::Result<UploadResponse> {
+        let upload_url = self.blob_upload_url(account_id);
+        let chunks = futures_util::stream::try_unfold(reader, |mut reader| async move {
+            let mut chunk = vec![0; UPLOAD_CHUNK_SIZE];
+            let read = reader.read(&mut chunk).await?;
+            if read == 0 {
+                Ok::<_, std::io::Error>(None)
+            } else {
+                chunk.truncate(read);
+                Ok(Some((chunk, reader)))
+            }
+        });
 
         serde_json::from_slice::<UploadResponse>(
             &Client::handle_error(
@@ -68,7 +129,8 @@ impl Client {
                         CONTENT_TYPE,
                         content_type.unwrap_or("application/octet-stream"),
                     )
-                    .body(blob)
+                    .header(CONTENT_LENGTH, length)
+                    .body(reqwest::Body::wrap_stream(chunks))
                     .send()
                     .await?,
             )
@@ -79,6 +141,21 @@ impl Client {
         .map_err(|err| err.into())
     }
 
+    /// Upload the file at `path` without reading it all into memory first; see
+    /// [`Client::upload_from`].
+    #[cfg(feature = "async")]
+    pub async fn upload_file(
+        &self,
+        account_id: Option<&str>,
+        path: impl AsRef<Path>,
+        content_type: Option<&str>,
+    ) -> crate::Result<UploadResponse> {
+        let file = tokio::fs::File::open(path).await?;
+        let length = file.metadata().await?.len();
+        self.upload_from(account_id, file, length, content_type)
+            .await
+    }
+
     #[cfg(feature = "blocking")]
     pub fn upload(
         &self,
@@ -86,22 +163,7 @@ impl Client {
         blob: Vec<u8>,
         content_type: Option<&str>,
     ) -> crate::Result<UploadResponse> {
-        let account_id = account_id.unwrap_or_else(|| self.default_account_id());
-        let mut upload_url =
-            String::with_capacity(self.session().upload_url().len() + account_id.len());
-
-        for part in self.upload_url() {
-            match part {
-                URLPart::Value(value) => {
-                    upload_url.push_str(value);
-                }
-                URLPart::Parameter(param) => {
-                    if let super::URLParameter::AccountId = param {
-                        upload_url.push_str(account_id);
-                    }
-                }
-            }
-        }
+        let upload_url = self.blob_upload_url(account_id);
 
         serde_json::from_slice::<UploadResponse>(
             &Client::handle_error(
@@ -122,6 +184,27 @@ impl Client {
         )
         .map_err(|err| err.into())
     }
+
+    fn blob_upload_url(&self, account_id: Option<&str>) -> String {
+        let account_id = account_id.unwrap_or_else(|| self.default_account_id());
+        let mut upload_url =
+            String::with_capacity(self.session().upload_url().len() + account_id.len());
+
+        for part in self.upload_url() {
+            match part {
+                URLPart::Value(value) => {
+                    upload_url.push_str(value);
+                }
+                URLPart::Parameter(param) => {
+                    if let super::URLParameter::AccountId = param {
+                        upload_url.push_str(account_id);
+                    }
+                }
+            }
+        }
+
+        upload_url
+    }
 }
 
 impl UploadResponse {
@@ -18,6 +18,9 @@ use std::{
     time::Duration,
 };
 
+#[cfg(feature = "async")]
+use std::future::Future;
+
 use ahash::AHashSet;
 use reqwest::{
     header::{self},
@@ -32,6 +35,7 @@ use crate::{
         response,
         session::{Session, URLPart},
     },
+    retry::{self, RetryPolicy},
     Error,
 };
 
@@ -59,6 +63,7 @@ pub struct Client {
     headers: header::HeaderMap,
     default_account_id: String,
     timeout: u64,
+    retry: RetryPolicy,
 
     #[cfg(feature = "websockets")]
     pub(crate) authorization: String,
@@ -71,6 +76,7 @@ pub struct ClientBuilder {
     trusted_hosts: AHashSet<String>,
     forwarded_for: Option<String>,
     timeout: u64,
+    retry: RetryPolicy,
 }
 
 impl Default for ClientBuilder {
@@ -86,6 +92,7 @@ impl ClientBuilder {
             trusted_hosts: AHashSet::new(),
             timeout: DEFAULT_TIMEOUT_MS,
             forwarded_for: None,
+            retry: RetryPolicy::default(),
         }
     }
 
@@ -99,6 +106,11 @@ impl ClientBuilder {
         self
     }
 
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
     pub fn follow_redirects(
         mut self,
         trusted_hosts: impl IntoIterator<Item = impl Into<String>>,
@@ -202,6 +214,7 @@ impl ClientBuilder {
             #[cfg(feature = "websockets")]
             authorization,
             timeout: self.timeout,
+            retry: self.retry,
             headers,
             default_account_id,
             #[cfg(feature = "websockets")]
@@ -286,6 +299,7 @@ impl ClientBuilder {
             #[cfg(feature = "websockets")]
             authorization,
             timeout: self.timeout,
+            retry: self.retry,
             headers,
             default_account_id,
             #[cfg(feature = "websockets")]
@@ -317,6 +331,15 @@ impl Client {
         self.timeout
     }
 
+    pub fn set_retry(&mut self, retry: RetryPolicy) -> &mut Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn retry(&self) -> &RetryPolicy {
+        &self.retry
+    }
+
     pub fn session(&self) -> Arc<Session> {
         self.session.lock().clone()
     }
@@ -365,19 +388,17 @@ impl Client {
     {
         let body = serde_json::to_string(&request)?;
         tracing::debug!(%body, "Sending request");
-        let response = Client::handle_error(
-            reqwest::Client::builder()
-                .redirect(self.redirect_policy())
-                .timeout(Duration::from_millis(self.timeout))
-                .default_headers(self.headers.clone())
-                .build()?
-                .post(&self.api_url)
-                .body(body)
-                .send()
-                .await?,
-        )
-        .await?;
-        let text = response.text().await?;
+        let idempotent = request
+            .method_calls
+            .iter()
+            .all(|(method, _, _)| retry::is_idempotent(*method));
+        let text = self
+            .with_retries(
+                idempotent,
+                |text: &String| retry::is_transient_method_error(text),
+                || self.post_api(&body),
+            )
+            .await?;
         tracing::debug!(response = %text, "Got response");
         let response: response::Response<R> = serde_json::from_slice(text.as_bytes())?;
 
@@ -388,6 +409,68 @@ impl Client {
         Ok(response)
     }
 
+    #[cfg(feature = "async")]
+    async fn post_api(&self, body: &str) -> (crate::Result<String>, Option<Duration>) {
+        let mut retry_after = None;
+        let result = async {
+            let response = reqwest::Client::builder()
+                .redirect(self.redirect_policy())
+                .timeout(Duration::from_millis(self.timeout))
+                .default_headers(self.headers.clone())
+                .build()?
+                .post(&self.api_url)
+                .body(body.to_string())
+                .send()
+                .await?;
+            retry_after = retry::retry_after(response.headers());
+            Ok::<_, Error>(Client::handle_error(response).await?.text().await?)
+        }
+        .await;
+        (result, retry_after)
+    }
+
+    /// Make a request with `attempt` until it succeeds, fails for a reason that isn't
+    /// transient, or the [`RetryPolicy`] gives up. `attempt` returns the result and the
+    /// `Retry-After` header, if any.
+    ///
+    /// Successful responses are retried if `is_transient` returns `true`, and returned as-is
+    /// once the retries run out. Unless the request is `idempotent`, errors are only retried
+    /// if the server certainly didn't process the request; see
+    /// [`retry::is_transient_error`].
+    #[cfg(feature = "async")]
+    pub(crate) async fn with_retries<T, F, Fut>(
+        &self,
+        idempotent: bool,
+        is_transient: impl Fn(&T) -> bool,
+        mut attempt: F,
+    ) -> crate::Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = (crate::Result<T>, Option<Duration>)>,
+    {
+        let mut retries = 0;
+        loop {
+            let (result, retry_after) = attempt().await;
+            let transient = match &result {
+                Ok(value) => is_transient(value),
+                Err(err) => retry::is_transient_error(err, idempotent),
+            };
+            match self.retry.delay(retries, retry_after) {
+                Some(delay) if transient => {
+                    tracing::debug!(
+                        retries,
+                        ?delay,
+                        error = ?result.as_ref().err(),
+                        "Retrying transient failure"
+                    );
+                    tokio::time::sleep(delay).await;
+                    retries += 1;
+                }
+                _ => return result,
+            }
+        }
+    }
+
     #[cfg(feature = "blocking")]
     pub fn send<R>(&self, request: &request::Request<'_>) -> crate::Result<response::Response<R>>
     where
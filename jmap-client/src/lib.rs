@@ -191,6 +191,7 @@ pub mod identity;
 pub mod mailbox;
 pub mod principal;
 pub mod push_subscription;
+pub mod retry;
 pub mod thread;
 pub mod vacation_response;
 
@@ -356,6 +357,7 @@ pub enum Error {
     Server(String),
     Method(MethodError),
     Set(SetError<String>),
+    Io(std::io::Error),
     #[cfg(feature = "websockets")]
     WebSocket(tokio_tungstenite::tungstenite::error::Error),
 }
@@ -390,6 +392,12 @@ impl From<SetError<String>> for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
 impl From<&str> for Error {
     fn from(s: &str) -> Self {
         Error::Internal(s.to_string())
@@ -413,6 +421,7 @@ impl Display for Error {
             Error::Server(e) => write!(f, "Server failed: {}", e),
             Error::Method(e) => write!(f, "Request failed: {}", e),
             Error::Set(e) => write!(f, "Set failed: {}", e),
+            Error::Io(e) => write!(f, "I/O error: {}", e),
             #[cfg(feature = "websockets")]
             Error::WebSocket(e) => write!(f, "WebSockets error: {}", e),
         }
@@ -225,6 +225,26 @@ impl Request<'_> {
         self.send_single().await
     }
 
+    /// Query mailboxes and fetch the matching ones in the same request, as two method calls
+    /// where the `Mailbox/get` refers back to the `Mailbox/query`'s IDs.
+    ///
+    /// The responses are the next two from [`Request::send`], in that order.
+    pub fn query_and_get_mailbox(
+        &mut self,
+        filter: Option<impl Into<Filter<super::query::Filter>>>,
+        sort: Option<impl IntoIterator<Item = Comparator<super::query::Comparator>>>,
+    ) -> &mut GetRequest<Mailbox<Set>> {
+        let query_request = self.query_mailbox();
+        if let Some(filter) = filter {
+            query_request.filter(filter);
+        }
+        if let Some(sort) = sort {
+            query_request.sort(sort.into_iter());
+        }
+        let ids = query_request.result_reference();
+        self.get_mailbox().ids_ref(ids)
+    }
+
     pub fn query_mailbox_changes(
         &mut self,
         since_query_state: impl Into<String>,
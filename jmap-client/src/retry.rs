@@ -0,0 +1,254 @@
+/*
+ * Copyright Stalwart Labs Ltd. See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+//! Retrying requests which fail for transient reasons, like a timeout, a `503 Service
+//! Unavailable`, or a `serverUnavailable` method error.
+//!
+//! A timeout doesn't mean the server didn't get the request, so requests which change
+//! something, like `Email/import` or `EmailSubmission/set`, are only retried when they
+//! certainly weren't processed. Otherwise, retrying could send an email twice.
+
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+
+use crate::{Error, Method};
+
+/// HTTP statuses worth retrying. The server rejected the request without processing it.
+const TRANSIENT_STATUSES: [u16; 2] = [429, 503];
+
+/// HTTP statuses worth retrying if the request is idempotent. These come from a gateway, so
+/// the server might have processed the request anyway.
+const TRANSIENT_GATEWAY_STATUSES: [u16; 2] = [502, 504];
+
+/// Method error types worth retrying.
+const TRANSIENT_METHOD_ERRORS: [&str; 2] = ["serverUnavailable", "rateLimit"];
+
+/// How often and how long to retry requests which fail for transient reasons.
+///
+/// Backoff doubles after each attempt, starting at `initial_backoff`. If the server sends a
+/// `Retry-After` header, it's used instead; if it asks us to wait longer than `max_backoff`,
+/// we give up instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Never retry.
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    /// How long to wait before retrying after `attempt` retries, or `None` to give up.
+    pub(crate) fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        if attempt >= self.max_retries {
+            return None;
+        }
+        match retry_after {
+            Some(retry_after) if retry_after > self.max_backoff => None,
+            Some(retry_after) => Some(retry_after),
+            None => Some(
+                self.initial_backoff
+                    .saturating_mul(2u32.saturating_pow(attempt))
+                    .min(self.max_backoff),
+            ),
+        }
+    }
+}
+
+/// Parse a `Retry-After` header, in either delay-seconds or HTTP-date form.
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    (date.with_timezone(&chrono::Utc) - chrono::Utc::now())
+        .to_std()
+        .ok()
+}
+
+/// Whether `error` might not happen if the request was made again.
+///
+/// Errors which don't rule out the server having processed the request, like timeouts, are
+/// only transient if the request is `idempotent`. Connection failures always are, since the
+/// request never reached the server.
+pub(crate) fn is_transient_error(error: &Error, idempotent: bool) -> bool {
+    let is_transient_status = |status: u32| {
+        let matches = |codes: &[u16]| codes.iter().any(|code| u32::from(*code) == status);
+        matches(&TRANSIENT_STATUSES) || (idempotent && matches(&TRANSIENT_GATEWAY_STATUSES))
+    };
+    match error {
+        Error::Transport(err) => err.is_connect() || (idempotent && err.is_timeout()),
+        Error::Server(status) => status
+            .split_whitespace()
+            .next()
+            .and_then(|code| code.parse().ok())
+            .map_or(false, is_transient_status),
+        Error::Problem(problem) => problem.status().map_or(false, is_transient_status),
+        _ => false,
+    }
+}
+
+/// Whether calling `method` twice has the same effect as calling it once, so it's safe to
+/// retry even if the first attempt might have been processed.
+pub(crate) fn is_idempotent(method: Method) -> bool {
+    matches!(
+        method,
+        Method::Echo
+            | Method::GetPushSubscription
+            | Method::GetMailbox
+            | Method::ChangesMailbox
+            | Method::QueryMailbox
+            | Method::QueryChangesMailbox
+            | Method::GetThread
+            | Method::ChangesThread
+            | Method::GetEmail
+            | Method::ChangesEmail
+            | Method::QueryEmail
+            | Method::QueryChangesEmail
+            | Method::ParseEmail
+            | Method::GetSearchSnippet
+            | Method::GetIdentity
+            | Method::ChangesIdentity
+            | Method::GetEmailSubmission
+            | Method::ChangesEmailSubmission
+            | Method::QueryEmailSubmission
+            | Method::QueryChangesEmailSubmission
+            | Method::GetVacationResponse
+            | Method::GetPrincipal
+            | Method::ChangesPrincipal
+            | Method::QueryPrincipal
+            | Method::QueryChangesPrincipal
+    )
+}
+
+/// Whether the API response `body` has only transient method errors, like
+/// `serverUnavailable`.
+///
+/// If any method call got a result, retrying the request could repeat it, so it isn't
+/// retried.
+pub(crate) fn is_transient_method_error(body: &str) -> bool {
+    let response: serde_json::Value = match serde_json::from_str(body) {
+        Ok(response) => response,
+        Err(_) => return false,
+    };
+    match response["methodResponses"].as_array() {
+        Some(responses) if !responses.is_empty() => responses.iter().all(|response| {
+            response[0] == "error"
+                && response[1]["type"]
+                    .as_str()
+                    .map_or(false, |error| TRANSIENT_METHOD_ERRORS.contains(&error))
+        }),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+
+    use super::*;
+
+    #[test]
+    fn test_delay() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay(0, None), Some(Duration::from_secs(1)));
+        assert_eq!(policy.delay(2, None), Some(Duration::from_secs(4)));
+        assert_eq!(policy.delay(3, None), None);
+        assert_eq!(
+            policy.delay(0, Some(Duration::from_secs(10))),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(policy.delay(0, Some(Duration::from_secs(60))), None);
+        assert_eq!(RetryPolicy::none().delay(0, None), None);
+
+        let policy = RetryPolicy {
+            max_retries: 10,
+            ..Default::default()
+        };
+        assert_eq!(policy.delay(8, None), Some(Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("120"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(120)));
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        // In the past.
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[test]
+    fn test_is_transient_error() {
+        let unavailable = Error::Server("503 Service Unavailable".to_string());
+        assert!(is_transient_error(&unavailable, true));
+        assert!(is_transient_error(&unavailable, false));
+        let gateway_timeout = Error::Server("504 Gateway Timeout".to_string());
+        assert!(is_transient_error(&gateway_timeout, true));
+        assert!(!is_transient_error(&gateway_timeout, false));
+        assert!(!is_transient_error(
+            &Error::Server("401 Unauthorized".to_string()),
+            true
+        ));
+    }
+
+    #[test]
+    fn test_is_idempotent() {
+        assert!(is_idempotent(Method::GetMailbox));
+        assert!(is_idempotent(Method::QueryEmail));
+        assert!(!is_idempotent(Method::ImportEmail));
+        assert!(!is_idempotent(Method::SetEmailSubmission));
+    }
+
+    #[test]
+    fn test_is_transient_method_error() {
+        assert!(is_transient_method_error(
+            r#"{"sessionState": "1", "methodResponses": [
+                ["error", {"type": "serverUnavailable"}, "s0"]
+            ]}"#
+        ));
+        assert!(!is_transient_method_error(
+            r#"{"sessionState": "1", "methodResponses": [
+                ["Email/set", {"accountId": "A1"}, "s0"],
+                ["error", {"type": "serverUnavailable"}, "s1"]
+            ]}"#
+        ));
+        assert!(!is_transient_method_error(
+            r#"{"sessionState": "1", "methodResponses": [
+                ["error", {"type": "invalidArguments"}, "s0"]
+            ]}"#
+        ));
+    }
+}
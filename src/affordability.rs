@@ -0,0 +1,112 @@
+//! Whether we can afford a unit, by the common rule that gross monthly income should be some
+//! multiple of the rent. Configured like:
+//!
+//! ```toml
+//! [affordability]
+//! income = 15000
+//! multiple = 3
+//! required = true
+//! ```
+//!
+//! Notifications say whether each unit passes and by how much. With `required`, units which
+//! don't pass aren't notified about at all, since applying for them is a wasted application fee.
+
+use std::fmt::Display;
+
+use serde::Deserialize;
+
+use crate::money::Money;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Affordability {
+    /// Gross monthly income.
+    pub income: Money,
+    /// How many times the rent the income needs to be.
+    #[serde(default = "default_multiple")]
+    pub multiple: f64,
+    /// Skip units which don't pass, even if they meet the qualifications.
+    #[serde(default)]
+    pub required: bool,
+}
+
+fn default_multiple() -> f64 {
+    3.0
+}
+
+impl Affordability {
+    /// The highest rent the income qualifies for.
+    pub fn max_rent(&self) -> Money {
+        Money::from_dollars(self.income.dollars() / self.multiple)
+    }
+
+    pub fn check(&self, rent: Money) -> Verdict {
+        Verdict {
+            rent,
+            max_rent: self.max_rent(),
+            multiple: self.multiple,
+        }
+    }
+}
+
+/// Whether a rent passes the [`Affordability`] rule.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Verdict {
+    pub rent: Money,
+    pub max_rent: Money,
+    multiple: f64,
+}
+
+impl Verdict {
+    pub fn passes(&self) -> bool {
+        self.rent <= self.max_rent
+    }
+}
+
+impl Display for Verdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            rent,
+            max_rent,
+            multiple,
+        } = *self;
+        if self.passes() {
+            write!(
+                f,
+                "Affordable at {multiple}× rent: {} under the {max_rent} limit",
+                max_rent - rent
+            )
+        } else {
+            write!(
+                f,
+                "Not affordable at {multiple}× rent: {} over the {max_rent} limit",
+                rent - max_rent
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let affordability: Affordability = toml::from_str("income = 13500").unwrap();
+        assert_eq!(affordability.max_rent(), Money::from_dollars(4500.0));
+
+        let verdict = affordability.check(Money::from_dollars(4260.0));
+        assert!(verdict.passes());
+        assert_eq!(
+            verdict.to_string(),
+            "Affordable at 3× rent: $240 under the $4,500 limit"
+        );
+
+        let verdict = affordability.check(Money::from_dollars(4720.0));
+        assert!(!verdict.passes());
+        assert_eq!(
+            verdict.to_string(),
+            "Not affordable at 3× rent: $220 over the $4,500 limit"
+        );
+    }
+}
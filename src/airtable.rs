@@ -0,0 +1,276 @@
+//! Mirror listings into an Airtable base, one record per listing.
+//!
+//! Create a [personal access token] with the `data.records:read` and `data.records:write`
+//! scopes, and configure it like:
+//!
+//! ```toml
+//! [airtable]
+//! token = "pat..."
+//! base-id = "appXXXXXXXXXXXXXX"
+//! table = "Apartments"
+//! ```
+//!
+//! The table needs these fields: `ID` and `Listing` (text), `Rent`, `Bedrooms`, and `Sq ft`
+//! (numbers), `Available` (date), `Status` (single select), and `URL` (URL). Only fields whose
+//! values have changed are written, so other fields are left alone.
+//!
+//! [personal access token]: https://airtable.com/developers/web/guides/personal-access-tokens
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use color_eyre::eyre;
+use color_eyre::eyre::eyre;
+use color_eyre::eyre::Context;
+use serde::Deserialize;
+use serde_json::json;
+use serde_json::Map;
+use serde_json::Value as JsonValue;
+
+use crate::api::Apartment;
+use crate::filter::Value;
+use crate::listing::Listing;
+use crate::secret;
+use crate::server::Snapshot;
+
+const API_URL: &str = "https://api.airtable.com/v0";
+
+/// Airtable allows five requests per second per base.
+const REQUEST_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The most records Airtable accepts in one create or update request.
+const BATCH_SIZE: usize = 10;
+
+type Fields = Map<String, JsonValue>;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct AirtableConfig {
+    /// A personal access token; see [`crate::secret`].
+    #[serde(deserialize_with = "secret::deserialize")]
+    pub token: String,
+    /// The ID from the base's URL, like `appXXXXXXXXXXXXXX`.
+    pub base_id: String,
+    /// The name or ID of the table to write listings to.
+    #[serde(default = "default_table")]
+    pub table: String,
+}
+
+fn default_table() -> String {
+    "Apartments".to_owned()
+}
+
+#[derive(Deserialize)]
+struct ListResponse {
+    records: Vec<Record>,
+    offset: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Record {
+    id: String,
+    #[serde(default)]
+    fields: Fields,
+}
+
+pub struct Airtable {
+    client: reqwest::Client,
+    config: AirtableConfig,
+}
+
+impl Airtable {
+    pub fn new(config: AirtableConfig) -> eyre::Result<Self> {
+        Ok(Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()?,
+            config,
+        })
+    }
+
+    /// Create records for new listings and update the changed fields of existing ones.
+    pub async fn sync(&self, snapshot: &Snapshot) -> eyre::Result<()> {
+        let records = self.records().await?;
+
+        let mut creates = Vec::new();
+        let mut updates = Vec::new();
+        for fields in listing_fields(snapshot) {
+            let id = fields
+                .get("ID")
+                .and_then(JsonValue::as_str)
+                .unwrap_or_default();
+            match records.get(id) {
+                Some(record) => {
+                    let changed = changed_fields(&record.fields, &fields);
+                    if !changed.is_empty() {
+                        updates.push(json!({ "id": record.id, "fields": changed }));
+                    }
+                }
+                None => creates.push(json!({ "fields": fields })),
+            }
+        }
+
+        for batch in creates.chunks(BATCH_SIZE) {
+            self.write(self.client.post(self.url()), batch)
+                .await
+                .wrap_err("Failed to create Airtable records")?;
+        }
+        // `PATCH` only touches the fields we send; `PUT` would clear the rest.
+        for batch in updates.chunks(BATCH_SIZE) {
+            self.write(self.client.patch(self.url()), batch)
+                .await
+                .wrap_err("Failed to update Airtable records")?;
+        }
+
+        tracing::debug!(
+            created = creates.len(),
+            updated = updates.len(),
+            "Synced Airtable base"
+        );
+        Ok(())
+    }
+
+    fn url(&self) -> String {
+        format!("{API_URL}/{}/{}", self.config.base_id, self.config.table)
+    }
+
+    /// Every record in the table, by the listing ID in its `ID` field.
+    async fn records(&self) -> eyre::Result<BTreeMap<String, Record>> {
+        let mut records = BTreeMap::new();
+        let mut offset = None;
+        loop {
+            let mut request = self.client.get(self.url());
+            if let Some(offset) = &offset {
+                request = request.query(&[("offset", offset)]);
+            }
+            let response = self
+                .request(request)
+                .await
+                .wrap_err("Failed to list Airtable records")?;
+            let response: ListResponse = serde_json::from_value(response)?;
+            for record in response.records {
+                if let Some(id) = record.fields.get("ID").and_then(JsonValue::as_str) {
+                    records.insert(id.to_owned(), record);
+                }
+            }
+            match response.offset {
+                Some(next) => offset = Some(next),
+                None => break,
+            }
+            tokio::time::sleep(REQUEST_INTERVAL).await;
+        }
+        Ok(records)
+    }
+
+    async fn write(
+        &self,
+        request: reqwest::RequestBuilder,
+        records: &[JsonValue],
+    ) -> eyre::Result<()> {
+        let body = json!({ "records": records, "typecast": true });
+        let request = request
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(&body)?);
+        self.request(request).await?;
+        tokio::time::sleep(REQUEST_INTERVAL).await;
+        Ok(())
+    }
+
+    async fn request(&self, request: reqwest::RequestBuilder) -> eyre::Result<JsonValue> {
+        let response = request.bearer_auth(&self.config.token).send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            return Err(eyre!("{status}: {text}"));
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+}
+
+/// The fields for each listing, listed or not.
+fn listing_fields(snapshot: &Snapshot) -> Vec<Fields> {
+    snapshot
+        .known_apartments
+        .values()
+        .chain(snapshot.unlisted_apartments.values())
+        .map(fields)
+        .chain(
+            snapshot
+                .known_posts
+                .values()
+                .chain(snapshot.unlisted_posts.values())
+                .map(fields),
+        )
+        .collect()
+}
+
+fn fields<T: Listing>(apt: &Apartment<T>) -> Fields {
+    let number = |name| match apt.inner.field(name) {
+        Some(Value::Number(number)) => json!(number),
+        _ => JsonValue::Null,
+    };
+    let status = if apt.unlisted.is_some() {
+        "Unlisted"
+    } else {
+        "Listed"
+    };
+    let fields = json!({
+        "ID": apt.id(),
+        "Listing": apt.inner.to_string(),
+        "Rent": apt.inner.rent(),
+        "Bedrooms": number("bedrooms"),
+        "Sq ft": number("sqft"),
+        "Available": apt.inner.available_date().map(|date| date.to_string()),
+        "Status": status,
+        "URL": apt.inner.url().unwrap_or_else(|| apt.source.clone()),
+    });
+    match fields {
+        JsonValue::Object(fields) => fields,
+        _ => unreachable!(),
+    }
+}
+
+/// The fields in `new` whose values differ from `old`.
+///
+/// Airtable leaves empty fields out of records, so a missing field is the same as `null`.
+fn changed_fields(old: &Fields, new: &Fields) -> Fields {
+    new.iter()
+        .filter(|(name, new)| {
+            let old = old.get(*name).unwrap_or(&JsonValue::Null);
+            match (old.as_f64(), new.as_f64()) {
+                // `3000` and `3000.0` are different JSON values, but the same number.
+                (Some(old), Some(new)) => old != new,
+                _ => old != *new,
+            }
+        })
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changed_fields() {
+        let fields = |value: JsonValue| match value {
+            JsonValue::Object(fields) => fields,
+            _ => unreachable!(),
+        };
+        let old = fields(json!({
+            "ID": "craigslist-7551234567",
+            "Rent": 3000,
+            "Notes": "Nice light",
+        }));
+        let new = fields(json!({
+            "ID": "craigslist-7551234567",
+            "Rent": 3000.0,
+            "Sq ft": null,
+            "Status": "Listed",
+        }));
+        assert_eq!(
+            changed_fields(&old, &new),
+            fields(json!({ "Status": "Listed" }))
+        );
+    }
+}
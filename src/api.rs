@@ -1,13 +1,29 @@
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::fmt::Display;
 
 use chrono::DateTime;
+use chrono::NaiveDate;
 use chrono::Utc;
 use color_eyre::eyre;
+use reqwest::Url;
 use serde::Deserialize;
 use serde::Serialize;
 use serde_json::Value;
 
+use crate::changes::Change;
+use crate::changes::FieldChange;
+use crate::changes::FLOOR_PLAN_IMAGES;
+use crate::changes::PRICES_BY_MOVE_IN_DATE;
+use crate::changes::PROMOTION_ORDER;
+use crate::concession::Concession;
+use crate::filter::Value as FilterValue;
+use crate::lease_term::LeaseOption;
+use crate::listing::Listing;
+use crate::money::Money;
+use crate::price_drop::PriceDrop;
+use crate::qualifications::Qualifications;
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(try_from = "ApiApartmentData")]
 pub struct ApartmentData {
@@ -19,16 +35,34 @@ impl TryFrom<ApiApartmentData> for ApartmentData {
 
     fn try_from(data: ApiApartmentData) -> Result<Self, Self::Error> {
         let mut apartments = Vec::with_capacity(data.units.len());
+        let promotions: BTreeMap<&str, &Promotion> = data
+            .promotions
+            .iter()
+            .map(|promotion| (promotion.id.as_str(), promotion))
+            .collect();
+
+        let listed = Utc::now();
+        for mut apt in data.units {
+            apt.floor = Floor::from_unit_number(&apt.number);
+            for applicable in &mut apt.promotions {
+                let promotion = promotions.get(applicable.promotion_id.as_str());
+                applicable.concession = promotion.and_then(|promotion| promotion.concession());
+                applicable.title = promotion.map(|promotion| promotion.title.clone());
+            }
 
-        for apt in data.units {
             apartments.push(Apartment {
-                inner: apt.clone(),
+                // Filled in by the caller, which knows where the data came from.
+                source: String::new(),
+                max_rent: Some(apt.lowest_rent.price.price),
+                inner: apt,
                 // history: vec![ApartmentSnapshot {
                 // inner: serde_json::to_value(&apt)?,
                 // observed: Utc::now(),
                 // }],
-                listed: Utc::now(),
+                listed,
                 unlisted: None,
+                reported: None,
+                also_listed_in: BTreeSet::new(),
             })
         }
 
@@ -36,30 +70,75 @@ impl TryFrom<ApiApartmentData> for ApartmentData {
     }
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
+/// The parts of `Fusion.globalContent` we use. The rest of it, like the community's details and
+/// pricing overview, is skipped while parsing rather than kept around.
+#[derive(Debug, Deserialize)]
 struct ApiApartmentData {
     units: Vec<ApiApartment>,
     promotions: Vec<Promotion>,
-    pricing_overview: Vec<PricingOverview>,
-    #[serde(flatten)]
-    extra: Value,
 }
 
+/// A tracked listing, with the times we first and last saw it.
 #[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct Apartment {
-    pub inner: ApiApartment,
+pub struct Apartment<T = ApiApartment> {
+    /// The URL of the source this listing was fetched from, like a community page.
+    #[serde(default = "default_source")]
+    pub source: String,
+    pub inner: T,
     // pub history: Vec<ApartmentSnapshot>,
     pub listed: DateTime<Utc>,
     pub unlisted: Option<DateTime<Utc>>,
+    /// The highest rent we've seen for this listing.
+    #[serde(default)]
+    pub max_rent: Option<Money>,
+    /// The listing as of the last change we reported, if it's changed too little to report
+    /// since; see [`crate::changes::IgnoreChanges`]. Changes are measured from here, so small
+    /// changes can't add up unnoticed.
+    #[serde(default = "Option::default", skip_serializing_if = "Option::is_none")]
+    pub reported: Option<T>,
+    /// Other sources which list this listing too, like a second Craigslist search which
+    /// returns the same post. It's unlisted once none of its sources list it.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub also_listed_in: BTreeSet<String>,
 }
 
-impl Apartment {
+impl<T: Listing> Apartment<T> {
+    /// Start tracking a listing from `source`, as of now.
+    pub fn new(source: &str, inner: T) -> Self {
+        Self {
+            source: source.to_owned(),
+            max_rent: inner.rent(),
+            inner,
+            listed: Utc::now(),
+            unlisted: None,
+            reported: None,
+            also_listed_in: BTreeSet::new(),
+        }
+    }
+
     pub fn id(&self) -> &str {
-        &self.inner.unit_id
+        self.inner.id()
+    }
+
+    /// Does the source at `source` list this listing?
+    pub fn listed_in(&self, source: &str) -> bool {
+        self.source == source || self.also_listed_in.contains(source)
     }
 
-    pub fn update_inner(&mut self, new_inner: ApiApartment) -> eyre::Result<()> {
+    /// Note that the source at `source` doesn't list this listing anymore, returning whether
+    /// any other source still does.
+    pub fn delist_from(&mut self, source: &str) -> bool {
+        self.also_listed_in.remove(source);
+        if self.source == source {
+            match self.also_listed_in.pop_first() {
+                Some(other) => self.source = other,
+                None => return false,
+            }
+        }
+        true
+    }
+
+    pub fn update_inner(&mut self, new_inner: T) -> eyre::Result<()> {
         self.inner = new_inner;
         // self.history.push(ApartmentSnapshot {
         // inner: serde_json::to_value(&self.inner)?,
@@ -69,7 +148,12 @@ impl Apartment {
     }
 }
 
-impl Display for Apartment {
+/// DBs written before we supported multiple sources only tracked Ava Capitol Hill.
+fn default_source() -> String {
+    crate::AVA_URL.to_owned()
+}
+
+impl<T: Display> Display for Apartment<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if let Some(unlisted) = self.unlisted {
             let tracked_duration = unlisted - self.listed;
@@ -97,6 +181,13 @@ pub struct ApiApartment {
     pub unit_id: String,
     #[serde(rename = "name")]
     pub number: String,
+    /// Derived from `number`; not part of the API response.
+    #[serde(default)]
+    pub floor: Option<Floor>,
+    /// This unit's page on the Avalon site, from [`ApiApartment::unit_url`]; not part of the
+    /// API response.
+    #[serde(default)]
+    pub page_url: Option<String>,
     #[serde(rename = "furnishStatus")]
     furnished: Furnished,
     floor_plan: FloorPlan,
@@ -116,22 +207,343 @@ pub struct ApiApartment {
 }
 
 impl ApiApartment {
-    pub fn meets_qualifications(&self) -> bool {
-        if let Furnished::Furnished = self.furnished {
-            tracing::debug!(number = self.number, "Skipping apartment; furnished");
-            false
-        } else if self.bedroom != 2 {
-            tracing::debug!(
-                number = self.number,
-                bedrooms = self.bedroom,
-                bathrooms = self.bathroom,
-                rent = self.lowest_rent.price.price,
-                "Skipping apartment; too few bedrooms"
-            );
-            false
+    /// Fill in the fields derived from other fields rather than the API response, like
+    /// `floor`, for a unit from the community at `community`.
+    ///
+    /// Units saved before those fields existed don't have them, and would otherwise be reported
+    /// as changed the next time they're scraped.
+    pub fn fill_derived_fields(&mut self, community: &str) {
+        self.floor = Floor::from_unit_number(&self.number);
+        self.page_url = self.unit_url(community);
+    }
+
+    /// This unit's page on the Avalon site, under the page of the community at `community`,
+    /// like `.../ava-capitol-hill/apartment/AVB-WA026-001-731/?floorPlan=f-b4v`.
+    pub fn unit_url(&self, community: &str) -> Option<String> {
+        let mut community = Url::parse(community).ok()?;
+        community.set_query(None);
+        community.set_fragment(None);
+        if !community.path().ends_with('/') {
+            community.set_path(&format!("{}/", community.path()));
+        }
+        let mut url = community
+            .join(&format!("apartment/{}/", self.unit_id))
+            .ok()?;
+        url.query_pairs_mut()
+            .append_pair("floorPlan", &self.floor_plan.name);
+        Some(url.into())
+    }
+
+    /// The virtual tour of this specific unit, if there is one.
+    ///
+    /// Some units have a virtual tour of a different unit with the same floor plan, which we
+    /// don't count.
+    fn actual_unit_tour(&self) -> Option<&VirtualTour> {
+        self.virtual_tour
+            .as_ref()
+            .filter(|virtual_tour| virtual_tour.is_actual_unit)
+    }
+
+    /// The lowest monthly cost of renting this apartment, with promotions amortized over the
+    /// lease.
+    ///
+    /// This is the sticker price if there are no applicable promotions.
+    pub fn effective_rent(&self) -> Money {
+        self.lease_options()
+            .into_iter()
+            .map(|option| option.monthly)
+            .fold(self.lowest_rent.price.price, Money::min)
+    }
+
+    /// Is the [`Self::effective_rent`] lower than the sticker price?
+    fn has_effective_discount(&self) -> bool {
+        self.effective_rent() < self.lowest_rent.price.price
+    }
+
+    /// When this apartment's promotions end, for those that do.
+    pub fn promotion_end_dates(&self) -> Vec<NaiveDate> {
+        self.promotions
+            .iter()
+            .filter_map(|promotion| promotion.end_date.as_ref())
+            .map(AvaDate::date)
+            .collect()
+    }
+
+    /// The promotions on this apartment which end, as their ID, title, and end date.
+    pub fn expiring_promotions(&self) -> Vec<(&str, &str, NaiveDate)> {
+        self.promotions
+            .iter()
+            .filter_map(|promotion| {
+                Some((
+                    promotion.promotion_id.as_str(),
+                    promotion.title.as_deref().unwrap_or("A promotion"),
+                    promotion.end_date.as_ref()?.date(),
+                ))
+            })
+            .collect()
+    }
+
+    /// The rent to check against [`Qualifications::rent`].
+    fn qualifying_rent(&self, qualifications: &Qualifications) -> Money {
+        if qualifications.use_effective_rent {
+            self.effective_rent()
         } else {
-            true
+            self.lowest_rent.price.price
+        }
+    }
+
+    /// Monthly rent per square foot, for comparing units of different sizes. `None` if the square
+    /// footage is unknown.
+    pub fn price_per_square_foot(&self, rent: Money) -> Option<f64> {
+        (self.square_feet > 0.0).then(|| rent.dollars() / self.square_feet)
+    }
+
+    /// The reason this apartment doesn't meet `qualifications`, if it doesn't.
+    fn disqualification(&self, qualifications: &Qualifications) -> Option<&'static str> {
+        let Qualifications {
+            bedrooms,
+            bathrooms,
+            rent,
+            use_effective_rent: _,
+            square_feet,
+            price_per_square_foot,
+            floor,
+            allow_furnished,
+            require_virtual_tour,
+            available_after: _,
+            available_before: _,
+            floor_plans: _,
+            exclude_floor_plans: _,
+            filter: _,
+            near: _,
+        } = qualifications;
+
+        if let (Furnished::Furnished, false) = (&self.furnished, allow_furnished) {
+            return Some("furnished");
+        }
+
+        if *require_virtual_tour && self.actual_unit_tour().is_none() {
+            return Some("no virtual tour");
+        }
+
+        bedrooms
+            .check(&self.bedroom, "too few bedrooms", "too many bedrooms")
+            .or_else(|| bathrooms.check(&self.bathroom, "too few bathrooms", "too many bathrooms"))
+            .or_else(|| {
+                rent.check(
+                    &self.qualifying_rent(qualifications),
+                    "too cheap",
+                    "too expensive",
+                )
+            })
+            .or_else(|| square_feet.check(&self.square_feet, "too small", "too big"))
+            .or_else(|| {
+                self.price_per_square_foot(self.qualifying_rent(qualifications))
+                    .and_then(|price| {
+                        price_per_square_foot.check(
+                            &price,
+                            "too cheap per square foot",
+                            "too expensive per square foot",
+                        )
+                    })
+            })
+            .or_else(|| {
+                self.floor
+                    .and_then(|Floor(number)| floor.check(&number, "too low", "too high"))
+            })
+            .or_else(|| qualifications.check_available(self.available_date.date()))
+            .or_else(|| qualifications.check_floor_plan(&self.floor_plan.name))
+            .or_else(|| qualifications.check_filter(|name| self.field(name)))
+    }
+}
+
+impl Listing for ApiApartment {
+    fn id(&self) -> &str {
+        &self.unit_id
+    }
+
+    fn listed_subject(&self) -> String {
+        let virtual_tour = match self.actual_unit_tour() {
+            Some(_) => " with virtual tour",
+            None => "",
+        };
+        format!(
+            "Apartment {} listed{virtual_tour}, available {}",
+            self.number,
+            self.available_date.date().format("%b %e %Y"),
+        )
+    }
+
+    fn unlisted_subject(&self) -> String {
+        format!("Apartment {} no longer available!", self.number)
+    }
+
+    fn changed_subject(&self) -> String {
+        format!("Apartment {} changed", self.number)
+    }
+
+    fn price_drop_subject(&self, drop: &PriceDrop) -> String {
+        format!("Apartment {} rent dropped {drop}", self.number)
+    }
+
+    fn rent(&self) -> Option<Money> {
+        Some(self.lowest_rent.price.price)
+    }
+
+    fn available_date(&self) -> Option<NaiveDate> {
+        Some(self.available_date.date())
+    }
+
+    fn url(&self) -> Option<String> {
+        self.page_url.clone()
+    }
+
+    fn virtual_tour_url(&self) -> Option<String> {
+        self.actual_unit_tour().map(VirtualTour::url)
+    }
+
+    /// Each lease has the best applicable promotion amortized over it.
+    fn lease_options(&self) -> Vec<LeaseOption> {
+        self.rent
+            .prices_per_movein_date
+            .iter()
+            .flat_map(|prices| {
+                prices
+                    .prices_per_terms
+                    .iter()
+                    .map(|(&term, price)| (prices.move_in_date.date(), term, price))
+            })
+            .map(|(move_in, term, price)| LeaseOption {
+                move_in,
+                term,
+                monthly: self
+                    .promotions
+                    .iter()
+                    .filter(|promotion| {
+                        promotion.terms.is_empty() || promotion.terms.contains(&term)
+                    })
+                    .filter_map(|promotion| promotion.concession)
+                    .map(|concession| {
+                        Money::from_dollars(concession.effective_rent(price.price.dollars(), term))
+                    })
+                    .fold(price.price, Money::min),
+            })
+            .collect()
+    }
+
+    fn meets_qualifications(&self, qualifications: &Qualifications) -> bool {
+        match self.disqualification(qualifications) {
+            Some(reason) => {
+                tracing::debug!(
+                    number = self.number,
+                    bedrooms = self.bedroom,
+                    bathrooms = self.bathroom,
+                    rent = %self.lowest_rent.price.price,
+                    "Skipping apartment; {reason}"
+                );
+                false
+            }
+            None => true,
+        }
+    }
+
+    fn field(&self, name: &str) -> Option<FilterValue> {
+        Some(match name {
+            "bedroom" | "bedrooms" => FilterValue::Number(self.bedroom as f64),
+            "bathroom" | "bathrooms" => FilterValue::Number(self.bathroom as f64),
+            "rent" => FilterValue::Number(self.lowest_rent.price.price.dollars()),
+            "effective_rent" => FilterValue::Number(self.effective_rent().dollars()),
+            "sqft" | "square_feet" => FilterValue::Number(self.square_feet),
+            "price_per_sqft" => {
+                FilterValue::Number(self.price_per_square_foot(self.lowest_rent.price.price)?)
+            }
+            "furnished" => FilterValue::Bool(self.furnished == Furnished::Furnished),
+            "virtual_tour" => FilterValue::Bool(self.actual_unit_tour().is_some()),
+            "plan" => FilterValue::String(self.floor_plan.name.clone()),
+            "number" => FilterValue::String(self.number.clone()),
+            "floor" => FilterValue::Number(self.floor?.0 as f64),
+            _ => return None,
+        })
+    }
+
+    fn changes(&self, new: &Self) -> Vec<FieldChange> {
+        let furnished = |apt: &Self| apt.furnished == Furnished::Furnished;
+        let virtual_tour = |apt: &Self| apt.actual_unit_tour().is_some();
+        let mut changes: Vec<FieldChange> = [
+            Change::new(self.lowest_rent.price.price, new.lowest_rent.price.price)
+                .map(FieldChange::Rent),
+            // Only interesting when there's a promotion, or it's just the rent again.
+            Change::new(self.effective_rent(), new.effective_rent())
+                .filter(|_| self.has_effective_discount() || new.has_effective_discount())
+                .map(FieldChange::EffectiveRent),
+            Change::new(self.available_date.date(), new.available_date.date())
+                .map(FieldChange::Available),
+            Change::new(
+                self.lowest_rent.term_length.clone(),
+                new.lowest_rent.term_length.clone(),
+            )
+            .map(FieldChange::LeaseTerm),
+            Change::new(self.bedroom, new.bedroom).map(FieldChange::Bedrooms),
+            Change::new(self.bathroom, new.bathroom).map(FieldChange::Bathrooms),
+            Change::new(self.square_feet, new.square_feet).map(FieldChange::SquareFeet),
+            Change::new(self.floor_plan.name.clone(), new.floor_plan.name.clone())
+                .map(FieldChange::FloorPlan),
+            Change::new(furnished(self), furnished(new)).map(FieldChange::Furnished),
+            Change::new(virtual_tour(self), virtual_tour(new)).map(FieldChange::VirtualTour),
+            Change::new(self.promotions.len(), new.promotions.len()).map(FieldChange::Promotions),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        // Whatever else changed, once the fields above are accounted for, is listed without
+        // details.
+        let mut rest = new.clone();
+        rest.lowest_rent.price = self.lowest_rent.price.clone();
+        rest.lowest_rent.term_length = self.lowest_rent.term_length.clone();
+        rest.available_date = self.available_date.clone();
+        rest.floor_plan.name = self.floor_plan.name.clone();
+        if furnished(self) != furnished(new) {
+            rest.furnished = self.furnished.clone();
         }
+        if virtual_tour(self) != virtual_tour(new) {
+            rest.virtual_tour = self.virtual_tour.clone();
+        }
+        if self.promotions.len() != new.promotions.len() {
+            rest.promotions = self.promotions.clone();
+        }
+        let sorted_promotions = |apt: &Self| {
+            let mut promotions = apt.promotions.clone();
+            promotions.sort_by(|a, b| a.promotion_id.cmp(&b.promotion_id));
+            promotions
+        };
+        let promotions_changed = sorted_promotions(self) != sorted_promotions(&rest);
+        changes.extend(
+            [
+                ("unit number", self.number != rest.number),
+                ("furnishing", self.furnished != rest.furnished),
+                (FLOOR_PLAN_IMAGES, self.floor_plan != rest.floor_plan),
+                (
+                    "virtual tour details",
+                    self.virtual_tour != rest.virtual_tour,
+                ),
+                (PRICES_BY_MOVE_IN_DATE, self.rent != rest.rent),
+                (
+                    "lowest rent move-in date",
+                    self.lowest_rent.date != rest.lowest_rent.date,
+                ),
+                ("promotion details", promotions_changed),
+                (
+                    PROMOTION_ORDER,
+                    !promotions_changed && self.promotions != rest.promotions,
+                ),
+                ("other details", self.extra != rest.extra),
+            ]
+            .into_iter()
+            .filter(|(_, changed)| *changed)
+            .map(|(field, _)| FieldChange::Other(field)),
+        );
+        changes
     }
 }
 
@@ -139,8 +551,8 @@ impl Display for ApiApartment {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let ApiApartment {
             number,
+            floor,
             floor_plan,
-            virtual_tour,
             bedroom,
             bathroom,
             square_feet,
@@ -150,11 +562,25 @@ impl Display for ApiApartment {
             ..
         } = self;
         let price = lowest_rent.price.price;
-        let available_date = available_date.format("%b %e %Y");
+        let effective_rent = self.effective_rent();
+        let effective_rent = if price - effective_rent >= Money::from_dollars(1.0) {
+            format!(" ({} effective)", effective_rent.round())
+        } else {
+            String::new()
+        };
+        let price_per_square_foot = match self.price_per_square_foot(price) {
+            Some(price) => format!(" ({}/sq/ft)", Money::from_dollars(price)),
+            None => String::new(),
+        };
+        let available_date = available_date.date().format("%b %e %Y");
         let floor_plan = &floor_plan.name;
-        let virtual_tour = match virtual_tour {
-            Some(virtual_tour) if virtual_tour.is_actual_unit => ", virtual tour",
-            _ => "",
+        let floor = match floor {
+            Some(floor) => format!("{floor} floor, "),
+            None => String::new(),
+        };
+        let virtual_tour = match self.actual_unit_tour() {
+            Some(_) => ", virtual tour",
+            None => "",
         };
         let furnished = match furnished {
             Furnished::Unfurnished => "",
@@ -164,9 +590,9 @@ impl Display for ApiApartment {
         write!(
             f,
             "Apartment {number} \
-             ({bedroom} bed {bathroom} bath, \
-             ${price}, \
-             {square_feet}sq/ft, \
+             ({floor}{bedroom} bed {bathroom} bath, \
+             {price}{effective_rent}, \
+             {square_feet}sq/ft{price_per_square_foot}, \
              avail. {available_date}, \
              plan {floor_plan}\
              {furnished}\
@@ -195,10 +621,21 @@ struct FloorPlan {
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 struct VirtualTour {
+    /// A Matterport space ID, or occasionally a full URL.
     space: String,
     is_actual_unit: bool,
 }
 
+impl VirtualTour {
+    fn url(&self) -> String {
+        if self.space.starts_with("http") {
+            self.space.clone()
+        } else {
+            format!("https://my.matterport.com/show/?m={}", self.space)
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 struct Rent {
@@ -216,8 +653,8 @@ struct PricesForMoveInDate {
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 struct Price {
-    price: f64,
-    net_effective_price: f64,
+    price: Money,
+    net_effective_price: Money,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
@@ -244,32 +681,88 @@ struct Promotion {
     disclaimer: String,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+impl Promotion {
+    fn concession(&self) -> Option<Concession> {
+        Concession::parse(&self.title).or_else(|| Concession::parse(&self.description))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct ApplicablePromotion {
     promotion_id: String,
     start_date: AvaDate,
     end_date: Option<AvaDate>,
     terms: Vec<usize>,
+    /// Parsed from the matching [`Promotion`]'s text, which isn't included with each unit.
+    #[serde(default)]
+    concession: Option<Concession>,
+    /// The matching [`Promotion`]'s title, like "6 Weeks Free!".
+    #[serde(default)]
+    title: Option<String>,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
-#[serde(rename_all = "camelCase")]
-struct PricingOverview {
-    display_name: String,
-    bedroom: usize,
-    r#type: String,
-    available: bool,
-    designated_lowest_price: Option<f64>,
-    on_demand_lowest_price: Option<f64>,
-    total_lowest_price: f64,
-    total_highest_price: f64,
+/// Ignores `concession` and `title`. Units saved before they existed don't have them, and they
+/// can't be filled in without the community's promotions, so comparing them would report every
+/// unit as changed after upgrading.
+impl PartialEq for ApplicablePromotion {
+    fn eq(&self, other: &Self) -> bool {
+        self.promotion_id == other.promotion_id
+            && self.start_date == other.start_date
+            && self.end_date == other.end_date
+            && self.terms == other.terms
+    }
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+/// The floor an apartment is on, like 7 for apartment 731.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(transparent)]
+pub struct Floor(pub u32);
+
+impl Floor {
+    /// Get the floor from a unit number, which is the floor followed by a two-digit unit, like
+    /// `731` or `1205`. Any prefix like `A-` in `A-312` is ignored.
+    pub fn from_unit_number(number: &str) -> Option<Self> {
+        let digits = number
+            .rsplit(|c: char| !c.is_ascii_digit())
+            .next()
+            .filter(|digits| digits.len() >= 3)?;
+        let floor = digits[..digits.len() - 2].parse().ok()?;
+        Some(Self(floor))
+    }
+}
+
+impl Display for Floor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let suffix = match (self.0 % 10, self.0 % 100) {
+            (_, 11..=13) => "th",
+            (1, _) => "st",
+            (2, _) => "nd",
+            (3, _) => "rd",
+            _ => "th",
+        };
+        write!(f, "{}{suffix}", self.0)
+    }
+}
+
+/// A date from the Avalon API. These are dates, but come with a time like 4:00 AM UTC (midnight
+/// in Avalon's time zone), so they're shown without one.
+#[derive(Clone, Deserialize, Serialize, PartialEq)]
 #[serde(transparent)]
 pub struct AvaDate(#[serde(with = "crate::ava_date")] DateTime<Utc>);
 
+impl AvaDate {
+    pub fn date(&self) -> NaiveDate {
+        self.0.naive_utc().date()
+    }
+}
+
+impl std::fmt::Debug for AvaDate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("AvaDate").field(&self.date()).finish()
+    }
+}
+
 impl std::ops::Deref for AvaDate {
     type Target = DateTime<Utc>;
 
@@ -278,60 +771,400 @@ impl std::ops::Deref for AvaDate {
     }
 }
 
+/// Listings for tests, public so the binary's tests can use them too.
+#[doc(hidden)]
+pub mod fixtures {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::craigslist::Post;
+
+    /// The Craigslist search [`craigslist_post`] is from.
+    pub const CRAIGSLIST_SEARCH: &str = "https://seattle.craigslist.org/search/apa?format=rss";
+
+    pub fn apartment_731() -> ApiApartment {
+        ApiApartment {
+            unit_id: "AVB-WA026-001-731".to_owned(),
+            number: "731".to_string(),
+            floor: Some(Floor(7)),
+            page_url: None,
+            furnished: Furnished::Unfurnished,
+            floor_plan: FloorPlan {
+                name: "f-b4v".to_string(),
+                low_resolution: "/floorplans/wa026/wa026-b4v-1268sf(1).jpg/128/96".to_string(),
+                high_resolution: "/floorplans/wa026/wa026-b4v-1268sf(1).jpg/1024/768".to_string(),
+            },
+            virtual_tour: None,
+            bedroom: 2,
+            bathroom: 2,
+            square_feet: 1268.0,
+            available_date: AvaDate(Utc.ymd(2022, 10, 21).and_hms_opt(4, 0, 0).unwrap()),
+            rent: Rent {
+                applied_discount: 0.0,
+                prices_per_movein_date: vec![PricesForMoveInDate {
+                    move_in_date: AvaDate(Utc.ymd(2022, 10, 21).and_hms_opt(4, 0, 0).unwrap()),
+                    prices_per_terms: BTreeMap::from([(
+                        2,
+                        Price {
+                            price: Money::from_dollars(4720.0),
+                            net_effective_price: Money::from_dollars(4720.0),
+                        },
+                    )]),
+                }],
+            },
+            lowest_rent: LowestRent {
+                date: AvaDate(Utc.ymd(2022, 10, 21).and_hms_opt(4, 0, 0).unwrap()),
+                term_length: "8".to_string(),
+                price: Price {
+                    price: Money::from_dollars(4260.0),
+                    net_effective_price: Money::from_dollars(4260.0),
+                },
+            },
+            promotions: vec![ApplicablePromotion {
+                promotion_id: "106246".to_string(),
+                start_date: AvaDate(Utc.ymd(2022, 10, 5).and_hms_opt(4, 0, 0).unwrap()),
+                end_date: Some(AvaDate(Utc.ymd(2022, 11, 30).and_hms_opt(4, 0, 0).unwrap())),
+                terms: vec![12],
+                concession: None,
+                title: Some("6 Weeks Free!".to_owned()),
+            }],
+            extra: serde_json::Value::Object(serde_json::Map::new()),
+        }
+    }
+
+    /// Unit 731 with its lowest rent changed to `rent`.
+    pub fn apartment_731_at(rent: f64) -> ApiApartment {
+        let mut apartment = apartment_731();
+        apartment.lowest_rent.price = Price {
+            price: Money::from_dollars(rent),
+            net_effective_price: Money::from_dollars(rent),
+        };
+        apartment
+    }
+
+    /// Unit 731 available on a different date.
+    pub fn apartment_731_available(available: DateTime<Utc>) -> ApiApartment {
+        ApiApartment {
+            available_date: AvaDate(available),
+            ..apartment_731()
+        }
+    }
+
+    /// A $3,000 Craigslist post.
+    pub fn craigslist_post() -> Post {
+        Post {
+            id: "craigslist-7551234567".to_owned(),
+            title: "2br in Capitol Hill".to_owned(),
+            price: Some(Money::from_dollars(3000.0)),
+            link: "https://seattle.craigslist.org/see/apa/d/7551234567.html".to_owned(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use chrono::TimeZone;
 
+    use super::fixtures::*;
     use super::*;
+    use crate::qualifications::Bounds;
+
+    #[test]
+    fn test_apartment_data() {
+        let mut unit = serde_json::to_value(apartment_731()).unwrap();
+        unit["floor"] = Value::Null;
+        unit["promotions"][0]["title"] = Value::Null;
+        let data: ApartmentData = serde_json::from_value(serde_json::json!({
+            "units": [unit],
+            "promotions": [{
+                "promotionId": "106246",
+                "promotionTitle": "6 Weeks Free!",
+                "promotionDescription": "",
+                "promotionDisclaimer": "",
+            }],
+            "pricingOverview": [],
+            "communityInfo": { "name": "AVA Capitol Hill" },
+        }))
+        .unwrap();
+
+        let [apartment]: [Apartment; 1] = data.apartments.try_into().unwrap();
+        assert_eq!(apartment.inner.floor, Some(Floor(7)));
+        assert_eq!(
+            apartment.inner.expiring_promotions(),
+            apartment_731().expiring_promotions()
+        );
+        assert_eq!(apartment.max_rent, Some(Money::from_dollars(4260.0)));
+    }
 
     #[test]
     fn test_api_apartment_display() {
         assert_eq!(
-            &ApiApartment {
-                unit_id: "AVB-WA026-001-731".to_owned(),
-                number: "731".to_string(),
-                furnished: Furnished::Unfurnished,
-                floor_plan: FloorPlan {
-                    name: "f-b4v".to_string(),
-                    low_resolution: "/floorplans/wa026/wa026-b4v-1268sf(1).jpg/128/96".to_string(),
-                    high_resolution: "/floorplans/wa026/wa026-b4v-1268sf(1).jpg/1024/768"
-                        .to_string()
+            apartment_731().to_string(),
+            "Apartment 731 (7th floor, 2 bed 2 bath, $4,260, 1268sq/ft ($3.36/sq/ft), \
+             avail. Oct 21 2022, plan f-b4v)"
+        );
+    }
+
+    #[test]
+    fn test_rent_float_noise() {
+        assert_eq!(apartment_731_at(4259.999999), apartment_731());
+        assert_ne!(apartment_731_at(4259.99), apartment_731());
+    }
+
+    #[test]
+    fn test_changes() {
+        let old = apartment_731();
+        assert_eq!(old.changes(&old), vec![]);
+
+        let mut new = apartment_731_available(Utc.ymd(2022, 11, 4).and_hms_opt(4, 0, 0).unwrap());
+        new.lowest_rent.price.price = Money::from_dollars(4195.0);
+        new.floor_plan.high_resolution.push_str("?v=2");
+        assert_eq!(
+            old.changes(&new)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "rent: $4,260 → $4,195",
+                "available: Oct 21 2022 → Nov 4 2022",
+                "floor plan images changed",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_floor() {
+        assert_eq!(Floor::from_unit_number("731"), Some(Floor(7)));
+        assert_eq!(Floor::from_unit_number("1205"), Some(Floor(12)));
+        assert_eq!(Floor::from_unit_number("A-312"), Some(Floor(3)));
+        assert_eq!(Floor::from_unit_number("PH"), None);
+        assert_eq!(Floor::from_unit_number("12"), None);
+
+        assert_eq!(Floor(1).to_string(), "1st");
+        assert_eq!(Floor(3).to_string(), "3rd");
+        assert_eq!(Floor(11).to_string(), "11th");
+        assert_eq!(Floor(22).to_string(), "22nd");
+    }
+
+    #[test]
+    fn test_virtual_tour() {
+        let mut apt = apartment_731();
+        let qualifications = Qualifications {
+            require_virtual_tour: true,
+            ..Default::default()
+        };
+        assert_eq!(apt.virtual_tour_url(), None);
+        assert_eq!(
+            apt.disqualification(&qualifications),
+            Some("no virtual tour")
+        );
+
+        apt.virtual_tour = Some(VirtualTour {
+            space: "zUqaEz4Qh9P".to_owned(),
+            is_actual_unit: false,
+        });
+        assert_eq!(apt.virtual_tour_url(), None);
+        assert_eq!(
+            apt.disqualification(&qualifications),
+            Some("no virtual tour")
+        );
+
+        apt.virtual_tour.as_mut().unwrap().is_actual_unit = true;
+        assert_eq!(
+            apt.virtual_tour_url().as_deref(),
+            Some("https://my.matterport.com/show/?m=zUqaEz4Qh9P")
+        );
+        assert_eq!(apt.disqualification(&qualifications), None);
+        assert_eq!(
+            apt.listed_subject(),
+            "Apartment 731 listed with virtual tour, available Oct 21 2022"
+        );
+    }
+
+    #[test]
+    fn test_unit_url() {
+        let apt = apartment_731();
+        let expected = "https://new.avaloncommunities.com/washington/seattle-apartments/\
+                        ava-capitol-hill/apartment/AVB-WA026-001-731/?floorPlan=f-b4v";
+        assert_eq!(apt.unit_url(crate::AVA_URL).as_deref(), Some(expected));
+        assert_eq!(
+            apt.unit_url(crate::AVA_URL.trim_end_matches('/'))
+                .as_deref(),
+            Some(expected)
+        );
+        assert_eq!(apt.unit_url("not a url"), None);
+    }
+
+    #[test]
+    fn test_derived_fields() {
+        // Saved before the derived fields existed.
+        let mut saved = apartment_731();
+        saved.floor = None;
+        saved.promotions[0].title = None;
+        let mut scraped = apartment_731();
+        scraped.page_url = scraped.unit_url(crate::AVA_URL);
+        scraped.promotions[0].concession = Some(Concession::MonthsFree(1.5));
+
+        saved.fill_derived_fields(crate::AVA_URL);
+        assert_eq!(saved.floor, Some(Floor(7)));
+        assert_eq!(saved, scraped);
+    }
+
+    #[test]
+    fn test_effective_rent() {
+        let mut apt = apartment_731();
+        assert_eq!(apt.effective_rent(), Money::from_dollars(4260.0));
+        assert_eq!(apt.best_deal(), None);
+
+        // One month free on a 12-month lease at $4400.
+        apt.rent.prices_per_movein_date[0].prices_per_terms.insert(
+            12,
+            Price {
+                price: Money::from_dollars(4400.0),
+                net_effective_price: Money::from_dollars(4400.0),
+            },
+        );
+        apt.promotions[0].concession = Some(Concession::MonthsFree(1.0));
+        assert_eq!(apt.effective_rent(), Money::from_dollars(4033.33));
+        assert_eq!(
+            apt.best_deal().unwrap().to_string(),
+            "12-month term at $4,033/mo, moving in Oct 21 2022"
+        );
+        assert_eq!(
+            apt.to_string(),
+            "Apartment 731 (7th floor, 2 bed 2 bath, $4,260 ($4,033 effective), \
+             1268sq/ft ($3.36/sq/ft), \
+             avail. Oct 21 2022, plan f-b4v)"
+        );
+
+        let qualifications = Qualifications {
+            rent: Bounds {
+                min: None,
+                max: Some(Money::from_dollars(4100.0)),
+            },
+            ..Default::default()
+        };
+        assert_eq!(apt.disqualification(&qualifications), Some("too expensive"));
+        assert_eq!(
+            apt.disqualification(&Qualifications {
+                use_effective_rent: true,
+                ..qualifications
+            }),
+            None
+        );
+    }
+
+    #[test]
+    fn test_disqualification() {
+        let apt = apartment_731();
+        assert_eq!(apt.disqualification(&Qualifications::default()), None);
+        assert_eq!(
+            apt.disqualification(&Qualifications {
+                bedrooms: Bounds::exactly(1),
+                ..Default::default()
+            }),
+            Some("too many bedrooms")
+        );
+        assert_eq!(
+            apt.disqualification(&Qualifications {
+                rent: Bounds {
+                    min: None,
+                    max: Some(Money::from_dollars(4000.0))
                 },
-                virtual_tour: None,
-                bedroom: 2,
-                bathroom: 2,
-                square_feet: 1268.0,
-                available_date: AvaDate(Utc.ymd(2022, 10, 21).and_hms_opt(4, 0, 0).unwrap()),
-                rent: Rent {
-                    applied_discount: 0.0,
-                    prices_per_movein_date: vec![PricesForMoveInDate {
-                        move_in_date: AvaDate(Utc.ymd(2022, 10, 21).and_hms_opt(4, 0, 0).unwrap()),
-                        prices_per_terms: maplit::btreemap! {
-                            2 => Price {
-                                price: 4720.0,
-                                net_effective_price: 4720.0
-                            }
-                        }
-                    }]
+                ..Default::default()
+            }),
+            Some("too expensive")
+        );
+        assert_eq!(
+            apt.disqualification(&Qualifications {
+                square_feet: Bounds {
+                    min: Some(1300.0),
+                    max: None
                 },
-                lowest_rent: LowestRent {
-                    date: AvaDate(Utc.ymd(2022, 10, 21).and_hms_opt(4, 0, 0).unwrap()),
-                    term_length: "8".to_string(),
-                    price: Price {
-                        price: 4260.0,
-                        net_effective_price: 4260.0,
-                    }
+                ..Default::default()
+            }),
+            Some("too small")
+        );
+        assert_eq!(
+            apt.disqualification(&Qualifications {
+                price_per_square_foot: Bounds {
+                    min: None,
+                    max: Some(3.25)
                 },
-                promotions: vec![ApplicablePromotion {
-                    promotion_id: "106246".to_string(),
-                    start_date: AvaDate(Utc.ymd(2022, 10, 5).and_hms_opt(4, 0, 0).unwrap()),
-                    end_date: Some(AvaDate(Utc.ymd(2022, 11, 30).and_hms_opt(4, 0, 0).unwrap())),
-                    terms: vec![12]
-                }],
-                extra: serde_json::Value::Object(serde_json::Map::new())
-            }
-            .to_string(),
-            "Apartment 731 (2 bed 2 bath, $4260, 1268sq/ft, avail. Oct 21 2022, plan f-b4v)"
+                ..Default::default()
+            }),
+            Some("too expensive per square foot")
+        );
+        assert_eq!(
+            apt.disqualification(&Qualifications {
+                available_after: NaiveDate::from_ymd_opt(2022, 11, 1),
+                ..Default::default()
+            }),
+            Some("available too soon")
+        );
+        assert_eq!(
+            apt.disqualification(&Qualifications {
+                available_after: NaiveDate::from_ymd_opt(2022, 10, 1),
+                available_before: NaiveDate::from_ymd_opt(2022, 10, 21),
+                ..Default::default()
+            }),
+            None
+        );
+        assert_eq!(
+            apt.disqualification(&Qualifications {
+                floor_plans: vec!["f-b2".to_owned(), "f-b4v".to_owned()],
+                ..Default::default()
+            }),
+            None
+        );
+        assert_eq!(
+            apt.disqualification(&Qualifications {
+                floor_plans: vec!["f-b2".to_owned()],
+                ..Default::default()
+            }),
+            Some("floor plan not allowed")
+        );
+        assert_eq!(
+            apt.disqualification(&Qualifications {
+                floor: Bounds {
+                    min: Some(8),
+                    max: None
+                },
+                ..Default::default()
+            }),
+            Some("too low")
+        );
+        assert_eq!(
+            apt.disqualification(&Qualifications {
+                filter: Some("floor >= 4".parse().unwrap()),
+                ..Default::default()
+            }),
+            None
+        );
+        assert_eq!(
+            apt.disqualification(&Qualifications {
+                exclude_floor_plans: vec!["f-b4v".to_owned()],
+                ..Default::default()
+            }),
+            Some("floor plan excluded")
+        );
+        assert_eq!(
+            apt.disqualification(&Qualifications {
+                filter: Some(
+                    "bedroom >= 2 && rent <= 4300 && (sqft / rent) > 0.28"
+                        .parse()
+                        .unwrap()
+                ),
+                ..Default::default()
+            }),
+            None
+        );
+        assert_eq!(
+            apt.disqualification(&Qualifications {
+                filter: Some("plan != \"f-b4v\"".parse().unwrap()),
+                ..Default::default()
+            }),
+            Some("doesn't match filter")
         );
     }
 }
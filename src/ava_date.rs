@@ -1,10 +1,23 @@
-use chrono::{DateTime, TimeZone, Utc};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
 use serde::{self, Deserialize, Deserializer, Serializer};
 
 /// A date format used by Avalon.
 /// Like `10/26/2022 4:00:00 AM +00:00`.
 const FORMAT: &'static str = "%m/%d/%Y %I:%M:%S %p %:z";
 
+/// Other formats with a time zone seen from Avalon, tried after [`FORMAT`].
+const FORMATS_WITH_OFFSET: &[&str] = &["%Y-%m-%dT%H:%M:%S%.f%:z", "%Y-%m-%dT%H:%M:%S%.f%z"];
+
+/// Formats without a time zone, which are assumed to be UTC.
+const FORMATS_WITHOUT_OFFSET: &[&str] = &[
+    "%m/%d/%Y %I:%M:%S %p",
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+];
+
+/// Bare dates, which are taken to be midnight UTC.
+const DATE_FORMATS: &[&str] = &["%Y-%m-%d", "%m/%d/%Y"];
+
 pub fn serialize<S>(date: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
@@ -18,6 +31,76 @@ where
     D: Deserializer<'de>,
 {
     let s = String::deserialize(deserializer)?;
-    Utc.datetime_from_str(&s, FORMAT)
-        .map_err(serde::de::Error::custom)
+    parse(&s).map_err(serde::de::Error::custom)
+}
+
+/// Parse a date in any of the formats Avalon uses, falling back to RFC 3339.
+fn parse(s: &str) -> Result<DateTime<Utc>, String> {
+    let s = s.trim();
+    std::iter::once(FORMAT)
+        .chain(FORMATS_WITH_OFFSET.iter().copied())
+        .find_map(|format| DateTime::parse_from_str(s, format).ok())
+        .map(|date| date.with_timezone(&Utc))
+        .or_else(|| {
+            FORMATS_WITHOUT_OFFSET
+                .iter()
+                .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+                .map(|date| Utc.from_utc_datetime(&date))
+        })
+        .or_else(|| {
+            DATE_FORMATS
+                .iter()
+                .find_map(|format| NaiveDate::parse_from_str(s, format).ok())
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+                .map(|date| Utc.from_utc_datetime(&date))
+        })
+        .or_else(|| {
+            DateTime::parse_from_rfc3339(s)
+                .ok()
+                .map(|date| date.with_timezone(&Utc))
+        })
+        .ok_or_else(|| {
+            format!(
+                "Unrecognized date {s:?}; expected a date like `10/26/2022 4:00:00 AM +00:00`, \
+                 `2022-10-26T04:00:00Z`, or `2022-10-26`"
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let expected = Utc.ymd(2022, 10, 26).and_hms_opt(4, 0, 0).unwrap();
+        assert_eq!(parse("10/26/2022 4:00:00 AM +00:00"), Ok(expected));
+        assert_eq!(parse("10/26/2022 12:00:00 AM -04:00"), Ok(expected));
+        assert_eq!(parse("2022-10-26T04:00:00Z"), Ok(expected));
+        assert_eq!(parse("2022-10-26T00:00:00-04:00"), Ok(expected));
+        assert_eq!(parse("2022-10-26T04:00:00.000+0000"), Ok(expected));
+        assert_eq!(parse("2022-10-26T04:00:00"), Ok(expected));
+        assert_eq!(parse("10/26/2022 4:00:00 AM"), Ok(expected));
+
+        let midnight = Utc.ymd(2022, 10, 26).and_hms_opt(0, 0, 0).unwrap();
+        assert_eq!(parse("2022-10-26"), Ok(midnight));
+        assert_eq!(parse("10/26/2022"), Ok(midnight));
+
+        let err = parse("next Tuesday").unwrap_err();
+        assert!(err.contains("\"next Tuesday\""), "{err}");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        #[derive(Debug, PartialEq, Deserialize, serde::Serialize)]
+        struct Date(#[serde(with = "super")] DateTime<Utc>);
+
+        let date = Date(Utc.ymd(2022, 10, 26).and_hms_opt(4, 0, 0).unwrap());
+        let json = serde_json::to_string(&date).unwrap();
+        assert_eq!(json, "\"10/26/2022 04:00:00 AM +00:00\"");
+        assert_eq!(serde_json::from_str::<Date>(&json).unwrap(), date);
+
+        let err = serde_json::from_str::<Date>("\"soon\"").unwrap_err();
+        assert!(err.to_string().contains("\"soon\""), "{err}");
+    }
 }
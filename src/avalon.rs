@@ -0,0 +1,114 @@
+//! Scraping listings from Avalon community pages, like [`crate::AVA_URL`].
+//!
+//! The listings are embedded in the page as JavaScript in a `<script id="fusion-metadata">`
+//! tag, which we evaluate with Node to get `Fusion.globalContent` as JSON.
+
+use std::sync::Arc;
+
+use camino::Utf8PathBuf;
+use color_eyre::eyre;
+use color_eyre::eyre::eyre;
+use soup::prelude::*;
+
+use crate::api;
+use crate::browser;
+use crate::http;
+use crate::node;
+
+const JS_PREFIX: &str = "window = {}; \
+                         window.Fusion = {}; \
+                         Fusion = window.Fusion; ";
+
+const JS_SUFFIX: &str = "console.log(JSON.stringify(Fusion.globalContent))";
+
+/// A client for fetching the listings on Avalon community pages.
+#[derive(Clone, Debug)]
+pub struct AvalonClient {
+    http: Arc<http::Client>,
+    /// Render pages in a headless browser when they can't be scraped directly.
+    headless_fallback: bool,
+    /// The Chromium executable for `headless_fallback`, found automatically if `None`.
+    chrome_executable: Option<Utf8PathBuf>,
+}
+
+impl AvalonClient {
+    pub fn new(http: Arc<http::Client>) -> Self {
+        Self {
+            http,
+            headless_fallback: false,
+            chrome_executable: None,
+        }
+    }
+
+    /// Render pages in a headless Chromium when they can't be scraped directly, e.g. when the
+    /// server returns a bot challenge.
+    ///
+    /// Requires the `headless-browser` feature.
+    pub fn headless_fallback(mut self, chrome_executable: Option<Utf8PathBuf>) -> Self {
+        self.headless_fallback = true;
+        self.chrome_executable = chrome_executable;
+        self
+    }
+
+    /// Fetch the listings on the community page at `url`.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_apartments(&self, url: &str) -> eyre::Result<api::ApartmentData> {
+        let response = self.http.get(url).await?;
+
+        tracing::trace!(?response, "Got response");
+
+        let body = response.text().await?;
+
+        tracing::trace!(html = body, "Got HTML");
+
+        // `Soup` isn't `Send`, so make sure it's dropped before we `.await` anything else.
+        let script_tag = Soup::new(&body)
+            .tag("script")
+            .attr("id", "fusion-metadata")
+            .find()
+            .map(|script_tag| script_tag.text());
+
+        let value = match script_tag {
+            Some(script_tag) => {
+                let script = format!("{JS_PREFIX}{script_tag}{JS_SUFFIX}");
+
+                tracing::trace!(script, "Extracted JavaScript");
+
+                let value = tracing::info_span!("js_eval").in_scope(|| node::js_eval(script))?;
+
+                tracing::trace!(value, "Evaluated JavaScript");
+
+                value
+            }
+            None if self.headless_fallback => {
+                tracing::warn!(
+                    "Could not find `<script id=\"fusion-metadata\">` tag, \
+                     falling back to headless browser"
+                );
+
+                let value =
+                    browser::fusion_global_content(url, self.chrome_executable.as_deref()).await?;
+
+                tracing::trace!(value, "Got `Fusion.globalContent` from headless browser");
+
+                value
+            }
+            None => {
+                return Err(eyre!(
+                    "Could not find `<script id=\"fusion-metadata\">` tag"
+                ));
+            }
+        };
+
+        let mut data: api::ApartmentData = tracing::info_span!("parse")
+            .in_scope(|| serde_json::from_str(&value))
+            .map_err(|err| format_serde_error::SerdeError::new(value.to_string(), err))?;
+
+        for apt in &mut data.apartments {
+            apt.source = url.to_owned();
+            apt.inner.page_url = apt.inner.unit_url(url);
+        }
+
+        Ok(data)
+    }
+}
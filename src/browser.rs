@@ -0,0 +1,74 @@
+//! A headless Chromium fallback for when the Avalon page can't be scraped directly.
+//!
+//! Sometimes the server returns a bot-challenge page, or a variant of the page without the
+//! `fusion-metadata` script tag. A real browser gets through those, so we can render the page
+//! and read `Fusion.globalContent` from the live DOM.
+
+use camino::Utf8Path;
+use color_eyre::eyre;
+#[cfg(feature = "headless-browser")]
+use color_eyre::eyre::eyre;
+#[cfg(feature = "headless-browser")]
+use color_eyre::eyre::Context;
+
+/// Render `url` in a headless Chromium and return `Fusion.globalContent` as a JSON string.
+///
+/// If `chrome_executable` is `None`, Chromium is located automatically.
+#[cfg(feature = "headless-browser")]
+#[tracing::instrument]
+pub async fn fusion_global_content(
+    url: &str,
+    chrome_executable: Option<&Utf8Path>,
+) -> eyre::Result<String> {
+    use chromiumoxide::Browser;
+    use chromiumoxide::BrowserConfig;
+    use futures::StreamExt;
+
+    let mut config = BrowserConfig::builder();
+    if let Some(path) = chrome_executable {
+        config = config.chrome_executable(path);
+    }
+    let (mut browser, mut handler) = Browser::launch(config.build().map_err(|err| eyre!(err))?)
+        .await
+        .wrap_err("Failed to launch headless Chromium")?;
+
+    // The handler drives the connection to the browser; it needs to be polled for anything to
+    // happen.
+    let handler = tokio::spawn(async move {
+        while let Some(event) = handler.next().await {
+            if event.is_err() {
+                break;
+            }
+        }
+    });
+
+    let value = async {
+        let page = browser.new_page(url).await?;
+        page.wait_for_navigation().await?;
+        let value: String = page
+            .evaluate("JSON.stringify(Fusion.globalContent)")
+            .await?
+            .into_value()?;
+        Ok::<_, eyre::Report>(value)
+    }
+    .await
+    .wrap_err("Failed to read `Fusion.globalContent` from headless Chromium");
+
+    if let Err(err) = browser.close().await {
+        tracing::warn!("Failed to close headless Chromium: {err}");
+    }
+    let _ = handler.await;
+
+    value
+}
+
+#[cfg(not(feature = "headless-browser"))]
+pub async fn fusion_global_content(
+    _url: &str,
+    _chrome_executable: Option<&Utf8Path>,
+) -> eyre::Result<String> {
+    Err(eyre::eyre!(
+        "`headless-fallback` is enabled, but ava-apartment-finder was built without the \
+         `headless-browser` feature"
+    ))
+}
@@ -0,0 +1,170 @@
+//! An iCalendar feed of when qualified units become available and when their promotions end,
+//! served at `/calendar.ics` by [`crate::server`] and optionally written to a file.
+
+use std::fmt::Write;
+
+use camino::Utf8Path;
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::NaiveDate;
+use chrono::Utc;
+use color_eyre::eyre;
+use color_eyre::eyre::Context;
+
+use crate::api::Apartment;
+use crate::listing::Listing;
+use crate::server::Snapshot;
+
+/// Render the calendar of currently listed units meeting the qualifications. `now` is used as
+/// the events' timestamp if we haven't ticked yet.
+pub fn render(snapshot: &Snapshot, now: DateTime<Utc>) -> String {
+    let stamp = snapshot.last_tick.unwrap_or(now).format("%Y%m%dT%H%M%SZ");
+    let mut events = String::new();
+
+    for apt in snapshot.known_apartments.values() {
+        if !apt.inner.meets_qualifications(&snapshot.qualifications) {
+            continue;
+        }
+        add_availability(&mut events, &stamp, apt);
+        for (index, end) in apt.inner.promotion_end_dates().into_iter().enumerate() {
+            add_event(
+                &mut events,
+                &stamp,
+                &format!("{}-promotion-{index}", apt.id()),
+                end,
+                &format!("Promotion ends: Apartment {}", apt.inner.number),
+                &apt.inner.to_string(),
+                &page(apt),
+            );
+        }
+    }
+    for post in snapshot.known_posts.values() {
+        if post.inner.meets_qualifications(&snapshot.qualifications) {
+            add_availability(&mut events, &stamp, post);
+        }
+    }
+
+    let mut calendar = String::new();
+    for line in [
+        "BEGIN:VCALENDAR",
+        "VERSION:2.0",
+        "PRODID:-//ava-apartment-finder//EN",
+        "X-WR-CALNAME:Apartment availability",
+    ] {
+        let _ = write!(calendar, "{line}\r\n");
+    }
+    calendar.push_str(&events);
+    calendar.push_str("END:VCALENDAR\r\n");
+    calendar
+}
+
+/// Write the calendar to `path`.
+pub fn write(path: &Utf8Path, snapshot: &Snapshot) -> eyre::Result<()> {
+    std::fs::write(path, render(snapshot, Utc::now()))
+        .wrap_err_with(|| format!("Failed to write calendar to `{path}`"))
+}
+
+fn add_availability<T: Listing>(
+    events: &mut String,
+    stamp: &impl std::fmt::Display,
+    apt: &Apartment<T>,
+) {
+    if let Some(available) = apt.inner.available_date() {
+        add_event(
+            events,
+            stamp,
+            &format!("{}-available", apt.id()),
+            available,
+            &format!("Available: {}", apt.inner),
+            &apt.inner.to_string(),
+            &page(apt),
+        );
+    }
+}
+
+/// `apt`'s own page, or the page it was listed on if it doesn't have one.
+fn page<T: Listing>(apt: &Apartment<T>) -> String {
+    apt.inner.url().unwrap_or_else(|| apt.source.clone())
+}
+
+/// Add an all-day event on `date`, linking to `url`.
+fn add_event(
+    events: &mut String,
+    stamp: &impl std::fmt::Display,
+    uid: &str,
+    date: NaiveDate,
+    summary: &str,
+    description: &str,
+    url: &str,
+) {
+    let end = date + Duration::days(1);
+    for line in [
+        "BEGIN:VEVENT".to_owned(),
+        format!("UID:{}@ava-apartment-finder", escape(uid)),
+        format!("DTSTAMP:{stamp}"),
+        format!("DTSTART;VALUE=DATE:{}", date.format("%Y%m%d")),
+        format!("DTEND;VALUE=DATE:{}", end.format("%Y%m%d")),
+        format!("SUMMARY:{}", escape(summary)),
+        format!("DESCRIPTION:{}", escape(description)),
+        format!("URL:{url}"),
+        "END:VEVENT".to_owned(),
+    ] {
+        events.push_str(&fold(&line));
+    }
+}
+
+/// Escape `text` for use in a property value.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Fold `line` so no line is longer than 75 bytes, and terminate it with CRLF.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 2);
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            // The leading space counts towards the line length.
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape("2 bed, 2 bath; $4260\nnice"),
+            "2 bed\\, 2 bath\\; $4260\\nnice"
+        );
+    }
+
+    #[test]
+    fn test_fold() {
+        assert_eq!(fold("SUMMARY:short"), "SUMMARY:short\r\n");
+        let folded = fold(&format!("DESCRIPTION:{}", "x".repeat(100)));
+        let lines: Vec<_> = folded.split("\r\n").collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].len(), 75);
+        assert_eq!(lines[1], format!(" {}", "x".repeat(37)));
+        assert_eq!(lines[2], "");
+    }
+}
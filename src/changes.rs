@@ -0,0 +1,270 @@
+//! Field-by-field changes to a listing, like `rent: $4,260 → $4,195`, for notifications about
+//! changed listings.
+//!
+//! Insignificant changes can be ignored, configured like:
+//!
+//! ```toml
+//! [ignore-changes]
+//! rent-under = 25
+//! floor-plan-images = true
+//! promotion-order = true
+//! fields = ["lease term"]
+//! ```
+//!
+//! Nothing is ignored by default. Listings with only ignored changes aren't reported as
+//! changed, but their new data is still saved. Changes are measured from the data we last
+//! reported, so small changes which add up, like rent falling $20 every day, are reported once
+//! they're past the thresholds.
+
+use std::fmt::Display;
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use crate::money::Money;
+
+/// A field's value before and after a change.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change<T> {
+    pub old: T,
+    pub new: T,
+}
+
+impl<T: PartialEq> Change<T> {
+    /// The change from `old` to `new`, or `None` if they're the same.
+    pub fn new(old: T, new: T) -> Option<Self> {
+        (old != new).then_some(Self { old, new })
+    }
+}
+
+/// A change to one field of a listing.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FieldChange {
+    Rent(Change<Money>),
+    /// The rent with promotions amortized over the lease; see
+    /// [`crate::api::ApiApartment::effective_rent`].
+    EffectiveRent(Change<Money>),
+    Available(Change<NaiveDate>),
+    /// The lease term the lowest rent is for, in months.
+    LeaseTerm(Change<String>),
+    Bedrooms(Change<usize>),
+    Bathrooms(Change<usize>),
+    SquareFeet(Change<f64>),
+    FloorPlan(Change<String>),
+    Furnished(Change<bool>),
+    VirtualTour(Change<bool>),
+    /// How many promotions apply.
+    Promotions(Change<usize>),
+    Title(Change<String>),
+    /// Some other field changed, which isn't interesting enough to show.
+    Other(&'static str),
+}
+
+impl FieldChange {
+    /// The name of the field that changed.
+    pub fn field(&self) -> &'static str {
+        match self {
+            FieldChange::Rent(_) => "rent",
+            FieldChange::EffectiveRent(_) => "effective rent",
+            FieldChange::Available(_) => "available",
+            FieldChange::LeaseTerm(_) => "lease term",
+            FieldChange::Bedrooms(_) => "bedrooms",
+            FieldChange::Bathrooms(_) => "bathrooms",
+            FieldChange::SquareFeet(_) => "square feet",
+            FieldChange::FloorPlan(_) => "floor plan",
+            FieldChange::Furnished(_) => "furnished",
+            FieldChange::VirtualTour(_) => "virtual tour",
+            FieldChange::Promotions(_) => "promotions",
+            FieldChange::Title(_) => "title",
+            FieldChange::Other(field) => field,
+        }
+    }
+}
+
+impl Display for FieldChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let field = self.field();
+        let yes_no = |value: bool| if value { "yes" } else { "no" };
+        match self {
+            FieldChange::Rent(Change { old, new })
+            | FieldChange::EffectiveRent(Change { old, new }) => {
+                write!(f, "{field}: {old} → {new}")
+            }
+            FieldChange::Available(Change { old, new }) => write!(
+                f,
+                "{field}: {} → {}",
+                old.format("%b %-d %Y"),
+                new.format("%b %-d %Y")
+            ),
+            FieldChange::LeaseTerm(Change { old, new }) => {
+                write!(f, "{field}: {old} months → {new} months")
+            }
+            FieldChange::Bedrooms(Change { old, new })
+            | FieldChange::Bathrooms(Change { old, new })
+            | FieldChange::Promotions(Change { old, new }) => write!(f, "{field}: {old} → {new}"),
+            FieldChange::SquareFeet(Change { old, new }) => write!(f, "{field}: {old} → {new}"),
+            FieldChange::FloorPlan(Change { old, new })
+            | FieldChange::Title(Change { old, new }) => {
+                write!(f, "{field}: {old:?} → {new:?}")
+            }
+            FieldChange::Furnished(Change { old, new })
+            | FieldChange::VirtualTour(Change { old, new }) => {
+                write!(f, "{field}: {} → {}", yes_no(*old), yes_no(*new))
+            }
+            FieldChange::Other(_) => write!(f, "{field} changed"),
+        }
+    }
+}
+
+/// The [`FieldChange::Other`] field for floor plan image URLs, which often change just to bust
+/// caches.
+pub const FLOOR_PLAN_IMAGES: &str = "floor plan images";
+
+/// The [`FieldChange::Other`] field for the same promotions being listed in a different order.
+pub const PROMOTION_ORDER: &str = "promotion order";
+
+/// The [`FieldChange::Other`] field for the table of prices for each move-in date and lease
+/// term, which only matters through the effective rent.
+pub const PRICES_BY_MOVE_IN_DATE: &str = "prices by move-in date";
+
+/// Which changes are too small to report.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct IgnoreChanges {
+    /// Ignore rent and effective rent changes smaller than this many dollars.
+    ///
+    /// When this is set, changes to the prices for other move-in dates and lease terms are
+    /// only noticed through the effective rent.
+    pub rent_under: Money,
+    /// Ignore changes to floor plan image URLs.
+    pub floor_plan_images: bool,
+    /// Ignore promotions being listed in a different order.
+    pub promotion_order: bool,
+    /// Ignore changes to these fields, named like in notifications, like `lease term`.
+    pub fields: Vec<String>,
+}
+
+impl Default for IgnoreChanges {
+    fn default() -> Self {
+        Self {
+            rent_under: Money::ZERO,
+            floor_plan_images: false,
+            promotion_order: false,
+            fields: Vec::new(),
+        }
+    }
+}
+
+impl IgnoreChanges {
+    /// Is `change` too small to report?
+    pub fn ignores(&self, change: &FieldChange) -> bool {
+        let field = change.field();
+        match change {
+            FieldChange::Rent(Change { old, new })
+            | FieldChange::EffectiveRent(Change { old, new })
+                if (*new - *old).abs() < self.rent_under =>
+            {
+                true
+            }
+            FieldChange::Other(FLOOR_PLAN_IMAGES) if self.floor_plan_images => true,
+            FieldChange::Other(PROMOTION_ORDER) if self.promotion_order => true,
+            FieldChange::Other(PRICES_BY_MOVE_IN_DATE) if self.rent_under > Money::ZERO => true,
+            _ => self.fields.iter().any(|ignored| ignored == field),
+        }
+    }
+
+    /// Are any of `changes` worth reporting?
+    pub fn any_significant(&self, changes: &[FieldChange]) -> bool {
+        changes.iter().any(|change| !self.ignores(change))
+    }
+}
+
+/// Summarize `changes` on one line, like `rent: $4,260 → $4,195; available: ...`.
+///
+/// Uninteresting [`FieldChange::Other`] changes are left out unless they're all there is.
+pub fn summary(changes: &[FieldChange]) -> String {
+    let interesting: Vec<String> = changes
+        .iter()
+        .filter(|change| !matches!(change, FieldChange::Other(_)))
+        .map(ToString::to_string)
+        .collect();
+    if interesting.is_empty() {
+        changes
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("; ")
+    } else {
+        interesting.join("; ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let rent = FieldChange::Rent(
+            Change::new(Money::from_dollars(4260.0), Money::from_dollars(4195.0)).unwrap(),
+        );
+        assert_eq!(rent.to_string(), "rent: $4,260 → $4,195");
+
+        let available = FieldChange::Available(
+            Change::new(
+                NaiveDate::from_ymd_opt(2022, 10, 21).unwrap(),
+                NaiveDate::from_ymd_opt(2022, 11, 4).unwrap(),
+            )
+            .unwrap(),
+        );
+        assert_eq!(available.to_string(), "available: Oct 21 2022 → Nov 4 2022");
+
+        assert_eq!(
+            FieldChange::VirtualTour(Change::new(false, true).unwrap()).to_string(),
+            "virtual tour: no → yes"
+        );
+        assert_eq!(Change::new(2, 2), None);
+
+        let other = FieldChange::Other("floor plan images");
+        assert_eq!(
+            summary(&[rent.clone(), other.clone(), available]),
+            "rent: $4,260 → $4,195; available: Oct 21 2022 → Nov 4 2022"
+        );
+        assert_eq!(summary(&[other]), "floor plan images changed");
+    }
+
+    #[test]
+    fn test_ignore_changes() {
+        let rent = |old, new| {
+            FieldChange::Rent(
+                Change::new(Money::from_dollars(old), Money::from_dollars(new)).unwrap(),
+            )
+        };
+        let ignore = IgnoreChanges::default();
+        assert!(!ignore.ignores(&rent(4260.0, 4255.0)));
+        assert!(!ignore.ignores(&FieldChange::Other(FLOOR_PLAN_IMAGES)));
+        assert!(!ignore.ignores(&FieldChange::Other(PROMOTION_ORDER)));
+        assert!(!ignore.ignores(&FieldChange::Other(PRICES_BY_MOVE_IN_DATE)));
+        assert!(!ignore.any_significant(&[]));
+
+        let ignore = IgnoreChanges {
+            rent_under: Money::from_dollars(10.0),
+            floor_plan_images: true,
+            promotion_order: true,
+            fields: vec!["lease term".to_owned()],
+        };
+        assert!(ignore.ignores(&FieldChange::Other(FLOOR_PLAN_IMAGES)));
+        assert!(ignore.ignores(&FieldChange::Other(PROMOTION_ORDER)));
+        assert!(ignore.ignores(&rent(4260.0, 4255.0)));
+        assert!(ignore.ignores(&rent(4255.0, 4260.0)));
+        assert!(!ignore.ignores(&rent(4260.0, 4250.0)));
+        assert!(ignore.ignores(&FieldChange::Other(PRICES_BY_MOVE_IN_DATE)));
+        assert!(ignore.ignores(&FieldChange::LeaseTerm(
+            Change::new("8".to_owned(), "12".to_owned()).unwrap()
+        )));
+        assert!(ignore.any_significant(&[
+            rent(4260.0, 4255.0),
+            FieldChange::Bedrooms(Change::new(1, 2).unwrap()),
+        ]));
+    }
+}
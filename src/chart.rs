@@ -0,0 +1,123 @@
+//! Price history charts, attached to notifications.
+
+use chrono::DateTime;
+use chrono::Utc;
+use color_eyre::eyre;
+use color_eyre::eyre::eyre;
+use plotters::prelude::*;
+
+use crate::jmap::Attachment;
+use crate::money::Money;
+use crate::timezone;
+
+const SIZE: (u32, u32) = (480, 240);
+
+/// Draw `history` (rents over time, oldest first) as an SVG line chart, extended to `now`.
+///
+/// Returns `None` if there's less than two points of history, which wouldn't be much of a chart.
+pub fn price_history(
+    title: &str,
+    history: &[(DateTime<Utc>, Money)],
+    now: DateTime<Utc>,
+) -> eyre::Result<Option<String>> {
+    if history.len() < 2 {
+        return Ok(None);
+    }
+    let history: Vec<(DateTime<Utc>, f64)> = history
+        .iter()
+        .map(|&(time, rent)| (time, rent.dollars()))
+        .collect();
+
+    // Rent changes are instantaneous, so draw a step chart rather than interpolating.
+    let mut points = Vec::with_capacity(history.len() * 2);
+    for window in history.windows(2) {
+        let [(time, rent), (next_time, _)] = [window[0], window[1]];
+        points.push((time, rent));
+        points.push((next_time, rent));
+    }
+    let (last_time, last_rent) = history[history.len() - 1];
+    points.push((last_time, last_rent));
+    points.push((now.max(last_time), last_rent));
+
+    let start = history[0].0;
+    let end = now.max(last_time);
+    let min = history
+        .iter()
+        .map(|(_, rent)| *rent)
+        .fold(f64::INFINITY, f64::min);
+    let max = history
+        .iter()
+        .map(|(_, rent)| *rent)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let padding = ((max - min) * 0.1).max(10.0);
+
+    let mut svg = String::new();
+    {
+        let root = SVGBackend::with_string(&mut svg, SIZE).into_drawing_area();
+        root.fill(&WHITE).map_err(|err| eyre!("{err}"))?;
+        let mut chart = ChartBuilder::on(&root)
+            .caption(title, ("sans-serif", 16))
+            .margin(8)
+            .x_label_area_size(24)
+            .y_label_area_size(56)
+            .build_cartesian_2d(start..end, (min - padding)..(max + padding))
+            .map_err(|err| eyre!("{err}"))?;
+        chart
+            .configure_mesh()
+            .x_labels(4)
+            .y_labels(5)
+            .x_label_formatter(&|time| timezone::format(*time, "%b %e"))
+            .y_label_formatter(&|rent| Money::from_dollars(*rent).round().to_string())
+            .draw()
+            .map_err(|err| eyre!("{err}"))?;
+        chart
+            .draw_series(LineSeries::new(points, &BLUE))
+            .map_err(|err| eyre!("{err}"))?;
+        root.present().map_err(|err| eyre!("{err}"))?;
+    }
+
+    Ok(Some(svg))
+}
+
+/// Draw `history` and wrap it up as an email attachment named after `id`.
+///
+/// Failing to draw a chart shouldn't stop a notification, so errors are logged and ignored.
+pub fn attachment(id: &str, history: &[(DateTime<Utc>, Money)]) -> Option<Attachment> {
+    match price_history(&format!("Rent for {id}"), history, Utc::now()) {
+        Ok(svg) => svg.map(|svg| Attachment {
+            filename: format!("{id}-rent.svg"),
+            content_type: "image/svg+xml".to_owned(),
+            data: svg.into_bytes(),
+        }),
+        Err(err) => {
+            tracing::warn!(id, "Failed to draw price history chart: {err:?}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_price_history() {
+        let day = |day| Utc.ymd(2022, 10, day).and_hms_opt(12, 0, 0).unwrap();
+        let now = day(21);
+        let dollars = Money::from_dollars;
+
+        assert_eq!(
+            price_history("", &[(day(1), dollars(4260.0))], now).unwrap(),
+            None
+        );
+
+        let history = [(day(1), dollars(4260.0)), (day(10), dollars(4100.0))];
+        let svg = price_history("Rent for 731", &history, now)
+            .unwrap()
+            .unwrap();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("Rent for 731"));
+    }
+}
@@ -0,0 +1,182 @@
+//! Whether to use colors, hyperlinks, and non-ASCII glyphs in terminal output.
+
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+
+use color_eyre::config::HookBuilder;
+use color_eyre::config::Theme;
+use color_eyre::eyre;
+
+/// When to use colors, set with `--color`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// Use colors if stdout is a terminal and `NO_COLOR` isn't set.
+    Auto,
+    Always,
+    Never,
+}
+
+static ASCII: AtomicBool = AtomicBool::new(false);
+static HYPERLINKS: AtomicBool = AtomicBool::new(false);
+
+/// Glyphs we use in terminal output, and the ASCII characters to replace them with.
+const GLYPHS: &[(&str, &str)] = &[("• ", "* "), ("⚠ ", "! ")];
+
+/// Apply `--color` and `--ascii` to all terminal output, and install the error report handler
+/// to match.
+pub fn init(choice: ColorChoice, ascii: bool) -> eyre::Result<()> {
+    // See: https://no-color.org/
+    let no_color = std::env::var_os("NO_COLOR").map_or(false, |value| !value.is_empty());
+    let colors = match choice {
+        ColorChoice::Always => Some(true),
+        ColorChoice::Never => Some(false),
+        ColorChoice::Auto if no_color => Some(false),
+        ColorChoice::Auto => None,
+    };
+    if let Some(colors) = colors {
+        owo_colors::set_override(colors);
+    }
+    ASCII.store(ascii, Ordering::Relaxed);
+    HYPERLINKS.store(
+        colors != Some(false) && supports_hyperlinks::on(supports_hyperlinks::Stream::Stdout),
+        Ordering::Relaxed,
+    );
+
+    let hook = HookBuilder::default();
+    if colors == Some(false) {
+        hook.theme(Theme::new()).install()
+    } else {
+        hook.install()
+    }
+}
+
+/// A bullet point and a space, like `• `.
+pub fn bullet() -> &'static str {
+    glyph(0)
+}
+
+/// A warning sign and a space, like `⚠ `.
+pub fn warning() -> &'static str {
+    glyph(1)
+}
+
+fn glyph(index: usize) -> &'static str {
+    let (glyph, ascii) = GLYPHS[index];
+    if ASCII.load(Ordering::Relaxed) {
+        ascii
+    } else {
+        glyph
+    }
+}
+
+/// Mark `text` as linking to `url`, like a Markdown link: `[text](url)`.
+///
+/// [`for_terminal`] turns these into clickable terminal hyperlinks, or plain URLs if the
+/// terminal doesn't support them. Elsewhere, like in log files, they're left as-is.
+pub fn link(text: impl Display, url: &str) -> String {
+    format!("[{text}]({url})")
+}
+
+/// Render [`link`]s in `text` and replace its glyphs with ASCII if `--ascii` is set. For text
+/// that's also used elsewhere, like in emails and log files.
+pub fn for_terminal(text: &str) -> Cow<'_, str> {
+    let text = if find_link(text).is_some() {
+        Cow::Owned(render_links(text, HYPERLINKS.load(Ordering::Relaxed)))
+    } else {
+        Cow::Borrowed(text)
+    };
+    if ASCII.load(Ordering::Relaxed) {
+        Cow::Owned(to_ascii(&text))
+    } else {
+        text
+    }
+}
+
+/// Replace each `[text](url)` with an OSC 8 terminal hyperlink if `hyperlinks` is set, or
+/// `text (url)` otherwise. The URL is left out if `text` already includes it.
+fn render_links(text: &str, hyperlinks: bool) -> String {
+    let mut rendered = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some((start, label_end, end)) = find_link(rest) {
+        rendered.push_str(&rest[..start]);
+        let label = &rest[start + 1..label_end];
+        let url = &rest[label_end + 2..end - 1];
+        if hyperlinks {
+            rendered.push_str(&format!("\x1b]8;;{url}\x1b\\{label}\x1b]8;;\x1b\\"));
+        } else if label.contains(url) {
+            rendered.push_str(label);
+        } else {
+            rendered.push_str(&format!("{label} ({url})"));
+        }
+        rest = &rest[end..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Find the first `[text](url)` in `text`, returning the indices of its `[`, the `]` ending its
+/// text, and the end of the link. Brackets in the link text have to be balanced.
+fn find_link(text: &str) -> Option<(usize, usize, usize)> {
+    text.match_indices("](").find_map(|(label_end, _)| {
+        let url = &text[label_end + 2..];
+        let url_len = url.find(|c: char| c == ')' || c.is_whitespace())?;
+        if !url.starts_with("http") || !url[url_len..].starts_with(')') {
+            return None;
+        }
+        let mut depth = 0;
+        for (i, c) in text[..label_end].char_indices().rev() {
+            match c {
+                ']' => depth += 1,
+                '[' if depth == 0 => return Some((i, label_end, label_end + 2 + url_len + 1)),
+                '[' => depth -= 1,
+                '\n' => return None,
+                _ => {}
+            }
+        }
+        None
+    })
+}
+
+fn to_ascii(text: &str) -> String {
+    GLYPHS.iter().fold(text.to_owned(), |text, (glyph, ascii)| {
+        text.replace(glyph, ascii)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_links() {
+        let text = format!(
+            "Newly listed apartments:\n• {}\n• {}",
+            link("Apartment 731", "https://example.com/ava"),
+            link(
+                "Craigslist post \"[NEW] 2br\" (https://example.com/post)",
+                "https://example.com/post"
+            ),
+        );
+        assert_eq!(
+            render_links(&text, false),
+            "Newly listed apartments:\n\
+            • Apartment 731 (https://example.com/ava)\n\
+            • Craigslist post \"[NEW] 2br\" (https://example.com/post)"
+        );
+        assert_eq!(
+            render_links(&link("731", "https://example.com"), true),
+            "\x1b]8;;https://example.com\x1b\\731\x1b]8;;\x1b\\"
+        );
+        assert_eq!(render_links("[not](a link)", true), "[not](a link)");
+    }
+
+    #[test]
+    fn test_to_ascii() {
+        assert_eq!(
+            to_ascii("Unlisted apartments:\n• 731\n⚠ 732"),
+            "Unlisted apartments:\n* 731\n! 732"
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! A side-by-side table of units, for the `compare` subcommand.
+
+use chrono::DateTime;
+use chrono::Utc;
+
+use crate::api::Apartment;
+use crate::filter::Value;
+use crate::listing::Listing;
+use crate::money::Money;
+use crate::score::ScoreWeights;
+
+/// The rows of the table, after the header row of unit numbers.
+const ROWS: [&str; 7] = [
+    "Rent",
+    "$/sq ft",
+    "Floor",
+    "Available",
+    "Effective rent",
+    "Days listed",
+    "Score",
+];
+
+/// A column of the table: the unit's number (or ID, if it doesn't have one) and a cell for each
+/// of [`ROWS`], as of `now`.
+pub fn column<T: Listing>(
+    apt: &Apartment<T>,
+    weights: &ScoreWeights,
+    now: DateTime<Utc>,
+) -> Vec<String> {
+    let field = |name| apt.inner.field(name);
+    let money = |name| match field(name) {
+        Some(Value::Number(dollars)) => Money::from_dollars(dollars).to_string(),
+        _ => String::new(),
+    };
+    let header = match field("number") {
+        Some(Value::String(number)) => number,
+        _ => apt.id().to_owned(),
+    };
+    let floor = match field("floor") {
+        Some(Value::Number(floor)) => floor.to_string(),
+        _ => String::new(),
+    };
+    let available = apt
+        .inner
+        .available_date()
+        .map(|date| date.format("%b %e %Y").to_string())
+        .unwrap_or_default();
+    let days_listed = (apt.unlisted.unwrap_or(now) - apt.listed).num_days();
+    let score = weights.score(&apt.inner, now.date_naive());
+    vec![
+        header,
+        money("rent"),
+        money("price_per_sqft"),
+        floor,
+        available,
+        money("effective_rent"),
+        days_listed.to_string(),
+        format!("{score:.1}"),
+    ]
+}
+
+/// Lay out `columns` from [`column`] as a table, with a label for each row.
+pub fn render(columns: &[Vec<String>]) -> String {
+    let labels: Vec<&str> = std::iter::once("").chain(ROWS).collect();
+    let width = |cells: &mut dyn Iterator<Item = &str>| {
+        cells.map(|cell| cell.chars().count()).max().unwrap_or(0)
+    };
+    let label_width = width(&mut labels.iter().copied());
+    let widths: Vec<usize> = columns
+        .iter()
+        .map(|column| width(&mut column.iter().map(String::as_str)))
+        .collect();
+
+    let mut table = String::new();
+    for (row, label) in labels.iter().enumerate() {
+        table.push_str(&format!("{label:<label_width$}"));
+        for (column, width) in columns.iter().zip(&widths) {
+            table.push_str(&format!("  {:>width$}", column[row]));
+        }
+        table.truncate(table.trim_end().len());
+        table.push('\n');
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use expect_test::expect;
+
+    use super::*;
+    use crate::api::fixtures::apartment_731;
+    use crate::api::fixtures::apartment_731_at;
+    use crate::api::fixtures::craigslist_post;
+    use crate::api::fixtures::CRAIGSLIST_SEARCH;
+
+    #[test]
+    fn test_render() {
+        let now = Utc.ymd(2022, 10, 21).and_hms_opt(12, 0, 0).unwrap();
+        let weights = ScoreWeights::default();
+        let mut apt = Apartment::new(crate::AVA_URL, apartment_731());
+        apt.listed = now - chrono::Duration::days(12);
+        let mut cheaper = Apartment::new(crate::AVA_URL, apartment_731_at(4100.0));
+        cheaper.listed = now - chrono::Duration::days(3);
+        let mut post = Apartment::new(CRAIGSLIST_SEARCH, craigslist_post());
+        post.listed = now;
+
+        let columns = [
+            column(&apt, &weights, now),
+            column(&cheaper, &weights, now),
+            column(&post, &weights, now),
+        ];
+        expect![[r#"
+                                    731          731  craigslist-7551234567
+            Rent                 $4,260       $4,100                 $3,000
+            $/sq ft               $3.36        $3.23
+            Floor                     7            7
+            Available       Oct 21 2022  Oct 21 2022
+            Effective rent       $4,260       $4,100
+            Days listed              12            3                      0
+            Score                 -29.9        -28.3                  -30.0
+        "#]]
+        .assert_eq(&render(&columns));
+    }
+}
@@ -0,0 +1,111 @@
+//! Lease concessions from promotions, like "1 month free on a 12-month lease".
+
+use serde::Deserialize;
+use serde::Serialize;
+
+/// A discount on a whole lease.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Concession {
+    /// Some number of months of rent free.
+    MonthsFree(f64),
+    /// Some number of weeks of rent free.
+    WeeksFree(f64),
+    /// A flat number of dollars off.
+    DollarsOff(f64),
+}
+
+impl Concession {
+    /// Parse a concession from promotion text like "6 Weeks Free!" or "$500 off your first
+    /// month".
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.to_lowercase();
+        let words: Vec<&str> = text
+            .split(|c: char| !(c.is_alphanumeric() || matches!(c, '$' | '.' | ',')))
+            .map(|word| word.trim_end_matches(['.', ',']))
+            .filter(|word| !word.is_empty())
+            .collect();
+
+        words
+            .windows(2)
+            .find_map(|pair| match pair {
+                [amount, "off"] => amount
+                    .strip_prefix('$')
+                    .and_then(|amount| amount.replace(',', "").parse().ok())
+                    .map(Concession::DollarsOff),
+                _ => None,
+            })
+            .or_else(|| {
+                words.windows(3).find_map(|triple| match triple {
+                    [count, "month" | "months", "free"] => {
+                        parse_count(count).map(Concession::MonthsFree)
+                    }
+                    [count, "week" | "weeks", "free"] => {
+                        parse_count(count).map(Concession::WeeksFree)
+                    }
+                    _ => None,
+                })
+            })
+    }
+
+    /// The effective monthly rent for a lease of `term` months at `rent` per month, with this
+    /// concession amortized over the lease.
+    pub fn effective_rent(&self, rent: f64, term: usize) -> f64 {
+        let term = term.max(1) as f64;
+        let discount = match *self {
+            Concession::MonthsFree(months) => months.min(term) * rent,
+            Concession::WeeksFree(weeks) => (weeks * 12.0 / 52.0).min(term) * rent,
+            Concession::DollarsOff(dollars) => dollars.min(term * rent),
+        };
+        (term * rent - discount) / term
+    }
+}
+
+fn parse_count(word: &str) -> Option<f64> {
+    Some(match word {
+        "a" | "one" => 1.0,
+        "two" => 2.0,
+        "three" => 3.0,
+        "four" => 4.0,
+        "five" => 5.0,
+        "six" => 6.0,
+        "seven" => 7.0,
+        "eight" => 8.0,
+        _ => return word.parse().ok(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Concession::parse("1 Month Free on 12-month leases"),
+            Some(Concession::MonthsFree(1.0))
+        );
+        assert_eq!(
+            Concession::parse("Up to Six Weeks Free!"),
+            Some(Concession::WeeksFree(6.0))
+        );
+        assert_eq!(
+            Concession::parse("Get $1,000 off your move-in."),
+            Some(Concession::DollarsOff(1000.0))
+        );
+        assert_eq!(Concession::parse("Reduced deposit"), None);
+    }
+
+    #[test]
+    fn test_effective_rent() {
+        assert_eq!(
+            Concession::MonthsFree(1.0).effective_rent(3900.0, 12),
+            3575.0
+        );
+        assert_eq!(
+            Concession::DollarsOff(1200.0).effective_rent(3000.0, 12),
+            2900.0
+        );
+        assert_eq!(Concession::MonthsFree(2.0).effective_rent(3000.0, 1), 0.0);
+    }
+}
@@ -0,0 +1,399 @@
+//! User configuration, read from a TOML file.
+
+use std::net::SocketAddr;
+
+use camino::Utf8Path;
+use camino::Utf8PathBuf;
+use color_eyre::eyre;
+use color_eyre::eyre::eyre;
+use color_eyre::eyre::Context;
+use jmap_client::email::EmailAddress;
+use serde::Deserialize;
+
+use crate::affordability::Affordability;
+use crate::airtable::AirtableConfig;
+use crate::changes::IgnoreChanges;
+use crate::geo::GeocodingConfig;
+use crate::http::Fixtures;
+use crate::http::RateLimit;
+use crate::jmap::JmapConfig;
+use crate::lease_term::MoveInWindow;
+use crate::market_report::Schedule;
+use crate::money::Currency;
+use crate::mqtt::MqttConfig;
+use crate::notion::NotionConfig;
+use crate::polling::Polling;
+use crate::price_drop::PriceDropAlert;
+use crate::qualifications::Qualifications;
+use crate::quiet_hours::QuietHours;
+use crate::sanity::SanityChecks;
+use crate::score::ScoreWeights;
+use crate::sheets::SheetsConfig;
+use crate::social::Account;
+use crate::social::SocialConfig;
+use crate::source::Source;
+use crate::statsd::StatsdConfig;
+use crate::timezone::DisplayTimeZone;
+use crate::trace::LogConfig;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    /// Avalon community pages to track.
+    pub communities: Vec<String>,
+
+    /// Craigslist search URLs (or their RSS feeds) to track alongside Avalon.
+    pub craigslist: Vec<String>,
+
+    /// The maximum number of sources to fetch at once.
+    pub max_concurrent_fetches: usize,
+
+    /// How often to check for new listings; see [`Polling`].
+    pub polling: Polling,
+
+    /// Rate limits for scraping, to stay polite and avoid getting banned.
+    pub rate_limit: RateLimit,
+
+    /// Fetch pages even if `robots.txt` disallows it.
+    pub ignore_robots_txt: bool,
+
+    /// Record or replay HTTP responses; only set from the command line.
+    #[serde(skip)]
+    pub fixtures: Option<Fixtures>,
+
+    /// Which apartments to notify about.
+    pub qualifications: Qualifications,
+
+    /// Notify about every apartment, not just the ones meeting `qualifications`.
+    pub notify_all: bool,
+
+    /// How to rank listings in notifications and `list` output.
+    pub score: ScoreWeights,
+
+    /// Send one email per tick listing every new apartment, best first, rather than one email
+    /// per apartment.
+    pub digest: bool,
+
+    /// When one tick would send more than this many notifications, like when a site
+    /// republishes all its listings at once, send one summary email instead, with sections for
+    /// new, unlisted, and changed units. Watched units going to `watch-to` still get their own
+    /// emails.
+    pub burst_threshold: Option<usize>,
+
+    /// Hold non-urgent notifications, like digests and unlisted units, until the morning.
+    ///
+    /// See [`crate::quiet_hours`] for the options.
+    pub quiet_hours: Option<QuietHours>,
+
+    /// When to send a weekly market report, like `{ day = "mon", hour = 8 }` for Mondays at 8am
+    /// in the display `timezone`. If unset, no reports are sent.
+    pub weekly_report: Option<Schedule>,
+
+    /// The time zone to show times in, like `America/New_York`. Defaults to the system's.
+    ///
+    /// See [`crate::timezone`] for details.
+    pub timezone: DisplayTimeZone,
+
+    /// How to show amounts of money, like `{ symbol = "€", symbol-after = true }`.
+    ///
+    /// See [`crate::money`] for the options.
+    pub currency: Currency,
+
+    /// Changes too small to report, like `{ rent-under = 25 }`.
+    ///
+    /// See [`crate::changes`] for the options.
+    pub ignore_changes: IgnoreChanges,
+
+    /// When to alert about a tracked listing's rent dropping, like
+    /// `{ amount = 100, percent = 5, since = "high" }`.
+    pub price_drop: PriceDropAlert,
+
+    /// Track the cheapest move-in date in this window for each qualified unit, like
+    /// `{ earliest = "2022-11-01", latest = "2022-12-15" }`, and alert when it changes.
+    ///
+    /// See [`crate::lease_term`] for details.
+    pub move_in: Option<MoveInWindow>,
+
+    /// Your gross monthly income and how many times the rent it needs to be, like
+    /// `{ income = 15000, multiple = 3 }`.
+    ///
+    /// See [`crate::affordability`] for the options.
+    pub affordability: Option<Affordability>,
+
+    /// Remind about promotions on qualified or watched units this many days before they end.
+    ///
+    /// See [`crate::reminders`] for details.
+    pub promotion_reminder_days: Option<u32>,
+
+    /// Remind about watched units this many days before they're available.
+    ///
+    /// See [`crate::reminders`] for details.
+    pub availability_reminder_days: Option<u32>,
+
+    /// Accounts to post qualified listings to, like a Mastodon or Bluesky bot.
+    ///
+    /// See [`crate::social`] for the options.
+    pub social: Vec<SocialConfig>,
+
+    /// Who to send notifications to, like `{ name = "Rebecca Turner", email = "..." }`.
+    pub to: EmailAddress,
+
+    /// Who to copy on notifications about apartments, visibly in the `Cc` header.
+    pub cc: Vec<EmailAddress>,
+
+    /// Who to quietly copy on notifications about apartments. They're only added to the
+    /// envelope, so other recipients can't see them.
+    pub bcc: Vec<EmailAddress>,
+
+    /// Where replies to notifications should go, instead of the `from` address in `[jmap]`.
+    ///
+    /// Replies with commands need to reach the `commands-mailbox`, so leave this unset if you
+    /// use it; see [`crate::email_commands`].
+    pub reply_to: Option<EmailAddress>,
+
+    /// Who to send notifications about watched units to, e.g. an address that forwards to your
+    /// phone.
+    ///
+    /// Defaults to `to`.
+    pub watch_to: Option<EmailAddress>,
+
+    /// Who to send alerts about the app itself (e.g. "monitoring is down") to.
+    ///
+    /// Defaults to `to`.
+    pub alert_to: Option<EmailAddress>,
+
+    /// Which JMAP server to send notifications through; see [`JmapConfig`].
+    pub jmap: JmapConfig,
+
+    /// When to treat a scrape as suspect, e.g. because the site returned a partial page, and skip
+    /// it. Suspect scrapes count as failures for `failure-alert-threshold`.
+    pub sanity_checks: SanityChecks,
+
+    /// Send an alert after a source fails to fetch (or a whole tick fails) this many times in a
+    /// row, and a recovery notice when it starts working again. Set to 0 to disable.
+    ///
+    /// Failed ticks are retried less often regardless; see [`Polling::backoff`].
+    pub failure_alert_threshold: usize,
+
+    /// Forget events older than this many days, so the DB doesn't grow forever. Events for
+    /// listings which are still listed are kept, so their price history stays complete. Set to 0
+    /// to keep every event.
+    pub event_retention_days: u32,
+
+    /// Serve an HTTP API over the tracker's state on this address, like `127.0.0.1:8080`.
+    ///
+    /// See [`crate::server`] for the endpoints.
+    pub listen: Option<SocketAddr>,
+
+    /// Listen for commands like `pause` and `reload-config` on a Unix socket at this path.
+    ///
+    /// See [`crate::control`] for the commands.
+    pub control_socket: Option<Utf8PathBuf>,
+
+    /// Write an RSS feed of events for qualified listings to this path after every tick.
+    ///
+    /// The feed is also served at `/feed.xml` if `listen` is set.
+    pub feed_path: Option<Utf8PathBuf>,
+
+    /// Write an iCalendar file of qualified units' availability and promotion end dates to this
+    /// path after every tick.
+    ///
+    /// The calendar is also served at `/calendar.ics` if `listen` is set.
+    pub calendar_path: Option<Utf8PathBuf>,
+
+    /// Sync listings and events to a Google Sheet after every tick.
+    ///
+    /// See [`crate::sheets`] for the options.
+    pub google_sheets: Option<SheetsConfig>,
+
+    /// Sync listings to a Notion database after every tick.
+    ///
+    /// See [`crate::notion`] for the options.
+    pub notion: Option<NotionConfig>,
+
+    /// Sync listings to an Airtable base after every tick.
+    ///
+    /// See [`crate::airtable`] for the options.
+    pub airtable: Option<AirtableConfig>,
+
+    /// Look up where communities are, for map links and the `near` qualification.
+    ///
+    /// See [`crate::geo`] for the options.
+    pub geocoding: Option<GeocodingConfig>,
+
+    /// Publish events to an MQTT broker, e.g. for Home Assistant.
+    ///
+    /// See [`crate::mqtt`] for the options and topics.
+    pub mqtt: Option<MqttConfig>,
+
+    /// Send metrics to a StatsD server. Not changed by `reload-config`.
+    ///
+    /// See [`crate::statsd`] for the options and metrics.
+    pub statsd: Option<StatsdConfig>,
+
+    /// Where and how to log; see [`LogConfig`].
+    pub log: LogConfig,
+
+    /// Report failed ticks, failing sources, and panics to this Sentry DSN, like
+    /// `https://<key>@o0.ingest.sentry.io/<project>`.
+    pub sentry_dsn: Option<String>,
+
+    /// A dead man's switch URL, like `https://hc-ping.com/<uuid>`, to ping after every tick.
+    ///
+    /// Failed ticks ping `<url>/fail` instead.
+    pub healthcheck_url: Option<String>,
+
+    /// Render the Avalon page in a headless Chromium if the `fusion-metadata` tag is missing,
+    /// e.g. when the server returns a bot challenge.
+    ///
+    /// Requires the `headless-browser` feature.
+    pub headless_fallback: bool,
+
+    /// The Chromium executable to use for `headless-fallback`. Found automatically if unset.
+    pub chrome_executable: Option<Utf8PathBuf>,
+
+    /// WASM plugins which decide what to notify about; see [`crate::plugin`].
+    ///
+    /// Requires the `plugins` feature.
+    pub plugins: Vec<Utf8PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            communities: vec![crate::AVA_URL.to_owned()],
+            craigslist: Vec::new(),
+            max_concurrent_fetches: 4,
+            polling: Default::default(),
+            rate_limit: Default::default(),
+            ignore_robots_txt: false,
+            fixtures: None,
+            qualifications: Default::default(),
+            notify_all: false,
+            score: Default::default(),
+            digest: false,
+            burst_threshold: None,
+            quiet_hours: None,
+            weekly_report: None,
+            timezone: Default::default(),
+            currency: Default::default(),
+            ignore_changes: Default::default(),
+            price_drop: Default::default(),
+            move_in: None,
+            affordability: None,
+            promotion_reminder_days: None,
+            availability_reminder_days: None,
+            social: Vec::new(),
+            to: ("Rebecca Turner", "rbt@fastmail.com").into(),
+            cc: Vec::new(),
+            bcc: Vec::new(),
+            reply_to: None,
+            watch_to: None,
+            alert_to: None,
+            jmap: Default::default(),
+            sanity_checks: Default::default(),
+            failure_alert_threshold: 3,
+            event_retention_days: 365,
+            listen: None,
+            control_socket: None,
+            feed_path: None,
+            calendar_path: None,
+            google_sheets: None,
+            notion: None,
+            airtable: None,
+            geocoding: None,
+            mqtt: None,
+            statsd: None,
+            log: Default::default(),
+            sentry_dsn: None,
+            healthcheck_url: None,
+            headless_fallback: false,
+            chrome_executable: None,
+            plugins: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// The default location of the config file, like
+    /// `~/.config/ava-apartment-finder/config.toml`.
+    pub fn default_path() -> eyre::Result<Utf8PathBuf> {
+        let mut path = Utf8PathBuf::from_path_buf(
+            dirs::config_dir().ok_or_else(|| eyre!("Could not locate config directory"))?,
+        )
+        .map_err(|path| eyre!("Config directory path contains invalid UTF-8: {path:?}"))?;
+        path.push("ava-apartment-finder");
+        path.push("config.toml");
+        Ok(path)
+    }
+
+    /// Load the config from `path`, or from [`Config::default_path`] if no path is given.
+    ///
+    /// If no path is given and the default config file doesn't exist, the default config is
+    /// used.
+    pub fn load(path: Option<&Utf8Path>) -> eyre::Result<Self> {
+        let path = match path {
+            Some(path) => path.to_owned(),
+            None => {
+                let path = Self::default_path()?;
+                if !path.exists() {
+                    tracing::debug!(%path, "No config file, using defaults");
+                    return Ok(Self::default());
+                }
+                path
+            }
+        };
+
+        tracing::debug!(%path, "Reading config");
+        let contents =
+            std::fs::read_to_string(&path).wrap_err_with(|| format!("Failed to read `{path}`"))?;
+        let config: Self = toml::from_str(&contents)
+            .wrap_err_with(|| format!("Failed to parse config `{path}`"))?;
+        config
+            .rate_limit
+            .validate()
+            .wrap_err_with(|| format!("Invalid config `{path}`"))?;
+        Ok(config)
+    }
+
+    /// Tokens, passwords, and email addresses to scrub from logs; see [`crate::redact`].
+    pub fn secrets(&self) -> Vec<String> {
+        let mut secrets = self.log.redact.clone();
+        secrets.extend(self.jmap.secrets());
+        secrets.extend(self.jmap.username.clone());
+        secrets.extend(
+            std::iter::once(&self.to)
+                .chain(std::iter::once(&self.jmap.from))
+                .chain(&self.cc)
+                .chain(&self.bcc)
+                .chain(&self.reply_to)
+                .chain(&self.watch_to)
+                .chain(&self.alert_to)
+                .map(|address| address.email().to_owned()),
+        );
+        secrets.extend(self.notion.as_ref().map(|notion| notion.token.clone()));
+        secrets.extend(
+            self.airtable
+                .as_ref()
+                .map(|airtable| airtable.token.clone()),
+        );
+        secrets.extend(self.mqtt.as_ref().and_then(|mqtt| mqtt.password.clone()));
+        secrets.extend(self.social.iter().map(|social| match &social.account {
+            Account::Mastodon { access_token, .. } => access_token.clone(),
+            Account::Bluesky { app_password, .. } => app_password.clone(),
+        }));
+        secrets.extend(self.sentry_dsn.clone());
+        secrets.extend(self.healthcheck_url.clone());
+        secrets
+    }
+
+    /// All the sources to fetch listings from.
+    pub fn sources(&self) -> Vec<Source> {
+        self.communities
+            .iter()
+            .cloned()
+            .map(Source::Avalon)
+            .chain(self.craigslist.iter().cloned().map(Source::Craigslist))
+            .collect()
+    }
+}
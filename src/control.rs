@@ -0,0 +1,305 @@
+//! A Unix socket for poking the running daemon without restarting it.
+//!
+//! The protocol is one command per line, answered with one line:
+//!
+//! - `tick-now`: Check for new listings now instead of waiting for the next poll.
+//! - `pause`: Stop checking for new listings until `resume`.
+//! - `resume`: Undo `pause`.
+//! - `reload-config`: Re-read the config file. Integrations like MQTT and the HTTP API keep
+//!   their old settings until restart.
+//! - `set-filter <expression>`: Replace the qualifications' `filter`, or clear it if no
+//!   expression is given. See [`crate::filter`] for the syntax.
+//! - `set-log-filter <directives>`: Change which logs are printed to the console, like
+//!   `info,ava_apartment_finder=trace`.
+//!
+//! Use the `control` subcommand, or something like `echo pause | nc -U <socket>`. Only the
+//! user running the daemon can connect to the socket.
+//!
+//! `SIGUSR1` also triggers `reload-config`, even if no socket is configured.
+
+use std::str::FromStr;
+
+use camino::Utf8Path;
+use camino::Utf8PathBuf;
+use color_eyre::eyre;
+use color_eyre::eyre::eyre;
+use color_eyre::eyre::Context;
+use tokio::sync::mpsc;
+use tokio::sync::oneshot;
+use tracing_subscriber::EnvFilter;
+
+use crate::filter::Filter;
+
+#[derive(Debug)]
+pub enum Command {
+    TickNow,
+    Pause,
+    Resume,
+    ReloadConfig,
+    SetFilter(Option<Filter>),
+    SetLogFilter(String),
+}
+
+impl FromStr for Command {
+    type Err = eyre::Report;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let (command, rest) = line
+            .trim()
+            .split_once(char::is_whitespace)
+            .unwrap_or((line.trim(), ""));
+        let rest = rest.trim();
+        let parsed = match command {
+            "tick-now" => Command::TickNow,
+            "pause" => Command::Pause,
+            "resume" => Command::Resume,
+            "reload-config" => Command::ReloadConfig,
+            "set-filter" => {
+                return Ok(Command::SetFilter(if rest.is_empty() {
+                    None
+                } else {
+                    Some(rest.parse()?)
+                }))
+            }
+            "set-log-filter" => {
+                EnvFilter::try_new(rest)
+                    .wrap_err_with(|| format!("Invalid filter directives `{rest}`"))?;
+                return Ok(Command::SetLogFilter(rest.to_owned()));
+            }
+            _ => {
+                return Err(eyre!(
+                    "Unknown command `{command}`; expected one of `tick-now`, `pause`, `resume`, \
+                    `reload-config`, `set-filter`, or `set-log-filter`"
+                ))
+            }
+        };
+        if !rest.is_empty() {
+            return Err(eyre!("`{command}` doesn't take any arguments"));
+        }
+        Ok(parsed)
+    }
+}
+
+/// A command from a client, waiting for a reply.
+#[derive(Debug)]
+pub struct Request {
+    pub command: Command,
+    reply: oneshot::Sender<String>,
+}
+
+impl Request {
+    pub fn reply(self, message: impl Into<String>) {
+        // The client may have hung up; that's fine.
+        let _ = self.reply.send(message.into());
+    }
+}
+
+/// Commands from the control socket and `SIGUSR1`.
+#[derive(Debug)]
+pub struct Control {
+    receiver: Option<mpsc::Receiver<Request>>,
+    path: Option<Utf8PathBuf>,
+}
+
+impl Control {
+    /// Listen on the socket at `path`, if given, and for `SIGUSR1` in the background.
+    pub fn listen(path: Option<&Utf8Path>) -> eyre::Result<Self> {
+        let (sender, receiver) = mpsc::channel(8);
+        if let Some(path) = path {
+            listen(path, sender.clone())?;
+        }
+        listen_for_reload_signal(sender)?;
+        Ok(Self {
+            receiver: Some(receiver),
+            path: path.map(ToOwned::to_owned),
+        })
+    }
+
+    /// Wait for the next command.
+    pub async fn recv(&mut self) -> Request {
+        match &mut self.receiver {
+            Some(receiver) => match receiver.recv().await {
+                Some(request) => request,
+                None => {
+                    self.receiver = None;
+                    std::future::pending().await
+                }
+            },
+            None => std::future::pending().await,
+        }
+    }
+}
+
+impl Drop for Control {
+    fn drop(&mut self) {
+        if let Some(path) = &self.path {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn listen(path: &Utf8Path, sender: mpsc::Sender<Request>) -> eyre::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    use tokio::io::AsyncBufReadExt;
+    use tokio::io::AsyncWriteExt;
+    use tokio::io::BufReader;
+    use tokio::net::UnixListener;
+
+    // A socket left behind by an instance that crashed. The DB lock means it isn't running.
+    if path.exists() {
+        std::fs::remove_file(path)
+            .wrap_err_with(|| format!("Failed to remove stale control socket `{path}`"))?;
+    }
+    let listener = UnixListener::bind(path)
+        .wrap_err_with(|| format!("Failed to listen on control socket `{path}`"))?;
+    // Otherwise other local users could pause the daemon or change its filter.
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+        .wrap_err_with(|| format!("Failed to restrict access to control socket `{path}`"))?;
+    tracing::info!(%path, "Listening for control commands");
+
+    tokio::spawn(async move {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(err) => {
+                    tracing::error!("Failed to accept control connection: {err}");
+                    continue;
+                }
+            };
+            let sender = sender.clone();
+            tokio::spawn(async move {
+                let (reader, mut writer) = stream.into_split();
+                let mut lines = BufReader::new(reader).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    tracing::info!(command = line, "Received control command");
+                    let reply = match line.parse() {
+                        Ok(command) => {
+                            let (reply, response) = oneshot::channel();
+                            if sender.send(Request { command, reply }).await.is_err() {
+                                return;
+                            }
+                            response
+                                .await
+                                .unwrap_or_else(|_| "Error: no reply".to_owned())
+                        }
+                        Err(err) => format!("Error: {err}"),
+                    };
+                    if writer
+                        .write_all(format!("{reply}\n").as_bytes())
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn listen(_path: &Utf8Path, _sender: mpsc::Sender<Request>) -> eyre::Result<()> {
+    Err(eyre!("Control sockets are only supported on Unix"))
+}
+
+/// Treat `SIGUSR1` like a `reload-config` command.
+#[cfg(unix)]
+fn listen_for_reload_signal(sender: mpsc::Sender<Request>) -> eyre::Result<()> {
+    use tokio::signal::unix::signal;
+    use tokio::signal::unix::SignalKind;
+
+    let mut signals =
+        signal(SignalKind::user_defined1()).wrap_err("Failed to listen for SIGUSR1")?;
+    tokio::spawn(async move {
+        while signals.recv().await.is_some() {
+            tracing::info!("Received SIGUSR1, reloading config");
+            let (reply, response) = oneshot::channel();
+            let command = Command::ReloadConfig;
+            if sender.send(Request { command, reply }).await.is_err() {
+                return;
+            }
+            if let Ok(response) = response.await {
+                tracing::info!("{response}");
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn listen_for_reload_signal(_sender: mpsc::Sender<Request>) -> eyre::Result<()> {
+    Ok(())
+}
+
+/// Send `command` to the daemon listening on `path` and return its reply.
+#[cfg(unix)]
+pub async fn send(path: &Utf8Path, command: &str) -> eyre::Result<String> {
+    use tokio::io::AsyncBufReadExt;
+    use tokio::io::AsyncWriteExt;
+    use tokio::io::BufReader;
+    use tokio::net::UnixStream;
+
+    let mut stream = UnixStream::connect(path).await.wrap_err_with(|| {
+        format!("Failed to connect to control socket `{path}`; is it running?")
+    })?;
+    stream
+        .write_all(format!("{command}\n").as_bytes())
+        .await
+        .wrap_err("Failed to send command")?;
+    let mut reply = String::new();
+    BufReader::new(stream)
+        .read_line(&mut reply)
+        .await
+        .wrap_err("Failed to read reply")?;
+    Ok(reply.trim_end().to_owned())
+}
+
+#[cfg(not(unix))]
+pub async fn send(_path: &Utf8Path, _command: &str) -> eyre::Result<String> {
+    Err(eyre!("Control sockets are only supported on Unix"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        assert!(matches!("tick-now".parse(), Ok(Command::TickNow)));
+        assert!(matches!(" pause\n".parse(), Ok(Command::Pause)));
+        assert!(matches!("set-filter".parse(), Ok(Command::SetFilter(None))));
+        assert!(matches!(
+            "set-filter bedroom >= 2 && rent <= 4300".parse(),
+            Ok(Command::SetFilter(Some(_)))
+        ));
+        assert!("set-filter rent <=".parse::<Command>().is_err());
+        assert!(matches!(
+            "set-log-filter info,ava_apartment_finder=trace".parse(),
+            Ok(Command::SetLogFilter(directives)) if directives == "info,ava_apartment_finder=trace"
+        ));
+        assert!("set-log-filter info,=[".parse::<Command>().is_err());
+        assert!("resume now".parse::<Command>().is_err());
+        assert!("explode".parse::<Command>().is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_socket_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("ava_control_{}.sock", std::process::id()));
+        let path = Utf8PathBuf::from_path_buf(path).unwrap();
+        let (sender, _receiver) = mpsc::channel(1);
+        listen(&path, sender).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,325 @@
+//! Craigslist housing searches, as a low-fidelity listing source.
+//!
+//! Craigslist only gives us a title, a price, and a link for each post, so these are tracked
+//! separately from Avalon units but run through the same diff and notification machinery.
+
+use std::fmt::Display;
+
+use chrono::NaiveDate;
+use color_eyre::eyre;
+use color_eyre::eyre::eyre;
+use color_eyre::eyre::Context;
+use reqwest::Url;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::api::Apartment;
+use crate::changes::Change;
+use crate::changes::FieldChange;
+use crate::filter::Value;
+use crate::http;
+use crate::lease_term::LeaseOption;
+use crate::listing::Listing;
+use crate::money::Money;
+use crate::price_drop::PriceDrop;
+use crate::qualifications::Qualifications;
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Post {
+    /// Synthesized from the Craigslist post ID, like `craigslist-7551234567`.
+    pub id: String,
+    pub title: String,
+    pub price: Option<Money>,
+    pub link: String,
+}
+
+impl Post {
+    fn new(title: &str, link: &str) -> eyre::Result<Self> {
+        // Craigslist double-escapes the dollar sign in titles.
+        let title = title.replace("&#x0024;", "$").trim().to_owned();
+        let post_id =
+            post_id(link).ok_or_else(|| eyre!("Could not find post ID in link `{link}`"))?;
+        Ok(Self {
+            id: format!("craigslist-{post_id}"),
+            price: parse_price(&title),
+            title,
+            link: link.to_owned(),
+        })
+    }
+}
+
+impl Listing for Post {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn listed_subject(&self) -> String {
+        format!("Craigslist post listed: {}", self.title)
+    }
+
+    fn unlisted_subject(&self) -> String {
+        format!("Craigslist post no longer available: {}", self.title)
+    }
+
+    fn changed_subject(&self) -> String {
+        format!("Craigslist post changed: {}", self.title)
+    }
+
+    fn price_drop_subject(&self, drop: &PriceDrop) -> String {
+        format!("Craigslist post price dropped {drop}: {}", self.title)
+    }
+
+    fn rent(&self) -> Option<Money> {
+        self.price
+    }
+
+    fn available_date(&self) -> Option<NaiveDate> {
+        None
+    }
+
+    fn url(&self) -> Option<String> {
+        Some(self.link.clone())
+    }
+
+    fn virtual_tour_url(&self) -> Option<String> {
+        None
+    }
+
+    fn lease_options(&self) -> Vec<LeaseOption> {
+        Vec::new()
+    }
+
+    fn meets_qualifications(&self, qualifications: &Qualifications) -> bool {
+        let reason = self
+            .price
+            .and_then(|price| {
+                qualifications
+                    .rent
+                    .check(&price, "too cheap", "too expensive")
+            })
+            .or_else(|| qualifications.check_filter(|name| self.field(name)));
+        if let Some(reason) = reason {
+            tracing::debug!(
+                title = self.title,
+                price = self.price.map(Money::dollars),
+                "Skipping post; {reason}"
+            );
+        }
+        reason.is_none()
+    }
+
+    fn field(&self, name: &str) -> Option<Value> {
+        match name {
+            "rent" | "price" => self.price.map(|price| Value::Number(price.dollars())),
+            "title" => Some(Value::String(self.title.clone())),
+            _ => None,
+        }
+    }
+
+    fn changes(&self, new: &Self) -> Vec<FieldChange> {
+        let rent = match (self.price, new.price) {
+            (Some(old), Some(new)) => Change::new(old, new).map(FieldChange::Rent),
+            (old, new) => (old != new).then_some(FieldChange::Other("rent")),
+        };
+        [
+            rent,
+            Change::new(self.title.clone(), new.title.clone()).map(FieldChange::Title),
+            (self.link != new.link).then_some(FieldChange::Other("link")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
+impl Display for Post {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Post {
+            title, price, link, ..
+        } = self;
+        match price {
+            Some(price) => write!(f, "Craigslist post {title:?} ({price}, {link})"),
+            None => write!(f, "Craigslist post {title:?} ({link})"),
+        }
+    }
+}
+
+/// Get the RSS feed URL for a Craigslist search URL.
+///
+/// Craigslist serves any search page as RSS if you add `format=rss` to the query.
+pub fn feed_url(search_url: &str) -> eyre::Result<Url> {
+    let mut url = Url::parse(search_url)
+        .wrap_err_with(|| format!("Invalid Craigslist URL `{search_url}`"))?;
+    if !url.query_pairs().any(|(key, _)| key == "format") {
+        url.query_pairs_mut().append_pair("format", "rss");
+    }
+    Ok(url)
+}
+
+#[tracing::instrument(skip(http))]
+pub async fn get_posts(
+    http: &http::Client,
+    search_url: &str,
+) -> eyre::Result<Vec<Apartment<Post>>> {
+    let url = feed_url(search_url)?;
+    let response = http.get(url).await?;
+
+    tracing::trace!(?response, "Got response");
+
+    let body = response.text().await?;
+
+    tracing::trace!(xml = body, "Got RSS");
+
+    Ok(parse_feed(&body)?
+        .into_iter()
+        .map(|post| Apartment::new(search_url, post))
+        .collect())
+}
+
+#[tracing::instrument(skip_all)]
+fn parse_feed(xml: &str) -> eyre::Result<Vec<Post>> {
+    let document = roxmltree::Document::parse(xml).wrap_err("Failed to parse Craigslist RSS")?;
+
+    document
+        .descendants()
+        .filter(|node| node.has_tag_name("item"))
+        .map(|item| {
+            let child_text = |name: &str| {
+                item.children()
+                    .find(|child| child.has_tag_name(name))
+                    .and_then(|child| child.text())
+            };
+            let title = child_text("title").ok_or_else(|| eyre!("RSS item has no title"))?;
+            let link = child_text("link").ok_or_else(|| eyre!("RSS item has no link"))?;
+            Post::new(title, link.trim())
+        })
+        .collect()
+}
+
+/// Get the post ID from a link like
+/// `https://seattle.craigslist.org/see/apa/d/seattle-sunny-2br/7551234567.html`.
+fn post_id(link: &str) -> Option<&str> {
+    let id = link
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()?
+        .trim_end_matches(".html");
+    if !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()) {
+        Some(id)
+    } else {
+        None
+    }
+}
+
+/// Get the price from a title like `$2,500 / 2br - 900ft2 - Sunny 2BR (Capitol Hill)`.
+fn parse_price(title: &str) -> Option<Money> {
+    let (_, rest) = title.split_once('$')?;
+    let digits: String = rest
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == ',')
+        .filter(|c| *c != ',')
+        .collect();
+    digits.parse().ok().map(Money::from_dollars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feed_url() {
+        assert_eq!(
+            feed_url("https://seattle.craigslist.org/search/apa?query=capitol+hill")
+                .unwrap()
+                .as_str(),
+            "https://seattle.craigslist.org/search/apa?query=capitol+hill&format=rss"
+        );
+        assert_eq!(
+            feed_url("https://seattle.craigslist.org/search/apa?format=rss")
+                .unwrap()
+                .as_str(),
+            "https://seattle.craigslist.org/search/apa?format=rss"
+        );
+    }
+
+    #[test]
+    fn test_parse_feed() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#" xmlns="http://purl.org/rss/1.0/">
+  <channel rdf:about="https://seattle.craigslist.org/search/apa?format=rss">
+    <title>craigslist seattle | apts/housing for rent search</title>
+  </channel>
+  <item rdf:about="https://seattle.craigslist.org/see/apa/d/seattle-sunny-2br/7551234567.html">
+    <title><![CDATA[&#x0024;2,500 / 2br - 900ft2 - Sunny 2BR (Capitol Hill)]]></title>
+    <link>https://seattle.craigslist.org/see/apa/d/seattle-sunny-2br/7551234567.html</link>
+  </item>
+  <item rdf:about="https://seattle.craigslist.org/see/apa/d/seattle-room/7551234568.html">
+    <title><![CDATA[Room for rent]]></title>
+    <link>https://seattle.craigslist.org/see/apa/d/seattle-room/7551234568.html</link>
+  </item>
+</rdf:RDF>"#;
+
+        assert_eq!(
+            parse_feed(xml).unwrap(),
+            vec![
+                Post {
+                    id: "craigslist-7551234567".to_owned(),
+                    title: "$2,500 / 2br - 900ft2 - Sunny 2BR (Capitol Hill)".to_owned(),
+                    price: Some(Money::from_dollars(2500.0)),
+                    link:
+                        "https://seattle.craigslist.org/see/apa/d/seattle-sunny-2br/7551234567.html"
+                            .to_owned(),
+                },
+                Post {
+                    id: "craigslist-7551234568".to_owned(),
+                    title: "Room for rent".to_owned(),
+                    price: None,
+                    link: "https://seattle.craigslist.org/see/apa/d/seattle-room/7551234568.html"
+                        .to_owned(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filter_skips_unknown_fields() {
+        let post = Post {
+            id: "craigslist-7551234567".to_owned(),
+            title: "$2,500 / 2br - 900ft2 - Sunny 2BR (Capitol Hill)".to_owned(),
+            price: Some(Money::from_dollars(2500.0)),
+            link: "https://seattle.craigslist.org/see/apa/d/seattle-sunny-2br/7551234567.html"
+                .to_owned(),
+        };
+        let qualifications = |filter: &str| Qualifications {
+            filter: Some(filter.parse().unwrap()),
+            ..Default::default()
+        };
+        assert!(post.meets_qualifications(&qualifications("sqft > 800 && floor >= 4")));
+        assert!(post.meets_qualifications(&qualifications("sqft > 800 && rent < 3000")));
+        assert!(!post.meets_qualifications(&qualifications("sqft > 800 && rent < 2000")));
+    }
+
+    #[tokio::test]
+    async fn test_get_posts_fixture() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures").into();
+        let http = http::Client::new(Default::default(), false, Some(http::Fixtures::Replay(dir)));
+        let posts = get_posts(
+            &http,
+            "https://seattle.craigslist.org/search/apa?query=capitol+hill",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            posts
+                .iter()
+                .map(|post| (post.inner.id.as_str(), post.inner.price.map(Money::dollars)))
+                .collect::<Vec<_>>(),
+            vec![
+                ("craigslist-7546911234", Some(2195.0)),
+                ("craigslist-7546909876", Some(1650.0)),
+                ("craigslist-7546905555", None),
+            ]
+        );
+    }
+}
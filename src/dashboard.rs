@@ -0,0 +1,181 @@
+//! A small HTML dashboard, served at `/` by [`crate::server`].
+
+use std::fmt::Write;
+
+use chrono::DateTime;
+use chrono::Utc;
+
+use crate::api::Apartment;
+use crate::events;
+use crate::html::escape;
+use crate::listing::Listing;
+use crate::money::Money;
+use crate::server::Snapshot;
+use crate::timezone;
+
+/// How many recent events to show.
+const RECENT_EVENTS: usize = 20;
+
+/// Render the dashboard. Unless `show_all` is set, only listings meeting the qualifications are
+/// shown.
+pub fn render(snapshot: &Snapshot, show_all: bool, now: DateTime<Utc>) -> String {
+    let mut rows = Vec::new();
+    rows.extend(
+        snapshot
+            .known_apartments
+            .values()
+            .filter_map(|apt| Row::new(snapshot, apt, show_all, now)),
+    );
+    rows.extend(
+        snapshot
+            .known_posts
+            .values()
+            .filter_map(|post| Row::new(snapshot, post, show_all, now)),
+    );
+    // Listings with unknown rents sort last.
+    rows.sort_by_key(|row| (row.rent.is_none(), row.rent));
+
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n\
+         <html><head><meta charset=\"utf-8\">\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\
+         <title>Ava Apartment Finder</title>\
+         <style>{STYLE}</style></head><body>\
+         <h1>{} {} available</h1>\
+         <p>{}</p>",
+        rows.len(),
+        if show_all {
+            "listings"
+        } else {
+            "qualified listings"
+        },
+        if show_all {
+            "<a href=\"?\">Only qualified listings</a>"
+        } else {
+            "<a href=\"?all=true\">All listings</a>"
+        },
+    );
+
+    html.push_str(
+        "<table><tr><th>Listing</th><th>Rent</th><th>Days listed</th><th>Price history</th></tr>",
+    );
+    for row in &rows {
+        let _ = write!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape(&row.summary),
+            row.rent.map(|rent| rent.to_string()).unwrap_or_default(),
+            row.days_listed,
+            sparkline(&row.history),
+        );
+    }
+    html.push_str("</table>");
+
+    html.push_str("<h2>Recent events</h2><ul>");
+    for event in snapshot.events.iter().rev().take(RECENT_EVENTS) {
+        let _ = write!(
+            html,
+            "<li>{} <b>{:?}</b>: {}</li>",
+            timezone::format(event.time, "%b %e %H:%M"),
+            event.kind,
+            escape(&event.summary)
+        );
+    }
+    html.push_str("</ul>");
+
+    if let Some(last_tick) = snapshot.last_tick {
+        let _ = write!(
+            html,
+            "<p class=\"footer\">Last updated {}</p>",
+            timezone::format(last_tick, "%b %e %Y %H:%M %Z")
+        );
+    }
+    html.push_str("</body></html>");
+    html
+}
+
+struct Row {
+    summary: String,
+    rent: Option<Money>,
+    days_listed: i64,
+    history: Vec<f64>,
+}
+
+impl Row {
+    fn new<T: Listing>(
+        snapshot: &Snapshot,
+        apt: &Apartment<T>,
+        show_all: bool,
+        now: DateTime<Utc>,
+    ) -> Option<Self> {
+        if !show_all && !apt.inner.meets_qualifications(&snapshot.qualifications) {
+            return None;
+        }
+        let mut history: Vec<f64> = events::price_history(&snapshot.events, apt.id())
+            .into_iter()
+            .map(|(_, rent)| rent.dollars())
+            .collect();
+        if history.is_empty() {
+            history.extend(apt.inner.rent().map(Money::dollars));
+        }
+        Some(Self {
+            summary: apt.inner.to_string(),
+            rent: apt.inner.rent(),
+            days_listed: (now - apt.listed).num_days(),
+            history,
+        })
+    }
+}
+
+/// Draw `values` as a tiny inline SVG line chart.
+fn sparkline(values: &[f64]) -> String {
+    const WIDTH: f64 = 100.0;
+    const HEIGHT: f64 = 20.0;
+
+    if values.len() < 2 {
+        return String::new();
+    }
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let range = if max > min { max - min } else { 1.0 };
+    let step = WIDTH / (values.len() - 1) as f64;
+    let points = itertools::join(
+        values.iter().enumerate().map(|(i, value)| {
+            let x = i as f64 * step;
+            let y = HEIGHT - (value - min) / range * HEIGHT;
+            format!("{x:.1},{y:.1}")
+        }),
+        " ",
+    );
+    format!(
+        "<svg width=\"{WIDTH}\" height=\"{HEIGHT}\" viewBox=\"-1 -1 {} {}\">\
+         <polyline fill=\"none\" stroke=\"currentColor\" points=\"{points}\"/></svg>",
+        WIDTH + 2.0,
+        HEIGHT + 2.0,
+    )
+}
+
+const STYLE: &str = "\
+body { font-family: system-ui, sans-serif; margin: 1em auto; max-width: 60em; padding: 0 1em; }
+table { border-collapse: collapse; width: 100%; }
+th, td { text-align: left; padding: 0.3em 0.5em; border-bottom: 1px solid #ddd; }
+.footer { color: #888; }
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(sparkline(&[4260.0]), "");
+        assert_eq!(
+            sparkline(&[4260.0, 4100.0, 4180.0]),
+            "<svg width=\"100\" height=\"20\" viewBox=\"-1 -1 102 22\">\
+             <polyline fill=\"none\" stroke=\"currentColor\" \
+             points=\"0.0,0.0 50.0,20.0 100.0,10.0\"/></svg>"
+        );
+    }
+}
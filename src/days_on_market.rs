@@ -0,0 +1,186 @@
+//! How long units stay listed before they're taken, so notifications can say how quickly to
+//! respond.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use serde::Serialize;
+
+use crate::api::Apartment;
+use crate::filter::Value;
+use crate::listing::Listing;
+use crate::market_report::mean;
+use crate::market_report::median;
+
+/// Groups with fewer units than this are too noisy to quote; we fall back to a broader group.
+const MIN_UNITS: usize = 3;
+
+/// Time on market for unlisted units, overall and grouped by bedroom count and floor plan.
+#[derive(Debug, Default, Serialize)]
+pub struct DaysOnMarket {
+    pub overall: Summary,
+    pub by_bedrooms: BTreeMap<u64, Summary>,
+    pub by_floor_plan: BTreeMap<String, Summary>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Summary {
+    pub units: usize,
+    pub mean_days: f64,
+    pub median_days: f64,
+}
+
+impl Summary {
+    fn new(mut days: Vec<f64>) -> Self {
+        if days.is_empty() {
+            return Self::default();
+        }
+        days.sort_by(f64::total_cmp);
+        Self {
+            units: days.len(),
+            mean_days: mean(&days),
+            median_days: median(&days),
+        }
+    }
+}
+
+impl DaysOnMarket {
+    /// Summarize how long the `unlisted` apartments were listed for. Apartments which are still
+    /// listed are skipped.
+    pub fn new<'a, T: Listing + 'a>(unlisted: impl IntoIterator<Item = &'a Apartment<T>>) -> Self {
+        let mut overall = Vec::new();
+        let mut by_bedrooms: BTreeMap<u64, Vec<f64>> = BTreeMap::new();
+        let mut by_floor_plan: BTreeMap<String, Vec<f64>> = BTreeMap::new();
+
+        for apt in unlisted {
+            let unlisted = match apt.unlisted {
+                Some(unlisted) => unlisted,
+                None => continue,
+            };
+            let days = (unlisted - apt.listed).num_minutes() as f64 / (60.0 * 24.0);
+            overall.push(days);
+            if let Some(Value::Number(bedrooms)) = apt.inner.field("bedrooms") {
+                by_bedrooms.entry(bedrooms as u64).or_default().push(days);
+            }
+            if let Some(Value::String(plan)) = apt.inner.field("plan") {
+                by_floor_plan.entry(plan).or_default().push(days);
+            }
+        }
+
+        Self {
+            overall: Summary::new(overall),
+            by_bedrooms: by_bedrooms
+                .into_iter()
+                .map(|(bedrooms, days)| (bedrooms, Summary::new(days)))
+                .collect(),
+            by_floor_plan: by_floor_plan
+                .into_iter()
+                .map(|(plan, days)| (plan, Summary::new(days)))
+                .collect(),
+        }
+    }
+
+    /// The typical time on market for units like `listing`, from the most specific group with
+    /// enough data: its floor plan, then its bedroom count, then all units.
+    pub fn typical(&self, listing: &impl Listing) -> Option<Typical<'_>> {
+        let floor_plan = match listing.field("plan") {
+            Some(Value::String(plan)) => self
+                .by_floor_plan
+                .get(&plan)
+                .map(|summary| (Group::FloorPlan(plan), summary)),
+            _ => None,
+        };
+        let bedrooms = match listing.field("bedrooms") {
+            Some(Value::Number(bedrooms)) => self
+                .by_bedrooms
+                .get(&(bedrooms as u64))
+                .map(|summary| (Group::Bedrooms(bedrooms as u64), summary)),
+            _ => None,
+        };
+        let overall = Some((Group::All, &self.overall));
+
+        [floor_plan, bedrooms, overall]
+            .into_iter()
+            .flatten()
+            .find(|(_, summary)| summary.units >= MIN_UNITS)
+            .map(|(group, summary)| Typical { group, summary })
+    }
+}
+
+/// The units a [`Typical`] time on market was computed from.
+pub enum Group {
+    FloorPlan(String),
+    Bedrooms(u64),
+    All,
+}
+
+/// The typical time on market for a group of units, like "9 days (median of 12 units with 2
+/// bedrooms)".
+pub struct Typical<'a> {
+    pub group: Group,
+    pub summary: &'a Summary,
+}
+
+impl Display for Typical<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let days = self.summary.median_days.round();
+        let plural = if days == 1.0 { "" } else { "s" };
+        let units = self.summary.units;
+        write!(f, "{days:.0} day{plural} (median of {units} units")?;
+        match &self.group {
+            Group::FloorPlan(plan) => write!(f, " with floor plan {plan})"),
+            Group::Bedrooms(bedrooms) => write!(f, " with {bedrooms} bedrooms)"),
+            Group::All => write!(f, ")"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::api::fixtures::craigslist_post;
+    use crate::api::fixtures::CRAIGSLIST_SEARCH;
+    use crate::craigslist::Post;
+
+    #[test]
+    fn test_days_on_market() {
+        let post = |id: u64, days, unlisted: bool| {
+            let mut apt = Apartment::new(
+                CRAIGSLIST_SEARCH,
+                Post {
+                    id: format!("craigslist-{id}"),
+                    link: format!("https://seattle.craigslist.org/see/apa/d/{id}.html"),
+                    ..craigslist_post()
+                },
+            );
+            apt.unlisted = unlisted.then(|| apt.listed + Duration::days(days));
+            apt
+        };
+        let posts = [
+            post(7551234561, 3, true),
+            post(7551234562, 9, true),
+            post(7551234563, 12, true),
+            post(7551234564, 30, false),
+        ];
+
+        let days_on_market = DaysOnMarket::new(&posts);
+        assert_eq!(
+            days_on_market.overall,
+            Summary {
+                units: 3,
+                mean_days: 8.0,
+                median_days: 9.0,
+            }
+        );
+        assert!(days_on_market.by_bedrooms.is_empty());
+
+        let typical = days_on_market.typical(&posts[3].inner).unwrap();
+        assert_eq!(typical.to_string(), "9 days (median of 3 units)");
+
+        assert!(DaysOnMarket::new(&posts[..2])
+            .typical(&posts[3].inner)
+            .is_none());
+    }
+}
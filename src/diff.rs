@@ -1,14 +1,66 @@
+//! Diffs of listings, for logs and notifications.
+//!
+//! There are a few [`Mode`]s, since a line-based diff of a one-line summary isn't much use:
+//! lines for multi-line text like `{:#?}` output, words for one-line summaries, and a
+//! structural diff of the serialized JSON, as [JSON Patch](https://www.rfc-editor.org/rfc/rfc6902)
+//! operations.
+
+use std::fmt::Debug;
 use std::fmt::Display;
 use std::fmt::Write;
 
 use color_eyre::eyre;
 use owo_colors::OwoColorize;
-use owo_colors::Stream::Stdout;
 use owo_colors::Style;
+use serde::Serialize;
+use serde_json::Value;
 use similar::ChangeTag;
 use similar::TextDiff;
 
-/// Format a diff of two strings, with colors if `Stdout` is a tty.
+use crate::html::escape;
+
+/// What a diff is rendered for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Render {
+    /// A terminal, with ANSI colors.
+    Ansi,
+    /// Plain text, like an email body or a log file.
+    Plain,
+    /// An HTML fragment, with inline styles.
+    Html,
+}
+
+/// How to compare two values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Line by line, for multi-line text.
+    Lines,
+    /// Word by word, inline, for one-line text.
+    Words,
+    /// Structurally, as JSON Patch operations on the serialized values.
+    Json,
+}
+
+/// Diff two values in `mode`: their pretty [`Debug`] output for [`Mode::Lines`], their
+/// [`Debug`] output for [`Mode::Words`], or their serialized JSON for [`Mode::Json`].
+pub fn diff_values<T: Debug + Serialize>(
+    old: &T,
+    new: &T,
+    mode: Mode,
+    target: Render,
+) -> eyre::Result<String> {
+    match mode {
+        Mode::Lines => diff(&format!("{old:#?}"), &format!("{new:#?}"), target),
+        Mode::Words => Ok(diff_words(&format!("{old:?}"), &format!("{new:?}"), target)),
+        Mode::Json => Ok(diff_json(
+            &serde_json::to_value(old)?,
+            &serde_json::to_value(new)?,
+            target,
+        )),
+    }
+}
+
+/// Format a diff of two strings for `target`.
 ///
 /// Like [`diff`] but includes a header showing the filenames.
 pub fn diff_header(
@@ -16,22 +68,24 @@ pub fn diff_header(
     new: &str,
     old_path: impl Display,
     new_path: impl Display,
+    target: Render,
 ) -> eyre::Result<String> {
-    Ok(format!(
-        "{} {}\n{} {}\n{}",
-        "---".if_supports_color(Stdout, |text| Style::new().bright_red().bold().style(text)),
-        old_path.if_supports_color(Stdout, |text| text.red()),
-        "+++".if_supports_color(Stdout, |text| Style::new()
-            .bright_green()
-            .bold()
-            .style(text)),
-        new_path.if_supports_color(Stdout, |text| text.green()),
-        diff(old, new)?
-    ))
-}
-
-/// Format a diff of two strings, with colors if `Stdout` is a tty.
-pub fn diff(old: &str, new: &str) -> eyre::Result<String> {
+    let header = format!(
+        "{} {}\n{} {}\n",
+        paint(target, "---", Part::OldHeader),
+        paint(target, old_path, Part::OldPath),
+        paint(target, "+++", Part::NewHeader),
+        paint(target, new_path, Part::NewPath),
+    );
+    Ok(wrap(target, header + &render(old, new, target)?))
+}
+
+/// Format a diff of two strings for `target`.
+pub fn diff(old: &str, new: &str, target: Render) -> eyre::Result<String> {
+    Ok(wrap(target, render(old, new, target)?))
+}
+
+fn render(old: &str, new: &str, target: Render) -> eyre::Result<String> {
     // Adapted from: https://github.com/mitsuhiko/similar/blob/77c20faf94c1969bcedc219851f7b89ab4a8ac5a/examples/terminal-inline.rs
 
     let mut ret = String::with_capacity(new.len());
@@ -46,37 +100,30 @@ pub fn diff(old: &str, new: &str) -> eyre::Result<String> {
         }
         for op in group {
             for change in diff.iter_inline_changes(op) {
-                let (sign, style, line_style) = match change.tag() {
-                    ChangeTag::Delete => ("-", Style::new().bright_red(), Style::new().red()),
-                    ChangeTag::Insert => ("+", Style::new().bright_green(), Style::new().green()),
-                    ChangeTag::Equal => (" ", Style::new().dimmed(), Style::new()),
-                };
+                let tag = change.tag();
                 write!(
                     &mut ret,
                     // NB: This uses a vertical line box drawing character (U+2502)
                     "{}{} │{}",
-                    Line(change.old_index()).if_supports_color(Stdout, |text| text.dimmed()),
-                    Line(change.new_index()).if_supports_color(Stdout, |text| text.dimmed()),
-                    sign.if_supports_color(Stdout, |text| style.bold().style(text)),
+                    paint(target, Line(change.old_index()), Part::LineNumber),
+                    paint(target, Line(change.new_index()), Part::LineNumber),
+                    paint(
+                        target,
+                        match tag {
+                            ChangeTag::Delete => "-",
+                            ChangeTag::Insert => "+",
+                            ChangeTag::Equal => " ",
+                        },
+                        Part::Sign(tag)
+                    ),
                 )?;
                 for (emphasized, value) in change.iter_strings_lossy() {
-                    if emphasized {
-                        write!(
-                            &mut ret,
-                            "{}",
-                            value.if_supports_color(Stdout, |text| style
-                                .underline()
-                                .bold()
-                                .on_black()
-                                .style(text))
-                        )?;
+                    let part = if emphasized {
+                        Part::Emphasized(tag)
                     } else {
-                        write!(
-                            &mut ret,
-                            "{}",
-                            value.if_supports_color(Stdout, |text| line_style.style(text))
-                        )?;
-                    }
+                        Part::Text(tag)
+                    };
+                    write!(&mut ret, "{}", paint(target, value, part))?;
                 }
                 if change.missing_newline() {
                     ret.push('\n');
@@ -88,6 +135,260 @@ pub fn diff(old: &str, new: &str) -> eyre::Result<String> {
     Ok(ret)
 }
 
+/// Format an inline, word-level diff of two strings for `target`, like
+/// `Apartment 731 ([-$4,260-]{+$4,100+}, ...)` in plain text.
+pub fn diff_words(old: &str, new: &str, target: Render) -> String {
+    let diff = TextDiff::from_words(old, new);
+    let changes: Vec<_> = diff
+        .iter_all_changes()
+        .map(|change| (change.tag(), change.value()))
+        .collect();
+
+    // A replaced phrase is rendered as one deletion and one insertion rather than alternating
+    // words, so changed words and the whitespace between them are collected until the next
+    // unchanged word.
+    let mut runs: Vec<(ChangeTag, String)> = Vec::new();
+    let (mut deleted, mut inserted) = (String::new(), String::new());
+    for (i, &(tag, value)) in changes.iter().enumerate() {
+        let between_changes = tag == ChangeTag::Equal
+            && value.trim().is_empty()
+            && !(deleted.is_empty() && inserted.is_empty())
+            && matches!(changes.get(i + 1), Some((next, _)) if *next != ChangeTag::Equal);
+        match tag {
+            ChangeTag::Delete => deleted.push_str(value),
+            ChangeTag::Insert => inserted.push_str(value),
+            ChangeTag::Equal if between_changes => {
+                deleted.push_str(value);
+                inserted.push_str(value);
+            }
+            ChangeTag::Equal => {
+                for (tag, text) in [
+                    (ChangeTag::Delete, std::mem::take(&mut deleted)),
+                    (ChangeTag::Insert, std::mem::take(&mut inserted)),
+                ] {
+                    if !text.is_empty() {
+                        runs.push((tag, text));
+                    }
+                }
+                match runs.last_mut() {
+                    Some((ChangeTag::Equal, text)) => text.push_str(value),
+                    _ => runs.push((tag, value.to_owned())),
+                }
+            }
+        }
+    }
+    for (tag, text) in [(ChangeTag::Delete, deleted), (ChangeTag::Insert, inserted)] {
+        if !text.is_empty() {
+            runs.push((tag, text));
+        }
+    }
+
+    let mut ret = String::with_capacity(new.len());
+    for (tag, text) in runs {
+        let (start, end) = match (target, tag) {
+            (Render::Plain, ChangeTag::Delete) => ("[-", "-]"),
+            (Render::Plain, ChangeTag::Insert) => ("{+", "+}"),
+            _ => ("", ""),
+        };
+        let part = match tag {
+            ChangeTag::Equal => Part::Text(tag),
+            ChangeTag::Delete | ChangeTag::Insert => Part::Emphasized(tag),
+        };
+        let _ = write!(ret, "{start}{}{end}", paint(target, text, part));
+    }
+    wrap(target, ret)
+}
+
+/// A JSON Patch operation.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOp {
+    Add {
+        path: String,
+        value: Value,
+    },
+    Remove {
+        path: String,
+        #[serde(skip)]
+        old: Value,
+    },
+    Replace {
+        path: String,
+        value: Value,
+        #[serde(skip)]
+        old: Value,
+    },
+}
+
+impl Display for PatchOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatchOp::Add { path, value } => write!(f, "add {path}: {value}"),
+            PatchOp::Remove { path, old } => write!(f, "remove {path} (was {old})"),
+            PatchOp::Replace { path, value, old } => write!(f, "replace {path}: {old} → {value}"),
+        }
+    }
+}
+
+/// The JSON Patch operations which turn `old` into `new`.
+///
+/// Objects and arrays are compared member by member, so a change deep in a value is a single
+/// `replace` of just that part. Array elements are compared by index.
+pub fn json_patch(old: &Value, new: &Value) -> Vec<PatchOp> {
+    let mut ops = Vec::new();
+    json_patch_at(String::new(), old, new, &mut ops);
+    ops
+}
+
+fn json_patch_at(path: String, old: &Value, new: &Value, ops: &mut Vec<PatchOp>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, old) in old {
+                let path = format!("{path}/{}", escape_pointer(key));
+                match new.get(key) {
+                    Some(new) => json_patch_at(path, old, new, ops),
+                    None => ops.push(PatchOp::Remove {
+                        path,
+                        old: old.clone(),
+                    }),
+                }
+            }
+            for (key, new) in new {
+                if !old.contains_key(key) {
+                    ops.push(PatchOp::Add {
+                        path: format!("{path}/{}", escape_pointer(key)),
+                        value: new.clone(),
+                    });
+                }
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            for (i, (old, new)) in old.iter().zip(new).enumerate() {
+                json_patch_at(format!("{path}/{i}"), old, new, ops);
+            }
+            // Remove from the end, so the indexes stay valid as the patch is applied.
+            for (i, old) in old.iter().enumerate().skip(new.len()).rev() {
+                ops.push(PatchOp::Remove {
+                    path: format!("{path}/{i}"),
+                    old: old.clone(),
+                });
+            }
+            for (i, new) in new.iter().enumerate().skip(old.len()) {
+                ops.push(PatchOp::Add {
+                    path: format!("{path}/{i}"),
+                    value: new.clone(),
+                });
+            }
+        }
+        _ if old != new => ops.push(PatchOp::Replace {
+            path,
+            value: new.clone(),
+            old: old.clone(),
+        }),
+        _ => {}
+    }
+}
+
+/// Escape a key for a JSON Pointer path segment.
+fn escape_pointer(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// Format a structural diff of two JSON values for `target`, one [`PatchOp`] per line.
+pub fn diff_json(old: &Value, new: &Value, target: Render) -> String {
+    let mut ret = String::new();
+    for op in json_patch(old, new) {
+        let part = match op {
+            PatchOp::Add { .. } => Part::Text(ChangeTag::Insert),
+            PatchOp::Remove { .. } => Part::Text(ChangeTag::Delete),
+            PatchOp::Replace { .. } => Part::Text(ChangeTag::Equal),
+        };
+        let _ = writeln!(ret, "{}", paint(target, op, part));
+    }
+    wrap(target, ret)
+}
+
+/// Wrap a rendered diff in a `<pre>` block, for HTML.
+fn wrap(target: Render, diff: String) -> String {
+    match target {
+        Render::Html => format!("<pre style=\"font-family: monospace\">{diff}</pre>"),
+        Render::Ansi | Render::Plain => diff,
+    }
+}
+
+/// The parts of a diff which are styled differently.
+#[derive(Clone, Copy, Debug)]
+enum Part {
+    OldHeader,
+    OldPath,
+    NewHeader,
+    NewPath,
+    LineNumber,
+    Sign(ChangeTag),
+    /// The part of a changed line that changed.
+    Emphasized(ChangeTag),
+    Text(ChangeTag),
+}
+
+impl Part {
+    fn style(self) -> Style {
+        match self {
+            Part::OldHeader => Style::new().bright_red().bold(),
+            Part::OldPath => Style::new().red(),
+            Part::NewHeader => Style::new().bright_green().bold(),
+            Part::NewPath => Style::new().green(),
+            Part::LineNumber => Style::new().dimmed(),
+            Part::Sign(tag) => Self::tag_style(tag).bold(),
+            Part::Emphasized(tag) => Self::tag_style(tag).underline().bold().on_black(),
+            Part::Text(ChangeTag::Delete) => Style::new().red(),
+            Part::Text(ChangeTag::Insert) => Style::new().green(),
+            Part::Text(ChangeTag::Equal) => Style::new(),
+        }
+    }
+
+    fn tag_style(tag: ChangeTag) -> Style {
+        match tag {
+            ChangeTag::Delete => Style::new().bright_red(),
+            ChangeTag::Insert => Style::new().bright_green(),
+            ChangeTag::Equal => Style::new().dimmed(),
+        }
+    }
+
+    /// Inline CSS for [`Render::Html`], or `None` for unstyled text.
+    fn css(self) -> Option<&'static str> {
+        match self {
+            Part::OldHeader | Part::Sign(ChangeTag::Delete) => {
+                Some("color: #c00; font-weight: bold")
+            }
+            Part::NewHeader | Part::Sign(ChangeTag::Insert) => {
+                Some("color: #080; font-weight: bold")
+            }
+            Part::OldPath | Part::Text(ChangeTag::Delete) => Some("color: #c00"),
+            Part::NewPath | Part::Text(ChangeTag::Insert) => Some("color: #080"),
+            Part::Emphasized(ChangeTag::Delete) => Some("color: #c00; background: #fdd"),
+            Part::Emphasized(ChangeTag::Insert) => Some("color: #080; background: #dfd"),
+            Part::LineNumber
+            | Part::Sign(ChangeTag::Equal)
+            | Part::Emphasized(ChangeTag::Equal) => Some("color: #888"),
+            Part::Text(ChangeTag::Equal) => None,
+        }
+    }
+}
+
+fn paint(target: Render, value: impl Display, part: Part) -> String {
+    match target {
+        Render::Ansi => value.style(part.style()).to_string(),
+        Render::Plain => value.to_string(),
+        Render::Html => {
+            let escaped = escape(&value.to_string());
+            match part.css() {
+                Some(css) => format!("<span style=\"{css}\">{escaped}</span>"),
+                None => escaped,
+            }
+        }
+    }
+}
+
 struct Line(Option<usize>);
 
 impl Display for Line {
@@ -98,3 +399,94 @@ impl Display for Line {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    use super::*;
+
+    #[test]
+    fn test_diff_header() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+        let diff = diff_header(old, new, "old", "new", Render::Plain).unwrap();
+        assert!(!diff.contains('\u{1b}'), "{diff:?}");
+        expect![[r#"
+            --- old
+            +++ new
+            1   1    │ a
+            2        │-b
+                2    │+B
+            3   3    │ c
+            4   4    │ d
+            5   5    │ e
+            ────────────────────────────────────────────────────────────────────────────────
+            8   8    │ h
+            9   9    │ i
+            10  10   │ j
+                11   │+k
+        "#]]
+        .assert_eq(&diff);
+    }
+
+    #[test]
+    fn test_diff_words() {
+        assert_eq!(
+            diff_words(
+                "Apartment 731 (2 bed, $4,260, avail. Oct 21 2022)",
+                "Apartment 731 (2 bed, $4,100, avail. Nov 15 2022)",
+                Render::Plain
+            ),
+            "Apartment 731 (2 bed, [-$4,260,-]{+$4,100,+} avail. [-Oct 21-]{+Nov 15+} 2022)"
+        );
+    }
+
+    #[test]
+    fn test_json_patch() {
+        let old = serde_json::json!({
+            "price": 4260.0,
+            "plan": { "name": "f-b4v", "image": "a.jpg" },
+            "promotions": ["a", "b"],
+            "a/b": 1,
+        });
+        let new = serde_json::json!({
+            "price": 4100.0,
+            "plan": { "name": "f-b4v" },
+            "promotions": ["a"],
+            "a/b": 1,
+            "tour": true,
+        });
+        let ops = json_patch(&old, &new);
+        assert_eq!(
+            serde_json::to_value(&ops).unwrap(),
+            serde_json::json!([
+                { "op": "remove", "path": "/plan/image" },
+                { "op": "replace", "path": "/price", "value": 4100.0 },
+                { "op": "remove", "path": "/promotions/1" },
+                { "op": "add", "path": "/tour", "value": true },
+            ])
+        );
+        assert_eq!(json_patch(&old, &old), vec![]);
+        assert_eq!(
+            diff_json(&old, &new, Render::Plain),
+            "remove /plan/image (was \"a.jpg\")\n\
+             replace /price: 4260.0 → 4100.0\n\
+             remove /promotions/1 (was \"b\")\n\
+             add /tour: true\n"
+        );
+        assert_eq!(escape_pointer("a/b~c"), "a~1b~0c");
+    }
+
+    #[test]
+    fn test_diff_html() {
+        let diff = diff("<a>\n", "<b>\n", Render::Html).unwrap();
+        assert!(!diff.contains('\u{1b}'), "{diff:?}");
+        assert!(!diff.contains("<a>"), "{diff:?}");
+        expect![[r#"
+            <pre style="font-family: monospace"><span style="color: #888">1   </span><span style="color: #888">    </span> │<span style="color: #c00; font-weight: bold">-</span><span style="color: #c00; background: #fdd">&lt;a&gt;</span><span style="color: #c00">
+            </span><span style="color: #888">    </span><span style="color: #888">1   </span> │<span style="color: #080; font-weight: bold">+</span><span style="color: #080; background: #dfd">&lt;b&gt;</span><span style="color: #080">
+            </span></pre>"#]]
+        .assert_eq(&diff);
+    }
+}
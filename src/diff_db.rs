@@ -0,0 +1,219 @@
+//! What changed between two DB files, for the `diff-db` subcommand, like after restoring a
+//! backup.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fmt::Display;
+
+use serde::Serialize;
+
+use crate::api;
+use crate::listing::Listing;
+use crate::ApartmentStore;
+use crate::DiffEngine;
+
+/// What changed between two DBs, printed as text by [`render`], or as JSON by
+/// `diff-db --output json`.
+#[derive(Debug, Default, Serialize)]
+pub struct DbDiff {
+    /// Listings in the new DB but not the old one.
+    pub listed: Vec<ListingDiff>,
+    /// Listings in the old DB but not the new one.
+    pub unlisted: Vec<ListingDiff>,
+    /// Listings in both DBs, with different data.
+    pub changed: Vec<ListingDiff>,
+    pub watched: IdsDiff,
+    pub ignored: IdsDiff,
+}
+
+/// A listing in a [`DbDiff`], as of the new DB if it's there.
+#[derive(Debug, Serialize)]
+pub struct ListingDiff {
+    pub id: String,
+    /// A human-readable description of the listing.
+    pub summary: String,
+    /// The fields that changed, like `rent: $4,260 → $4,100`. Empty unless the listing changed.
+    pub changes: Vec<String>,
+}
+
+impl ListingDiff {
+    fn new<T: Listing>(listing: &T, changes: Vec<String>) -> Self {
+        Self {
+            id: listing.id().to_owned(),
+            summary: listing.to_string(),
+            changes,
+        }
+    }
+}
+
+/// IDs added to and removed from a set in a [`DbDiff`], like the watched units.
+#[derive(Debug, Default, Serialize)]
+pub struct IdsDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl IdsDiff {
+    fn new(old: &BTreeSet<String>, new: &BTreeSet<String>) -> Self {
+        Self {
+            added: new.difference(old).cloned().collect(),
+            removed: old.difference(new).cloned().collect(),
+        }
+    }
+}
+
+/// The listings added, unlisted, and changed from `old` to `new`, according to `engine`, and
+/// the units watched or ignored in one but not the other.
+pub fn diff(old: &ApartmentStore, new: &ApartmentStore, engine: &DiffEngine) -> DbDiff {
+    let mut diff = DbDiff {
+        watched: IdsDiff::new(&old.watched, &new.watched),
+        ignored: IdsDiff::new(&old.ignored, &new.ignored),
+        ..Default::default()
+    };
+    listings(
+        &mut diff,
+        engine,
+        &old.known_apartments,
+        &new.known_apartments,
+    );
+    listings(&mut diff, engine, &old.known_posts, &new.known_posts);
+    diff
+}
+
+/// Add the listings which changed from `old` to `new` to `diff`, source by source.
+fn listings<T: Listing>(
+    diff: &mut DbDiff,
+    engine: &DiffEngine,
+    old: &BTreeMap<String, api::Apartment<T>>,
+    new: &BTreeMap<String, api::Apartment<T>>,
+) {
+    let sources: BTreeSet<&str> = old
+        .values()
+        .chain(new.values())
+        .flat_map(|apt| std::iter::once(&apt.source).chain(&apt.also_listed_in))
+        .map(String::as_str)
+        .collect();
+    for source in sources {
+        let new_data = new
+            .values()
+            .filter(|apt| apt.listed_in(source))
+            .cloned()
+            .collect();
+        let source_diff = engine.diff(source, &mut old.clone(), &mut BTreeMap::new(), new_data);
+        diff.listed.extend(
+            source_diff
+                .added
+                .iter()
+                .map(|unit| ListingDiff::new(unit, Vec::new())),
+        );
+        diff.unlisted.extend(
+            source_diff
+                .removed
+                .iter()
+                .map(|unit| ListingDiff::new(&unit.inner, Vec::new())),
+        );
+        diff.changed
+            .extend(source_diff.changed.iter().map(|changed| {
+                let changes = changed.old.changes(&changed.new);
+                ListingDiff::new(
+                    &changed.new,
+                    changes.iter().map(ToString::to_string).collect(),
+                )
+            }));
+    }
+}
+
+/// `diff` as text, with a section for each kind of change.
+pub fn render(diff: &DbDiff) -> String {
+    let listings = |listings: &[ListingDiff]| {
+        listings
+            .iter()
+            .map(|listing| {
+                std::iter::once(listing.summary.clone())
+                    .chain(listing.changes.iter().map(|change| format!("• {change}")))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+            .collect::<Vec<_>>()
+    };
+    let ids = |ids: &IdsDiff| {
+        ids.added
+            .iter()
+            .map(|id| format!("{id} (added)"))
+            .chain(ids.removed.iter().map(|id| format!("{id} (removed)")))
+            .collect::<Vec<_>>()
+    };
+    let sections: Vec<String> = [
+        section("Listed", listings(&diff.listed)),
+        section("Unlisted", listings(&diff.unlisted)),
+        section("Changed", listings(&diff.changed)),
+        section("Watched", ids(&diff.watched)),
+        section("Ignored", ids(&diff.ignored)),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if sections.is_empty() {
+        "No differences\n".to_owned()
+    } else {
+        sections.join("\n")
+    }
+}
+
+/// A section titled `title` listing `items`, or `None` if there aren't any.
+fn section(title: &str, items: Vec<impl Display>) -> Option<String> {
+    let items: Vec<String> = items
+        .into_iter()
+        .map(|item| format!("• {}\n", item.to_string().replace('\n', "\n  ")))
+        .collect();
+    (!items.is_empty()).then(|| format!("{title}:\n{}", items.concat()))
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    use super::*;
+    use crate::api::fixtures::apartment_731;
+    use crate::api::fixtures::apartment_731_at;
+    use crate::AVA_URL;
+
+    #[test]
+    fn test_render() {
+        let store = |units: Vec<api::ApiApartment>| ApartmentStore {
+            known_apartments: units
+                .into_iter()
+                .map(|unit| (unit.unit_id.clone(), api::Apartment::new(AVA_URL, unit)))
+                .collect(),
+            ..Default::default()
+        };
+        let mut unit_732 = apartment_731();
+        unit_732.unit_id = "AVB-WA026-001-732".to_owned();
+        unit_732.number = "732".to_owned();
+        let mut unit_733 = apartment_731();
+        unit_733.unit_id = "AVB-WA026-001-733".to_owned();
+        unit_733.number = "733".to_owned();
+
+        let old = store(vec![apartment_731(), unit_732]);
+        let mut new = store(vec![apartment_731_at(4100.0), unit_733]);
+        new.watched.insert(apartment_731().unit_id);
+
+        let engine = DiffEngine::default();
+        let render = |old, new| render(&diff(old, new, &engine));
+        expect![[r#"
+            Listed:
+            • Apartment 733 (7th floor, 2 bed 2 bath, $4,260, 1268sq/ft ($3.36/sq/ft), avail. Oct 21 2022, plan f-b4v)
+
+            Unlisted:
+            • Apartment 732 (7th floor, 2 bed 2 bath, $4,260, 1268sq/ft ($3.36/sq/ft), avail. Oct 21 2022, plan f-b4v)
+
+            Changed:
+            • Apartment 731 (7th floor, 2 bed 2 bath, $4,100, 1268sq/ft ($3.23/sq/ft), avail. Oct 21 2022, plan f-b4v)
+              • rent: $4,260 → $4,100
+
+            Watched:
+            • AVB-WA026-001-731 (added)
+        "#]].assert_eq(&render(&old, &new));
+        assert_eq!(render(&old, &old), "No differences\n");
+    }
+}
@@ -0,0 +1,396 @@
+//! Comparing freshly-fetched listings to the ones we already know about.
+
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fmt::Display;
+
+use chrono::Utc;
+
+use crate::api;
+use crate::changes;
+use crate::changes::IgnoreChanges;
+use crate::diff;
+use crate::events::Event;
+use crate::events::EventKind;
+use crate::listing::Listing;
+use crate::money::Money;
+use crate::price_drop;
+
+/// The listings from one source which were added, removed, or changed since the last fetch.
+#[derive(Clone, Debug)]
+pub struct ApartmentsDiff<T = api::ApiApartment> {
+    pub added: Vec<T>,
+    pub removed: Vec<api::Apartment<T>>,
+    pub changed: Vec<ChangedApartment<T>>,
+    /// How many known listings changed in ways which aren't reported, like ignored changes or
+    /// being listed by another source, which still need to be saved.
+    pub updated: usize,
+}
+
+impl<T> Default for ApartmentsDiff<T> {
+    fn default() -> Self {
+        Self {
+            added: Vec::new(),
+            removed: Vec::new(),
+            changed: Vec::new(),
+            updated: 0,
+        }
+    }
+}
+
+impl<T> ApartmentsDiff<T> {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Whether the known or unlisted listings changed at all, reported or not.
+    pub fn needs_saving(&self) -> bool {
+        !self.is_empty() || self.updated > 0
+    }
+}
+
+impl<T: Listing> ApartmentsDiff<T> {
+    /// The events to record for this diff of listings from the source at `source`.
+    pub fn events(&self, source: &str) -> Vec<Event> {
+        self.added
+            .iter()
+            .map(|unit| Event::new(EventKind::Listed, source, unit))
+            .chain(
+                self.removed
+                    .iter()
+                    .map(|unit| Event::new(EventKind::Unlisted, source, &unit.inner)),
+            )
+            .chain(
+                self.changed
+                    .iter()
+                    .map(|changed| Event::new(EventKind::Changed, source, &changed.new)),
+            )
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ChangedApartment<T = api::ApiApartment> {
+    pub old: T,
+    pub new: T,
+    /// The highest rent seen for this listing before `new`.
+    pub max_rent: Option<Money>,
+}
+
+impl<T: Listing> ChangedApartment<T> {
+    /// If the rent dropped enough to alert about, by how much.
+    pub fn price_drop(&self, alert: &price_drop::PriceDropAlert) -> Option<price_drop::PriceDrop> {
+        alert.check(self.old.rent()?, self.max_rent, self.new.rent()?)
+    }
+
+    /// A diff of the listing's full data, for debugging: the one-line summaries word by word,
+    /// then the serialized data structurally.
+    pub fn debug_diff(&self) -> String {
+        let Self { old, new, .. } = self;
+        let summary = diff::diff_words(&old.to_string(), &new.to_string(), diff::Render::Plain);
+        match diff::diff_values(old, new, diff::Mode::Json, diff::Render::Plain) {
+            Ok(data) => format!("{summary}\n{data}"),
+            Err(err) => format!("{summary}\n{err:?}"),
+        }
+    }
+}
+
+impl<T: Listing> Display for ChangedApartment<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.new)?;
+        for change in self.old.changes(&self.new) {
+            write!(f, "\n• {change}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Computes [`ApartmentsDiff`]s, leaving out changes which are too small to report.
+#[derive(Clone, Debug, Default)]
+pub struct DiffEngine {
+    ignore: IgnoreChanges,
+    /// Price drops worth alerting about are always reported, even if `ignore` would drop them.
+    price_drop: price_drop::PriceDropAlert,
+}
+
+impl DiffEngine {
+    pub fn new(ignore: IgnoreChanges, price_drop: price_drop::PriceDropAlert) -> Self {
+        Self { ignore, price_drop }
+    }
+
+    /// Update `known` to contain exactly the listings in `new_data` from the source at
+    /// `source` and return the changes.
+    ///
+    /// Listings from `source` in `known` but not in `new_data` are marked as unlisted and moved
+    /// to `unlisted`, unless another source still lists them. Listings from other sources are
+    /// left alone; if `new_data` has one, it's the same listing, now listed in `source` too.
+    #[tracing::instrument(skip_all)]
+    pub fn diff<T: Listing>(
+        &self,
+        source: &str,
+        known: &mut BTreeMap<String, api::Apartment<T>>,
+        unlisted: &mut BTreeMap<String, api::Apartment<T>>,
+        new_data: Vec<api::Apartment<T>>,
+    ) -> ApartmentsDiff<T> {
+        let mut diff = ApartmentsDiff::default();
+        // The IDs of the apartments in the new data, so we can find the known apartments from
+        // `source` which aren't listed anymore.
+        let mut seen = BTreeSet::new();
+
+        for apt in new_data {
+            seen.insert(apt.id().to_owned());
+            match known.entry(apt.id().to_owned()) {
+                Entry::Occupied(mut entry) => self.update(source, entry.get_mut(), apt, &mut diff),
+                Entry::Vacant(entry) => {
+                    // A new apartment!!!
+                    diff.added.push(apt.inner.clone());
+                    entry.insert(apt);
+                }
+            }
+        }
+
+        // Move the apartments which weren't in the new data to `unlisted`, noting when they
+        // were unlisted.
+        let mut gone = Vec::new();
+        for (id, apt) in known.iter_mut() {
+            if apt.listed_in(source) && !seen.contains(id) {
+                if apt.delist_from(source) {
+                    diff.updated += 1;
+                } else {
+                    gone.push(id.clone());
+                }
+            }
+        }
+        let now = Utc::now();
+        for id in gone {
+            if let Some(mut unit) = known.remove(&id) {
+                unit.unlisted = Some(now);
+                diff.removed.push(unit.clone());
+                unlisted.insert(id, unit);
+            }
+        }
+
+        diff
+    }
+
+    /// Update `known_unit` to the freshly-fetched `apt` from `source` with the same ID, keeping
+    /// the time it was first listed, the sources listing it, and the highest rent seen for it.
+    ///
+    /// Adds the change since the last reported data to `diff`, if it's significant enough to
+    /// report. Otherwise, the last reported data is kept in [`api::Apartment::reported`] to
+    /// compare the next update against, and the unit is counted in
+    /// [`ApartmentsDiff::updated`] if anything changed.
+    fn update<T: Listing>(
+        &self,
+        source: &str,
+        known_unit: &mut api::Apartment<T>,
+        mut apt: api::Apartment<T>,
+        diff: &mut ApartmentsDiff<T>,
+    ) {
+        // `api::Apartment::new` sets the listed time to now, so copy the original one.
+        apt.listed = known_unit.listed;
+        apt.source = std::mem::take(&mut known_unit.source);
+        apt.also_listed_in = std::mem::take(&mut known_unit.also_listed_in);
+        let listed_elsewhere = apt.source != source && apt.also_listed_in.insert(source.to_owned());
+        apt.max_rent = match (known_unit.max_rent, apt.max_rent) {
+            (Some(old), Some(new)) => Some(old.max(new)),
+            (old, new) => old.or(new),
+        };
+        let old = std::mem::replace(known_unit, apt);
+        let updated = listed_elsewhere
+            || old.inner != known_unit.inner
+            || old.max_rent != known_unit.max_rent;
+        // Measure from the last data we reported, so e.g. a rent which falls a little every
+        // tick is reported once the drops add up.
+        let was_reported = old.reported.is_some();
+        let baseline = old.reported.unwrap_or(old.inner);
+        if baseline == known_unit.inner {
+            // Forgetting the last reported data is a change too.
+            if updated || was_reported {
+                diff.updated += 1;
+            }
+            return;
+        }
+
+        // It's different data! Show what changed.
+        let changed = ChangedApartment {
+            old: baseline,
+            new: known_unit.inner.clone(),
+            max_rent: old.max_rent,
+        };
+        let changes = changed.old.changes(&changed.new);
+        let significant =
+            self.ignore.any_significant(&changes) || changed.price_drop(&self.price_drop).is_some();
+        tracing::debug!(
+            id = changed.new.id(),
+            significant,
+            "Changed: {}\n{}",
+            changes::summary(&changes),
+            changed.debug_diff()
+        );
+        // Only report the change if it's not just noise.
+        if significant {
+            diff.changed.push(changed);
+        } else {
+            known_unit.reported = Some(changed.old);
+            if updated || !was_reported {
+                diff.updated += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    use super::*;
+    use crate::api::fixtures::apartment_731;
+    use crate::api::fixtures::apartment_731_at;
+    use crate::AVA_URL;
+
+    #[test]
+    fn test_changed_apartment_display() {
+        let changed = ChangedApartment {
+            old: apartment_731(),
+            new: apartment_731_at(4100.0),
+            max_rent: None,
+        };
+        expect![[r#"
+            Apartment 731 (7th floor, 2 bed 2 bath, $4,100, 1268sq/ft ($3.23/sq/ft), avail. Oct 21 2022, plan f-b4v)
+            • rent: $4,260 → $4,100"#]].assert_eq(&changed.to_string());
+    }
+
+    #[test]
+    fn test_diff_ignores_noise() {
+        let diff = |new: api::ApiApartment, ignore: IgnoreChanges| {
+            let mut known = BTreeMap::new();
+            known.insert(
+                apartment_731().unit_id,
+                api::Apartment::new(AVA_URL, apartment_731()),
+            );
+            let new = vec![api::Apartment::new(AVA_URL, new)];
+            DiffEngine::new(ignore, Default::default()).diff(
+                AVA_URL,
+                &mut known,
+                &mut BTreeMap::new(),
+                new,
+            )
+        };
+
+        let ignore = IgnoreChanges {
+            rent_under: Money::from_dollars(25.0),
+            ..Default::default()
+        };
+        assert!(diff(apartment_731_at(4250.0), ignore.clone()).is_empty());
+        assert_eq!(
+            diff(apartment_731_at(4200.0), ignore.clone()).changed.len(),
+            1
+        );
+        assert_eq!(
+            diff(apartment_731_at(4250.0), Default::default())
+                .changed
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_diff_small_changes_add_up() {
+        let engine = DiffEngine::new(
+            IgnoreChanges {
+                rent_under: Money::from_dollars(25.0),
+                ..Default::default()
+            },
+            Default::default(),
+        );
+        let mut known = BTreeMap::new();
+        known.insert(
+            apartment_731().unit_id,
+            api::Apartment::new(AVA_URL, apartment_731()),
+        );
+        let mut diff = |rent| {
+            let new = vec![api::Apartment::new(AVA_URL, apartment_731_at(rent))];
+            engine.diff(AVA_URL, &mut known, &mut BTreeMap::new(), new)
+        };
+
+        // Ignored changes aren't reported, but still need saving.
+        let ignored = diff(4240.0);
+        assert!(ignored.is_empty() && ignored.needs_saving());
+        assert!(!diff(4240.0).needs_saving());
+        let changed = diff(4220.0).changed;
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].old.rent(), Some(Money::from_dollars(4260.0)));
+        assert_eq!(changed[0].new.rent(), Some(Money::from_dollars(4220.0)));
+        // The reported rent is the new baseline.
+        assert!(diff(4200.0).is_empty());
+        assert_eq!(diff(4190.0).changed.len(), 1);
+
+        // Drops worth alerting about are reported even if they're under `rent-under`.
+        let engine = DiffEngine {
+            price_drop: price_drop::PriceDropAlert {
+                amount: Some(Money::from_dollars(10.0)),
+                ..Default::default()
+            },
+            ..engine
+        };
+        let mut known = BTreeMap::new();
+        known.insert(
+            apartment_731().unit_id,
+            api::Apartment::new(AVA_URL, apartment_731()),
+        );
+        let new = vec![api::Apartment::new(AVA_URL, apartment_731_at(4245.0))];
+        let diff = engine.diff(AVA_URL, &mut known, &mut BTreeMap::new(), new);
+        assert_eq!(diff.changed.len(), 1);
+    }
+
+    #[test]
+    fn test_diff_in_place() {
+        let craigslist = "https://seattle.craigslist.org/search/apa?format=rss";
+        let mut other = apartment_731();
+        other.unit_id = "AVB-WA026-001-732".to_owned();
+        let mut known = BTreeMap::new();
+        let mut tracked = api::Apartment::new(AVA_URL, apartment_731());
+        tracked.listed -= chrono::Duration::days(3);
+        let listed = tracked.listed;
+        known.insert(apartment_731().unit_id, tracked);
+        known.insert(
+            other.unit_id.clone(),
+            api::Apartment::new(craigslist, other.clone()),
+        );
+        let mut unlisted = BTreeMap::new();
+        let engine = DiffEngine::default();
+
+        // Changed units keep their listed time and highest rent.
+        let new = vec![api::Apartment::new(AVA_URL, apartment_731_at(4100.0))];
+        let diff = engine.diff(AVA_URL, &mut known, &mut unlisted, new);
+        assert_eq!(diff.changed.len(), 1);
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+        let unit = &known[&apartment_731().unit_id];
+        assert_eq!(unit.listed, listed);
+        assert_eq!(unit.max_rent, Some(Money::from_dollars(4260.0)));
+
+        // Units from other sources are left alone.
+        let diff = engine.diff(AVA_URL, &mut known, &mut unlisted, Vec::new());
+        assert_eq!(diff.removed.len(), 1);
+        assert!(diff.removed[0].unlisted.is_some());
+        assert_eq!(known.keys().collect::<Vec<_>>(), [&other.unit_id]);
+        assert!(unlisted.contains_key(&apartment_731().unit_id));
+
+        // A unit with the same ID from another source is the same unit, listed in both.
+        let new = vec![api::Apartment::new(AVA_URL, other.clone())];
+        let diff = engine.diff(AVA_URL, &mut known, &mut unlisted, new);
+        assert!(diff.is_empty() && diff.needs_saving());
+        assert!(known[&other.unit_id].listed_in(AVA_URL));
+        assert!(known[&other.unit_id].listed_in(craigslist));
+
+        // It's only unlisted once neither source lists it.
+        let diff = engine.diff(craigslist, &mut known, &mut unlisted, Vec::new());
+        assert!(diff.is_empty() && diff.needs_saving());
+        assert_eq!(known[&other.unit_id].source, AVA_URL);
+        let diff = engine.diff(AVA_URL, &mut known, &mut unlisted, Vec::new());
+        assert_eq!(diff.removed.len(), 1);
+        assert!(known.is_empty());
+    }
+}
@@ -0,0 +1,151 @@
+//! Managing units by replying to their notification emails, enabled with `commands-mailbox`
+//! in the `[jmap]` table:
+//!
+//! ```toml
+//! [jmap]
+//! commands-mailbox = { role = "inbox" }
+//! ```
+//!
+//! Reply to a notification about a unit with a command on the first line:
+//!
+//! - `IGNORE`, `UNIGNORE`, `WATCH`, `UNWATCH`: Like the subcommands of the same names.
+//! - `SNOOZE 7d`: Don't notify about the unit for a while, in hours (`h`), days (`d`), or
+//!   weeks (`w`). A bare number is days.
+//!
+//! Commands aren't case-sensitive. The unit is found from the notification's `Message-ID`, so
+//! digests and reports can't be replied to. Only replies from `to` or `watch-to` are obeyed, and
+//! each reply is only handled once; the mailbox is checked at the start of every tick.
+
+use std::str::FromStr;
+
+use chrono::Duration;
+use color_eyre::eyre;
+use color_eyre::eyre::eyre;
+use jmap_client::email::EmailAddress;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum EmailCommand {
+    Ignore,
+    Unignore,
+    Watch,
+    Unwatch,
+    Snooze(Duration),
+}
+
+impl EmailCommand {
+    /// Find the command in the body of a reply, which is its first non-blank line.
+    pub fn from_reply(text: &str) -> eyre::Result<Self> {
+        text.lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .ok_or_else(|| eyre!("Reply is empty"))?
+            .parse()
+    }
+}
+
+impl FromStr for EmailCommand {
+    type Err = eyre::Report;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default().to_ascii_lowercase();
+        let parsed = match command.as_str() {
+            "ignore" => EmailCommand::Ignore,
+            "unignore" => EmailCommand::Unignore,
+            "watch" => EmailCommand::Watch,
+            "unwatch" => EmailCommand::Unwatch,
+            "snooze" => EmailCommand::Snooze(parse_duration(
+                words
+                    .next()
+                    .ok_or_else(|| eyre!("`SNOOZE` needs a duration, like `SNOOZE 7d`"))?,
+            )?),
+            _ => return Err(eyre!("Unknown command `{line}`")),
+        };
+        if words.next().is_some() {
+            return Err(eyre!("Unexpected text after command `{line}`"));
+        }
+        Ok(parsed)
+    }
+}
+
+/// Parse a duration like `12h`, `7d`, `2w`, or `3` (days).
+fn parse_duration(duration: &str) -> eyre::Result<Duration> {
+    let split = duration
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(duration.len());
+    let (count, unit) = duration.split_at(split);
+    let count: i64 = count
+        .parse()
+        .map_err(|_| eyre!("Invalid duration `{duration}`"))?;
+    match unit.to_ascii_lowercase().as_str() {
+        "h" => Ok(Duration::hours(count)),
+        "" | "d" => Ok(Duration::days(count)),
+        "w" => Ok(Duration::weeks(count)),
+        _ => Err(eyre!(
+            "Invalid duration `{duration}`, expected hours (`h`), days (`d`), or weeks (`w`)"
+        )),
+    }
+}
+
+/// Whether a reply from `from` should be obeyed, i.e. it's from one of the `allowed`
+/// notification recipients.
+pub fn is_authorized<'a>(
+    from: &[String],
+    mut allowed: impl Iterator<Item = &'a EmailAddress>,
+) -> bool {
+    allowed.any(|allowed| {
+        from.iter()
+            .any(|from| from.eq_ignore_ascii_case(allowed.email()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_reply() {
+        assert_eq!(
+            EmailCommand::from_reply("\n  watch\n\nOn Oct 1, Ava wrote:\n> ...").unwrap(),
+            EmailCommand::Watch
+        );
+        assert_eq!(
+            EmailCommand::from_reply("IGNORE").unwrap(),
+            EmailCommand::Ignore
+        );
+        assert_eq!(
+            EmailCommand::from_reply("Snooze 7d").unwrap(),
+            EmailCommand::Snooze(Duration::days(7))
+        );
+        assert_eq!(
+            EmailCommand::from_reply("SNOOZE 12H").unwrap(),
+            EmailCommand::Snooze(Duration::hours(12))
+        );
+        assert_eq!(
+            EmailCommand::from_reply("snooze 2w").unwrap(),
+            EmailCommand::Snooze(Duration::weeks(2))
+        );
+        assert_eq!(
+            EmailCommand::from_reply("snooze 3").unwrap(),
+            EmailCommand::Snooze(Duration::days(3))
+        );
+        assert!(EmailCommand::from_reply("snooze").is_err());
+        assert!(EmailCommand::from_reply("snooze 3 months").is_err());
+        assert!(EmailCommand::from_reply("Thanks!").is_err());
+        assert!(EmailCommand::from_reply("\n\n").is_err());
+    }
+
+    #[test]
+    fn test_is_authorized() {
+        let to: EmailAddress = ("Me", "me@example.com").into();
+        assert!(is_authorized(
+            &["Me@Example.com".to_owned()],
+            [&to].into_iter()
+        ));
+        assert!(!is_authorized(
+            &["you@example.com".to_owned()],
+            [&to].into_iter()
+        ));
+        assert!(!is_authorized(&[], [&to].into_iter()));
+    }
+}
@@ -0,0 +1,74 @@
+//! Reporting errors and panics to Sentry, so new failure modes (a site redesign breaking the
+//! parser, an expired token) show up without grepping the logs.
+//!
+//! Enabled by setting `sentry-dsn` in the config file.
+
+use std::sync::Arc;
+
+use color_eyre::eyre;
+use sentry::integrations::tracing::EventFilter;
+use sentry::ClientInitGuard;
+use sentry::ClientOptions;
+use tracing::Level;
+use tracing::Subscriber;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use crate::redact::redact;
+
+/// Start reporting panics to Sentry at `dsn`. Messages are scrubbed with [`redact`]. Reporting
+/// stops when the returned guard is dropped, after sending anything still queued.
+pub fn init(dsn: &str) -> eyre::Result<ClientInitGuard> {
+    let guard = sentry::init((
+        dsn,
+        ClientOptions {
+            release: sentry::release_name!(),
+            before_send: Some(Arc::new(|mut event| {
+                event.message = event.message.as_deref().map(redact);
+                for exception in &mut event.exception.values {
+                    exception.value = exception.value.as_deref().map(redact);
+                }
+                Some(event)
+            })),
+            before_breadcrumb: Some(Arc::new(|mut breadcrumb| {
+                breadcrumb.message = breadcrumb.message.as_deref().map(redact);
+                Some(breadcrumb)
+            })),
+            ..Default::default()
+        },
+    ));
+    if !guard.is_enabled() {
+        return Err(eyre::eyre!("Invalid Sentry DSN `{dsn}`"));
+    }
+    Ok(guard)
+}
+
+/// A layer recording log messages as breadcrumbs on later reports, so they show what led up to
+/// an error. Does nothing unless [`init`] has been called.
+pub fn layer<S>() -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    sentry::integrations::tracing::layer().event_filter(|metadata| {
+        if *metadata.level() <= Level::INFO {
+            EventFilter::Breadcrumb
+        } else {
+            EventFilter::Ignore
+        }
+    })
+}
+
+/// Report `err`, with `context` like the tick number or source URL attached as tags.
+pub fn report(err: &eyre::Report, context: &[(&str, String)]) {
+    sentry::with_scope(
+        |scope| {
+            for (key, value) in context {
+                scope.set_tag(key, value);
+            }
+        },
+        || {
+            let err: &(dyn std::error::Error + 'static) = err.as_ref();
+            sentry::capture_error(err)
+        },
+    );
+}
@@ -0,0 +1,86 @@
+//! A log of everything that's happened to the listings we track.
+//!
+//! Unlike the notifications we send, events are recorded for every listing, qualified or not,
+//! so they double as the price history.
+
+use std::collections::HashMap;
+
+use async_graphql::Enum;
+use async_graphql::SimpleObject;
+use chrono::DateTime;
+use chrono::Utc;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::listing::Listing;
+use crate::money::Money;
+
+#[derive(Clone, Debug, Deserialize, Serialize, SimpleObject)]
+pub struct Event {
+    /// The event's number in the log, counting from 0, which stays the same when older events
+    /// are pruned. Set when it's recorded; see [`crate::store::ApartmentStore::record_events`].
+    #[serde(default)]
+    pub seq: u64,
+    pub time: DateTime<Utc>,
+    /// The [`Listing::id`] of the listing this happened to.
+    pub id: String,
+    /// The URL of the source the listing came from.
+    pub source: String,
+    /// A link to the listing's own page, if it has one.
+    #[serde(default)]
+    pub url: Option<String>,
+    pub kind: EventKind,
+    /// The listing's rent after the event, if known.
+    pub rent: Option<Money>,
+    /// A human-readable description of the listing after the event.
+    pub summary: String,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, Enum)]
+#[serde(rename_all = "kebab-case")]
+pub enum EventKind {
+    Listed,
+    Unlisted,
+    Changed,
+}
+
+/// The rents recorded for each listing, by ID, oldest first.
+///
+/// Takes one pass over `events`, so it's much cheaper than calling [`price_history`] for every
+/// listing.
+pub fn price_histories(events: &[Event]) -> HashMap<&str, Vec<(DateTime<Utc>, Money)>> {
+    let mut histories: HashMap<&str, Vec<_>> = HashMap::new();
+    for event in events {
+        if let Some(rent) = event.rent {
+            histories
+                .entry(&event.id)
+                .or_default()
+                .push((event.time, rent));
+        }
+    }
+    histories
+}
+
+/// The rents recorded for the listing with ID `id`, oldest first.
+pub fn price_history(events: &[Event], id: &str) -> Vec<(DateTime<Utc>, Money)> {
+    events
+        .iter()
+        .filter(|event| event.id == id)
+        .filter_map(|event| Some((event.time, event.rent?)))
+        .collect()
+}
+
+impl Event {
+    pub fn new(kind: EventKind, source: &str, listing: &impl Listing) -> Self {
+        Self {
+            seq: 0,
+            time: Utc::now(),
+            id: listing.id().to_owned(),
+            source: source.to_owned(),
+            url: listing.url(),
+            kind,
+            rent: listing.rent(),
+            summary: listing.to_string(),
+        }
+    }
+}
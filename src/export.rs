@@ -0,0 +1,131 @@
+//! Everything that gets a copy of the tracker's state after every tick: files, spreadsheets,
+//! and the like.
+
+use std::sync::Arc;
+
+use color_eyre::eyre;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use crate::airtable::Airtable;
+use crate::calendar;
+use crate::config::Config;
+use crate::feed;
+use crate::mqtt::Mqtt;
+use crate::notion::Notion;
+use crate::server::Snapshot;
+use crate::sheets::Sheets;
+
+pub struct Exporters {
+    sheets: Option<Sheets>,
+    notion: Option<Background>,
+    airtable: Option<Airtable>,
+    mqtt: Option<Mqtt>,
+}
+
+/// An exporter running in its own task, for ones too slow to hold up the tick, like Notion,
+/// which is rate limited to a few requests a second.
+///
+/// Snapshots sent while it's busy are skipped in favor of the latest.
+struct Background {
+    snapshots: watch::Sender<Option<Arc<Snapshot>>>,
+    task: JoinHandle<()>,
+}
+
+impl Background {
+    fn notion(mut notion: Notion) -> Self {
+        let (snapshots, mut receiver) = watch::channel(None::<Arc<Snapshot>>);
+        let task = tokio::spawn(async move {
+            while receiver.changed().await.is_ok() {
+                let snapshot = receiver.borrow_and_update().clone();
+                if let Some(snapshot) = snapshot {
+                    if let Err(err) = notion.sync(&snapshot).await {
+                        tracing::error!("Failed to sync Notion database: {err:?}");
+                    }
+                }
+            }
+        });
+        Self { snapshots, task }
+    }
+}
+
+impl Exporters {
+    /// Set up the exporters enabled in `config`. `next_event_seq` is the [`Event::seq`] of the
+    /// next event to be recorded, so earlier events aren't published again.
+    ///
+    /// [`Event::seq`]: crate::events::Event::seq
+    pub fn new(config: &Config, next_event_seq: u64) -> eyre::Result<Self> {
+        Ok(Self {
+            sheets: config.google_sheets.clone().map(Sheets::new).transpose()?,
+            notion: config
+                .notion
+                .clone()
+                .map(Notion::new)
+                .transpose()?
+                .map(Background::notion),
+            airtable: config.airtable.clone().map(Airtable::new).transpose()?,
+            mqtt: config
+                .mqtt
+                .clone()
+                .map(|config| Mqtt::new(config, next_event_seq)),
+        })
+    }
+
+    /// Whether there's anything to export to, so we can skip taking a snapshot.
+    pub fn is_empty(&self, config: &Config) -> bool {
+        self.sheets.is_none()
+            && self.notion.is_none()
+            && self.airtable.is_none()
+            && self.mqtt.is_none()
+            && config.feed_path.is_none()
+            && config.calendar_path.is_none()
+    }
+
+    /// Export `snapshot` everywhere, logging failures. Slow exporters, like Notion, finish in
+    /// the background.
+    pub async fn export(&mut self, config: &Config, snapshot: &Arc<Snapshot>) {
+        if let Some(path) = &config.feed_path {
+            if let Err(err) = feed::write(path, snapshot) {
+                tracing::error!("{err:?}");
+            }
+        }
+        if let Some(path) = &config.calendar_path {
+            if let Err(err) = calendar::write(path, snapshot) {
+                tracing::error!("{err:?}");
+            }
+        }
+        if let Some(sheets) = &self.sheets {
+            if let Err(err) = sheets.sync(snapshot).await {
+                tracing::error!("Failed to sync Google Sheet: {err:?}");
+            }
+        }
+        if let Some(notion) = &self.notion {
+            notion.snapshots.send_replace(Some(snapshot.clone()));
+        }
+        if let Some(airtable) = &self.airtable {
+            if let Err(err) = airtable.sync(snapshot).await {
+                tracing::error!("Failed to sync Airtable base: {err:?}");
+            }
+        }
+        if let Some(mqtt) = &mut self.mqtt {
+            if let Err(err) = mqtt.publish(snapshot) {
+                tracing::error!("Failed to publish to MQTT: {err:?}");
+            }
+        }
+    }
+
+    /// Finish sending anything still in flight, including the latest snapshot to background
+    /// exporters.
+    pub async fn close(self) {
+        if let Some(Background { snapshots, task }) = self.notion {
+            // The task stops once it's synced the last snapshot and sees the channel is closed.
+            drop(snapshots);
+            if let Err(err) = task.await {
+                tracing::error!("Notion sync task panicked: {err}");
+            }
+        }
+        if let Some(mqtt) = self.mqtt {
+            mqtt.disconnect().await;
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! An RSS feed of listing events, served at `/feed.xml` by [`crate::server`] and optionally
+//! written to a file, so other people can follow along in their feed reader.
+
+use std::fmt::Write;
+
+use camino::Utf8Path;
+use color_eyre::eyre;
+use color_eyre::eyre::Context;
+
+use crate::events::Event;
+use crate::events::EventKind;
+use crate::html::escape;
+use crate::listing::Listing;
+use crate::server::Snapshot;
+
+/// How many recent events to include.
+const ITEMS: usize = 50;
+
+/// Render the feed. Unless `show_all` is set, only events for listings meeting the
+/// qualifications are included.
+pub fn render(snapshot: &Snapshot, show_all: bool) -> String {
+    let mut rss = String::new();
+    let _ = write!(
+        rss,
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <rss version=\"2.0\"><channel>\
+         <title>Ava Apartment Finder</title>\
+         <link>{}</link>\
+         <description>New, changed, and unlisted apartments</description>",
+        escape(crate::AVA_URL),
+    );
+    if let Some(last_tick) = snapshot.last_tick {
+        let _ = write!(
+            rss,
+            "<lastBuildDate>{}</lastBuildDate>",
+            last_tick.to_rfc2822()
+        );
+    }
+
+    let events = snapshot
+        .events
+        .iter()
+        .rev()
+        .filter(|event| show_all || is_qualified(snapshot, event))
+        .take(ITEMS);
+    for event in events {
+        let kind = match event.kind {
+            EventKind::Listed => "Listed",
+            EventKind::Unlisted => "Unlisted",
+            EventKind::Changed => "Changed",
+        };
+        let _ = write!(
+            rss,
+            "<item>\
+             <title>{kind}: {}</title>\
+             <link>{}</link>\
+             <description>{}</description>\
+             <pubDate>{}</pubDate>\
+             <guid isPermaLink=\"false\">{}-{}-{kind}</guid>\
+             </item>",
+            escape(&event.summary),
+            escape(event.url.as_deref().unwrap_or(&event.source)),
+            escape(&event.summary),
+            event.time.to_rfc2822(),
+            escape(&event.id),
+            event.time.timestamp(),
+        );
+    }
+
+    rss.push_str("</channel></rss>\n");
+    rss
+}
+
+/// Write the feed of qualified listings to `path`.
+pub fn write(path: &Utf8Path, snapshot: &Snapshot) -> eyre::Result<()> {
+    std::fs::write(path, render(snapshot, false))
+        .wrap_err_with(|| format!("Failed to write feed to `{path}`"))
+}
+
+/// Does the listing `event` is about meet the qualifications? Listings we've forgotten about
+/// don't.
+fn is_qualified(snapshot: &Snapshot, event: &Event) -> bool {
+    let qualifications = &snapshot.qualifications;
+    let apartment = snapshot
+        .known_apartments
+        .get(&event.id)
+        .or_else(|| snapshot.unlisted_apartments.get(&event.id))
+        .map(|apt| apt.inner.meets_qualifications(qualifications));
+    let post = || {
+        snapshot
+            .known_posts
+            .get(&event.id)
+            .or_else(|| snapshot.unlisted_posts.get(&event.id))
+            .map(|post| post.inner.meets_qualifications(qualifications))
+    };
+    apartment.or_else(post).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use chrono::Utc;
+
+    use super::*;
+    use crate::money::Money;
+
+    #[test]
+    fn test_render() {
+        let snapshot = Snapshot {
+            events: vec![Event {
+                seq: 0,
+                time: Utc.ymd(2022, 10, 21).and_hms_opt(12, 0, 0).unwrap(),
+                id: "craigslist-7551234567".to_owned(),
+                source: "https://seattle.craigslist.org/search/apa?format=rss".to_owned(),
+                url: Some("https://seattle.craigslist.org/see/apa/d/7551234567.html".to_owned()),
+                kind: EventKind::Listed,
+                rent: Some(Money::from_dollars(3000.0)),
+                summary: "2br in Capitol Hill & Eastlake".to_owned(),
+            }]
+            .into(),
+            ..Default::default()
+        };
+
+        // We don't know anything about the listing, so it's not qualified.
+        assert!(!render(&snapshot, false).contains("<item>"));
+
+        let rss = render(&snapshot, true);
+        assert!(rss.contains(
+            "<item><title>Listed: 2br in Capitol Hill &amp; Eastlake</title>\
+             <link>https://seattle.craigslist.org/see/apa/d/7551234567.html</link>\
+             <description>2br in Capitol Hill &amp; Eastlake</description>\
+             <pubDate>Fri, 21 Oct 2022 12:00:00 +0000</pubDate>\
+             <guid isPermaLink=\"false\">craigslist-7551234567-1666353600-Listed</guid></item>"
+        ));
+    }
+}
@@ -0,0 +1,450 @@
+//! A small expression language for matching apartments, like
+//! `bedroom >= 2 && rent <= 4300 && (sqft / rent) > 0.28`.
+//!
+//! Expressions support numbers, `"strings"`, `true`/`false`, variables (which fields are
+//! available depends on the listing), arithmetic (`+ - * /`), comparisons
+//! (`== != < <= > >=`), boolean logic (`&& || !`), and parentheses.
+//!
+//! Variables must be one of [`VARIABLES`], so a typo like `rnet` is an error rather than a
+//! filter that never applies. Variables a listing doesn't have, like `sqft` for a Craigslist
+//! post, are unknown, and so is anything computed from them. `unknown && false` is `false` and `unknown || true` is `true`,
+//! like SQL's `NULL`, and a filter which is unknown overall passes. That way a listing is only
+//! checked against the fields it has.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use color_eyre::eyre;
+use color_eyre::eyre::eyre;
+use serde::Deserialize;
+
+/// The variables filters may use. Each listing type has some of them; see
+/// [`crate::listing::Listing::field`].
+pub const VARIABLES: [&str; 16] = [
+    "bedroom",
+    "bedrooms",
+    "bathroom",
+    "bathrooms",
+    "rent",
+    "effective_rent",
+    "price",
+    "sqft",
+    "square_feet",
+    "price_per_sqft",
+    "furnished",
+    "virtual_tour",
+    "plan",
+    "number",
+    "floor",
+    "title",
+];
+
+/// A value in a filter expression.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Bool(bool),
+    String(String),
+}
+
+impl Value {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "number",
+            Value::Bool(_) => "boolean",
+            Value::String(_) => "string",
+        }
+    }
+
+    fn as_bool(&self) -> eyre::Result<bool> {
+        match self {
+            Value::Bool(boolean) => Ok(*boolean),
+            value => Err(eyre!(
+                "Expected a boolean, got {} {value}",
+                value.type_name()
+            )),
+        }
+    }
+
+    fn as_number(&self) -> eyre::Result<f64> {
+        match self {
+            Value::Number(number) => Ok(*number),
+            value => Err(eyre!(
+                "Expected a number, got {} {value}",
+                value.type_name()
+            )),
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Number(number) => write!(f, "{number}"),
+            Value::Bool(boolean) => write!(f, "{boolean}"),
+            Value::String(string) => write!(f, "{string:?}"),
+        }
+    }
+}
+
+/// A parsed filter expression.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Filter {
+    source: String,
+    expr: Expr,
+}
+
+impl Filter {
+    /// Evaluate the filter, looking up variables with `lookup`.
+    ///
+    /// Passes if the result depends on variables `lookup` doesn't know. Fails if the expression
+    /// doesn't evaluate to a boolean.
+    pub fn matches(&self, lookup: impl Fn(&str) -> Option<Value>) -> eyre::Result<bool> {
+        match self.expr.eval(&lookup)? {
+            None => Ok(true),
+            Some(Value::Bool(matches)) => Ok(matches),
+            Some(value) => Err(eyre!(
+                "Filter `{}` evaluated to {value}, not a boolean",
+                self.source
+            )),
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = eyre::Report;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.expr(0)?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(eyre!("Unexpected {token:?} in filter `{source}`"));
+        }
+        Ok(Self {
+            source: source.to_owned(),
+            expr,
+        })
+    }
+}
+
+impl TryFrom<String> for Filter {
+    type Error = eyre::Report;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        source.parse()
+    }
+}
+
+impl Display for Filter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Literal(Value),
+    Variable(String),
+    Not(Box<Expr>),
+    Negate(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BinOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl BinOp {
+    fn from_token(token: &str) -> Option<Self> {
+        Some(match token {
+            "||" => BinOp::Or,
+            "&&" => BinOp::And,
+            "==" => BinOp::Eq,
+            "!=" => BinOp::Ne,
+            "<" => BinOp::Lt,
+            "<=" => BinOp::Le,
+            ">" => BinOp::Gt,
+            ">=" => BinOp::Ge,
+            "+" => BinOp::Add,
+            "-" => BinOp::Sub,
+            "*" => BinOp::Mul,
+            "/" => BinOp::Div,
+            _ => return None,
+        })
+    }
+
+    /// Higher binds tighter.
+    fn precedence(self) -> u8 {
+        match self {
+            BinOp::Or => 1,
+            BinOp::And => 2,
+            BinOp::Eq | BinOp::Ne => 3,
+            BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => 4,
+            BinOp::Add | BinOp::Sub => 5,
+            BinOp::Mul | BinOp::Div => 6,
+        }
+    }
+}
+
+impl Expr {
+    /// Evaluate the expression, or `None` if it depends on a variable `lookup` doesn't know.
+    fn eval(&self, lookup: &impl Fn(&str) -> Option<Value>) -> eyre::Result<Option<Value>> {
+        Ok(Some(match self {
+            Expr::Literal(value) => value.clone(),
+            Expr::Variable(name) => return Ok(lookup(name)),
+            Expr::Not(expr) => match expr.eval(lookup)? {
+                Some(value) => Value::Bool(!value.as_bool()?),
+                None => return Ok(None),
+            },
+            Expr::Negate(expr) => match expr.eval(lookup)? {
+                Some(value) => Value::Number(-value.as_number()?),
+                None => return Ok(None),
+            },
+            Expr::Binary(op @ (BinOp::Or | BinOp::And), lhs, rhs) => {
+                // `a || b` is `true` if either side is, even if the other is unknown, and
+                // `a && b` is `false` if either side is.
+                let short_circuit = *op == BinOp::Or;
+                let lhs = lhs.eval(lookup)?.map(|lhs| lhs.as_bool()).transpose()?;
+                if lhs == Some(short_circuit) {
+                    return Ok(Some(Value::Bool(short_circuit)));
+                }
+                let rhs = rhs.eval(lookup)?.map(|rhs| rhs.as_bool()).transpose()?;
+                match (lhs, rhs) {
+                    (_, Some(rhs)) if rhs == short_circuit => Value::Bool(short_circuit),
+                    (Some(_), Some(_)) => Value::Bool(!short_circuit),
+                    _ => return Ok(None),
+                }
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = match (lhs.eval(lookup)?, rhs.eval(lookup)?) {
+                    (Some(lhs), Some(rhs)) => (lhs, rhs),
+                    _ => return Ok(None),
+                };
+                match op {
+                    BinOp::Eq => Value::Bool(lhs == rhs),
+                    BinOp::Ne => Value::Bool(lhs != rhs),
+                    BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => {
+                        let ordering = match (&lhs, &rhs) {
+                            (Value::Number(lhs), Value::Number(rhs)) => lhs.partial_cmp(rhs),
+                            (Value::String(lhs), Value::String(rhs)) => lhs.partial_cmp(rhs),
+                            _ => None,
+                        }
+                        .ok_or_else(|| eyre!("Can't compare {lhs} and {rhs}"))?;
+                        Value::Bool(match op {
+                            BinOp::Lt => ordering.is_lt(),
+                            BinOp::Le => ordering.is_le(),
+                            BinOp::Gt => ordering.is_gt(),
+                            _ => ordering.is_ge(),
+                        })
+                    }
+                    _ => {
+                        let (lhs, rhs) = (lhs.as_number()?, rhs.as_number()?);
+                        Value::Number(match op {
+                            BinOp::Add => lhs + rhs,
+                            BinOp::Sub => lhs - rhs,
+                            BinOp::Mul => lhs * rhs,
+                            _ => lhs / rhs,
+                        })
+                    }
+                }
+            }
+        }))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    String(String),
+    Ident(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+}
+
+/// Operators, longest first so that e.g. `<=` isn't lexed as `<` `=`.
+const OPERATORS: [&str; 13] = [
+    "&&", "||", "==", "!=", "<=", ">=", "<", ">", "+", "-", "*", "/", "!",
+];
+
+fn tokenize(source: &str) -> eyre::Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+
+    while let Some(c) = rest.chars().next() {
+        if c == '(' {
+            tokens.push(Token::LParen);
+            rest = &rest[1..];
+        } else if c == ')' {
+            tokens.push(Token::RParen);
+            rest = &rest[1..];
+        } else if c == '"' {
+            let end = rest[1..]
+                .find('"')
+                .ok_or_else(|| eyre!("Unterminated string in filter `{source}`"))?;
+            tokens.push(Token::String(rest[1..end + 1].to_owned()));
+            rest = &rest[end + 2..];
+        } else if c.is_ascii_digit() || c == '.' {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '_'))
+                .unwrap_or(rest.len());
+            let number = rest[..end].replace('_', "");
+            tokens.push(Token::Number(number.parse().map_err(|err| {
+                eyre!("Invalid number `{number}` in filter `{source}`: {err}")
+            })?));
+            rest = &rest[end..];
+        } else if c.is_alphabetic() || c == '_' {
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..end].to_owned()));
+            rest = &rest[end..];
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        } else {
+            return Err(eyre!("Unexpected character `{c}` in filter `{source}`"));
+        }
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// Parse an expression containing only binary operators with at least `min_precedence`.
+    fn expr(&mut self, min_precedence: u8) -> eyre::Result<Expr> {
+        let mut lhs = self.unary()?;
+
+        while let Some(Token::Op(op)) = self.tokens.get(self.pos) {
+            let op = match BinOp::from_token(op) {
+                Some(op) if op.precedence() >= min_precedence => op,
+                _ => break,
+            };
+            self.pos += 1;
+            let rhs = self.expr(op.precedence() + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> eyre::Result<Expr> {
+        match self.next() {
+            Some(Token::Op("!")) => Ok(Expr::Not(Box::new(self.unary()?))),
+            Some(Token::Op("-")) => Ok(Expr::Negate(Box::new(self.unary()?))),
+            Some(Token::Number(number)) => Ok(Expr::Literal(Value::Number(number))),
+            Some(Token::String(string)) => Ok(Expr::Literal(Value::String(string))),
+            Some(Token::Ident(ident)) => Ok(match ident.as_str() {
+                "true" => Expr::Literal(Value::Bool(true)),
+                "false" => Expr::Literal(Value::Bool(false)),
+                _ if VARIABLES.contains(&ident.as_str()) => Expr::Variable(ident),
+                _ => {
+                    return Err(eyre!(
+                        "Unknown variable `{ident}`; expected one of: {}",
+                        VARIABLES.join(", ")
+                    ))
+                }
+            }),
+            Some(Token::LParen) => {
+                let expr = self.expr(0)?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(expr),
+                    token => Err(eyre!("Expected `)`, found {token:?}")),
+                }
+            }
+            token => Err(eyre!("Expected an expression, found {token:?}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<Value> {
+        match name {
+            "bedroom" => Some(Value::Number(2.0)),
+            "rent" => Some(Value::Number(4260.0)),
+            "sqft" => Some(Value::Number(1268.0)),
+            "plan" => Some(Value::String("f-b4v".to_owned())),
+            "furnished" => Some(Value::Bool(false)),
+            _ => None,
+        }
+    }
+
+    fn matches(source: &str) -> bool {
+        source.parse::<Filter>().unwrap().matches(lookup).unwrap()
+    }
+
+    #[test]
+    fn test_filter_example() {
+        assert!(matches(
+            "bedroom >= 2 && rent <= 4300 && (sqft / rent) > 0.28"
+        ));
+        assert!(!matches("bedroom >= 2 && rent <= 4000"));
+    }
+
+    #[test]
+    fn test_filter_precedence() {
+        assert!(matches("1 + 2 * 3 == 7"));
+        assert!(matches("(1 + 2) * 3 == 9"));
+        assert!(matches("false && false || true"));
+        assert!(matches("-rent < 0"));
+        assert!(matches("!furnished && plan == \"f-b4v\""));
+    }
+
+    #[test]
+    fn test_filter_unknown_variables() {
+        // `lookup` doesn't know `floor`.
+        assert!(matches("floor >= 4"));
+        assert!(matches("!(floor >= 4)"));
+        assert!(matches("floor >= 4 && rent <= 4300"));
+        assert!(!matches("floor >= 4 && rent <= 4000"));
+        assert!(!matches("rent <= 4000 && floor >= 4"));
+        assert!(matches("rent <= 4000 || floor >= 4"));
+        assert!(matches("floor * 2 > 8 || false"));
+    }
+
+    #[test]
+    fn test_filter_misspelled_variable() {
+        let err = "rnet < 3000".parse::<Filter>().unwrap_err();
+        assert!(err.to_string().starts_with("Unknown variable `rnet`"));
+    }
+
+    #[test]
+    fn test_filter_errors() {
+        assert!("rent <= ".parse::<Filter>().is_err());
+        assert!("(rent <= 4000".parse::<Filter>().is_err());
+        assert!("rent 4000".parse::<Filter>().is_err());
+        assert!("rent ~ 4000".parse::<Filter>().is_err());
+
+        let filter: Filter = "rent + 1".parse().unwrap();
+        assert!(filter.matches(lookup).is_err());
+    }
+}
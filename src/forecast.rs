@@ -0,0 +1,198 @@
+//! Rough forecasts of where rents are heading, from the recent price history of each floor plan,
+//! like "Estimate: likely to drop ~$80 in the next 2 weeks".
+//!
+//! These are guesses from a handful of data points, and are always labeled as estimates in
+//! notifications. The forecasting is behind the [`Model`] trait so a smarter model can replace
+//! [`LinearTrend`] later.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Utc;
+
+use crate::api::Apartment;
+use crate::filter::Value;
+use crate::listing::Listing;
+use crate::money::Money;
+
+/// How far ahead to forecast.
+const HORIZON_DAYS: i64 = 14;
+
+/// How far back to look for prices. Older prices say little about where rents are heading now.
+const LOOKBACK_DAYS: i64 = 60;
+
+/// Forecast changes smaller than this aren't worth mentioning.
+const MIN_CHANGE: Money = Money::from_cents(20_00);
+
+/// A series of rents observed for one unit, oldest first.
+pub type History = Vec<(DateTime<Utc>, Money)>;
+
+/// A way to predict how rents will change.
+pub trait Model {
+    /// The expected change in rent over `horizon` for units of a floor plan, from the
+    /// `histories` of each unit with that floor plan. `None` if there isn't enough data.
+    fn predict(&self, histories: &[History], horizon: Duration) -> Option<Money>;
+}
+
+/// A least-squares line through a floor plan's prices over time.
+///
+/// Each unit's prices are measured from its own average, so a floor plan's pricier units (e.g.
+/// on higher floors) don't look like a trend when they're listed later.
+pub struct LinearTrend;
+
+impl LinearTrend {
+    /// Fitting a line needs at least this many prices, from at least this many days.
+    const MIN_POINTS: usize = 3;
+    const MIN_SPAN_DAYS: f64 = 7.0;
+}
+
+impl Model for LinearTrend {
+    fn predict(&self, histories: &[History], horizon: Duration) -> Option<Money> {
+        let days = |time: DateTime<Utc>| time.timestamp() as f64 / (24.0 * 60.0 * 60.0);
+        let (mut covariance, mut variance, mut points) = (0.0, 0.0, 0);
+        let (mut first, mut last) = (f64::INFINITY, f64::NEG_INFINITY);
+        for history in histories.iter().filter(|history| !history.is_empty()) {
+            let n = history.len() as f64;
+            let mean_day = history.iter().map(|&(time, _)| days(time)).sum::<f64>() / n;
+            let mean_rent = history.iter().map(|(_, rent)| rent.dollars()).sum::<f64>() / n;
+            for &(time, rent) in history {
+                let day = days(time);
+                covariance += (day - mean_day) * (rent.dollars() - mean_rent);
+                variance += (day - mean_day).powi(2);
+                first = first.min(day);
+                last = last.max(day);
+                points += 1;
+            }
+        }
+        if points < Self::MIN_POINTS || last - first < Self::MIN_SPAN_DAYS || variance == 0.0 {
+            return None;
+        }
+        let dollars_per_day = covariance / variance;
+        Some(Money::from_dollars(
+            dollars_per_day * horizon.num_days() as f64,
+        ))
+    }
+}
+
+/// A predicted change in rent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Forecast {
+    pub change: Money,
+    pub horizon: Duration,
+}
+
+impl Display for Forecast {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let direction = if self.change < Money::ZERO {
+            "drop"
+        } else {
+            "rise"
+        };
+        // Round to $10, so the estimate doesn't look more precise than it is.
+        let amount = Money::from_dollars((self.change.abs().dollars() / 10.0).round() * 10.0);
+        let days = self.horizon.num_days();
+        let horizon = if days % 7 == 0 {
+            match days / 7 {
+                1 => "week".to_owned(),
+                weeks => format!("{weeks} weeks"),
+            }
+        } else {
+            format!("{days} days")
+        };
+        write!(
+            f,
+            "Estimate: likely to {direction} ~{amount} in the next {horizon}, \
+             based on this floor plan's price history"
+        )
+    }
+}
+
+/// Forecasts for each floor plan with enough recent price history across `apartments`, as of
+/// `now`, from their [`events::price_histories`].
+///
+/// [`events::price_histories`]: crate::events::price_histories
+pub fn by_floor_plan<'a, T: Listing + 'a>(
+    model: &dyn Model,
+    apartments: impl IntoIterator<Item = &'a Apartment<T>>,
+    price_histories: &HashMap<&str, History>,
+    now: DateTime<Utc>,
+) -> BTreeMap<String, Forecast> {
+    let since = now - Duration::days(LOOKBACK_DAYS);
+    let mut histories: BTreeMap<String, Vec<History>> = BTreeMap::new();
+    for apt in apartments {
+        if let Some(Value::String(plan)) = apt.inner.field("plan") {
+            let history = price_histories
+                .get(apt.id())
+                .into_iter()
+                .flatten()
+                .filter(|(time, _)| *time >= since)
+                .copied()
+                .collect();
+            histories.entry(plan).or_default().push(history);
+        }
+    }
+    let horizon = Duration::days(HORIZON_DAYS);
+    histories
+        .into_iter()
+        .filter_map(|(plan, histories)| {
+            let change = model.predict(&histories, horizon)?;
+            (change.abs() >= MIN_CHANGE).then_some((plan, Forecast { change, horizon }))
+        })
+        .collect()
+}
+
+/// The forecast for `listing`'s floor plan, if there is one.
+pub fn describe(listing: &impl Listing, forecasts: &BTreeMap<String, Forecast>) -> Option<String> {
+    match listing.field("plan") {
+        Some(Value::String(plan)) => forecasts.get(&plan).map(Forecast::to_string),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_linear_trend() {
+        let day = |day| Utc.ymd(2022, 8, day).and_hms_opt(12, 0, 0).unwrap();
+        let dollars = Money::from_dollars;
+        let horizon = Duration::days(14);
+
+        // Two units of the same floor plan, $400 apart, both dropping $10 a day.
+        let histories = [
+            vec![(day(1), dollars(4400.0)), (day(11), dollars(4300.0))],
+            vec![(day(5), dollars(4760.0)), (day(15), dollars(4660.0))],
+        ];
+        let change = LinearTrend.predict(&histories, horizon);
+        assert_eq!(change, Some(dollars(-140.0)));
+        assert_eq!(
+            Forecast {
+                change: change.unwrap(),
+                horizon
+            }
+            .to_string(),
+            "Estimate: likely to drop ~$140 in the next 2 weeks, \
+             based on this floor plan's price history"
+        );
+
+        // Not enough data.
+        assert_eq!(LinearTrend.predict(&histories[..1], horizon), None);
+        assert_eq!(
+            LinearTrend.predict(
+                &[vec![
+                    (day(1), dollars(4400.0)),
+                    (day(2), dollars(4300.0)),
+                    (day(3), dollars(4200.0)),
+                ]],
+                horizon
+            ),
+            None
+        );
+    }
+}
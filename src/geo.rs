@@ -0,0 +1,227 @@
+//! Where communities are, for map links and the `near` qualification, configured like:
+//!
+//! ```toml
+//! [geocoding]
+//! map = "google"
+//!
+//! [geocoding.addresses]
+//! "https://new.avaloncommunities.com/washington/seattle-apartments/ava-capitol-hill/" = "1830 Broadway, Seattle, WA 98122"
+//! ```
+//!
+//! Community addresses are geocoded with [Nominatim](https://nominatim.org/) (OpenStreetMap's
+//! geocoder) the first time they're fetched, and the coordinates are kept in the DB.
+//! Communities without a configured address are looked up by the name and city in their URL;
+//! see [`community_query`].
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use color_eyre::eyre;
+use color_eyre::eyre::Context;
+use reqwest::Url;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// The public Nominatim search endpoint.
+pub const NOMINATIM_URL: &str = "https://nominatim.openstreetmap.org/search";
+
+/// The mean radius of the Earth, in kilometers.
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// A point on the Earth, in degrees.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl Location {
+    /// The great-circle distance to `other`, in kilometers.
+    pub fn distance_km(&self, other: &Location) -> f64 {
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lon = (other.longitude - self.longitude).to_radians();
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+    }
+
+    /// A link to this location on `map`.
+    pub fn map_url(&self, map: MapProvider) -> String {
+        let Self {
+            latitude,
+            longitude,
+        } = self;
+        match map {
+            MapProvider::Openstreetmap => format!(
+                "https://www.openstreetmap.org/?mlat={latitude}&mlon={longitude}\
+                 #map=17/{latitude}/{longitude}"
+            ),
+            MapProvider::Google => {
+                format!("https://www.google.com/maps/search/?api=1&query={latitude},{longitude}")
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct GeocodingConfig {
+    /// The Nominatim search endpoint to use.
+    pub url: String,
+    /// Which map to link to in notifications.
+    pub map: MapProvider,
+    /// Community addresses, by page URL.
+    pub addresses: BTreeMap<String, String>,
+}
+
+impl Default for GeocodingConfig {
+    fn default() -> Self {
+        Self {
+            url: NOMINATIM_URL.to_owned(),
+            map: MapProvider::default(),
+            addresses: BTreeMap::new(),
+        }
+    }
+}
+
+impl GeocodingConfig {
+    /// What to search for to find the community with the page at `url`.
+    pub fn address(&self, url: &str) -> Option<String> {
+        self.addresses
+            .get(url)
+            .cloned()
+            .or_else(|| community_query(url))
+    }
+}
+
+/// Which map to link to in notifications.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MapProvider {
+    #[default]
+    Openstreetmap,
+    Google,
+}
+
+/// A search query for the community with the page at `url`, from its name and city, like
+/// `AVA Capitol Hill, Seattle, Washington` for
+/// `https://new.avaloncommunities.com/washington/seattle-apartments/ava-capitol-hill/`.
+pub fn community_query(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let segments: Vec<&str> = url.path_segments()?.filter(|s| !s.is_empty()).collect();
+    let [state, city, community] = segments[..] else {
+        return None;
+    };
+    let words = |slug: &str| {
+        slug.split('-')
+            .map(|word| match word {
+                "ava" | "avb" => word.to_uppercase(),
+                _ => {
+                    let mut chars = word.chars();
+                    chars
+                        .next()
+                        .map(|first| first.to_uppercase().chain(chars).collect())
+                        .unwrap_or_default()
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(" ")
+    };
+    let city = city.strip_suffix("-apartments").unwrap_or(city);
+    Some(format!(
+        "{}, {}, {}",
+        words(community),
+        words(city),
+        words(state)
+    ))
+}
+
+/// A Nominatim search result. Coordinates are strings, like `"47.6175"`.
+#[derive(Deserialize)]
+struct Place {
+    lat: String,
+    lon: String,
+}
+
+/// Looks up addresses with a Nominatim server.
+#[derive(Clone, Debug)]
+pub struct Geocoder {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl Geocoder {
+    /// A geocoder for the Nominatim search endpoint at `url`, like [`NOMINATIM_URL`].
+    pub fn new(url: &str) -> eyre::Result<Self> {
+        Ok(Self {
+            // Nominatim's usage policy requires an identifying user agent.
+            client: reqwest::Client::builder()
+                .user_agent(concat!("ava-apartment-finder/", env!("CARGO_PKG_VERSION")))
+                .timeout(Duration::from_secs(30))
+                .build()?,
+            url: url.to_owned(),
+        })
+    }
+
+    /// The location of `address`, or `None` if it couldn't be found.
+    #[tracing::instrument(skip(self))]
+    pub async fn geocode(&self, address: &str) -> eyre::Result<Option<Location>> {
+        let text = self
+            .client
+            .get(&self.url)
+            .query(&[("q", address), ("format", "json"), ("limit", "1")])
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let places: Vec<Place> =
+            serde_json::from_str(&text).wrap_err("Failed to parse geocoding response")?;
+        places
+            .first()
+            .map(|place| {
+                Ok(Location {
+                    latitude: place.lat.parse().wrap_err("Invalid latitude")?,
+                    longitude: place.lon.parse().wrap_err("Invalid longitude")?,
+                })
+            })
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance() {
+        let capitol_hill = Location {
+            latitude: 47.6175,
+            longitude: -122.3210,
+        };
+        let space_needle = Location {
+            latitude: 47.6205,
+            longitude: -122.3493,
+        };
+        let distance = capitol_hill.distance_km(&space_needle);
+        assert!((distance - 2.14).abs() < 0.01, "{distance}");
+        assert_eq!(capitol_hill.distance_km(&capitol_hill), 0.0);
+
+        assert_eq!(
+            capitol_hill.map_url(MapProvider::Google),
+            "https://www.google.com/maps/search/?api=1&query=47.6175,-122.321"
+        );
+    }
+
+    #[test]
+    fn test_community_query() {
+        assert_eq!(
+            community_query(crate::AVA_URL).as_deref(),
+            Some("AVA Capitol Hill, Seattle, Washington")
+        );
+        assert_eq!(
+            community_query("https://new.avaloncommunities.com/").as_deref(),
+            None
+        );
+    }
+}
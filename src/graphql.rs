@@ -0,0 +1,278 @@
+//! A GraphQL API over the tracker's state, served at `/graphql` by [`crate::server`], so the
+//! dashboard and scripts can ask for exactly what they need instead of downloading everything.
+//!
+//! ```graphql
+//! {
+//!   listings(filter: "bedrooms >= 2 && rent < 4300", limit: 10) {
+//!     id
+//!     summary
+//!     rent
+//!     priceHistory { time rent }
+//!   }
+//!   events(since: "2022-10-21T00:00:00Z", kind: LISTED) { time id summary }
+//! }
+//! ```
+//!
+//! Listings are filtered with the same expressions as the `filter` qualification; see
+//! [`crate::filter`].
+
+use std::sync::Arc;
+
+use async_graphql::ComplexObject;
+use async_graphql::Context;
+use async_graphql::EmptyMutation;
+use async_graphql::EmptySubscription;
+use async_graphql::Object;
+use async_graphql::SimpleObject;
+use chrono::DateTime;
+use chrono::NaiveDate;
+use chrono::Utc;
+
+use crate::api::Apartment;
+use crate::events;
+use crate::events::Event;
+use crate::events::EventKind;
+use crate::filter::Filter;
+use crate::listing::Listing;
+use crate::money::Money;
+use crate::server::Snapshot;
+use crate::server::Snapshots;
+
+/// The most results returned by a single query, whatever `limit` is.
+const MAX_LIMIT: usize = 500;
+
+pub type Schema = async_graphql::Schema<Query, EmptyMutation, EmptySubscription>;
+
+pub fn schema(snapshots: Snapshots) -> Schema {
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+        .data(snapshots)
+        .finish()
+}
+
+fn snapshot(ctx: &Context<'_>) -> Arc<Snapshot> {
+    ctx.data_unchecked::<Snapshots>().borrow().clone()
+}
+
+/// Apply `offset` and `limit` to `items`.
+fn paginate<T>(items: impl Iterator<Item = T>, offset: usize, limit: usize) -> Vec<T> {
+    items.skip(offset).take(limit.min(MAX_LIMIT)).collect()
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// Apartments and posts, ordered by ID.
+    async fn listings(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "An expression listings must match, like `bedrooms >= 2`.")]
+        filter: Option<String>,
+        #[graphql(desc = "Only listings meeting the configured qualifications.", default)]
+        qualified: bool,
+        #[graphql(desc = "Include listings which are no longer available.", default)]
+        include_unlisted: bool,
+        #[graphql(default)] offset: usize,
+        #[graphql(default = 100)] limit: usize,
+    ) -> async_graphql::Result<Vec<ListingNode>> {
+        let snapshot = snapshot(ctx);
+        let filter = filter.map(|filter| filter.parse::<Filter>()).transpose()?;
+
+        let mut nodes = Vec::new();
+        let mut add = |node: Option<ListingNode>| nodes.extend(node);
+        for apt in snapshot.known_apartments.values() {
+            add(ListingNode::matching(
+                &snapshot,
+                apt,
+                filter.as_ref(),
+                qualified,
+            ));
+        }
+        for post in snapshot.known_posts.values() {
+            add(ListingNode::matching(
+                &snapshot,
+                post,
+                filter.as_ref(),
+                qualified,
+            ));
+        }
+        if include_unlisted {
+            for apt in snapshot.unlisted_apartments.values() {
+                add(ListingNode::matching(
+                    &snapshot,
+                    apt,
+                    filter.as_ref(),
+                    qualified,
+                ));
+            }
+            for post in snapshot.unlisted_posts.values() {
+                add(ListingNode::matching(
+                    &snapshot,
+                    post,
+                    filter.as_ref(),
+                    qualified,
+                ));
+            }
+        }
+        nodes.sort_by(|a, b| a.id.cmp(&b.id));
+
+        Ok(paginate(nodes.into_iter(), offset, limit))
+    }
+
+    /// A single listing by ID, listed or not.
+    async fn listing(&self, ctx: &Context<'_>, id: String) -> Option<ListingNode> {
+        let snapshot = snapshot(ctx);
+        let apartment = snapshot
+            .known_apartments
+            .get(&id)
+            .or_else(|| snapshot.unlisted_apartments.get(&id))
+            .map(|apt| ListingNode::new(&snapshot, apt));
+        apartment.or_else(|| {
+            snapshot
+                .known_posts
+                .get(&id)
+                .or_else(|| snapshot.unlisted_posts.get(&id))
+                .map(|post| ListingNode::new(&snapshot, post))
+        })
+    }
+
+    /// Events, oldest first.
+    async fn events(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "Only events after this time.")] since: Option<DateTime<Utc>>,
+        #[graphql(desc = "Only events for the listing with this ID.")] id: Option<String>,
+        kind: Option<EventKind>,
+        #[graphql(default)] offset: usize,
+        #[graphql(default = 100)] limit: usize,
+    ) -> Vec<Event> {
+        let snapshot = snapshot(ctx);
+        let events = snapshot
+            .events
+            .iter()
+            .filter(|event| since.map_or(true, |since| event.time > since))
+            .filter(|event| id.as_ref().map_or(true, |id| &event.id == id))
+            .filter(|event| kind.map_or(true, |kind| event.kind == kind))
+            .cloned();
+        paginate(events, offset, limit)
+    }
+
+    /// When the last successful tick finished.
+    async fn last_tick(&self, ctx: &Context<'_>) -> Option<DateTime<Utc>> {
+        snapshot(ctx).last_tick
+    }
+}
+
+/// A tracked apartment or post.
+#[derive(SimpleObject)]
+#[graphql(name = "Listing", complex)]
+pub struct ListingNode {
+    id: String,
+    /// The URL of the source this listing was fetched from.
+    source: String,
+    /// A human-readable description of the listing.
+    summary: String,
+    /// A link to the listing's own page, if it has one.
+    url: Option<String>,
+    rent: Option<Money>,
+    /// The highest rent we've seen for this listing.
+    max_rent: Option<Money>,
+    available_date: Option<NaiveDate>,
+    virtual_tour_url: Option<String>,
+    /// Whether the listing meets the configured qualifications.
+    qualified: bool,
+    listed: DateTime<Utc>,
+    unlisted: Option<DateTime<Utc>>,
+}
+
+impl ListingNode {
+    fn new<T: Listing>(snapshot: &Snapshot, apt: &Apartment<T>) -> Self {
+        Self {
+            id: apt.id().to_owned(),
+            source: apt.source.clone(),
+            summary: apt.inner.to_string(),
+            url: apt.inner.url(),
+            rent: apt.inner.rent(),
+            max_rent: apt.max_rent,
+            available_date: apt.inner.available_date(),
+            virtual_tour_url: apt.inner.virtual_tour_url(),
+            qualified: apt.inner.meets_qualifications(&snapshot.qualifications),
+            listed: apt.listed,
+            unlisted: apt.unlisted,
+        }
+    }
+
+    /// A node for `apt`, if it matches `filter` and, if `qualified_only` is set, the
+    /// qualifications.
+    fn matching<T: Listing>(
+        snapshot: &Snapshot,
+        apt: &Apartment<T>,
+        filter: Option<&Filter>,
+        qualified_only: bool,
+    ) -> Option<Self> {
+        if let Some(filter) = filter {
+            match filter.matches(|name| apt.inner.field(name)) {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(err) => {
+                    tracing::debug!(id = apt.id(), %filter, "Failed to evaluate filter: {err}");
+                    return None;
+                }
+            }
+        }
+        let node = Self::new(snapshot, apt);
+        (!qualified_only || node.qualified).then_some(node)
+    }
+}
+
+#[derive(SimpleObject)]
+struct PricePoint {
+    time: DateTime<Utc>,
+    rent: Money,
+}
+
+#[ComplexObject]
+impl ListingNode {
+    /// The rents recorded for this listing, oldest first.
+    async fn price_history(&self, ctx: &Context<'_>) -> Vec<PricePoint> {
+        events::price_history(&snapshot(ctx).events, &self.id)
+            .into_iter()
+            .map(|(time, rent)| PricePoint { time, rent })
+            .collect()
+    }
+
+    /// Everything that's happened to this listing, oldest first.
+    async fn events(&self, ctx: &Context<'_>) -> Vec<Event> {
+        snapshot(ctx)
+            .events
+            .iter()
+            .filter(|event| event.id == self.id)
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::watch;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_schema() {
+        let (_sender, snapshots) = watch::channel(Arc::new(Snapshot::default()));
+        let response = schema(snapshots)
+            .execute("{ listings(filter: \"bedrooms >= 2\") { id } events { id } lastTick }")
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            serde_json::json!({ "listings": [], "events": [], "lastTick": null })
+        );
+
+        let response = schema(watch::channel(Arc::new(Snapshot::default())).1)
+            .execute("{ listings(filter: \"bedrooms >=\") { id } }")
+            .await;
+        assert_eq!(response.errors.len(), 1);
+    }
+}
@@ -0,0 +1,53 @@
+//! Dead man's switch pings, for services like [Healthchecks.io](https://healthchecks.io/).
+//!
+//! We ping after every tick, so the service can notify us if the pings stop because the daemon
+//! died.
+
+use std::time::Duration;
+
+use color_eyre::eyre;
+
+#[derive(Debug)]
+pub struct Healthcheck {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl Healthcheck {
+    pub fn new(url: String) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                // Don't hold up the next tick if the service is down.
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+            url,
+        }
+    }
+
+    /// Report a successful tick.
+    pub async fn success(&self) {
+        self.ping(self.url.clone(), String::new()).await
+    }
+
+    /// Report a failed tick, including the error in the ping body.
+    pub async fn failure(&self, err: &eyre::Report) {
+        let url = format!("{}/fail", self.url.trim_end_matches('/'));
+        self.ping(url, format!("{err:?}")).await
+    }
+
+    /// Failing to ping isn't fatal; if it keeps failing, the service will let us know.
+    async fn ping(&self, url: String, body: String) {
+        let result = self
+            .client
+            .post(&url)
+            .body(body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        match result {
+            Ok(_) => tracing::debug!(url, "Pinged healthcheck"),
+            Err(err) => tracing::warn!(url, "Failed to ping healthcheck: {err}"),
+        }
+    }
+}
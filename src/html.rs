@@ -0,0 +1,29 @@
+//! Helpers for writing HTML, for the dashboard, feeds, and HTML diffs.
+
+/// Escape `text` for use in HTML text or a quoted attribute.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape("Craigslist post \"2br <3\" & more"),
+            "Craigslist post &quot;2br &lt;3&quot; &amp; more"
+        );
+    }
+}
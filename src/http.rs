@@ -0,0 +1,497 @@
+//! The HTTP client used for scraping, with global and per-host rate limiting and `robots.txt`
+//! support.
+//!
+//! Responses can also be recorded to fixture files with `--record <dir>`, and replayed with
+//! `--replay <dir>` to run without touching the network. Each fixture is a JSON file named
+//! after its URL:
+//!
+//! ```json
+//! { "url": "https://seattle.craigslist.org/...", "status": 200, "body": "<?xml ..." }
+//! ```
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use camino::Utf8Path;
+use camino::Utf8PathBuf;
+use color_eyre::eyre;
+use color_eyre::eyre::eyre;
+use color_eyre::eyre::Context;
+use reqwest::IntoUrl;
+use reqwest::Response;
+use reqwest::Url;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::robots::Robots;
+
+/// The user agent we look for in `robots.txt` files.
+const ROBOTS_USER_AGENT: &str = "ava-apartment-finder";
+
+/// Rate limits for scraping, in requests per minute.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct RateLimit {
+    /// Requests per minute across all hosts.
+    pub global: Option<f64>,
+
+    /// Requests per minute to each host.
+    pub per_host: Option<f64>,
+
+    /// How many requests can be made back-to-back before the limit kicks in.
+    pub burst: u32,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            global: None,
+            per_host: Some(10.0),
+            burst: 1,
+        }
+    }
+}
+
+impl RateLimit {
+    /// Check that the rates are positive, so waiting for a token takes a finite time.
+    pub fn validate(&self) -> eyre::Result<()> {
+        for (name, rate) in [("global", self.global), ("per-host", self.per_host)] {
+            if let Some(rate) = rate {
+                if !(rate.is_finite() && rate > 0.0) {
+                    return Err(eyre!(
+                        "`rate-limit.{name}` must be a positive number of requests per minute, \
+                         not {rate}"
+                    ));
+                }
+            }
+        }
+        if self.burst == 0 {
+            return Err(eyre!("`rate-limit.burst` must be at least 1"));
+        }
+        Ok(())
+    }
+}
+
+/// Recording responses to fixture files, or replaying them instead of fetching.
+#[derive(Clone, Debug)]
+pub enum Fixtures {
+    /// Fetch pages as usual, and save each response to this directory.
+    Record(Utf8PathBuf),
+    /// Don't fetch anything; read responses from this directory instead.
+    ///
+    /// Rate limits and `robots.txt` are skipped, since no requests are made.
+    Replay(Utf8PathBuf),
+}
+
+/// A recorded response.
+#[derive(Debug, Deserialize, Serialize)]
+struct Fixture {
+    url: String,
+    status: u16,
+    body: String,
+}
+
+impl Fixture {
+    fn read(dir: &Utf8Path, url: &Url) -> eyre::Result<Self> {
+        let path = dir.join(fixture_name(url));
+        let json = std::fs::read_to_string(&path).wrap_err_with(|| {
+            format!("No fixture for {url} at {path}; record one with `--record {dir}`")
+        })?;
+        serde_json::from_str(&json).wrap_err_with(|| format!("Failed to parse fixture {path}"))
+    }
+
+    fn write(&self, dir: &Utf8Path, url: &Url) -> eyre::Result<()> {
+        std::fs::create_dir_all(dir).wrap_err_with(|| format!("Failed to create {dir}"))?;
+        let path = dir.join(fixture_name(url));
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)
+            .wrap_err_with(|| format!("Failed to write fixture {path}"))?;
+        tracing::debug!(%url, %path, "Recorded fixture");
+        Ok(())
+    }
+
+    fn into_response(self) -> eyre::Result<Response> {
+        Ok(::http::Response::builder()
+            .status(self.status)
+            .body(self.body)?
+            .into())
+    }
+}
+
+/// `url`'s path and query string, which `robots.txt` rules are matched against.
+fn path_and_query(url: &Url) -> String {
+    match url.query() {
+        Some(query) => format!("{}?{query}", url.path()),
+        None => url.path().to_owned(),
+    }
+}
+
+/// The file name to record `url`'s response to.
+///
+/// Readable, but with a hash of the whole URL in case two URLs differ only in punctuation or
+/// after the first 100 characters.
+fn fixture_name(url: &Url) -> String {
+    let url = url.as_str();
+    let readable = url
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    // FNV-1a, which unlike `DefaultHasher` is stable across Rust versions.
+    let hash = url.bytes().fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    });
+    let readable: String = readable.chars().take(100).collect();
+    format!("{readable}-{hash:016x}.json")
+}
+
+#[derive(Debug, Default)]
+pub struct Client {
+    client: reqwest::Client,
+    limit: RateLimit,
+    buckets: Mutex<Buckets>,
+    /// If `true`, fetch pages even if `robots.txt` disallows it.
+    ignore_robots_txt: bool,
+    fixtures: Option<Fixtures>,
+    /// `robots.txt` rules for each host, fetched on first use.
+    robots: tokio::sync::Mutex<HashMap<String, Arc<Robots>>>,
+}
+
+#[derive(Debug, Default)]
+struct Buckets {
+    global: Option<TokenBucket>,
+    per_host: HashMap<String, TokenBucket>,
+}
+
+impl Client {
+    pub fn new(limit: RateLimit, ignore_robots_txt: bool, fixtures: Option<Fixtures>) -> Self {
+        Self {
+            client: Default::default(),
+            ignore_robots_txt,
+            fixtures,
+            robots: Default::default(),
+            buckets: Mutex::new(Buckets {
+                global: limit
+                    .global
+                    .map(|rate| TokenBucket::new(rate, limit.burst, Instant::now())),
+                per_host: Default::default(),
+            }),
+            limit,
+        }
+    }
+
+    /// Make a `GET` request, waiting for the rate limit first if needed.
+    ///
+    /// Fails if `robots.txt` disallows fetching `url`, unless `ignore_robots_txt` is set.
+    ///
+    /// When replaying fixtures, the response is read from a file instead.
+    pub async fn get(&self, url: impl IntoUrl) -> eyre::Result<Response> {
+        let url = url.into_url()?;
+        if let Some(Fixtures::Replay(dir)) = &self.fixtures {
+            tracing::debug!(%url, "Replaying fixture");
+            return Fixture::read(dir, &url)?.into_response();
+        }
+        let host = url
+            .host_str()
+            .ok_or_else(|| eyre!("URL has no host: {url}"))?
+            .to_owned();
+
+        let robots = self.robots(&url).await;
+        if !robots.allows(&path_and_query(&url)) {
+            if self.ignore_robots_txt {
+                tracing::debug!(%url, "robots.txt disallows fetching URL, ignoring");
+            } else {
+                return Err(eyre!(
+                    "robots.txt disallows fetching {url}; \
+                     use `--ignore-robots-txt` to fetch it anyways"
+                ));
+            }
+        }
+
+        self.wait_for(&host, robots.crawl_delay).await;
+
+        let response = self
+            .client
+            .get(url.clone())
+            .send()
+            .await
+            .wrap_err_with(|| format!("Failed to fetch {url}"))?;
+
+        match &self.fixtures {
+            Some(Fixtures::Record(dir)) => {
+                let fixture = Fixture {
+                    url: url.to_string(),
+                    status: response.status().as_u16(),
+                    body: response
+                        .text()
+                        .await
+                        .wrap_err_with(|| format!("Failed to read {url}"))?,
+                };
+                fixture.write(dir, &url)?;
+                fixture.into_response()
+            }
+            _ => Ok(response),
+        }
+    }
+
+    /// Get the `robots.txt` rules for `url`'s host, fetching them if we haven't yet.
+    ///
+    /// If `robots.txt` can't be fetched, everything is allowed.
+    async fn robots(&self, url: &Url) -> Arc<Robots> {
+        if let Some(Fixtures::Replay(_)) = &self.fixtures {
+            return Default::default();
+        }
+        let origin = url.origin().ascii_serialization();
+        let mut robots = self.robots.lock().await;
+        if let Some(rules) = robots.get(&origin) {
+            return rules.clone();
+        }
+
+        let rules = Arc::new(match self.fetch_robots(&origin).await {
+            Ok(rules) => rules,
+            Err(err) => {
+                tracing::warn!("Failed to fetch robots.txt for {origin}, ignoring: {err:?}");
+                Robots::default()
+            }
+        });
+        tracing::debug!(origin, ?rules, "Fetched robots.txt");
+        robots.insert(origin, rules.clone());
+        rules
+    }
+
+    async fn fetch_robots(&self, origin: &str) -> eyre::Result<Robots> {
+        let response = self
+            .client
+            .get(format!("{origin}/robots.txt"))
+            .send()
+            .await?;
+        if response.status().is_client_error() {
+            // No `robots.txt`, no rules.
+            return Ok(Robots::default());
+        }
+        let text = response.error_for_status()?.text().await?;
+        Ok(Robots::parse(&text, ROBOTS_USER_AGENT))
+    }
+
+    /// The shortest interval we can fetch all of `urls` in, given the rate limits and
+    /// `robots.txt` crawl delays.
+    pub async fn min_poll_interval<'a>(&self, urls: impl IntoIterator<Item = &'a str>) -> Duration {
+        // Each URL is fetched once per tick, so count the requests to each origin.
+        let mut requests_per_origin: BTreeMap<String, (Url, u32)> = BTreeMap::new();
+        for url in urls {
+            match Url::parse(url) {
+                Ok(url) => {
+                    requests_per_origin
+                        .entry(url.origin().ascii_serialization())
+                        .or_insert((url, 0))
+                        .1 += 1
+                }
+                Err(err) => tracing::warn!(url, "Invalid URL: {err}"),
+            }
+        }
+
+        let mut interval = Duration::ZERO;
+        let mut total_requests = 0;
+        for (url, requests) in requests_per_origin.into_values() {
+            total_requests += requests;
+            let robots = self.robots(&url).await;
+            let delay = [
+                robots.crawl_delay,
+                self.limit
+                    .per_host
+                    .map(|rate| Duration::from_secs_f64(60.0 / rate)),
+            ]
+            .into_iter()
+            .flatten()
+            .max()
+            .unwrap_or_default();
+            interval = interval.max(delay.saturating_mul(requests));
+        }
+
+        if let Some(rate) = self.limit.global {
+            interval =
+                interval.max(Duration::from_secs_f64(60.0 / rate).saturating_mul(total_requests));
+        }
+
+        interval
+    }
+
+    /// Wait until both the global and `host` rate limits allow a request, and take a token from
+    /// each.
+    ///
+    /// If `crawl_delay` is set, requests to `host` are at least that far apart.
+    async fn wait_for(&self, host: &str, crawl_delay: Option<Duration>) {
+        loop {
+            let wait = {
+                let mut buckets = self.buckets.lock().expect("Rate limiter lock poisoned");
+                let now = Instant::now();
+                let Buckets { global, per_host } = &mut *buckets;
+                let crawl_delay_rate = crawl_delay
+                    .filter(|delay| !delay.is_zero())
+                    .map(|delay| 60.0 / delay.as_secs_f64());
+                let rate = match (self.limit.per_host, crawl_delay_rate) {
+                    (Some(rate), Some(crawl_delay_rate)) => Some(rate.min(crawl_delay_rate)),
+                    (rate, crawl_delay_rate) => rate.or(crawl_delay_rate),
+                };
+                let mut host_bucket = rate.map(|rate| {
+                    per_host
+                        .entry(host.to_owned())
+                        .or_insert_with(|| TokenBucket::new(rate, self.limit.burst, now))
+                });
+
+                let wait = [global.as_mut(), host_bucket.as_deref_mut()]
+                    .into_iter()
+                    .flatten()
+                    .map(|bucket| bucket.wait_time(now))
+                    .max()
+                    .unwrap_or_default();
+
+                if wait.is_zero() {
+                    if let Some(bucket) = global {
+                        bucket.take();
+                    }
+                    if let Some(bucket) = host_bucket {
+                        bucket.take();
+                    }
+                    return;
+                }
+
+                wait
+            };
+
+            tracing::debug!(host, ?wait, "Rate limited, waiting");
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// A token bucket, refilled at a constant rate up to some capacity.
+#[derive(Debug)]
+struct TokenBucket {
+    /// Tokens per second.
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(per_minute: f64, burst: u32, now: Instant) -> Self {
+        let capacity = f64::from(burst.max(1));
+        Self {
+            rate: per_minute / 60.0,
+            capacity,
+            tokens: capacity,
+            last_refill: now,
+        }
+    }
+
+    /// Refill the bucket and return how long until a token is available.
+    fn wait_time(&mut self, now: Instant) -> Duration {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) / self.rate)
+        }
+    }
+
+    /// Take a token. Only call this after [`TokenBucket::wait_time`] returns zero.
+    fn take(&mut self) {
+        self.tokens -= 1.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_secs(duration: Duration, secs: f64) {
+        assert!(
+            (duration.as_secs_f64() - secs).abs() < 0.001,
+            "{duration:?} != {secs}s"
+        );
+    }
+
+    #[test]
+    fn test_rate_limit_validate() {
+        let limit = |global, per_host| RateLimit {
+            global,
+            per_host,
+            burst: 1,
+        };
+        assert!(RateLimit::default().validate().is_ok());
+        assert!(limit(Some(60.0), None).validate().is_ok());
+        assert!(limit(Some(0.0), None).validate().is_err());
+        assert!(limit(None, Some(-1.0)).validate().is_err());
+        assert!(limit(None, Some(f64::INFINITY)).validate().is_err());
+        assert!(limit(None, Some(f64::NAN)).validate().is_err());
+    }
+
+    #[test]
+    fn test_fixture_name() {
+        let name = |url| fixture_name(&Url::parse(url).unwrap());
+        assert_eq!(
+            name("https://seattle.craigslist.org/search/apa?query=capitol+hill&format=rss"),
+            "seattle-craigslist-org-search-apa-query-capitol-hill-format-rss-5750830169a41e7b.json"
+        );
+        assert_ne!(
+            name("https://example.com/a-b"),
+            name("https://example.com/a/b")
+        );
+    }
+
+    #[test]
+    fn test_path_and_query() {
+        let path = |url| path_and_query(&Url::parse(url).unwrap());
+        assert_eq!(path("https://example.com/search/apa"), "/search/apa");
+        assert_eq!(
+            path("https://example.com/search/apa?format=rss#results"),
+            "/search/apa?format=rss"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replay() {
+        let dir = Utf8PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures"));
+        let client = Client::new(Default::default(), false, Some(Fixtures::Replay(dir)));
+        let response = client
+            .get("https://seattle.craigslist.org/search/apa?query=capitol+hill&format=rss")
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+        assert!(response.text().await.unwrap().contains("<rdf:RDF"));
+        assert!(client.get("https://example.com/").await.is_err());
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let start = Instant::now();
+        // One request every 6 seconds, up to 2 at once.
+        let mut bucket = TokenBucket::new(10.0, 2, start);
+
+        assert_eq!(bucket.wait_time(start), Duration::ZERO);
+        bucket.take();
+        assert_eq!(bucket.wait_time(start), Duration::ZERO);
+        bucket.take();
+        assert_secs(bucket.wait_time(start), 6.0);
+
+        let later = start + Duration::from_secs(3);
+        assert_secs(bucket.wait_time(later), 3.0);
+
+        let much_later = start + Duration::from_secs(600);
+        assert_eq!(bucket.wait_time(much_later), Duration::ZERO);
+        bucket.take();
+        assert_eq!(bucket.wait_time(much_later), Duration::ZERO);
+    }
+}
@@ -0,0 +1,300 @@
+//! The `init` subcommand: a wizard which asks for the essentials and writes a config file.
+//!
+//! The community page is checked by fetching its listings, and the mail settings by sending a
+//! test email, so typos show up now rather than on the first tick. Everything else keeps its
+//! default, and can be added to the file later; see [`crate::config::Config`].
+
+use std::io::BufRead;
+use std::io::Write;
+use std::sync::Arc;
+
+use camino::Utf8Path;
+use camino::Utf8PathBuf;
+use color_eyre::eyre;
+use color_eyre::eyre::eyre;
+use color_eyre::eyre::Context;
+
+use crate::config::Config;
+use crate::http;
+use crate::jmap;
+use crate::AvalonClient;
+use crate::AVA_URL;
+
+/// How to log in to the JMAP server. Secrets stay in environment variables, not the config.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Login {
+    /// A bearer token in the environment variable `env`.
+    Token { env: String },
+    /// `username`, with the password in the environment variable `env`.
+    Password { username: String, env: String },
+}
+
+/// Where notifications go, besides email.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Channel {
+    /// Just email.
+    Email,
+    /// An RSS feed written to a file; see [`crate::feed`].
+    Feed(Utf8PathBuf),
+    /// An iCalendar file of availability dates; see [`crate::calendar`].
+    Calendar(Utf8PathBuf),
+}
+
+/// Everything the wizard asks for.
+#[derive(Clone, Debug)]
+pub struct Answers {
+    pub community: String,
+    pub to_name: String,
+    pub to_email: String,
+    pub from_email: String,
+    pub session_url: String,
+    pub login: Login,
+    pub channel: Channel,
+}
+
+/// The config file for `answers`.
+pub fn render(answers: &Answers) -> String {
+    let mut config = format!(
+        "# Written by `ava-apartment-finder init`.\n\
+         communities = [{}]\n\
+         to = {{ name = {}, email = {} }}\n",
+        quote(&answers.community),
+        quote(&answers.to_name),
+        quote(&answers.to_email),
+    );
+    match &answers.channel {
+        Channel::Email => {}
+        Channel::Feed(path) => config.push_str(&format!("feed-path = {}\n", quote(path.as_str()))),
+        Channel::Calendar(path) => {
+            config.push_str(&format!("calendar-path = {}\n", quote(path.as_str())))
+        }
+    }
+    config.push_str(&format!(
+        "\n[jmap]\nsession-url = {}\nfrom = {{ name = \"Ava Apartment Finder\", email = {} }}\n",
+        quote(&answers.session_url),
+        quote(&answers.from_email),
+    ));
+    match &answers.login {
+        Login::Token { env } => config.push_str(&format!("token-env = {}\n", quote(env))),
+        Login::Password { username, env } => config.push_str(&format!(
+            "username = {}\npassword-env = {}\n",
+            quote(username),
+            quote(env)
+        )),
+    }
+    config
+}
+
+/// `value` as a TOML string.
+fn quote(value: &str) -> String {
+    toml::Value::from(value).to_string()
+}
+
+/// Ask for the settings, check them, and write the config to `path`. Refuses to overwrite an
+/// existing config unless `force` is set.
+pub async fn run(path: &Utf8Path, force: bool) -> eyre::Result<()> {
+    if path.exists() && !force {
+        return Err(eyre!(
+            "`{path}` already exists; pass `--force` to overwrite it"
+        ));
+    }
+    let mut prompt = Prompt::new();
+
+    println!("Which community should we watch? Paste the URL of its page on the Avalon site.");
+    let community = loop {
+        let url = prompt.ask("Community URL", Some(AVA_URL))?;
+        let http = Arc::new(http::Client::new(Default::default(), false, None));
+        match AvalonClient::new(http).get_apartments(&url).await {
+            Ok(data) => {
+                println!("Found {} units listed.", data.apartments.len());
+                break url;
+            }
+            Err(err) => {
+                println!("Couldn't get the listings from that page: {err:#}");
+                if prompt.confirm("Use it anyway?", false)? {
+                    break url;
+                }
+            }
+        }
+    };
+
+    println!("\nWho should notifications go to?");
+    let to_name = prompt.ask("Your name", None)?;
+    let to_email = prompt.ask("Your email", None)?;
+
+    println!("\nNotifications are sent through a JMAP server, like Fastmail.");
+    let defaults = jmap::JmapConfig::default();
+    let session_url = prompt.ask("JMAP session URL", Some(&defaults.session_url))?;
+    let from_email = prompt.ask(
+        "Send from (one of the account's sending addresses)",
+        Some(&to_email),
+    )?;
+    let login = if prompt.confirm("Log in with an API token?", true)? {
+        Login::Token {
+            env: prompt.ask(
+                "Environment variable holding the token",
+                Some(&defaults.token_env),
+            )?,
+        }
+    } else {
+        Login::Password {
+            username: prompt.ask("Username", Some(&from_email))?,
+            env: prompt.ask(
+                "Environment variable holding the password",
+                Some(&defaults.password_env),
+            )?,
+        }
+    };
+
+    let env = match &login {
+        Login::Token { env } | Login::Password { env, .. } => env,
+    };
+    if std::env::var_os(env).is_none() {
+        println!("`${env}` isn't set, so the test email will fail. Set it before continuing.");
+    }
+
+    println!("\nBesides email, listings can also go to:");
+    println!("  1. Nothing else");
+    println!("  2. An RSS feed file, for a feed reader");
+    println!("  3. An iCalendar file of availability dates, for a calendar app");
+    let channel = loop {
+        match prompt.ask("Choice", Some("1"))?.as_str() {
+            "1" => break Channel::Email,
+            "2" => break Channel::Feed(prompt.ask("Feed path", Some("ava-feed.xml"))?.into()),
+            "3" => {
+                break Channel::Calendar(
+                    prompt
+                        .ask("Calendar path", Some("ava-calendar.ics"))?
+                        .into(),
+                )
+            }
+            _ => println!("Pick 1, 2, or 3."),
+        }
+    };
+
+    let contents = render(&Answers {
+        community,
+        to_name,
+        to_email,
+        from_email,
+        session_url,
+        login,
+        channel,
+    });
+    let config: Config =
+        toml::from_str(&contents).wrap_err("Failed to parse the generated config")?;
+
+    println!("\nSending a test email to {}...", config.to);
+    let sent = jmap::Mailer::new(config.jmap.clone())
+        .send(&jmap::Email {
+            to: config.to.clone(),
+            subject: "Ava Apartment Finder is set up".to_owned(),
+            body: format!("Notifications about new listings will be sent here.\n\nConfig: {path}"),
+            attachments: Vec::new(),
+            unit: None,
+            cc: Vec::new(),
+            bcc: Vec::new(),
+            reply_to: None,
+            send_at: None,
+        })
+        .await;
+    match sent {
+        Ok(()) => println!("Sent! Check your inbox."),
+        Err(err) => {
+            println!("Couldn't send the test email: {err:#}");
+            if !prompt.confirm("Write the config anyway?", false)? {
+                return Err(eyre!("Not writing the config"));
+            }
+        }
+    }
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).wrap_err_with(|| format!("Failed to create `{dir}`"))?;
+    }
+    std::fs::write(path, contents).wrap_err_with(|| format!("Failed to write `{path}`"))?;
+    println!("Wrote {path}");
+    Ok(())
+}
+
+/// Questions on stdout, answered on stdin.
+struct Prompt {
+    lines: std::io::Lines<std::io::StdinLock<'static>>,
+}
+
+impl Prompt {
+    fn new() -> Self {
+        Self {
+            lines: std::io::stdin().lock().lines(),
+        }
+    }
+
+    /// Print `prompt` and read a line, trimmed.
+    fn read_line(&mut self, prompt: &str) -> eyre::Result<String> {
+        print!("{prompt}");
+        std::io::stdout().flush()?;
+        let line = self
+            .lines
+            .next()
+            .ok_or_else(|| eyre!("Setup cancelled"))??;
+        Ok(line.trim().to_owned())
+    }
+
+    /// Ask `question`, with a `default` answer for an empty line. Asks again until we get an
+    /// answer.
+    fn ask(&mut self, question: &str, default: Option<&str>) -> eyre::Result<String> {
+        loop {
+            let answer = match default {
+                Some(default) => self.read_line(&format!("{question} [{default}]: "))?,
+                None => self.read_line(&format!("{question}: "))?,
+            };
+            match (answer.is_empty(), default) {
+                (true, Some(default)) => return Ok(default.to_owned()),
+                (true, None) => continue,
+                (false, _) => return Ok(answer),
+            }
+        }
+    }
+
+    /// Ask a yes-or-no `question`.
+    fn confirm(&mut self, question: &str, default: bool) -> eyre::Result<bool> {
+        let hint = if default { "Y/n" } else { "y/N" };
+        loop {
+            match self.read_line(&format!("{question} [{hint}] "))?.as_str() {
+                "" => return Ok(default),
+                "y" | "Y" | "yes" => return Ok(true),
+                "n" | "N" | "no" => return Ok(false),
+                _ => println!("Answer y or n."),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let answers = Answers {
+            community: AVA_URL.to_owned(),
+            to_name: "Rebecca Turner".to_owned(),
+            to_email: "rbt@example.com".to_owned(),
+            from_email: "ava@example.com".to_owned(),
+            session_url: "https://mail.example.com/.well-known/jmap".to_owned(),
+            login: Login::Password {
+                username: "ava@example.com".to_owned(),
+                env: "JMAP_PASSWORD".to_owned(),
+            },
+            channel: Channel::Feed("ava-feed.xml".into()),
+        };
+        let config: Config = toml::from_str(&render(&answers)).unwrap();
+        assert_eq!(config.communities, [AVA_URL]);
+        assert_eq!(config.to.to_string(), "Rebecca Turner <rbt@example.com>");
+        assert_eq!(config.feed_path, Some("ava-feed.xml".into()));
+        assert_eq!(
+            config.jmap.session_url,
+            "https://mail.example.com/.well-known/jmap"
+        );
+        assert_eq!(config.jmap.username.as_deref(), Some("ava@example.com"));
+    }
+}
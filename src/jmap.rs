@@ -1,3 +1,10 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use chrono::DateTime;
+use chrono::SecondsFormat;
 use chrono::TimeZone;
 use chrono::Utc;
 use color_eyre::eyre;
@@ -7,83 +14,515 @@ use jmap_client::client::Client;
 use jmap_client::client::Credentials;
 use jmap_client::core::query::Comparator;
 use jmap_client::core::query::Filter;
+use jmap_client::core::response::EmailSetResponse;
+use jmap_client::core::set::SetObject;
+use jmap_client::email::query::Filter as EmailFilter;
 use jmap_client::email::EmailAddress;
+use jmap_client::email::EmailBodyPart;
+use jmap_client::email::Property as EmailProperty;
+use jmap_client::email_submission::Address;
+use jmap_client::email_submission::Delivered;
+use jmap_client::email_submission::DeliveryStatus;
+use jmap_client::email_submission::Property as SubmissionProperty;
+use jmap_client::email_submission::UndoStatus;
 use jmap_client::identity::Property as IdentityProperty;
 use jmap_client::mailbox::query::Comparator as MailboxComparator;
 use jmap_client::mailbox::query::Filter as MailboxFilter;
+use jmap_client::mailbox::Mailbox;
 use jmap_client::mailbox::Property as MailboxProperty;
 use jmap_client::mailbox::Role;
+use jmap_client::retry::RetryPolicy;
+use jmap_client::URI;
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use tracing::Instrument;
+
+use crate::redact;
+use crate::secret;
+
+/// How to connect to a JMAP server (RFC 8620) to send notifications, configured in the `[jmap]`
+/// table of the config file. Defaults to Fastmail, with an API token in `$FASTMAIL_API_TOKEN`.
+///
+/// For another server, like Stalwart or Cyrus:
+///
+/// ```toml
+/// [jmap]
+/// session-url = "https://mail.example.com/.well-known/jmap"
+/// username = "me@example.com"
+/// password-env = "JMAP_PASSWORD"
+/// from = { name = "Ava Apartment Finder", email = "me@example.com" }
+/// ```
+///
+/// The token or password can also be kept in the OS keyring or a file instead of the
+/// environment; see [`crate::secret`]:
+///
+/// ```toml
+/// [jmap]
+/// token = { keyring = "fastmail" }
+/// ```
+///
+/// Or with OAuth 2.0, instead of `username` or a long-lived token:
+///
+/// ```toml
+/// [jmap.oauth]
+/// token-url = "https://auth.example.com/oauth/token"
+/// client-id = "ava-apartment-finder"
+/// client-secret = "..."
+/// refresh-token = "..."
+/// ```
+///
+/// Not changed by `reload-config`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct JmapConfig {
+    /// The server's session resource. Redirects are followed within the same host.
+    pub session_url: String,
+    /// The environment variable holding a bearer token, used unless `token`, `username`, or
+    /// `oauth` is set.
+    pub token_env: String,
+    /// A bearer token, instead of `token-env`.
+    #[serde(deserialize_with = "secret::deserialize_option")]
+    pub token: Option<String>,
+    /// Log in with this username and the password in `password-env` instead of a token.
+    pub username: Option<String>,
+    /// The environment variable holding the password for `username`, used unless `password`
+    /// is set.
+    pub password_env: String,
+    /// The password for `username`, instead of `password-env`.
+    #[serde(deserialize_with = "secret::deserialize_option")]
+    pub password: Option<String>,
+    /// Which account to send from, if the credentials can access several. Defaults to the
+    /// primary mail account.
+    pub account_id: Option<String>,
+    /// Who notifications are from. This must match one of the account's sending identities.
+    pub from: EmailAddress,
+    /// Which mailbox to file sent notifications into.
+    pub mailbox: MailboxChoice,
+    /// Where to look for replies to notifications with commands like `WATCH`, usually the
+    /// inbox. Off by default; see [`crate::email_commands`].
+    pub commands_mailbox: Option<MailboxChoice>,
+    /// Get short-lived access tokens from an OAuth 2.0 authorization server.
+    pub oauth: Option<OAuthConfig>,
+    /// How many times to retry requests which fail for transient reasons, like a `503` or a
+    /// `serverUnavailable` error. Waits 1, 2, 4, ... seconds in between, or as long as the
+    /// server's `Retry-After` asks, up to 30 seconds.
+    pub retries: u32,
+}
+
+impl Default for JmapConfig {
+    fn default() -> Self {
+        Self {
+            session_url: "https://api.fastmail.com/jmap/session".to_owned(),
+            token_env: "FASTMAIL_API_TOKEN".to_owned(),
+            token: None,
+            username: None,
+            password_env: "JMAP_PASSWORD".to_owned(),
+            password: None,
+            account_id: None,
+            from: ("Ava Apartment Finder", "rbt@fastmail.com").into(),
+            mailbox: Default::default(),
+            commands_mailbox: None,
+            oauth: None,
+            retries: RetryPolicy::default().max_retries,
+        }
+    }
+}
+
+impl JmapConfig {
+    fn credentials(&self) -> eyre::Result<Credentials> {
+        match &self.username {
+            Some(username) => {
+                let password = secret_or_env(&self.password, &self.password_env)?;
+                Ok(Credentials::basic(username, &password))
+            }
+            None => Ok(Credentials::bearer(secret_or_env(
+                &self.token,
+                &self.token_env,
+            )?)),
+        }
+    }
+
+    /// The password, token, or OAuth secrets, for [`crate::redact`].
+    pub fn secrets(&self) -> Vec<String> {
+        match (&self.oauth, &self.username) {
+            (Some(oauth), _) => std::iter::once(oauth.refresh_token.clone())
+                .chain(oauth.client_secret.clone())
+                .collect(),
+            (None, Some(_)) => secret_or_env(&self.password, &self.password_env)
+                .ok()
+                .into_iter()
+                .collect(),
+            (None, None) => secret_or_env(&self.token, &self.token_env)
+                .ok()
+                .into_iter()
+                .collect(),
+        }
+    }
+}
+
+/// `secret` from the config, or the environment variable `env` if it isn't set.
+fn secret_or_env(secret: &Option<String>, env: &str) -> eyre::Result<String> {
+    match secret {
+        Some(secret) => Ok(secret.clone()),
+        None => std::env::var(env).wrap_err_with(|| format!("Couldn't get ${env}")),
+    }
+}
+
+/// An OAuth 2.0 client with a refresh token, for getting access tokens for the JMAP server.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct OAuthConfig {
+    /// The authorization server's token endpoint.
+    pub token_url: String,
+    pub client_id: String,
+    /// Leave unset for public clients.
+    #[serde(default, deserialize_with = "secret::deserialize_option")]
+    pub client_secret: Option<String>,
+    /// A refresh token from authorizing this client, e.g. with the provider's authorization
+    /// code or device flow.
+    ///
+    /// If the server rotates refresh tokens, the new one is only kept in memory, so this needs
+    /// updating if it's been invalidated by the time we restart.
+    #[serde(deserialize_with = "secret::deserialize")]
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    /// Seconds until `access_token` expires.
+    expires_in: Option<u64>,
+    /// A replacement for the refresh token we used, if the server rotates them.
+    refresh_token: Option<String>,
+}
+
+impl OAuthConfig {
+    /// Trade `refresh_token` for a new access token.
+    async fn refresh(&self, refresh_token: &str) -> eyre::Result<TokenResponse> {
+        let mut form = vec![
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+            ("client_id", &self.client_id),
+        ];
+        if let Some(client_secret) = &self.client_secret {
+            form.push(("client_secret", client_secret));
+        }
+        let response = reqwest::Client::new()
+            .post(&self.token_url)
+            .form(&form)
+            .send()
+            .await
+            .wrap_err_with(|| {
+                format!("Failed to request an access token from {}", self.token_url)
+            })?;
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .wrap_err("Failed to read token response")?;
+        if !status.is_success() {
+            return Err(eyre!("Failed to refresh access token ({status}): {body}"));
+        }
+        serde_json::from_str(&body).wrap_err("Failed to parse token response")
+    }
+}
+
+/// Which mailbox to file sent notifications into, like `{ name = "Apartments" }` to keep them
+/// out of the inbox, or `{ role = "archive" }`. Defaults to the inbox.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MailboxChoice {
+    /// The mailbox with this role, like `inbox` or `archive`.
+    Role(Role),
+    /// The mailbox with this name. A top-level mailbox is created if there isn't one.
+    Name(String),
+}
 
-const API_ENDPOINT: &str = "https://api.fastmail.com/jmap/session";
+impl Default for MailboxChoice {
+    fn default() -> Self {
+        Self::Role(Role::Inbox)
+    }
+}
+
+impl MailboxChoice {
+    fn matches(&self, mailbox: &Mailbox) -> bool {
+        match self {
+            MailboxChoice::Role(role) => mailbox.role() == *role,
+            MailboxChoice::Name(name) => mailbox.name() == Some(name.as_str()),
+        }
+    }
+}
+
+/// Sends emails from one address, connecting on the first send and reusing the connection
+/// after that.
+///
+/// If the server stops accepting our session, we reconnect and try again once. With OAuth, we
+/// also reconnect with a fresh access token shortly before the current one expires.
+pub struct Mailer {
+    config: JmapConfig,
+    connection: Mutex<Connection>,
+}
+
+#[derive(Default)]
+struct Connection {
+    identity: Option<Arc<SendingIdentity>>,
+    /// When the OAuth access token `identity` was connected with expires.
+    expires: Option<Instant>,
+    /// The latest OAuth refresh token, if the server has replaced the configured one.
+    refresh_token: Option<String>,
+}
+
+impl Connection {
+    /// Whether we should get a new access token before using the connection at `now`.
+    fn is_expiring(&self, now: Instant) -> bool {
+        self.expires
+            .map_or(false, |expires| expires <= now + ACCESS_TOKEN_MARGIN)
+    }
+}
+
+/// The `partId` of an email's plain text body.
+const TEXT_PART_ID: &str = "text";
+/// The `partId` of an email's HTML body.
+const HTML_PART_ID: &str = "html";
+
+/// How long before an OAuth access token expires to replace it, so it doesn't expire while
+/// we're sending an email.
+const ACCESS_TOKEN_MARGIN: Duration = Duration::from_secs(60);
+
+impl Mailer {
+    pub fn new(config: JmapConfig) -> Self {
+        Self {
+            config,
+            connection: Default::default(),
+        }
+    }
+
+    pub async fn send(&self, email: &Email) -> eyre::Result<()> {
+        self.with_identity(|identity| async move { identity.send(email).await })
+            .await
+    }
+
+    /// Replies to notifications in the `commands-mailbox` which haven't been processed yet.
+    pub async fn replies(&self) -> eyre::Result<Vec<Reply>> {
+        self.with_identity(|identity| async move { identity.replies().await })
+            .await
+    }
+
+    /// Mark the reply with ID `id` as processed, so [`Mailer::replies`] skips it.
+    pub async fn mark_processed(&self, id: &str) -> eyre::Result<()> {
+        self.with_identity(|identity| async move { identity.mark_processed(id).await })
+            .await
+    }
+
+    /// Run `operation` with a connected identity, reconnecting and retrying once if our session
+    /// was rejected.
+    async fn with_identity<T, F>(
+        &self,
+        operation: impl Fn(Arc<SendingIdentity>) -> F,
+    ) -> eyre::Result<T>
+    where
+        F: Future<Output = eyre::Result<T>>,
+    {
+        let mut connection = self.connection.lock().await;
+        if connection.identity.is_none() || connection.is_expiring(Instant::now()) {
+            self.connect(&mut connection).await?;
+        }
+        let identity = connection.identity.clone().expect("Connected above");
+        match operation(identity).await {
+            Err(err) if err.downcast_ref::<Unauthorized>().is_some() => {
+                tracing::info!("Email session expired, reconnecting: {err:#}");
+                self.connect(&mut connection).await?;
+                operation(connection.identity.clone().expect("Connected above")).await
+            }
+            result => result,
+        }
+    }
+
+    async fn connect(&self, connection: &mut Connection) -> eyre::Result<()> {
+        connection.identity = None;
+        let credentials = match &self.config.oauth {
+            Some(oauth) => {
+                let refresh_token = connection
+                    .refresh_token
+                    .as_deref()
+                    .unwrap_or(&oauth.refresh_token);
+                let token = oauth.refresh(refresh_token).await?;
+                redact::add_secret(&token.access_token);
+                if let Some(refresh_token) = &token.refresh_token {
+                    redact::add_secret(refresh_token);
+                }
+                tracing::debug!(expires_in = token.expires_in, "Got a new access token");
+                connection.expires = token
+                    .expires_in
+                    .map(|expires_in| Instant::now() + Duration::from_secs(expires_in));
+                if token.refresh_token.is_some() {
+                    connection.refresh_token = token.refresh_token;
+                }
+                Credentials::bearer(token.access_token)
+            }
+            None => self.config.credentials()?,
+        };
+        connection.identity = Some(Arc::new(
+            SendingIdentity::new(&self.config, credentials)
+                .await
+                .wrap_err("Unable to determine email sending identity")?,
+        ));
+        Ok(())
+    }
+}
+
+/// The server rejected our credentials or session, so reconnecting might help.
+#[derive(Debug)]
+struct Unauthorized(jmap_client::Error);
+
+impl std::fmt::Display for Unauthorized {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for Unauthorized {}
+
+/// Convert a JMAP error, keeping track of whether it was [`Unauthorized`].
+fn jmap_error(err: jmap_client::Error) -> eyre::Report {
+    let unauthorized = match &err {
+        jmap_client::Error::Problem(problem) => matches!(problem.status(), Some(401 | 403)),
+        jmap_client::Error::Server(status) => {
+            status.starts_with("401") || status.starts_with("403")
+        }
+        _ => false,
+    };
+    if unauthorized {
+        eyre::Report::new(Unauthorized(err))
+    } else {
+        eyre!("{err}")
+    }
+}
 
 pub struct SendingIdentity {
     from: EmailAddress,
-    client: Client,
+    client: Arc<Client>,
     mailbox_id: String,
+    /// Where to look for replies with commands; see [`JmapConfig::commands_mailbox`].
+    commands_mailbox_id: Option<String>,
     identity_id: String,
 }
 
 impl SendingIdentity {
-    pub async fn new(from: EmailAddress) -> eyre::Result<Self> {
-        let bearer_token =
-            std::env::var("FASTMAIL_API_TOKEN").wrap_err("Couldn't get $FASTMAIL_API_TOKEN")?;
+    pub async fn new(config: &JmapConfig, credentials: Credentials) -> eyre::Result<Self> {
+        let from = config.from.clone();
+        let choice = &config.mailbox;
+        let host = reqwest::Url::parse(&config.session_url)
+            .wrap_err_with(|| format!("Invalid JMAP session URL `{}`", config.session_url))?
+            .host_str()
+            .map(ToOwned::to_owned);
 
-        let client = Client::new()
-            .credentials(Credentials::Bearer(bearer_token))
-            .connect(API_ENDPOINT)
+        let mut client = Client::new()
+            .credentials(credentials)
+            .follow_redirects(host)
+            .retry(RetryPolicy {
+                max_retries: config.retries,
+                ..Default::default()
+            })
+            .connect(&config.session_url)
             .await
             .map_err(|err| eyre!("{err}"))
-            .wrap_err("Failed to connect to server")?;
+            .wrap_err_with(|| format!("Failed to connect to {}", config.session_url))?;
 
-        tracing::debug!("Email client initialized");
+        // The client defaults to an arbitrary primary account, which may not be for mail.
+        let account_id = match &config.account_id {
+            Some(account_id) => Some(account_id.clone()),
+            None => client
+                .session()
+                .primary_accounts()
+                .find(|(capability, _)| *capability == URI::Mail.as_ref())
+                .map(|(_, account_id)| account_id.clone()),
+        };
+        if let Some(account_id) = account_id {
+            client.set_default_account_id(account_id);
+        }
+
+        tracing::debug!(
+            account_id = client.default_account_id(),
+            "Email client initialized"
+        );
 
+        // Look up the mailboxes and identities in one round-trip.
         let mailbox_filter: Option<Filter<MailboxFilter>> = None;
         let mailbox_sort: Option<Vec<Comparator<MailboxComparator>>> = None;
-        let mailboxes = client
-            .mailbox_query(mailbox_filter, mailbox_sort)
+        let mut request = client.build();
+        request
+            .query_and_get_mailbox(mailbox_filter, mailbox_sort)
+            .properties([
+                MailboxProperty::Id,
+                MailboxProperty::Name,
+                MailboxProperty::ParentId,
+                MailboxProperty::Role,
+            ]);
+        request.get_identity().properties([
+            IdentityProperty::Id,
+            IdentityProperty::Name,
+            IdentityProperty::Email,
+            IdentityProperty::ReplyTo,
+        ]);
+        let mut responses = request
+            .send()
             .await
+            .map_err(|err| eyre!("{err}"))?
+            .unwrap_method_responses()
+            .into_iter();
+        let mut next_response = || {
+            responses
+                .next()
+                .ok_or_else(|| eyre!("JMAP server returned too few method responses"))
+        };
+        next_response()?
+            .unwrap_query_mailbox()
             .map_err(|err| eyre!("{err}"))?;
+        let mailboxes = next_response()?
+            .unwrap_get_mailbox()
+            .map_err(|err| eyre!("{err}"))?
+            .take_list();
+        let identities = next_response()?
+            .unwrap_get_identity()
+            .map_err(|err| eyre!("{err}"))?
+            .take_list();
 
-        let mut mailbox_id = None;
-
-        for id in mailboxes.ids() {
-            let mailbox = client
-                .mailbox_get(
-                    id,
-                    Some(vec![
-                        MailboxProperty::Name,
-                        MailboxProperty::ParentId,
-                        MailboxProperty::Role,
-                    ]),
-                )
-                .await
-                .map_err(|err| eyre!("{err}"))?
-                .ok_or_else(|| eyre!("Unable to find mailbox {id}"))?;
+        let find_mailbox = |choice: &MailboxChoice| {
+            mailboxes
+                .iter()
+                .find(|mailbox| choice.matches(mailbox))
+                .and_then(|mailbox| mailbox.id())
+                .map(ToOwned::to_owned)
+        };
+        let mailbox_id = find_mailbox(choice);
+        let commands_mailbox_id = match &config.commands_mailbox {
+            Some(choice) => Some(find_mailbox(choice).ok_or_else(|| {
+                eyre!("Unable to find the mailbox for email commands, {choice:?}")
+            })?),
+            None => None,
+        };
 
-            if let Role::Inbox = mailbox.role() {
-                mailbox_id = Some(id);
+        let mailbox_id = match (mailbox_id, choice) {
+            (Some(mailbox_id), _) => mailbox_id,
+            (None, MailboxChoice::Role(role)) => {
+                return Err(eyre!("Unable to find a mailbox with role {role:?}"));
             }
-        }
-
-        let mailbox_id = mailbox_id
-            .ok_or_else(|| eyre!("Unable to find Inbox ID"))?
-            .to_owned();
+            (None, MailboxChoice::Name(name)) => {
+                tracing::info!(name, "Creating mailbox for notifications");
+                client
+                    .mailbox_create(name, None::<String>, Role::None)
+                    .await
+                    .map_err(|err| eyre!("{err}"))
+                    .wrap_err_with(|| format!("Failed to create mailbox {name:?}"))?
+                    .id()
+                    .ok_or_else(|| eyre!("Created mailbox {name:?} has no ID"))?
+                    .to_owned()
+            }
+        };
 
         tracing::debug!("Using mailbox ID {mailbox_id}");
 
-        let identities = client
-            .identity_get(
-                None,
-                Some(vec![
-                    IdentityProperty::Id,
-                    IdentityProperty::Name,
-                    IdentityProperty::Email,
-                    IdentityProperty::ReplyTo,
-                ]),
-            )
-            .await
-            .map_err(|err| eyre!("{err}"))?;
-
         let mut identity = None;
         for ident in identities {
             if ident.email() == Some(from.email()) && from.name() == ident.name() {
@@ -98,49 +537,199 @@ impl SendingIdentity {
             .to_owned();
 
         Ok(Self {
-            client,
+            client: Arc::new(client),
             from,
             mailbox_id,
+            commands_mailbox_id,
             identity_id,
         })
     }
 
-    pub async fn send(&self, email: &Email) -> eyre::Result<()> {
-        let keywords: Option<Vec<&'static str>> = None;
+    async fn replies(&self) -> eyre::Result<Vec<Reply>> {
+        let mailbox_id = match &self.commands_mailbox_id {
+            Some(mailbox_id) => mailbox_id,
+            None => return Ok(Vec::new()),
+        };
+        let mut request = self.client.build();
+        let ids = request
+            .query_email()
+            .filter(Filter::and([
+                EmailFilter::in_mailbox(mailbox_id),
+                EmailFilter::not_keyword(PROCESSED_KEYWORD),
+            ]))
+            .result_reference();
+        request.get_email().ids_ref(ids).properties([
+            EmailProperty::Id,
+            EmailProperty::From,
+            EmailProperty::InReplyTo,
+            EmailProperty::Preview,
+        ]);
+        let mut responses = request
+            .send()
+            .await
+            .map_err(jmap_error)?
+            .unwrap_method_responses()
+            .into_iter();
+        let emails = responses
+            .nth(1)
+            .ok_or_else(|| eyre!("JMAP server returned too few method responses"))?
+            .unwrap_get_email()
+            .map_err(jmap_error)?
+            .take_list();
 
-        let imported_email = self
+        Ok(emails
+            .into_iter()
+            .filter_map(|email| {
+                Some(Reply {
+                    id: email.id()?.to_owned(),
+                    from: email
+                        .from()
+                        .unwrap_or_default()
+                        .iter()
+                        .map(|address| address.email().to_owned())
+                        .collect(),
+                    in_reply_to: email.in_reply_to().unwrap_or_default().to_vec(),
+                    text: email.preview().unwrap_or_default().to_owned(),
+                })
+            })
+            .collect())
+    }
+
+    /// The envelope's sender, with a `HOLDUNTIL` parameter to deliver the email at `send_at`
+    /// (RFC 4865) if the server supports it. Servers limit how long emails can be held, so
+    /// `send_at` may be moved earlier.
+    fn mail_from(&self, send_at: Option<DateTime<Utc>>, now: DateTime<Utc>) -> Address {
+        let address = Address::new(self.from.email());
+        let max_delay = self
             .client
-            .email_import(
-                format!(
-                    "To: {}\r\n\
-                    From: {}\r\n\
-                    Subject: {}\r\n\
-                    \r\n\
-                    {}\r\n",
-                    email.to,
-                    self.from,
-                    email.subject,
-                    email.body.to_string().replace('\n', "\r\n")
+            .session()
+            .submission_capabilities()
+            .map_or(0, |capabilities| capabilities.max_delayed_send());
+        match send_at {
+            Some(send_at) if send_at > now && max_delay > 0 => {
+                let send_at = send_at.min(now + chrono::Duration::seconds(max_delay as i64));
+                tracing::debug!(%send_at, "Holding email until later");
+                address
+                    .parameter(
+                        "HOLDUNTIL",
+                        Some(send_at.to_rfc3339_opts(SecondsFormat::Secs, true)),
+                    )
+                    .into()
+            }
+            Some(_) if max_delay == 0 => {
+                tracing::debug!("Server doesn't support delayed sending, sending now");
+                address.into()
+            }
+            _ => address.into(),
+        }
+    }
+
+    async fn mark_processed(&self, id: &str) -> eyre::Result<()> {
+        self.client
+            .email_set_keyword(id, PROCESSED_KEYWORD, true)
+            .await
+            .map_err(jmap_error)?;
+        Ok(())
+    }
+
+    pub async fn send(&self, email: &Email) -> eyre::Result<()> {
+        // The server tells us when our copy of the session is out of date, e.g. because our
+        // account's capabilities changed.
+        if !self.client.is_session_updated() {
+            tracing::debug!("Refreshing email session");
+            self.client
+                .refresh_session()
+                .await
+                .map_err(jmap_error)
+                .wrap_err("Failed to refresh session")?;
+        }
+
+        // Attachments are uploaded first and referenced by blob ID; the server builds the
+        // MIME message from the parts.
+        let mut attachments = Vec::with_capacity(email.attachments.len());
+        for attachment in &email.attachments {
+            let blob_id = self
+                .client
+                .upload(
+                    None,
+                    attachment.data.clone(),
+                    Some(&attachment.content_type),
                 )
-                .as_bytes()
-                .to_vec(),
-                [&self.mailbox_id],
-                keywords,
-                None,
+                .await
+                .map_err(jmap_error)
+                .wrap_err_with(|| format!("Failed to upload attachment {}", attachment.filename))?
+                .take_blob_id();
+            tracing::debug!(
+                filename = attachment.filename,
+                blob_id,
+                "Uploaded attachment"
+            );
+            attachments.push(
+                EmailBodyPart::new()
+                    .blob_id(blob_id)
+                    .name(&attachment.filename)
+                    .content_type(&attachment.content_type),
+            );
+        }
+
+        // Giving both a text and an HTML body makes the server send them as
+        // `multipart/alternative`, and it takes care of encoding headers and bodies.
+        let now = Utc::now();
+        let mut request = self.client.build();
+        let create = request
+            .set_email()
+            .create()
+            .mailbox_ids([&self.mailbox_id])
+            .message_id([message_id(&self.from, now, email.unit.as_deref())])
+            .sent_at(now.timestamp())
+            .from([self.from.clone()])
+            .to([email.to.clone()])
+            .subject(&email.subject)
+            .body_value(TEXT_PART_ID.to_owned(), email.body.as_str())
+            .text_body(
+                EmailBodyPart::new()
+                    .part_id(TEXT_PART_ID)
+                    .content_type("text/plain"),
             )
+            .body_value(HTML_PART_ID.to_owned(), text_to_html(&email.body))
+            .html_body(
+                EmailBodyPart::new()
+                    .part_id(HTML_PART_ID)
+                    .content_type("text/html"),
+            );
+        if !email.cc.is_empty() {
+            create.cc(email.cc.clone());
+        }
+        if let Some(reply_to) = &email.reply_to {
+            create.reply_to([reply_to.clone()]);
+        }
+        for attachment in attachments {
+            create.attachment(attachment);
+        }
+        let create_id = create
+            .create_id()
+            .ok_or_else(|| eyre!("Email has no creation ID"))?;
+        let created_email = request
+            .send_single::<EmailSetResponse>()
             .await
-            .map_err(|err| eyre!("{err}"))
-            .wrap_err("Failed to import email")?;
+            .and_then(|mut response| response.created(&create_id))
+            .map_err(jmap_error)
+            .wrap_err("Failed to create email")?;
 
-        let email_id = imported_email
+        let email_id = created_email
             .id()
-            .ok_or_else(|| eyre!("Imported email has no ID"))?;
+            .ok_or_else(|| eyre!("Created email has no ID"))?;
 
-        tracing::debug!(id = email_id, "Imported email");
+        tracing::debug!(id = email_id, "Created email");
 
         let submission = self
             .client
-            .email_submission_create(email_id, &self.identity_id)
+            .email_submission_create_envelope(
+                email_id,
+                &self.identity_id,
+                self.mail_from(email.send_at, now),
+                email.recipients(),
+            )
             .await
             .map_err(|err| eyre!("{err}"))
             .wrap_err("Failed to send email")?;
@@ -152,19 +741,437 @@ impl SendingIdentity {
             "Sent email!"
         );
 
+        match submission.id() {
+            // Held emails aren't delivered for hours, longer than we keep checking.
+            Some(_) if email.send_at.map_or(false, |send_at| send_at > now) => {}
+            Some(id) => {
+                tokio::spawn(
+                    track_delivery(self.client.clone(), id.to_owned(), email.subject.clone())
+                        .in_current_span(),
+                );
+            }
+            None => tracing::warn!("Email submission has no ID, so its delivery can't be tracked"),
+        }
+
         Ok(())
     }
 }
 
+/// How long to wait before each check on whether a sent email was delivered. Most mail is
+/// delivered within seconds, but greylisting can hold it up for several minutes.
+const DELIVERY_CHECKS: [Duration; 5] = [
+    Duration::from_secs(10),
+    Duration::from_secs(30),
+    Duration::from_secs(60),
+    Duration::from_secs(5 * 60),
+    Duration::from_secs(15 * 60),
+];
+
+/// What's happened to a submitted email, according to the server.
+#[derive(Debug, PartialEq, Eq)]
+enum Delivery {
+    /// Still queued, or the submission can still be undone.
+    Pending,
+    /// Delivered, or handed off to a server that won't tell us more.
+    Delivered,
+    /// The submission was canceled before it was sent.
+    Canceled,
+    /// The recipients the server gave up on, with its SMTP replies.
+    Failed(Vec<(String, String)>),
+}
+
+fn delivery<'a>(
+    undo_status: Option<&UndoStatus>,
+    statuses: impl IntoIterator<Item = (&'a String, &'a DeliveryStatus)>,
+) -> Delivery {
+    if undo_status == Some(&UndoStatus::Canceled) {
+        return Delivery::Canceled;
+    }
+    let mut pending = undo_status == Some(&UndoStatus::Pending);
+    let mut failed = Vec::new();
+    for (recipient, status) in statuses {
+        match status.delivered() {
+            Delivered::No => failed.push((recipient.clone(), status.smtp_reply().to_owned())),
+            Delivered::Queued => pending = true,
+            Delivered::Yes | Delivered::Unknown => {}
+        }
+    }
+    if !failed.is_empty() {
+        Delivery::Failed(failed)
+    } else if pending {
+        Delivery::Pending
+    } else {
+        Delivery::Delivered
+    }
+}
+
+/// Check on the submission `submission_id` until the server says it was delivered or failed,
+/// and log the outcome.
+async fn track_delivery(client: Arc<Client>, submission_id: String, subject: String) {
+    for wait in DELIVERY_CHECKS {
+        tokio::time::sleep(wait).await;
+        let properties = vec![
+            SubmissionProperty::UndoStatus,
+            SubmissionProperty::DeliveryStatus,
+        ];
+        let submission = match client
+            .email_submission_get(&submission_id, Some(properties))
+            .await
+        {
+            Ok(Some(submission)) => submission,
+            Ok(None) => {
+                // Servers may clean up submissions once they're done with them.
+                tracing::debug!(
+                    subject,
+                    "Email submission is gone, assuming it was delivered"
+                );
+                return;
+            }
+            Err(err) => {
+                tracing::debug!(subject, "Failed to check email delivery: {err}");
+                continue;
+            }
+        };
+        match delivery(
+            submission.undo_status(),
+            submission.delivery_status().into_iter().flatten(),
+        ) {
+            Delivery::Pending => {}
+            Delivery::Delivered => {
+                tracing::debug!(subject, "Email delivered");
+                return;
+            }
+            Delivery::Canceled => {
+                tracing::warn!(subject, "Email was canceled before it was sent");
+                return;
+            }
+            Delivery::Failed(failed) => {
+                for (to, reply) in failed {
+                    tracing::error!(subject, to, reply, "Email wasn't delivered");
+                    metrics::counter!("email.delivery_failures").increment(1);
+                }
+                return;
+            }
+        }
+    }
+    tracing::warn!(
+        subject,
+        "Email still hasn't been delivered; no longer checking on it"
+    );
+}
+
 #[derive(Debug)]
 pub struct Email {
     pub to: EmailAddress,
     pub subject: String,
+    /// The plain text body. An HTML version with clickable links is sent alongside it; see
+    /// [`text_to_html`].
     pub body: String,
+    pub attachments: Vec<Attachment>,
+    /// The ID of the unit this email is about, if it's about one, so replies can manage it.
+    pub unit: Option<String>,
+    pub cc: Vec<EmailAddress>,
+    /// Recipients who are only in the envelope, not the headers.
+    pub bcc: Vec<EmailAddress>,
+    pub reply_to: Option<EmailAddress>,
+    /// Hold the email on the server until this time, if the server supports delayed sending;
+    /// see [`crate::quiet_hours`].
+    pub send_at: Option<DateTime<Utc>>,
+}
+
+/// A reply to one of our emails, which may have a command in it.
+#[derive(Debug)]
+pub struct Reply {
+    pub id: String,
+    /// The sender's addresses.
+    pub from: Vec<String>,
+    /// The `Message-ID`s of the emails this replies to.
+    pub in_reply_to: Vec<String>,
+    /// The start of the plain text body.
+    pub text: String,
+}
+
+/// Keyword we set on replies once we've processed them.
+const PROCESSED_KEYWORD: &str = "$ava-processed";
+
+/// A unique `Message-ID` (without the angle brackets) for an email from `from` sent at `now`.
+///
+/// If the email is about `unit`, its ID is included, so we can tell which unit a reply is about
+/// with [`unit_from_message_id`]. IDs which aren't valid in a `Message-ID` are left out.
+fn message_id(from: &EmailAddress, now: DateTime<Utc>, unit: Option<&str>) -> String {
+    let domain = from
+        .email()
+        .rsplit_once('@')
+        .map_or("localhost", |(_, domain)| domain);
+    let unit = unit
+        .filter(|unit| {
+            !unit.is_empty()
+                && unit
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+        })
+        .map(|unit| format!(".unit-{unit}"))
+        .unwrap_or_default();
+    format!(
+        "ava-apartment-finder.{}.{}{unit}@{domain}",
+        now.timestamp_nanos(),
+        std::process::id()
+    )
+}
+
+/// The unit ID in a `Message-ID` from [`message_id`], if any.
+pub fn unit_from_message_id(message_id: &str) -> Option<&str> {
+    let (local, _domain) = message_id
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .split_once('@')?;
+    let mut parts = local.strip_prefix("ava-apartment-finder.")?.splitn(3, '.');
+    let _timestamp = parts.next()?;
+    let _pid = parts.next()?;
+    parts.next()?.strip_prefix("unit-")
+}
+
+/// Render a plain text email body as HTML, keeping its line breaks and indentation and making
+/// URLs clickable.
+fn text_to_html(text: &str) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html><body><div style=\"font-family: sans-serif; white-space: pre-wrap\">",
+    );
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            html.push('\n');
+        }
+        for (j, word) in line.split(' ').enumerate() {
+            if j > 0 {
+                html.push(' ');
+            }
+            // Leave trailing punctuation out of links, like the period ending a sentence.
+            let url = word.trim_end_matches(|c: char| matches!(c, '.' | ',' | ')' | ';' | ':'));
+            if url.starts_with("https://") || url.starts_with("http://") {
+                let escaped = escape_html(url);
+                html.push_str(&format!("<a href=\"{escaped}\">{escaped}</a>"));
+                html.push_str(&escape_html(&word[url.len()..]));
+            } else {
+                html.push_str(&escape_html(word));
+            }
+        }
+    }
+    html.push_str("</div></body></html>\n");
+    html
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[derive(Debug)]
+pub struct Attachment {
+    pub filename: String,
+    pub content_type: String,
+    pub data: Vec<u8>,
 }
 
 impl Email {
-    pub async fn send(&self, identity: &SendingIdentity) -> eyre::Result<()> {
-        identity.send(self).await
+    pub async fn send(&self, mailer: &Mailer) -> eyre::Result<()> {
+        mailer.send(self).await
+    }
+
+    /// Everyone the email is delivered to, for the envelope. `bcc` isn't in the headers, so
+    /// the server wouldn't know about them otherwise.
+    fn recipients(&self) -> Vec<&str> {
+        std::iter::once(&self.to)
+            .chain(&self.cc)
+            .chain(&self.bcc)
+            .map(|address| address.email())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_to_html() {
+        assert_eq!(
+            text_to_html("Apartment <731> & more\n  Tour: https://example.com/tour?a=1&b=2."),
+            "<!DOCTYPE html>\n<html><body>\
+            <div style=\"font-family: sans-serif; white-space: pre-wrap\">\
+            Apartment &lt;731&gt; &amp; more\n  \
+            Tour: <a href=\"https://example.com/tour?a=1&amp;b=2\">\
+            https://example.com/tour?a=1&amp;b=2</a>.\
+            </div></body></html>\n"
+        );
+    }
+
+    #[test]
+    fn test_recipients() {
+        let email = Email {
+            to: ("Me", "me@example.com").into(),
+            subject: "New listing".to_owned(),
+            body: String::new(),
+            attachments: Vec::new(),
+            unit: None,
+            cc: vec![("Partner", "partner@example.com").into()],
+            bcc: vec![("Archive", "archive@example.com").into()],
+            reply_to: None,
+            send_at: None,
+        };
+        assert_eq!(
+            email.recipients(),
+            vec![
+                "me@example.com",
+                "partner@example.com",
+                "archive@example.com"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_message_id() {
+        let from = ("Ava Apartment Finder", "ava@example.com").into();
+        let now = Utc.ymd(2022, 10, 1).and_hms_opt(12, 0, 0).unwrap();
+        let id = message_id(&from, now, None);
+        assert!(id.starts_with("ava-apartment-finder.1664625600000000000."));
+        assert!(id.ends_with("@example.com"));
+        assert_eq!(unit_from_message_id(&id), None);
+
+        let id = message_id(&from, now, Some("AVB-WA026-001-731"));
+        assert!(id.ends_with(".unit-AVB-WA026-001-731@example.com"));
+        assert_eq!(unit_from_message_id(&id), Some("AVB-WA026-001-731"));
+        assert_eq!(
+            unit_from_message_id(&format!("<{id}>")),
+            Some("AVB-WA026-001-731")
+        );
+
+        let id = message_id(&from, now, Some("731 <bad>"));
+        assert_eq!(unit_from_message_id(&id), None);
+        assert_eq!(unit_from_message_id("CAF00@mail.gmail.com"), None);
+    }
+
+    #[test]
+    fn test_delivery() {
+        let recipient = "ava@example.com".to_owned();
+        let status = |reply: &str, delivered: &str| -> DeliveryStatus {
+            serde_json::from_value(serde_json::json!({
+                "smtpReply": reply,
+                "delivered": delivered,
+                "displayed": "unknown",
+            }))
+            .unwrap()
+        };
+        let queued = status("250 OK", "queued");
+        let yes = status("250 OK", "yes");
+        let no = status("550 No such user", "no");
+
+        assert_eq!(delivery(Some(&UndoStatus::Pending), []), Delivery::Pending);
+        assert_eq!(
+            delivery(Some(&UndoStatus::Final), [(&recipient, &queued)]),
+            Delivery::Pending
+        );
+        assert_eq!(
+            delivery(Some(&UndoStatus::Final), [(&recipient, &yes)]),
+            Delivery::Delivered
+        );
+        assert_eq!(delivery(Some(&UndoStatus::Final), []), Delivery::Delivered);
+        assert_eq!(
+            delivery(Some(&UndoStatus::Final), [(&recipient, &no)]),
+            Delivery::Failed(vec![(recipient.clone(), "550 No such user".to_owned())])
+        );
+        assert_eq!(
+            delivery(Some(&UndoStatus::Canceled), []),
+            Delivery::Canceled
+        );
+    }
+
+    #[test]
+    fn test_is_expiring() {
+        let now = Instant::now();
+        let connection = |expires| Connection {
+            expires,
+            ..Default::default()
+        };
+        assert!(!connection(None).is_expiring(now));
+        assert!(!connection(Some(now + Duration::from_secs(3600))).is_expiring(now));
+        assert!(connection(Some(now + Duration::from_secs(30))).is_expiring(now));
+    }
+
+    #[test]
+    fn test_mailbox_choice() {
+        #[derive(Deserialize)]
+        struct Config {
+            mailbox: MailboxChoice,
+        }
+        let parse = |text: &str| toml::from_str::<Config>(text).unwrap().mailbox;
+        assert_eq!(
+            parse(r#"mailbox = { name = "Apartments" }"#),
+            MailboxChoice::Name("Apartments".to_owned())
+        );
+        assert_eq!(
+            parse(r#"mailbox = { role = "archive" }"#),
+            MailboxChoice::Role(Role::Archive)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_to_mock_server() {
+        let server = crate::mock_jmap::MockJmap::start().await;
+        let mailer = Mailer::new(server.config());
+        mailer
+            .send(&Email {
+                to: ("Me", "me@example.com").into(),
+                subject: "Unit 731 is available".to_owned(),
+                body: "Unit 731\nTour: https://example.com/tour".to_owned(),
+                attachments: vec![Attachment {
+                    filename: "chart.svg".to_owned(),
+                    content_type: "image/svg+xml".to_owned(),
+                    data: b"<svg/>".to_vec(),
+                }],
+                unit: Some("ava-731".to_owned()),
+                cc: vec![("Roommate", "roommate@example.com").into()],
+                bcc: vec![("Archive", "archive@example.com").into()],
+                reply_to: Some(("Me", "me@example.com").into()),
+                send_at: None,
+            })
+            .await
+            .unwrap();
+
+        let emails = server.emails();
+        assert_eq!(emails.len(), 1);
+        let email = &emails[0];
+        assert_eq!(email["subject"], "Unit 731 is available");
+        assert_eq!(email["mailboxIds"], serde_json::json!({ "inbox": true }));
+        assert_eq!(email["from"][0]["email"], "ava@example.com");
+        assert_eq!(email["to"][0]["email"], "me@example.com");
+        assert_eq!(email["cc"][0]["email"], "roommate@example.com");
+        assert_eq!(email["replyTo"][0]["email"], "me@example.com");
+        assert!(email.get("bcc").is_none());
+        assert_eq!(
+            unit_from_message_id(email["messageId"][0].as_str().unwrap()),
+            Some("ava-731")
+        );
+        assert_eq!(email["attachments"][0]["blobId"], "blob-1");
+        assert_eq!(server.blobs(), vec![b"<svg/>".to_vec()]);
+
+        let submissions = server.submissions();
+        assert_eq!(submissions.len(), 1);
+        let envelope = &submissions[0]["envelope"];
+        assert_eq!(envelope["mailFrom"]["email"], "ava@example.com");
+        assert_eq!(
+            envelope["rcptTo"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|address| address["email"].as_str().unwrap())
+                .collect::<Vec<_>>(),
+            vec![
+                "me@example.com",
+                "roommate@example.com",
+                "archive@example.com"
+            ]
+        );
     }
 }
@@ -0,0 +1,123 @@
+//! Finding the cheapest way to rent a unit, across lease terms and move-in dates.
+//!
+//! To track the cheapest move-in date for each qualified unit within the dates you could move,
+//! and get an alert when it changes, configure a window like:
+//!
+//! ```toml
+//! move-in = { earliest = "2022-11-01", latest = "2022-12-15" }
+//! ```
+
+use std::fmt::Display;
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::money::Money;
+
+/// One way to rent a unit: moving in on a date, on a lease of some number of months.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LeaseOption {
+    pub move_in: NaiveDate,
+    /// The lease length, in months.
+    pub term: usize,
+    /// The monthly rent, with promotions amortized over the lease.
+    pub monthly: Money,
+}
+
+/// The option in `options` with the lowest average monthly cost.
+///
+/// We compare monthly rather than total costs, which would always favor the shortest lease. Ties
+/// go to the shorter lease, then the earlier move-in date.
+pub fn best(options: impl IntoIterator<Item = LeaseOption>) -> Option<LeaseOption> {
+    options
+        .into_iter()
+        .min_by_key(|option| (option.monthly, option.term, option.move_in))
+}
+
+/// The dates we could move in on. Either end may be left open.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct MoveInWindow {
+    pub earliest: Option<NaiveDate>,
+    pub latest: Option<NaiveDate>,
+}
+
+impl MoveInWindow {
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.earliest.map_or(true, |earliest| date >= earliest)
+            && self.latest.map_or(true, |latest| date <= latest)
+    }
+
+    /// The [`best`] of `options` moving in during this window.
+    pub fn best(&self, options: impl IntoIterator<Item = LeaseOption>) -> Option<LeaseOption> {
+        best(
+            options
+                .into_iter()
+                .filter(|option| self.contains(option.move_in)),
+        )
+    }
+}
+
+impl Display for LeaseOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}-month term at {}/mo, moving in {}",
+            self.term,
+            self.monthly.round(),
+            self.move_in.format("%b %e %Y")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_best() {
+        let option = |day, term, monthly| LeaseOption {
+            move_in: NaiveDate::from_ymd_opt(2022, 11, day).unwrap(),
+            term,
+            monthly: Money::from_dollars(monthly),
+        };
+        let best = best([
+            option(14, 12, 4260.0),
+            option(28, 14, 4090.0),
+            option(14, 14, 4090.0),
+            option(14, 18, 4090.0),
+        ])
+        .unwrap();
+        assert_eq!(best, option(14, 14, 4090.0));
+        assert_eq!(
+            best.to_string(),
+            "14-month term at $4,090/mo, moving in Nov 14 2022"
+        );
+        assert_eq!(super::best([]), None);
+    }
+
+    #[test]
+    fn test_move_in_window() {
+        let option = |day, monthly| LeaseOption {
+            move_in: NaiveDate::from_ymd_opt(2022, 11, day).unwrap(),
+            term: 12,
+            monthly: Money::from_dollars(monthly),
+        };
+        let options = [option(10, 4000.0), option(20, 4200.0), option(30, 4100.0)];
+        let window = MoveInWindow {
+            earliest: NaiveDate::from_ymd_opt(2022, 11, 15),
+            latest: None,
+        };
+        assert_eq!(window.best(options), Some(option(30, 4100.0)));
+        let window = MoveInWindow {
+            latest: NaiveDate::from_ymd_opt(2022, 11, 25),
+            ..window
+        };
+        assert_eq!(window.best(options), Some(option(20, 4200.0)));
+        assert_eq!(
+            MoveInWindow::default().best(options),
+            Some(option(10, 4000.0))
+        );
+    }
+}
@@ -0,0 +1,73 @@
+//! Tracking apartment listings over time.
+//!
+//! This is the library behind the `ava-apartment-finder` binary, for building other frontends
+//! and notifiers on the same data:
+//!
+//! - [`AvalonClient`] scrapes the listings from an Avalon community page.
+//! - [`DiffEngine`] compares freshly-scraped listings to the ones we already know about.
+//! - [`ApartmentStore`] is the DB of known listings and their history, saved as JSON.
+//!
+//! ```no_run
+//! # async fn example() -> color_eyre::eyre::Result<()> {
+//! use std::path::Path;
+//! use std::sync::Arc;
+//!
+//! use ava_apartment_finder::changes::IgnoreChanges;
+//! use ava_apartment_finder::http;
+//! use ava_apartment_finder::price_drop::PriceDropAlert;
+//! use ava_apartment_finder::ApartmentStore;
+//! use ava_apartment_finder::AvalonClient;
+//! use ava_apartment_finder::DiffEngine;
+//! use ava_apartment_finder::AVA_URL;
+//!
+//! let client = AvalonClient::new(Arc::new(http::Client::default()));
+//! let mut store = ApartmentStore::load(Path::new("ava_db.json"))?;
+//! let data = client.get_apartments(AVA_URL).await?;
+//! let diff = DiffEngine::new(IgnoreChanges::default(), PriceDropAlert::default()).diff(
+//!     AVA_URL,
+//!     &mut store.known_apartments,
+//!     &mut store.unlisted_apartments,
+//!     data.apartments,
+//! );
+//! for unit in &diff.added {
+//!     println!("New: {unit}");
+//! }
+//! store.save(Path::new("ava_db.json"))?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod affordability;
+pub mod api;
+pub mod ava_date;
+pub mod avalon;
+pub mod browser;
+pub mod changes;
+pub mod concession;
+pub mod craigslist;
+pub mod diff;
+pub mod diff_engine;
+pub mod duration;
+pub mod events;
+pub mod filter;
+pub mod geo;
+pub mod html;
+pub mod http;
+pub mod lease_term;
+pub mod listing;
+pub mod money;
+pub mod node;
+pub mod price_drop;
+pub mod qualifications;
+pub mod reminders;
+pub mod robots;
+pub mod store;
+pub mod timezone;
+
+pub use avalon::AvalonClient;
+pub use diff_engine::DiffEngine;
+pub use store::ApartmentStore;
+
+/// The AVA Capitol Hill community page, which is tracked if no communities are configured.
+pub const AVA_URL: &str =
+    "https://new.avaloncommunities.com/washington/seattle-apartments/ava-capitol-hill/";
@@ -0,0 +1,70 @@
+use std::fmt::Debug;
+use std::fmt::Display;
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::changes::FieldChange;
+use crate::filter::Value;
+use crate::lease_term;
+use crate::lease_term::LeaseOption;
+use crate::money::Money;
+use crate::price_drop::PriceDrop;
+use crate::qualifications::Qualifications;
+
+/// A unit observed from some source, which we track and diff across ticks.
+pub trait Listing: Clone + Debug + Display + PartialEq + Serialize {
+    /// A stable identifier for this listing, unique across all sources.
+    ///
+    /// This is used as the key in the DB.
+    fn id(&self) -> &str;
+
+    /// Email subject for a notification that this listing has appeared.
+    fn listed_subject(&self) -> String;
+
+    /// Email subject for a notification that this listing has disappeared.
+    fn unlisted_subject(&self) -> String;
+
+    /// Email subject for a notification that this listing has changed.
+    fn changed_subject(&self) -> String;
+
+    /// Email subject for a notification that this listing's rent has dropped.
+    fn price_drop_subject(&self, drop: &PriceDrop) -> String;
+
+    /// The monthly rent, if known.
+    fn rent(&self) -> Option<Money>;
+
+    /// The date this listing is available to move in, if known.
+    fn available_date(&self) -> Option<NaiveDate>;
+
+    /// A link to this listing's page, if it has its own.
+    fn url(&self) -> Option<String>;
+
+    /// A link to a virtual tour of this specific unit, if there is one.
+    fn virtual_tour_url(&self) -> Option<String>;
+
+    /// Every move-in date and lease term this listing is offered with, if known.
+    fn lease_options(&self) -> Vec<LeaseOption>;
+
+    /// The cheapest lease term and move-in date, if there's more than one to choose from.
+    fn best_deal(&self) -> Option<LeaseOption> {
+        let options = self.lease_options();
+        if options.len() > 1 {
+            lease_term::best(options)
+        } else {
+            None
+        }
+    }
+
+    /// Does this listing meet the user's `qualifications`?
+    ///
+    /// Sources which don't know a field skip the checks for it.
+    fn meets_qualifications(&self, qualifications: &Qualifications) -> bool;
+
+    /// Look up a field by name, for [`crate::filter`] expressions. Names are from
+    /// [`crate::filter::VARIABLES`].
+    fn field(&self, name: &str) -> Option<Value>;
+
+    /// The fields that differ between `self` and a `new` observation of the same listing.
+    fn changes(&self, new: &Self) -> Vec<FieldChange>;
+}
@@ -0,0 +1,83 @@
+//! A lock file next to the DB, so two copies of the app don't overwrite each other's changes
+//! (and send every notification twice).
+
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::Read;
+use std::io::Seek;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use color_eyre::eyre;
+use color_eyre::eyre::eyre;
+use color_eyre::eyre::Context;
+use fs2::FileExt;
+
+/// An exclusive lock on the DB, held until this is dropped.
+///
+/// The lock is released by the OS when the process exits, so a crash doesn't leave a stale lock
+/// behind.
+#[derive(Debug)]
+pub struct DbLock {
+    _file: File,
+}
+
+impl DbLock {
+    /// Lock the DB at `db_path`, failing if another process already holds the lock.
+    pub fn acquire(db_path: &Path) -> eyre::Result<Self> {
+        let path = lock_path(db_path);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(&path)
+            .wrap_err_with(|| format!("Failed to open lock file {path:?}"))?;
+
+        if file.try_lock_exclusive().is_err() {
+            let mut pid = String::new();
+            let _ = file.read_to_string(&mut pid);
+            let holder = match pid.trim() {
+                "" => String::new(),
+                pid => format!(" (process {pid})"),
+            };
+            return Err(eyre!(
+                "Another instance{holder} is already using {db_path:?}; \
+                stop it first, or delete {path:?} if you're sure it isn't running"
+            ));
+        }
+
+        // Record our PID for the error message above. Failing to isn't a big deal.
+        let result = file
+            .set_len(0)
+            .and_then(|()| file.rewind())
+            .and_then(|()| write!(file, "{}", std::process::id()));
+        if let Err(err) = result {
+            tracing::debug!("Failed to write PID to lock file: {err}");
+        }
+
+        Ok(Self { _file: file })
+    }
+}
+
+fn lock_path(db_path: &Path) -> PathBuf {
+    let mut path = db_path.as_os_str().to_owned();
+    path.push(".lock");
+    PathBuf::from(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock() {
+        let db_path = std::env::temp_dir().join(format!("ava_db_{}.json", std::process::id()));
+        let lock = DbLock::acquire(&db_path).unwrap();
+        let err = DbLock::acquire(&db_path).unwrap_err();
+        assert!(err.to_string().contains(&std::process::id().to_string()));
+        drop(lock);
+        DbLock::acquire(&db_path).unwrap();
+        let _ = std::fs::remove_file(lock_path(&db_path));
+    }
+}
@@ -1,184 +1,1144 @@
 #![allow(dead_code)]
 
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::fmt::Display;
-use std::fs::File;
-use std::io::BufWriter;
+use std::hash::Hash;
+use std::hash::Hasher;
 use std::path::Path;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
 use std::time::Duration;
+use std::time::Instant;
 
+use camino::Utf8PathBuf;
+use chrono::DateTime;
 use chrono::Utc;
+use clap::CommandFactory;
 use clap::Parser;
+use clap::Subcommand;
 use color_eyre::eyre;
 use color_eyre::eyre::eyre;
 use color_eyre::eyre::Context;
-use serde::Deserialize;
-use serde::Serialize;
-use soup::prelude::*;
-
-mod api;
-mod ava_date;
-mod diff;
-mod duration;
+use tokio::sync::watch;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tracing::Instrument;
+
+use ava_apartment_finder::affordability;
+use ava_apartment_finder::api;
+use ava_apartment_finder::changes;
+use ava_apartment_finder::craigslist;
+use ava_apartment_finder::diff_engine::ApartmentsDiff;
+use ava_apartment_finder::diff_engine::ChangedApartment;
+use ava_apartment_finder::events;
+use ava_apartment_finder::filter;
+use ava_apartment_finder::geo;
+use ava_apartment_finder::html;
+use ava_apartment_finder::http;
+use ava_apartment_finder::lease_term;
+use ava_apartment_finder::listing;
+use ava_apartment_finder::money;
+use ava_apartment_finder::price_drop;
+use ava_apartment_finder::qualifications;
+use ava_apartment_finder::reminders;
+use ava_apartment_finder::store;
+use ava_apartment_finder::timezone;
+use ava_apartment_finder::ApartmentStore;
+use ava_apartment_finder::AvalonClient;
+use ava_apartment_finder::DiffEngine;
+use ava_apartment_finder::AVA_URL;
+
+mod airtable;
+mod calendar;
+mod chart;
+mod color;
+mod compare;
+mod config;
+mod control;
+mod dashboard;
+mod days_on_market;
+mod diff_db;
+mod email_commands;
+mod error_reporting;
+mod export;
+mod feed;
+mod forecast;
+mod graphql;
+mod healthcheck;
+mod init;
 mod jmap;
-mod node;
+mod lock;
+mod market_report;
+mod mock_jmap;
+mod mqtt;
+mod notion;
+mod output;
+mod plugin;
+mod polling;
+mod price_range;
+mod quiet_hours;
+mod redact;
+mod sanity;
+mod score;
+mod secret;
+mod server;
+mod sheets;
+mod shutdown;
+mod simulate;
+mod social;
+mod source;
+mod statsd;
+mod systemd;
+mod tick_summary;
 mod trace;
+mod tui;
 mod wrap;
 
-const DATA_PATH: &str = "ava_db.json";
+use config::Config;
+use days_on_market::DaysOnMarket;
+use events::EventKind;
+use forecast::Forecast;
+use jmap_client::email::EmailAddress;
+use listing::Listing;
+use output::ListingJson;
+use output::OutputFormat;
+use price_range::PriceRange;
+use source::Listings;
+use source::Source;
+use tick_summary::TickSummary;
 
-const AVA_URL: &str =
-    "https://new.avaloncommunities.com/washington/seattle-apartments/ava-capitol-hill/";
+const DATA_PATH: &str = "ava_db.json";
 
-const JS_PREFIX: &str = "window = {}; \
-                         window.Fusion = {}; \
-                         Fusion = window.Fusion; ";
-const JS_SUFFIX: &str = "console.log(JSON.stringify(Fusion.globalContent))";
+/// The exit status for `once` when some sources couldn't be fetched.
+const SOURCES_FAILED: u8 = 2;
 
-const SECONDS_PER_MINUTE: u64 = 50;
+/// Tracing target for events about apartments which don't meet the qualifications.
+///
+/// Enable with `--tracing-filter ava_apartment_finder::everything=debug`, or at runtime with
+/// `control set-log-filter info,ava_apartment_finder::everything=debug`.
+const EVERYTHING: &str = "ava_apartment_finder::everything";
 
 #[derive(Parser)]
 struct Args {
-    #[clap(long, default_value = "info")]
-    tracing_filter: String,
+    /// Filter directives for console output, like `info,ava_apartment_finder=debug`.
+    ///
+    /// Defaults to `filter` in the `[log]` config, or `info`.
+    #[clap(long)]
+    tracing_filter: Option<String>,
+
+    /// Start a new JSONL log file once the current one reaches this many megabytes. Log files
+    /// are also started daily.
+    #[clap(long, default_value = "64")]
+    log_max_mb: u64,
+
+    /// Delete log files older than this many days at startup. Set to 0 to keep them forever.
+    #[clap(long, default_value = "30")]
+    log_retention_days: u64,
+
+    /// Export traces to this OpenTelemetry collector over OTLP/gRPC, like
+    /// `http://localhost:4317`.
+    ///
+    /// Requires the `otlp` feature.
+    #[clap(long)]
+    otlp_endpoint: Option<String>,
+
+    /// Path to the config file.
+    ///
+    /// Defaults to `~/.config/ava-apartment-finder/config.toml`, if it exists.
+    #[clap(long)]
+    config: Option<Utf8PathBuf>,
+
+    /// Fetch pages even if `robots.txt` disallows it.
+    #[clap(long)]
+    ignore_robots_txt: bool,
+
+    /// Save every scraped page to a fixture file in this directory, for `--replay`.
+    #[clap(long, conflicts_with = "replay")]
+    record: Option<Utf8PathBuf>,
+
+    /// Read scraped pages from the fixture files in this directory, recorded with `--record`,
+    /// instead of fetching them.
+    #[clap(long)]
+    replay: Option<Utf8PathBuf>,
+
+    /// Log to the systemd journal, with structured fields, instead of printing logs.
+    ///
+    /// Same as `console-format = "journald"` in the `[log]` config.
+    #[clap(long)]
+    systemd: bool,
+
+    /// When to use colors in terminal output. Colors are also disabled if `NO_COLOR` is set.
+    #[clap(long, value_enum, default_value = "auto")]
+    color: color::ColorChoice,
+
+    /// Only use ASCII in terminal output, like `*` instead of `•`.
+    #[clap(long)]
+    ascii: bool,
+
+    /// How `list`, `report`, `compare`, and `diff-db` print their results: as text, or as
+    /// stable JSON for scripts.
+    #[clap(long, value_enum, default_value = "text", global = true)]
+    output: OutputFormat,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
 }
 
-#[tokio::main]
-async fn main() -> eyre::Result<()> {
-    color_eyre::install()?;
-    let args = Args::parse();
-    let log_file = trace::install_tracing(&args.tracing_filter)?;
-    tracing::info!("Logging to {log_file}");
-
-    let data_path = Path::new(&DATA_PATH);
-    let mut app: App = if data_path.exists() {
-        tracing::info!(path = ?data_path, "DB path exists, reading");
-        serde_json::from_str(
-            &std::fs::read_to_string(&data_path)
-                .wrap_err_with(|| format!("Failed to read `{data_path:?}`"))?,
+#[derive(Subcommand)]
+enum Command {
+    /// Watch for apartments and send notifications. This is the default.
+    Run,
+
+    /// Check for new listings and send notifications once, then exit.
+    ///
+    /// For running from cron or a systemd timer. Exits with status 1 on errors, or 2 if some
+    /// sources couldn't be fetched.
+    Once,
+
+    /// Never notify about a unit. It's still tracked in the DB.
+    ///
+    /// The daemon locks the DB, so stop it before running this.
+    Ignore {
+        /// A unit ID like `AVB-WA026-001-731`, or an apartment number like `731`.
+        unit: String,
+    },
+
+    /// Print the currently listed apartments, best first.
+    List,
+
+    /// Print the weekly market report.
+    Report,
+
+    /// Print a table comparing units side by side: rent, floor, availability, score, and so
+    /// on.
+    Compare {
+        /// Unit IDs like `AVB-WA026-001-731`, or apartment numbers like `731`.
+        #[clap(required = true)]
+        units: Vec<String>,
+    },
+
+    /// Print what changed between two DB files, like after restoring a backup: listings added,
+    /// unlisted, or changed, and units watched or ignored.
+    DiffDb {
+        /// The older DB, like a backup of `ava_db.json`.
+        old: PathBuf,
+        /// The newer DB.
+        new: PathBuf,
+    },
+
+    /// Browse tracked listings interactively, and watch or ignore them.
+    ///
+    /// The daemon locks the DB, so stop it before running this.
+    Tui,
+
+    /// Resume notifying about a unit previously passed to `ignore`.
+    Unignore {
+        /// A unit ID like `AVB-WA026-001-731`, or an apartment number like `731`.
+        unit: String,
+    },
+
+    /// Notify about any change to a unit, even if it doesn't meet the qualifications.
+    ///
+    /// Notifications for watched units go to `watch-to` if it's configured. The daemon locks the
+    /// DB, so stop it before running this.
+    Watch {
+        /// A unit ID like `AVB-WA026-001-731`, or an apartment number like `731`.
+        unit: String,
+    },
+
+    /// Stop watching a unit previously passed to `watch`.
+    Unwatch {
+        /// A unit ID like `AVB-WA026-001-731`, or an apartment number like `731`.
+        unit: String,
+    },
+
+    /// Run a fake community through the real diff, notification, and DB code, to demo
+    /// integrations and filters offline.
+    ///
+    /// Each tick is a simulated day. Emails go to an in-process mock JMAP server, which only
+    /// logs them, unless `--live` is passed. The DB is written to `--db` instead of the real
+    /// one.
+    Simulate {
+        /// How many days to simulate.
+        #[clap(long, default_value = "30")]
+        days: u32,
+
+        /// Seed for the random changes. The same seed gives the same simulation.
+        #[clap(long, default_value = "0")]
+        seed: u64,
+
+        /// Seconds to wait between simulated days.
+        #[clap(long, default_value = "0")]
+        delay: f64,
+
+        /// Where to write the simulated DB. It's overwritten.
+        #[clap(long, default_value = "ava_db.simulate.json")]
+        db: PathBuf,
+
+        /// Send notifications to the configured email and social accounts, and use the other
+        /// configured integrations. Otherwise, emails go to an in-process mock JMAP server
+        /// which only logs them, and everything else that leaves this machine is turned off.
+        #[clap(long)]
+        live: bool,
+    },
+
+    /// Set up a config file interactively: pick a community, enter where notifications go, and
+    /// check the mail settings with a test email.
+    ///
+    /// Writes to `--config`, or the default config path.
+    Init {
+        /// Overwrite the config file if it already exists.
+        #[clap(long)]
+        force: bool,
+    },
+
+    /// Print a completion script for `shell`, like
+    /// `ava-apartment-finder completions zsh > _ava-apartment-finder`.
+    Completions {
+        #[clap(value_enum)]
+        shell: clap_complete::Shell,
+    },
+
+    /// Print a man page, like `ava-apartment-finder man > ava-apartment-finder.1`.
+    Man,
+
+    /// Send a command like `pause` or `set-filter rent < 4000` to the running daemon.
+    ///
+    /// Requires `control-socket` to be configured.
+    Control {
+        /// `tick-now`, `pause`, `resume`, `reload-config`, `set-filter [expression]`, or
+        /// `set-log-filter <directives>`.
+        #[clap(required = true)]
+        command: Vec<String>,
+    },
+}
+
+impl Command {
+    /// Whether this command saves the DB, and so needs the [`lock::DbLock`].
+    fn writes_db(&self) -> bool {
+        !matches!(
+            self,
+            Command::List
+                | Command::Report
+                | Command::Compare { .. }
+                | Command::DiffDb { .. }
+                | Command::Completions { .. }
+                | Command::Man
+                | Command::Init { .. }
+                | Command::Control { .. }
+                | Command::Simulate { .. }
         )
-        .wrap_err_with(|| format!("Failed to load Apartment data from `{data_path:?}`"))?
-    } else {
-        tracing::info!(path = ?data_path, "No DB, initializing");
-        App::default()
-    };
+    }
+}
 
-    tracing::info!("Tracking {} apartments", app.known_apartments.len());
+#[tokio::main]
+async fn main() -> ExitCode {
+    match try_main().await {
+        Ok(status) => status,
+        Err(err) => {
+            // Print the report ourselves, rather than returning it, so secrets are scrubbed.
+            eprintln!("Error: {}", redact::redact(&format!("{err:?}")));
+            ExitCode::FAILURE
+        }
+    }
+}
 
-    let sending_identity =
-        jmap::SendingIdentity::new(("Ava Apartment Finder", "rbt@fastmail.com").into())
-            .await
-            .wrap_err("Unable to determine email sending identity")?;
+async fn try_main() -> eyre::Result<ExitCode> {
+    let mut args = Args::parse();
+    color::init(args.color, args.ascii)?;
+    // These don't need a config or DB: completions and the man page are generated when
+    // packaging, and `init` writes the config.
+    match args.command {
+        Some(Command::Completions { shell }) => {
+            let mut command = Args::command();
+            let name = command.get_name().to_owned();
+            clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Command::Man) => {
+            clap_mangen::Man::new(Args::command())
+                .render(&mut std::io::stdout())
+                .wrap_err("Failed to write man page")?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Command::Init { force }) => {
+            let path = match &args.config {
+                Some(path) => path.clone(),
+                None => Config::default_path()?,
+            };
+            init::run(&path, force).await?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        _ => {}
+    }
+    // Read the config first, so it can configure logging.
+    let config = load_config(&args)?;
+    redact::set_secrets(config.secrets());
+    timezone::set(config.timezone);
+    money::set(config.currency.clone());
+    let _sentry = config
+        .sentry_dsn
+        .as_deref()
+        .map(error_reporting::init)
+        .transpose()?;
+    let mut log_config = config.log.clone();
+    if args.systemd {
+        log_config.console_format = trace::ConsoleFormat::Journald;
+    }
+    let (log_file, log_filter) = trace::install_tracing(
+        args.tracing_filter
+            .as_deref()
+            .or(config.log.filter.as_deref())
+            .unwrap_or("info"),
+        &log_config,
+        &trace::LogFiles {
+            max_bytes: args.log_max_mb * 1024 * 1024,
+            retention: (args.log_retention_days > 0)
+                .then(|| Duration::from_secs(args.log_retention_days * 24 * 60 * 60)),
+        },
+        args.otlp_endpoint.as_deref(),
+    )?;
+    if let Some(log_file) = log_file {
+        tracing::info!("Logging to {log_file}");
+    }
 
-    app.sending_identity = Some(sending_identity);
+    let command = args.command.take().unwrap_or(Command::Run);
+    if let Command::Control { command } = &command {
+        let path = config
+            .control_socket
+            .as_deref()
+            .ok_or_else(|| eyre!("`control-socket` isn't configured"))?;
+        println!("{}", control::send(path, &command.join(" ")).await?);
+        return Ok(ExitCode::SUCCESS);
+    }
+    // Hold the lock until we exit, so another instance can't overwrite the DB under us.
+    let _lock = if command.writes_db() {
+        Some(lock::DbLock::acquire(Path::new(DATA_PATH))?)
+    } else {
+        None
+    };
+    if let Some(statsd) = &config.statsd {
+        // Simulated listings shouldn't show up in real metrics.
+        if !matches!(command, Command::Simulate { live: false, .. }) {
+            statsd::install(statsd)?;
+        }
+    }
 
-    loop {
-        match app.tick().await {
-            Ok(()) => {}
-            Err(err) => {
-                tracing::error!("{err:?}");
+    let mut app = App::load(Path::new(DATA_PATH))?;
+    app.config = config;
 
-                let email_err = app.send(&jmap::Email {
-                    to: ("Rebecca Turner", "rbt@fastmail.com").into(),
-                    subject: format!("Ava Apartment Finder error: {err}"),
-                    body: format!(
-                        "{err:?}\n\n\
-                        You'll probably be getting this email every 5 minutes until you fix the bug. \
-                        Sorry about that.\n\
-                        —Past Rebecca"
-                    ),
-                }).await;
-                if let Err(err) = email_err {
-                    tracing::error!("Error sending error email: {err:?}");
-                };
+    let result = match command {
+        Command::Run => run(app, &args, log_filter).await,
+        Command::Once => {
+            let status = once(app).await;
+            trace::shutdown().await;
+            return status;
+        }
+        Command::Control { .. }
+        | Command::Completions { .. }
+        | Command::Man
+        | Command::Init { .. } => unreachable!("handled above"),
+        Command::List => app.list(args.output),
+        Command::Report => {
+            let (apartments, events) = (&app.store.known_apartments, &app.store.events);
+            match args.output {
+                OutputFormat::Text => {
+                    let report = market_report::render(apartments, events, Utc::now());
+                    print!("{}", color::for_terminal(&report));
+                }
+                OutputFormat::Json => {
+                    let report = market_report::summarize(apartments, events, Utc::now());
+                    print!("{}", output::json(&report)?);
+                }
             }
+            Ok(())
         }
-        // Wait 5 minutes before checking again.
-        tokio::time::sleep(Duration::from_secs(5 * SECONDS_PER_MINUTE)).await;
-    }
+        Command::Compare { units } => {
+            print!("{}", app.compare(&units, args.output)?);
+            Ok(())
+        }
+        Command::DiffDb { old, new } => {
+            let diff = diff_db::diff(
+                &ApartmentStore::load(&old)?,
+                &ApartmentStore::load(&new)?,
+                &DiffEngine::new(
+                    app.config.ignore_changes.clone(),
+                    app.config.price_drop.clone(),
+                ),
+            );
+            match args.output {
+                OutputFormat::Text => print!("{}", color::for_terminal(&diff_db::render(&diff))),
+                OutputFormat::Json => print!("{}", output::json(&diff)?),
+            }
+            Ok(())
+        }
+        Command::Tui => tui::run(&mut app),
+        Command::Simulate {
+            days,
+            seed,
+            delay,
+            db,
+            live,
+        } => {
+            let mut config = std::mem::take(&mut app.config);
+            // Held until the simulation's done, so the server keeps running.
+            let mock_jmap = if live {
+                None
+            } else {
+                let server = mock_jmap::MockJmap::start().await;
+                simulate::offline(&mut config, server.config());
+                Some(server)
+            };
+            let mut simulated = App {
+                config,
+                db_path: Some(db),
+                ..Default::default()
+            };
+            simulate::run(
+                &mut simulated,
+                days,
+                seed,
+                Duration::from_secs_f64(delay.max(0.0)),
+            )
+            .await?;
+            if let Some(server) = mock_jmap {
+                tracing::info!(
+                    emails = server.emails().len(),
+                    "Emails went to a mock JMAP server; pass `--live` to send them"
+                );
+            }
+            Ok(())
+        }
+        Command::Ignore { unit } => {
+            let id = app.resolve_unit(&unit)?;
+            if app.store_mut().ignored.insert(id.clone()) {
+                tracing::info!("Ignoring {id}");
+            } else {
+                tracing::info!("Already ignoring {id}");
+            }
+            app.save()
+        }
+        Command::Unignore { unit } => {
+            let id = app.resolve_unit(&unit)?;
+            if app.store_mut().ignored.remove(&id) {
+                tracing::info!("No longer ignoring {id}");
+            } else {
+                tracing::info!("{id} wasn't ignored");
+            }
+            app.save()
+        }
+        Command::Watch { unit } => {
+            let id = app.resolve_unit(&unit)?;
+            if app.store_mut().watched.insert(id.clone()) {
+                tracing::info!("Watching {id}");
+                app.store.watched_since.insert(id, Utc::now());
+            } else {
+                tracing::info!("Already watching {id}");
+            }
+            app.save()
+        }
+        Command::Unwatch { unit } => {
+            let id = app.resolve_unit(&unit)?;
+            if app.store_mut().watched.remove(&id) {
+                tracing::info!("No longer watching {id}");
+            } else {
+                tracing::info!("{id} wasn't watched");
+            }
+            app.save()
+        }
+    };
+    trace::shutdown().await;
+    result.map(|()| ExitCode::SUCCESS)
 }
 
-#[tracing::instrument]
-async fn get_apartments() -> eyre::Result<api::ApartmentData> {
-    let response = reqwest::get(AVA_URL).await?;
+fn load_config(args: &Args) -> eyre::Result<Config> {
+    let mut config = Config::load(args.config.as_deref())?;
+    config.ignore_robots_txt |= args.ignore_robots_txt;
+    config.fixtures = match (&args.record, &args.replay) {
+        (Some(dir), _) => Some(http::Fixtures::Record(dir.clone())),
+        (_, Some(dir)) => Some(http::Fixtures::Replay(dir.clone())),
+        (None, None) => None,
+    };
+    Ok(config)
+}
 
-    tracing::trace!(?response, "Got response");
+/// Watch for apartments and send notifications, forever.
+async fn run(mut app: App, args: &Args, log_filter: trace::FilterHandle) -> eyre::Result<()> {
+    app.connect().await?;
 
-    let body = response.text().await?;
+    let poll_interval = app.config.polling.min_interval();
+    let min_poll_interval = app
+        .http
+        .min_poll_interval(app.config.sources().iter().map(Source::url))
+        .await;
+    tracing::info!(?min_poll_interval, "Effective minimum poll interval");
+    if poll_interval < min_poll_interval {
+        tracing::warn!(
+            ?poll_interval,
+            ?min_poll_interval,
+            "Polling faster than rate limits and robots.txt allow; ticks will be slowed down"
+        );
+    }
 
-    tracing::trace!(html = body, "Got HTML");
+    tracing::info!("Tracking {} apartments", app.store.known_apartments.len());
 
-    let soup = Soup::new(&body);
+    let healthcheck = app
+        .config
+        .healthcheck_url
+        .clone()
+        .map(healthcheck::Healthcheck::new);
+    let mut exporters = export::Exporters::new(&app.config, app.store.next_event_seq)?;
 
-    let script_tag = soup
-        .tag("script")
-        .attr("id", "fusion-metadata")
-        .find()
-        .ok_or_else(|| eyre!("Could not find `<script id=\"fusion-metadata\">` tag"))?
-        .text();
+    let mut last_tick = None;
+    let snapshots = match app.config.listen {
+        Some(addr) => {
+            let (sender, receiver) = watch::channel(Arc::new(app.snapshot(last_tick)));
+            let server = server::serve(addr, receiver)?;
+            tokio::spawn(async move {
+                if let Err(err) = server.await {
+                    tracing::error!("{err:?}");
+                }
+            });
+            Some(sender)
+        }
+        None => None,
+    };
 
-    let script = format!("{JS_PREFIX}{script_tag}{JS_SUFFIX}");
+    if let Some(timeout) = systemd::watchdog_timeout() {
+        let poll_interval = app.config.polling.max_interval();
+        if timeout < poll_interval * 2 {
+            tracing::warn!(
+                ?timeout,
+                ?poll_interval,
+                "The systemd watchdog timeout is too short; we only ping it once per tick"
+            );
+        }
+    }
 
-    tracing::trace!(script, "Extracted JavaScript");
+    let mut control = control::Control::listen(app.config.control_socket.as_deref())?;
+    let mut paused = false;
+    let mut signals = shutdown::listen()?;
+    systemd::ready();
+    let started = Utc::now();
+    let events_before = app.store.next_event_seq;
+    let mut ticks = 0;
+    let mut failed_ticks = 0;
+    // Whether we've sent an alert about `failed_ticks`.
+    let mut escalated = false;
+    // Whether a tick was interrupted partway through. Its changes to the store may not have
+    // been reported yet, so they mustn't be saved.
+    let mut aborted = false;
 
-    let value = node::js_eval(script)?;
+    'run: loop {
+        let result = {
+            let tick = app.tick();
+            tokio::pin!(tick);
+            tokio::select! {
+                result = &mut tick => Some(result),
+                _ = signals.changed() => {
+                    tracing::info!(
+                        "Finishing the current tick before shutting down; \
+                        interrupt again to abort it"
+                    );
+                    tokio::select! {
+                        result = &mut tick => Some(result),
+                        _ = signals.changed() => None,
+                    }
+                }
+            }
+        };
 
-    tracing::trace!(value, "Evaluated JavaScript");
+        let threshold = app.config.failure_alert_threshold;
+        match result {
+            None => {
+                tracing::warn!("Aborted tick");
+                aborted = true;
+            }
+            Some(Ok(())) => {
+                ticks += 1;
+                last_tick = Some(Utc::now());
+                if let Some(healthcheck) = &healthcheck {
+                    healthcheck.success().await;
+                }
+                // Failing sources are alerted on separately, but if every source failed we
+                // should still back off.
+                let sources = app.config.sources();
+                if !sources.is_empty()
+                    && sources
+                        .iter()
+                        .all(|source| app.store.failures.contains_key(source.url()))
+                {
+                    failed_ticks += 1;
+                } else {
+                    if escalated {
+                        escalated = false;
+                        app.alert(
+                            "Ava Apartment Finder recovered".to_owned(),
+                            format!("Ticks are succeeding again after {failed_ticks} failures."),
+                        )
+                        .await;
+                    }
+                    failed_ticks = 0;
+                }
+            }
+            Some(Err(err)) => {
+                tracing::error!("{err:?}");
+                failed_ticks += 1;
+                error_reporting::report(
+                    &err,
+                    &[
+                        ("tick", (ticks + 1).to_string()),
+                        ("failed_ticks", failed_ticks.to_string()),
+                    ],
+                );
 
-    Ok(serde_json::from_str(&value)
-        .map_err(|err| format_serde_error::SerdeError::new(value.to_string(), err))?)
-}
+                if let Some(healthcheck) = &healthcheck {
+                    healthcheck.failure(&err).await;
+                }
 
-// --
+                if threshold > 0 && failed_ticks >= threshold && !escalated {
+                    escalated = true;
+                    app.alert(
+                        format!("Ava Apartment Finder error: {err}"),
+                        format!(
+                            "Ticks have failed {failed_ticks} times in a row. Retrying less often \
+                            until they succeed; you'll get an email when they do.\n\n\
+                            {err:?}"
+                        ),
+                    )
+                    .await;
+                }
+            }
+        }
+        if let Err(err) = app.send_weekly_report().await {
+            tracing::error!("Failed to send weekly report: {err:?}");
+        }
 
-#[derive(Clone, Debug, Default)]
-struct ApartmentsDiff {
-    added: Vec<api::ApiApartment>,
-    removed: Vec<api::Apartment>,
-    changed: Vec<ChangedApartment>,
-}
+        if snapshots.is_some() || !exporters.is_empty(&app.config) {
+            let snapshot = Arc::new(app.snapshot(last_tick));
+            exporters.export(&app.config, &snapshot).await;
+            if let Some(snapshots) = &snapshots {
+                snapshots.send_replace(snapshot);
+            }
+        }
 
-impl ApartmentsDiff {
-    fn is_empty(&self) -> bool {
-        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+        if *signals.borrow() > 0 {
+            break;
+        }
+
+        systemd::watchdog(&format!(
+            "Tracking {} apartments and {} posts; last successful tick {}",
+            app.store.known_apartments.len(),
+            app.store.known_posts.len(),
+            last_tick.map_or_else(|| "never".to_owned(), |tick| tick.to_rfc3339()),
+        ));
+
+        let poll_interval = app
+            .config
+            .polling
+            .next_interval(&app.store.events, Utc::now());
+        let poll_interval = app.config.polling.backoff(poll_interval, failed_ticks);
+        tracing::debug!(?poll_interval, "Waiting before checking again");
+        let sleep = tokio::time::sleep(poll_interval);
+        tokio::pin!(sleep);
+        loop {
+            tokio::select! {
+                () = &mut sleep, if !paused => break,
+                _ = signals.changed() => break 'run,
+                request = control.recv() => match request.command {
+                    control::Command::TickNow => {
+                        request.reply("Checking for new listings");
+                        break;
+                    }
+                    control::Command::Pause => {
+                        paused = true;
+                        request.reply("Paused");
+                    }
+                    control::Command::Resume => {
+                        paused = false;
+                        request.reply("Resumed");
+                    }
+                    control::Command::ReloadConfig => match load_config(args) {
+                        Ok(config) => {
+                            redact::set_secrets(config.secrets());
+                            timezone::set(config.timezone);
+                            money::set(config.currency.clone());
+                            if let Some(filter) = &config.log.filter {
+                                if let Err(err) = log_filter.set(filter) {
+                                    tracing::error!("{err:?}");
+                                }
+                            }
+                            app.config = config;
+                            request.reply(
+                                "Reloaded config; restart to apply changes to integrations \
+                                and the HTTP API",
+                            );
+                        }
+                        Err(err) => {
+                            tracing::error!("Failed to reload config: {err:?}");
+                            request.reply(format!("Error: {err}"));
+                        }
+                    },
+                    control::Command::SetLogFilter(ref directives) => {
+                        let reply = match log_filter.set(directives) {
+                            Ok(()) => format!("Log filter set to `{directives}`"),
+                            Err(err) => format!("Error: {err:?}"),
+                        };
+                        request.reply(reply);
+                    }
+                    control::Command::SetFilter(ref filter) => {
+                        app.config.qualifications.filter = filter.clone();
+                        let reply = match filter {
+                            Some(filter) => format!("Filter set to `{filter}`"),
+                            None => "Filter cleared".to_owned(),
+                        };
+                        request.reply(reply);
+                    }
+                },
+            }
+        }
     }
-}
 
-#[derive(Clone, Debug)]
-struct ChangedApartment {
-    old: api::ApiApartment,
-    new: api::ApiApartment,
+    systemd::stopping();
+    exporters.close().await;
+    if aborted {
+        // Keep the last saved DB, so the next run finds the same changes and reports them.
+        tracing::warn!("Not saving the aborted tick's changes");
+    } else {
+        // Save anything changed between ticks, like when the weekly report was sent.
+        app.save()?;
+    }
+    tracing::info!(
+        ticks,
+        uptime = %(Utc::now() - started),
+        apartments = app.store.known_apartments.len(),
+        posts = app.store.known_posts.len(),
+        new_events = app.store.next_event_seq - events_before,
+        "Shut down cleanly"
+    );
+    Ok(())
 }
 
-impl Display for ChangedApartment {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let Self { old, new } = self;
-        write!(
-            f,
-            "{}",
-            diff::diff_header(
-                &format!("{old:#?}"),
-                &format!("{new:#?}"),
-                &old.to_string(),
-                &new.to_string(),
-            )
-            .unwrap_or_else(|err| format!("{err:?}"))
-        )
+/// Tick once, for running from cron or a systemd timer.
+///
+/// Exits with status 1 if the tick fails outright, or [`SOURCES_FAILED`] if it succeeds but
+/// some sources couldn't be fetched.
+async fn once(mut app: App) -> eyre::Result<ExitCode> {
+    app.connect().await?;
+    let healthcheck = app
+        .config
+        .healthcheck_url
+        .clone()
+        .map(healthcheck::Healthcheck::new);
+    let mut exporters = export::Exporters::new(&app.config, app.store.next_event_seq)?;
+
+    if let Err(err) = app.tick().await {
+        error_reporting::report(&err, &[]);
+        if let Some(healthcheck) = &healthcheck {
+            healthcheck.failure(&err).await;
+        }
+        return Err(err);
+    }
+    if let Some(healthcheck) = &healthcheck {
+        healthcheck.success().await;
+    }
+    if let Err(err) = app.send_weekly_report().await {
+        tracing::error!("Failed to send weekly report: {err:?}");
+    }
+
+    if !exporters.is_empty(&app.config) {
+        let snapshot = Arc::new(app.snapshot(Some(Utc::now())));
+        exporters.export(&app.config, &snapshot).await;
+    }
+    exporters.close().await;
+
+    let failed = app
+        .config
+        .sources()
+        .iter()
+        .filter(|source| app.store.failures.contains_key(source.url()))
+        .count();
+    if failed > 0 {
+        tracing::warn!("Failed to fetch {failed} sources");
+        Ok(ExitCode::from(SOURCES_FAILED))
+    } else {
+        Ok(ExitCode::SUCCESS)
     }
 }
 
-#[derive(Default, Deserialize, Serialize)]
+#[derive(Default)]
 struct App {
-    #[serde(skip)]
-    sending_identity: Option<jmap::SendingIdentity>,
-    known_apartments: BTreeMap<String, api::Apartment>,
-    unlisted_apartments: BTreeMap<String, api::Apartment>,
+    config: Config,
+    http: Arc<http::Client>,
+    mailer: Option<jmap::Mailer>,
+    social: Option<social::Poster>,
+    plugins: plugin::Plugins,
+    geocoder: Option<geo::Geocoder>,
+    /// What's happened so far in the current tick.
+    summary: TickSummary,
+    /// Where to write the DB, if not [`DATA_PATH`], like for `simulate`.
+    db_path: Option<PathBuf>,
+    /// A hash of the DB as of the last write, so we don't rewrite it when nothing's changed.
+    last_saved: Arc<std::sync::Mutex<Option<u64>>>,
+    /// Counts changes to `store`. Bumped through [`App::store_mut`], or directly where that
+    /// would borrow too much, so ticks where nothing changed don't clone or serialize the DB at
+    /// all.
+    generation: u64,
+    /// The `generation` the DB was last written at, if it has been.
+    saved_generation: Option<u64>,
+    /// The last snapshot and the `generation` it was taken at, so unchanged ticks can share its
+    /// listings and events instead of copying them again.
+    last_snapshot: Option<(u64, server::Snapshot)>,
+    store: ApartmentStore,
 }
 
 impl App {
+    /// Load the DB from `path`, or start a new one if it doesn't exist.
+    fn load(path: &Path) -> eyre::Result<Self> {
+        Ok(Self {
+            store: ApartmentStore::load(path)?,
+            ..Default::default()
+        })
+    }
+
+    /// The store, to change it, marking it as needing to be written.
+    fn store_mut(&mut self) -> &mut ApartmentStore {
+        self.generation += 1;
+        &mut self.store
+    }
+
+    /// Where to write the DB: [`DATA_PATH`], or `db_path` if it's set.
+    fn db_path(&self) -> &Path {
+        self.db_path
+            .as_deref()
+            .unwrap_or_else(|| Path::new(DATA_PATH))
+    }
+
+    /// Write the DB, unless it hasn't changed since the last write.
+    fn save(&mut self) -> eyre::Result<()> {
+        if self.saved_generation == Some(self.generation) {
+            return Ok(());
+        }
+        save_if_changed(&self.store, self.db_path(), &self.last_saved)?;
+        self.saved_generation = Some(self.generation);
+        Ok(())
+    }
+
+    /// Like [`App::save`], but serializing and writing the DB on a blocking thread, so a large
+    /// DB doesn't hold up the async runtime. Only cloning the store happens here.
+    async fn save_in_background(&mut self) -> eyre::Result<()> {
+        if self.saved_generation == Some(self.generation) {
+            return Ok(());
+        }
+        let store = self.store.clone();
+        let path = self.db_path().to_owned();
+        let last_saved = self.last_saved.clone();
+        tokio::task::spawn_blocking(move || save_if_changed(&store, &path, &last_saved))
+            .await
+            .wrap_err("DB write task panicked")??;
+        self.saved_generation = Some(self.generation);
+        Ok(())
+    }
+
+    /// Send the weekly market report, if it's scheduled and due.
+    async fn send_weekly_report(&mut self) -> eyre::Result<()> {
+        let schedule = match &self.config.weekly_report {
+            Some(schedule) => schedule,
+            None => return Ok(()),
+        };
+        let now = Utc::now();
+        let timezone = self.config.timezone;
+        let local = |time: DateTime<Utc>| timezone.naive_local(time);
+        if !schedule.is_due(self.store.last_weekly_report.map(local), local(now)) {
+            return Ok(());
+        }
+
+        tracing::info!("Sending weekly report");
+        self.send(&jmap::Email {
+            to: self.config.to.clone(),
+            subject: format!(
+                "Weekly apartment report for {}",
+                timezone.format(now, "%b %e %Y")
+            ),
+            body: market_report::render(&self.store.known_apartments, &self.store.events, now),
+            attachments: market_report::charts(
+                &self.store.known_apartments,
+                &self.store.events,
+                now,
+            ),
+            unit: None,
+            cc: self.config.cc.clone(),
+            bcc: self.config.bcc.clone(),
+            reply_to: self.config.reply_to.clone(),
+            send_at: self.quiet_send_at(),
+        })
+        .await?;
+        self.store_mut().last_weekly_report = Some(now);
+        self.save_in_background().await
+    }
+
+    /// Set up the HTTP client and notification senders, so we can tick.
+    async fn connect(&mut self) -> eyre::Result<()> {
+        self.http = Arc::new(http::Client::new(
+            self.config.rate_limit.clone(),
+            self.config.ignore_robots_txt,
+            self.config.fixtures.clone(),
+        ));
+
+        // Connects on the first email, so ticks without news don't need the email server.
+        self.mailer = Some(jmap::Mailer::new(self.config.jmap.clone()));
+
+        if !self.config.social.is_empty() {
+            self.social = Some(social::Poster::new(self.config.social.clone())?);
+        }
+        self.plugins = plugin::Plugins::load(&self.config.plugins)?;
+        self.geocoder = self
+            .config
+            .geocoding
+            .as_ref()
+            .map(|config| geo::Geocoder::new(&config.url))
+            .transpose()?;
+        Ok(())
+    }
+
+    /// Copy the state for the HTTP API, sharing the listings and events with the last snapshot
+    /// if they haven't changed since.
+    fn snapshot(&mut self, last_tick: Option<DateTime<Utc>>) -> server::Snapshot {
+        let snapshot = match &self.last_snapshot {
+            Some((generation, last)) if *generation == self.generation => server::Snapshot {
+                last_tick,
+                qualifications: self.config.qualifications.clone(),
+                ..last.clone()
+            },
+            _ => server::Snapshot {
+                known_apartments: Arc::new(self.store.known_apartments.clone()),
+                unlisted_apartments: Arc::new(self.store.unlisted_apartments.clone()),
+                known_posts: Arc::new(self.store.known_posts.clone()),
+                unlisted_posts: Arc::new(self.store.unlisted_posts.clone()),
+                events: Arc::new(self.store.events.clone()),
+                last_tick,
+                qualifications: self.config.qualifications.clone(),
+            },
+        };
+        self.last_snapshot = Some((self.generation, snapshot.clone()));
+        snapshot
+    }
+
+    /// Print the listed apartments and posts, best first, with their scores.
+    fn list(&self, output: OutputFormat) -> eyre::Result<()> {
+        let weights = &self.config.score;
+        if output == OutputFormat::Json {
+            let now = Utc::now();
+            let mut listings: Vec<_> = self
+                .store
+                .known_apartments
+                .values()
+                .map(|apt| ListingJson::new(apt, weights, now))
+                .chain(
+                    self.store
+                        .known_posts
+                        .values()
+                        .map(|post| ListingJson::new(post, weights, now)),
+                )
+                .collect();
+            listings.sort_by(|a, b| b.score.total_cmp(&a.score));
+            print!("{}", output::json(&listings)?);
+            return Ok(());
+        }
+
+        let today = Utc::now().naive_utc().date();
+        let mut scored: Vec<(f64, String)> = self
+            .store
+            .known_apartments
+            .values()
+            .map(|apt| (weights.score(&apt.inner, today), listing_link(apt)))
+            .chain(
+                self.store
+                    .known_posts
+                    .values()
+                    .map(|post| (weights.score(&post.inner, today), listing_link(post))),
+            )
+            .collect();
+        scored.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+        for (score, listing) in scored {
+            println!("{score:>6.1}  {}", color::for_terminal(&listing));
+        }
+        Ok(())
+    }
+
+    /// A table comparing `units`, each a unit ID or apartment number, or the units as JSON.
+    fn compare(&self, units: &[String], output: OutputFormat) -> eyre::Result<String> {
+        let weights = &self.config.score;
+        let now = Utc::now();
+        let (mut columns, mut listings) = (Vec::new(), Vec::new());
+        for unit in units {
+            let id = self.resolve_unit(unit)?;
+            let store = &self.store;
+            if let Some(apt) = store
+                .known_apartments
+                .get(&id)
+                .or_else(|| store.unlisted_apartments.get(&id))
+            {
+                columns.push(compare::column(apt, weights, now));
+                listings.push(ListingJson::new(apt, weights, now));
+            } else {
+                let post = store
+                    .known_posts
+                    .get(&id)
+                    .or_else(|| store.unlisted_posts.get(&id))
+                    .expect("resolved units are in the DB");
+                columns.push(compare::column(post, weights, now));
+                listings.push(ListingJson::new(post, weights, now));
+            }
+        }
+        match output {
+            OutputFormat::Text => Ok(compare::render(&columns)),
+            OutputFormat::Json => output::json(&listings),
+        }
+    }
+
+    /// Find the ID of a unit given either its ID or its apartment number.
+    fn resolve_unit(&self, unit: &str) -> eyre::Result<String> {
+        let apartments = || {
+            self.store
+                .known_apartments
+                .values()
+                .chain(self.store.unlisted_apartments.values())
+        };
+
+        if apartments().any(|apt| apt.id() == unit)
+            || self.store.known_posts.contains_key(unit)
+            || self.store.unlisted_posts.contains_key(unit)
+        {
+            return Ok(unit.to_owned());
+        }
+
+        let matches: BTreeSet<&str> = apartments()
+            .filter(|apt| apt.inner.number == unit)
+            .map(|apt| apt.id())
+            .collect();
+        match matches.len() {
+            0 => Err(eyre!("No unit with ID or number `{unit}`")),
+            1 => Ok(matches.into_iter().next().unwrap().to_owned()),
+            _ => Err(eyre!(
+                "Multiple units have number `{unit}`, use an ID instead: {}",
+                itertools::join(matches, ", ")
+            )),
+        }
+    }
+
+    #[tracing::instrument(skip_all, fields(subject = %email.subject))]
     async fn send(&self, email: &jmap::Email) -> eyre::Result<()> {
-        match &self.sending_identity {
-            Some(identity) => email.send(&identity).await,
+        match &self.mailer {
+            Some(mailer) => {
+                email.send(mailer).await?;
+                self.summary.record_notification();
+                metrics::counter!("notifications").increment(1);
+                Ok(())
+            }
             None => Err(eyre!(
                 "No email credentials found, unable to send email: {}",
                 email.subject
@@ -186,135 +1146,1433 @@ impl App {
         }
     }
 
-    /// One 'tick' of the app. Get new apartment data and report changes.
+    /// One 'tick' of the app. Get new apartment data from each source and report changes.
+    ///
+    /// Ends with a summary event on the [`tick_summary::TICK_SUMMARY`] target, even if the tick
+    /// fails.
     #[tracing::instrument(skip(self))]
     async fn tick(&mut self) -> eyre::Result<()> {
-        let diff = self.compute_diff().await?;
+        let started = Instant::now();
+        self.summary = TickSummary::default();
+        if let Err(err) = self.process_email_commands().await {
+            tracing::error!("Failed to process email commands: {err:?}");
+        }
+        let result = self.fetch_and_update().await;
+        let duration = started.elapsed();
+        self.summary.emit(duration, result.as_ref().err());
+        let status = if result.is_ok() { "ok" } else { "error" };
+        metrics::counter!("ticks", "result" => status).increment(1);
+        metrics::histogram!("tick.duration").record(duration.as_secs_f64());
+        result
+    }
 
-        if diff.is_empty() {
-            tracing::debug!(total_available = self.known_apartments.len(), "No news :(");
-        } else {
-            tracing::debug!(
-                total_available = self.known_apartments.len(),
-                added = diff.added.len(),
-                removed = diff.removed.len(),
-                changed = diff.changed.len(),
-                "Data has changed!"
+    async fn fetch_and_update(&mut self) -> eyre::Result<()> {
+        self.prune_events();
+
+        let semaphore = Arc::new(Semaphore::new(self.config.max_concurrent_fetches.max(1)));
+        let mut fetches = JoinSet::new();
+
+        for source in self.config.sources() {
+            let http = self.http.clone();
+            let config = self.config.clone();
+            let semaphore = semaphore.clone();
+            fetches.spawn(
+                async move {
+                    let (listings, latency) = match semaphore.acquire_owned().await {
+                        Ok(_permit) => {
+                            let started = Instant::now();
+                            let listings = source.fetch(&http, &config).await;
+                            (listings, started.elapsed())
+                        }
+                        Err(err) => (Err(err.into()), Duration::ZERO),
+                    };
+                    (source, listings, latency)
+                }
+                .in_current_span(),
             );
+        }
 
-            if !diff.added.is_empty() {
-                tracing::info!(
-                    "Newly listed apartments:\n{}",
-                    to_bullet_list(diff.added.iter())
+        // One broken source shouldn't stop us from reporting on the others; failures are
+        // tracked per-source and alerted on separately.
+        while let Some(joined) = fetches.join_next().await {
+            let (source, listings, latency) = joined.wrap_err("Fetch task panicked")?;
+            self.summary.record_fetch(latency);
+            let url = source.url().to_owned();
+            metrics::histogram!("fetch.duration", "source" => url.clone())
+                .record(latency.as_secs_f64());
+            let status = if listings.is_ok() { "ok" } else { "error" };
+            metrics::counter!("fetches", "source" => url, "result" => status).increment(1);
+            match listings {
+                Ok(listings) => {
+                    let suspect = self.sanity_check(&source, &listings);
+                    let accepted = suspect.is_none() || self.accept_suspect(&source, &listings);
+                    match suspect {
+                        // Treat a suspect scrape like a failed fetch, so we alert if it keeps
+                        // happening rather than notifying about every unit being unlisted.
+                        Some(reason) if !accepted => {
+                            let err = eyre!("Suspect scrape, skipping update: {reason}");
+                            self.summary.failed_sources += 1;
+                            self.record_failure(&source, err).await;
+                        }
+                        _ => {
+                            if let Some(reason) = suspect {
+                                tracing::warn!(
+                                    %source,
+                                    "Accepting suspect scrape, which has been the same {} times \
+                                     in a row: {reason}",
+                                    self.config.sanity_checks.accept_after
+                                );
+                            }
+                            if self.store.suspect_scrapes.contains_key(source.url()) {
+                                self.store_mut().suspect_scrapes.remove(source.url());
+                            }
+                            self.record_success(&source).await;
+                            if let Err(err) = self.update(&source, listings).await {
+                                self.summary.failed_reports += 1;
+                                tracing::error!(%source, "Failed to report changes: {err:?}");
+                            }
+                        }
+                    }
+                }
+                Err(err) => {
+                    self.summary.failed_sources += 1;
+                    self.record_failure(&source, err).await;
+                }
+            }
+        }
+
+        self.save_in_background().await
+    }
+
+    /// Forget events older than `event-retention-days`; see [`ApartmentStore::prune_events`].
+    fn prune_events(&mut self) {
+        let days = self.config.event_retention_days;
+        if days == 0 {
+            return;
+        }
+        let cutoff = Utc::now() - chrono::Duration::days(days.into());
+        let pruned = self.store.prune_events(cutoff);
+        if pruned > 0 {
+            self.generation += 1;
+            tracing::info!(pruned, days, "Forgot old events");
+        }
+    }
+
+    /// Note that `source` was fetched successfully, sending a recovery notice if we'd alerted
+    /// that it was failing.
+    async fn record_success(&mut self, source: &Source) {
+        let threshold = self.config.failure_alert_threshold;
+        if let Some(failures) = self.store.failures.get(source.url()).copied() {
+            self.store_mut().failures.remove(source.url());
+            tracing::info!(%source, failures, "Source recovered");
+            if threshold > 0 && failures >= threshold {
+                self.alert(
+                    format!("Monitoring recovered: {source}"),
+                    format!("{source} is working again after {failures} consecutive failures."),
+                )
+                .await;
+            }
+        }
+    }
+
+    /// Note that `source` failed to fetch, sending a "monitoring is down" alert once it's failed
+    /// `failure_alert_threshold` times in a row.
+    async fn record_failure(&mut self, source: &Source, err: eyre::Report) {
+        let failures = self
+            .store_mut()
+            .failures
+            .entry(source.url().to_owned())
+            .or_default();
+        *failures += 1;
+        let failures = *failures;
+
+        tracing::error!(%source, failures, "Failed to fetch listings: {err:?}");
+        // Only report the start of a streak; the rest are probably the same problem.
+        if failures == 1 {
+            error_reporting::report(&err, &[("source", source.url().to_owned())]);
+        }
+
+        if failures == self.config.failure_alert_threshold {
+            self.alert(
+                format!("Monitoring is down: {source}"),
+                format!(
+                    "Failed to fetch {source} {failures} times in a row. \
+                    You won't hear about new listings from it until this is fixed.\n\n\
+                    {err:?}"
+                ),
+            )
+            .await;
+        }
+    }
+
+    /// Send an alert about the app itself, rather than about apartments.
+    ///
+    /// Errors sending the alert are logged rather than returned.
+    async fn alert(&self, subject: String, body: String) {
+        let email = jmap::Email {
+            to: self
+                .config
+                .alert_to
+                .as_ref()
+                .unwrap_or(&self.config.to)
+                .clone(),
+            subject,
+            body,
+            attachments: Vec::new(),
+            unit: None,
+            cc: Vec::new(),
+            bcc: Vec::new(),
+            reply_to: self.config.reply_to.clone(),
+            send_at: None,
+        };
+        if let Err(err) = self.send(&email).await {
+            tracing::error!("Error sending alert email: {err:?}");
+        }
+    }
+
+    /// Check the `listings` fetched from `source` against what we already know, returning the
+    /// reason they look wrong, if they do. See [`sanity::SanityChecks`].
+    fn sanity_check(&self, source: &Source, listings: &Listings) -> Option<String> {
+        let checks = &self.config.sanity_checks;
+        match listings {
+            Listings::Avalon(apartments) => checks.check(
+                self.store
+                    .known_apartments
+                    .values()
+                    .filter(|apt| apt.listed_in(source.url())),
+                apartments,
+            ),
+            Listings::Craigslist(posts) => checks.check(
+                self.store
+                    .known_posts
+                    .values()
+                    .filter(|post| post.listed_in(source.url())),
+                posts,
+            ),
+        }
+    }
+
+    /// Note that the `listings` fetched from `source` failed [`App::sanity_check`], and decide
+    /// whether to accept them anyway. See [`sanity::SanityChecks::accept`].
+    fn accept_suspect(&mut self, source: &Source, listings: &Listings) -> bool {
+        self.generation += 1;
+        let checks = &self.config.sanity_checks;
+        let suspect = self
+            .store
+            .suspect_scrapes
+            .entry(source.url().to_owned())
+            .or_default();
+        match listings {
+            Listings::Avalon(apartments) => checks.accept(suspect, apartments),
+            Listings::Craigslist(posts) => checks.accept(suspect, posts),
+        }
+    }
+
+    /// Update our data with the `listings` fetched from `source` and report the changes.
+    #[tracing::instrument(skip_all, fields(%source))]
+    async fn update(&mut self, source: &Source, listings: Listings) -> eyre::Result<()> {
+        match listings {
+            Listings::Avalon(apartments) => {
+                self.locate(source).await;
+                self.summary.units_seen += apartments.len();
+                metrics::gauge!("listings", "source" => source.url().to_owned())
+                    .set(apartments.len() as f64);
+                let diff = DiffEngine::new(
+                    self.config.ignore_changes.clone(),
+                    self.config.price_drop.clone(),
+                )
+                .diff(
+                    source.url(),
+                    &mut self.store.known_apartments,
+                    &mut self.store.unlisted_apartments,
+                    apartments,
+                );
+                self.record_diff(&diff);
+                if diff.needs_saving() {
+                    self.generation += 1;
+                }
+                self.store.record_events(diff.events(source.url()));
+                let days_on_market = DaysOnMarket::new(self.store.unlisted_apartments.values());
+                let histories = events::price_histories(&self.store.events);
+                let floor_plan_prices = price_range::by_floor_plan(
+                    self.store
+                        .known_apartments
+                        .values()
+                        .chain(self.store.unlisted_apartments.values()),
+                    &histories,
+                );
+                let forecasts = forecast::by_floor_plan(
+                    &forecast::LinearTrend,
+                    self.store
+                        .known_apartments
+                        .values()
+                        .chain(self.store.unlisted_apartments.values()),
+                    &histories,
+                    Utc::now(),
+                );
+                self.report(
+                    source,
+                    self.store.known_apartments.len(),
+                    diff,
+                    &days_on_market,
+                    &floor_plan_prices,
+                    &forecasts,
+                )
+                .await?;
+                self.track_move_in(source).await?;
+                self.remind_promotions(source).await?;
+                self.remind_availability(source).await
+            }
+            Listings::Craigslist(posts) => {
+                self.summary.units_seen += posts.len();
+                metrics::gauge!("listings", "source" => source.url().to_owned())
+                    .set(posts.len() as f64);
+                let diff = DiffEngine::new(
+                    self.config.ignore_changes.clone(),
+                    self.config.price_drop.clone(),
+                )
+                .diff(
+                    source.url(),
+                    &mut self.store.known_posts,
+                    &mut self.store.unlisted_posts,
+                    posts,
                 );
+                self.record_diff(&diff);
+                if diff.needs_saving() {
+                    self.generation += 1;
+                }
+                self.store.record_events(diff.events(source.url()));
+                let days_on_market = DaysOnMarket::new(self.store.unlisted_posts.values());
+                let histories = events::price_histories(&self.store.events);
+                let floor_plan_prices = price_range::by_floor_plan(
+                    self.store
+                        .known_posts
+                        .values()
+                        .chain(self.store.unlisted_posts.values()),
+                    &histories,
+                );
+                let forecasts = forecast::by_floor_plan(
+                    &forecast::LinearTrend,
+                    self.store
+                        .known_posts
+                        .values()
+                        .chain(self.store.unlisted_posts.values()),
+                    &histories,
+                    Utc::now(),
+                );
+                self.report(
+                    source,
+                    self.store.known_posts.len(),
+                    diff,
+                    &days_on_market,
+                    &floor_plan_prices,
+                    &forecasts,
+                )
+                .await
+            }
+        }
+    }
 
-                for unit in diff.added {
-                    // if unit.meets_qualifications() {}
-                    self.send(&jmap::Email {
-                        to: ("Rebecca Turner", "rbt@fastmail.com").into(),
-                        subject: format!(
-                            "Apartment {} listed, available {}",
-                            unit.number,
-                            unit.available_date.format("%b %e %Y"),
-                        ),
-                        body: format!("{unit}"),
-                    })
-                    .await?;
+    /// Count the changes in `diff` towards the tick summary and metrics.
+    fn record_diff<T>(&mut self, diff: &ApartmentsDiff<T>) {
+        self.summary.added += diff.added.len();
+        self.summary.removed += diff.removed.len();
+        self.summary.changed += diff.changed.len();
+        for (kind, count) in [
+            ("added", diff.added.len()),
+            ("removed", diff.removed.len()),
+            ("changed", diff.changed.len()),
+        ] {
+            metrics::counter!("changes", "kind" => kind).increment(count as u64);
+        }
+    }
+
+    /// Split `items` from `source` into those we should notify about and those we shouldn't,
+    /// according to the configured qualifications, ignored and watched units, and plugin
+    /// `decisions`.
+    fn partition_qualified<I, T: Listing>(
+        &self,
+        items: Vec<I>,
+        source: &Source,
+        decisions: &BTreeMap<String, plugin::Decision>,
+        listing: impl Fn(&I) -> &T,
+    ) -> (Vec<I>, Vec<I>) {
+        let qualifications = &self.config.qualifications;
+        let near_enough = qualifications
+            .check_location(self.store.locations.get(source.url()))
+            .is_none();
+        let affordable = |listing: &T| match (&self.config.affordability, listing.rent()) {
+            (Some(affordability), Some(rent)) if affordability.required => {
+                affordability.check(rent).passes()
+            }
+            _ => true,
+        };
+        items.into_iter().partition(|item| {
+            let listing = listing(item);
+            let decided = decisions
+                .get(listing.id())
+                .and_then(|decision| decision.notify);
+            !self.store.ignored.contains(listing.id())
+                && !self.is_snoozed(listing.id())
+                && decided.unwrap_or_else(|| {
+                    self.store.watched.contains(listing.id())
+                        || self.config.notify_all
+                        || (near_enough
+                            && affordable(listing)
+                            && listing.meets_qualifications(qualifications))
+                })
+        })
+    }
+
+    /// Ask the plugins what to do about each listing in `diff`, by ID.
+    fn plugin_decisions<T: Listing>(
+        &self,
+        source: &Source,
+        diff: &ApartmentsDiff<T>,
+    ) -> BTreeMap<String, plugin::Decision> {
+        if self.plugins.is_empty() {
+            return BTreeMap::new();
+        }
+        let listings = diff
+            .added
+            .iter()
+            .map(|unit| (EventKind::Listed, unit))
+            .chain(
+                diff.removed
+                    .iter()
+                    .map(|unit| (EventKind::Unlisted, &unit.inner)),
+            )
+            .chain(
+                diff.changed
+                    .iter()
+                    .map(|changed| (EventKind::Changed, &changed.new)),
+            );
+        listings
+            .map(|(kind, listing)| {
+                let event = events::Event::new(kind, source.url(), listing);
+                (
+                    listing.id().to_owned(),
+                    self.plugins.decide(&event, listing),
+                )
+            })
+            .collect()
+    }
+
+    /// Geocode the community at `source`, if geocoding is configured and we don't know where it
+    /// is yet. Failures are logged and retried next time.
+    async fn locate(&mut self, source: &Source) {
+        let (config, geocoder) = match (&self.config.geocoding, &self.geocoder) {
+            (Some(config), Some(geocoder)) => (config, geocoder),
+            _ => return,
+        };
+        if !matches!(source, Source::Avalon(_)) || self.store.locations.contains_key(source.url()) {
+            return;
+        }
+        let address = match config.address(source.url()) {
+            Some(address) => address,
+            None => {
+                tracing::debug!(%source, "Don't know the community's address");
+                return;
+            }
+        };
+        match geocoder.geocode(&address).await {
+            Ok(Some(location)) => {
+                tracing::info!(%source, address, ?location, "Geocoded community");
+                self.store_mut()
+                    .locations
+                    .insert(source.url().to_owned(), location);
+            }
+            Ok(None) => tracing::warn!(%source, address, "Couldn't find community's address"),
+            Err(err) => tracing::warn!(%source, address, "Failed to geocode community: {err:?}"),
+        }
+    }
+
+    /// A line linking to the community at `source` on a map, if we know where it is.
+    fn map_link(&self, source: &Source) -> String {
+        let map = self
+            .config
+            .geocoding
+            .as_ref()
+            .map(|config| config.map)
+            .unwrap_or_default();
+        self.store
+            .locations
+            .get(source.url())
+            .map(|location| format!("\nMap: {}", location.map_url(map)))
+            .unwrap_or_default()
+    }
+
+    /// When to deliver a non-urgent email composed now, according to the `quiet-hours`.
+    fn quiet_send_at(&self) -> Option<DateTime<Utc>> {
+        self.config
+            .quiet_hours
+            .as_ref()
+            .and_then(|quiet_hours| quiet_hours.send_at(Utc::now(), self.config.timezone))
+    }
+
+    /// Whether the unit with ID `id` has been snoozed, and the snooze hasn't run out.
+    fn is_snoozed(&self, id: &str) -> bool {
+        self.store
+            .snoozed
+            .get(id)
+            .map_or(false, |until| *until > Utc::now())
+    }
+
+    /// Apply commands from replies to notification emails; see [`email_commands`].
+    async fn process_email_commands(&mut self) -> eyre::Result<()> {
+        let mailer = match &self.mailer {
+            Some(mailer) if self.config.jmap.commands_mailbox.is_some() => mailer,
+            _ => return Ok(()),
+        };
+        let now = Utc::now();
+        let mut commands = Vec::new();
+        for reply in mailer.replies().await? {
+            let unit = reply
+                .in_reply_to
+                .iter()
+                .find_map(|id| jmap::unit_from_message_id(id))
+                .map(ToOwned::to_owned);
+            let allowed = std::iter::once(&self.config.to).chain(&self.config.watch_to);
+            if !email_commands::is_authorized(&reply.from, allowed) {
+                tracing::warn!(from = ?reply.from, "Ignoring email command from unknown sender");
+            } else if let Some(unit) = unit {
+                match email_commands::EmailCommand::from_reply(&reply.text) {
+                    Ok(command) => commands.push((unit, command)),
+                    Err(err) => tracing::warn!(unit, "Invalid email command: {err}"),
                 }
+            } else {
+                tracing::debug!("Reply isn't about a unit, skipping");
             }
+            // Mark even invalid commands as processed, so we only warn about them once.
+            mailer.mark_processed(&reply.id).await?;
+        }
 
-            if !diff.removed.is_empty() {
-                tracing::info!(
-                    "Unlisted apartments:\n{}",
-                    to_bullet_list(diff.removed.iter())
-                );
+        let changed = !commands.is_empty();
+        for (unit, command) in commands {
+            self.apply_email_command(unit, command, now);
+        }
+        let snoozed = self.store.snoozed.len();
+        if self.store.snoozed.values().any(|until| *until <= now) {
+            self.store_mut().snoozed.retain(|_, until| *until > now);
+        }
+        if changed || self.store.snoozed.len() != snoozed {
+            self.save_in_background().await?;
+        }
+        Ok(())
+    }
 
-                for unit in diff.removed {
-                    self.send(&jmap::Email {
-                        to: ("Rebecca Turner", "rbt@fastmail.com").into(),
-                        subject: format!("Apartment {} no longer available!", unit.inner.number),
-                        body: format!("{unit}\nTracked since: {}", unit.listed),
-                    })
-                    .await?;
+    fn apply_email_command(
+        &mut self,
+        unit: String,
+        command: email_commands::EmailCommand,
+        now: DateTime<Utc>,
+    ) {
+        use email_commands::EmailCommand;
+        let store = self.store_mut();
+        match command {
+            EmailCommand::Ignore => {
+                tracing::info!("Ignoring {unit}, by email");
+                store.ignored.insert(unit);
+            }
+            EmailCommand::Unignore => {
+                tracing::info!("No longer ignoring {unit}, by email");
+                store.ignored.remove(&unit);
+            }
+            EmailCommand::Watch => {
+                tracing::info!("Watching {unit}, by email");
+                if store.watched.insert(unit.clone()) {
+                    store.watched_since.insert(unit, now);
                 }
             }
+            EmailCommand::Unwatch => {
+                tracing::info!("No longer watching {unit}, by email");
+                store.watched.remove(&unit);
+            }
+            EmailCommand::Snooze(duration) => {
+                let until = now + duration;
+                tracing::info!(%until, "Snoozing {unit}, by email");
+                store.snoozed.insert(unit, until);
+            }
+        }
+    }
+
+    /// Post about `kind` happening to `listing` on the configured social media accounts, if it
+    /// meets the qualifications. Watched units are private, so they aren't posted otherwise.
+    async fn post_social(&self, kind: EventKind, listing: &impl Listing, source: &Source) {
+        if let Some(social) = &self.social {
+            if listing.meets_qualifications(&self.config.qualifications) {
+                social.post(kind, listing, source.url()).await;
+            }
+        }
+    }
 
-            if !diff.changed.is_empty() {
-                tracing::info!(
-                    "Changed apartments:\n{}",
-                    to_bullet_list(diff.changed.iter().map(|c| c.new.clone()))
-                );
+    /// Who to notify about the unit with ID `id`, if it's watched and `watch-to` is configured.
+    fn watch_recipient(&self, id: &str) -> Option<&EmailAddress> {
+        self.config
+            .watch_to
+            .as_ref()
+            .filter(|_| self.store.watched.contains(id))
+    }
+
+    /// Who to notify about the unit with ID `id`.
+    fn recipient(&self, id: &str) -> EmailAddress {
+        self.watch_recipient(id).unwrap_or(&self.config.to).clone()
+    }
+
+    /// Track the cheapest date in the `move-in` window for each qualified unit from `source`,
+    /// and send an alert when it moves.
+    async fn track_move_in(&mut self, source: &Source) -> eyre::Result<()> {
+        let window = match &self.config.move_in {
+            Some(window) => window,
+            None => return Ok(()),
+        };
+        let units = self
+            .store
+            .known_apartments
+            .values()
+            .filter(|apt| apt.listed_in(source.url()))
+            .collect();
+        let (qualified, _) =
+            self.partition_qualified(units, source, &BTreeMap::new(), |apt| &apt.inner);
+
+        let mut best_move_in = BTreeMap::new();
+        let mut moved = Vec::new();
+        for apt in qualified {
+            let best = match window.best(apt.inner.lease_options()) {
+                Some(best) => best,
+                None => continue,
+            };
+            if let Some(old) = self.store.best_move_in.get(apt.id()) {
+                if old.move_in != best.move_in {
+                    moved.push((apt.inner.clone(), *old, best));
+                }
             }
+            best_move_in.insert(apt.id().to_owned(), best);
         }
 
-        let data_file =
-            File::create(&DATA_PATH).wrap_err_with(|| format!("Failed to open {DATA_PATH:?}"))?;
-        serde_json::to_writer_pretty(BufWriter::new(data_file), self)
-            .wrap_err("Failed to write DB")?;
+        // Forget units from this source which are gone or no longer qualify.
+        let known = &self.store.known_apartments;
+        let mut kept = self.store.best_move_in.clone();
+        kept.retain(|id, _| {
+            known
+                .get(id)
+                .map_or(false, |apt| !apt.listed_in(source.url()))
+        });
+        kept.extend(best_move_in);
+        if kept != self.store.best_move_in {
+            self.store_mut().best_move_in = kept;
+        }
 
+        for (unit, old, best) in moved {
+            tracing::info!(id = unit.id(), %old, %best, "Cheapest move-in date changed");
+            self.send(&jmap::Email {
+                to: self.recipient(unit.id()),
+                subject: format!(
+                    "Apartment {} is now cheapest moving in {}",
+                    unit.number,
+                    best.move_in.format("%b %e %Y")
+                ),
+                body: format!(
+                    "{unit}\n{}\n\nCheapest move-in: {best}\nPreviously: {old}",
+                    unit.url().unwrap_or_else(|| source.url().to_owned())
+                ),
+                attachments: Vec::new(),
+                unit: Some(unit.id().to_owned()),
+                cc: self.config.cc.clone(),
+                bcc: self.config.bcc.clone(),
+                reply_to: self.config.reply_to.clone(),
+                send_at: self.quiet_send_at(),
+            })
+            .await?;
+        }
         Ok(())
     }
 
-    /// Fetch new apartment data, update `known_apartments` to include it, and return the
-    /// changes with the previous `known_apartments`.
-    #[tracing::instrument(skip(self))]
-    async fn compute_diff(&mut self) -> eyre::Result<ApartmentsDiff> {
-        let new_data = get_apartments().await?;
-        let mut diff = ApartmentsDiff::default();
-        // A clone of `known_apartments`. We remove each apartment in the _new_
-        // data from this map to compute the set of apartments present in the previous
-        // data and not present now; that is, the set of apartments that have been
-        // _unlisted_.
-        let mut removed: BTreeMap<_, _> = std::mem::take(&mut self.known_apartments);
-
-        for mut apt in new_data.apartments {
-            // Did we have any data for this apartment already?
-            // Remember we have the old apartments (minus the ones we've already seen
-            // in the new data) in `removed`.
-            match removed.remove(apt.id()) {
-                Some(known_unit) => {
-                    // This apartment wasn't listed now, so copy the listed
-                    // time from the old data, as the
-                    // `impl TryFrom<api::ApartmentData> for api::ApartmentData`
-                    // just... inserts the current time!
-                    apt.listed = known_unit.listed;
-                    // apt.history.extend(known_unit.history);
-                    // We already have data for an apartment with the same `unit_id`.
-                    if &apt.inner != &known_unit.inner {
-                        // It's different data! Show what changed.
-                        let changed = ChangedApartment {
-                            old: known_unit.inner.clone(),
-                            new: apt.inner.clone(),
-                        };
-                        // Mark this apartment as changed.
-                        diff.changed.push(changed);
-                    }
-                    // No new data.
+    /// Send a reminder for each promotion on a qualified or watched unit from `source` ending
+    /// within `promotion-reminder-days`.
+    async fn remind_promotions(&mut self, source: &Source) -> eyre::Result<()> {
+        let days = match self.config.promotion_reminder_days {
+            Some(days) => days,
+            None => return Ok(()),
+        };
+        let today = self.config.timezone.naive_local(Utc::now()).date();
+        if self
+            .store
+            .promotion_reminders
+            .values()
+            .any(|end| *end < today)
+        {
+            self.store_mut()
+                .promotion_reminders
+                .retain(|_, end| *end >= today);
+        }
+
+        let units = self
+            .store
+            .known_apartments
+            .values()
+            .filter(|apt| apt.listed_in(source.url()))
+            .collect();
+        let (qualified, _) =
+            self.partition_qualified(units, source, &BTreeMap::new(), |apt| &apt.inner);
+        let mut due = Vec::new();
+        for apt in qualified {
+            for (promotion, title, end) in apt.inner.expiring_promotions() {
+                let key = reminders::key(apt.id(), promotion, end);
+                if reminders::is_due(end, today, days)
+                    && !self.store.promotion_reminders.contains_key(&key)
+                {
+                    due.push((key, apt.inner.clone(), title.to_owned(), end));
                 }
-                None => {
-                    // A new apartment!!!
-                    diff.added.push(apt.inner.clone());
+            }
+        }
+
+        for (key, unit, title, end) in due {
+            let ends = reminders::ends(end, today);
+            tracing::info!(id = unit.id(), title, %end, "Promotion ending soon");
+            self.send(&jmap::Email {
+                to: self.recipient(unit.id()),
+                subject: format!("{title} on apartment {} ends {ends}", unit.number),
+                body: format!(
+                    "{unit}\n{}\n\n{title} ends {}.",
+                    unit.url().unwrap_or_else(|| source.url().to_owned()),
+                    end.format("%a %b %e %Y")
+                ),
+                attachments: Vec::new(),
+                unit: Some(unit.id().to_owned()),
+                cc: self.config.cc.clone(),
+                bcc: self.config.bcc.clone(),
+                reply_to: self.config.reply_to.clone(),
+                send_at: self.quiet_send_at(),
+            })
+            .await?;
+            self.store_mut().promotion_reminders.insert(key, end);
+        }
+        Ok(())
+    }
+
+    /// Send a reminder for each watched unit from `source` available within
+    /// `availability-reminder-days`, with its rent and how it's changed since we started
+    /// watching it.
+    async fn remind_availability(&mut self, source: &Source) -> eyre::Result<()> {
+        let now = Utc::now();
+        // Units watched from the TUI, or before we kept track, count from now.
+        if !self.store.watched_since.keys().eq(&self.store.watched) {
+            let store = self.store_mut();
+            store
+                .watched_since
+                .retain(|id, _| store.watched.contains(id));
+            for id in &store.watched {
+                store.watched_since.entry(id.clone()).or_insert(now);
+            }
+        }
+
+        let days = match self.config.availability_reminder_days {
+            Some(days) => days,
+            None => return Ok(()),
+        };
+        let today = self.config.timezone.naive_local(now).date();
+        let store = &self.store;
+        let due: Vec<_> = store
+            .known_apartments
+            .values()
+            .filter(|apt| apt.listed_in(source.url()) && store.watched.contains(apt.id()))
+            .filter(|apt| !self.is_snoozed(apt.id()))
+            .filter_map(|apt| {
+                let available = apt.inner.available_date()?;
+                let due = reminders::is_due(available, today, days)
+                    && store.availability_reminders.get(apt.id()) != Some(&available);
+                due.then(|| (apt.inner.clone(), available))
+            })
+            .collect();
+
+        for (unit, available) in due {
+            let rent = unit.rent();
+            let watched = self.store.watched_since.get(unit.id()).copied();
+            let watched_rent = watched.and_then(|watched| {
+                events::price_history(&self.store.events, unit.id())
+                    .into_iter()
+                    .take_while(|(time, _)| *time <= watched)
+                    .last()
+                    .map(|(_, rent)| rent)
+            });
+            let rent = match (rent, watched_rent, watched) {
+                (Some(rent), Some(watched_rent), Some(watched)) => format!(
+                    "Rent is {rent}, {}.",
+                    reminders::since_watching(rent, watched_rent, watched)
+                ),
+                (Some(rent), _, _) => format!("Rent is {rent}."),
+                (None, _, _) => String::new(),
+            };
+            let available_in = reminders::ends(available, today);
+            tracing::info!(id = unit.id(), %available, "Watched unit available soon");
+            self.send(&jmap::Email {
+                to: self.recipient(unit.id()),
+                subject: format!("Apartment {} is available {available_in}", unit.number),
+                body: format!(
+                    "{unit}\n{}\n\nAvailable {}. {rent}",
+                    unit.url().unwrap_or_else(|| source.url().to_owned()),
+                    available.format("%a %b %e %Y")
+                ),
+                attachments: Vec::new(),
+                unit: Some(unit.id().to_owned()),
+                cc: self.config.cc.clone(),
+                bcc: self.config.bcc.clone(),
+                reply_to: self.config.reply_to.clone(),
+                send_at: self.quiet_send_at(),
+            })
+            .await?;
+            self.store_mut()
+                .availability_reminders
+                .insert(unit.id().to_owned(), available);
+        }
+        Ok(())
+    }
+
+    /// Log the changes in `diff` and send notifications for them.
+    ///
+    /// New listings mention the typical time on market for similar units, from `days_on_market`.
+    /// New and changed listings mention the lowest and highest rents seen for the unit and its
+    /// floor plan, from `floor_plan_prices`, and an estimate of where the floor plan's rents are
+    /// heading, from `forecasts`. Qualified listings are also posted to social media, linking to
+    /// `source` if they don't have their own page.
+    #[tracing::instrument(skip_all)]
+    async fn report<T: Listing>(
+        &self,
+        source: &Source,
+        total_available: usize,
+        diff: ApartmentsDiff<T>,
+        days_on_market: &DaysOnMarket,
+        floor_plan_prices: &BTreeMap<String, PriceRange>,
+        forecasts: &BTreeMap<String, Forecast>,
+    ) -> eyre::Result<()> {
+        if diff.is_empty() {
+            tracing::debug!(total_available, "No news :(");
+            return Ok(());
+        }
+
+        tracing::debug!(
+            total_available,
+            added = diff.added.len(),
+            removed = diff.removed.len(),
+            changed = diff.changed.len(),
+            "Data has changed!"
+        );
+
+        // Only qualified apartments are worth notifying about, but we log everything (including
+        // ignored units) on the `everything` target in case the qualifications are too strict.
+        let decisions = self.plugin_decisions(source, &diff);
+        let (mut added, unqualified_added) =
+            self.partition_qualified(diff.added, source, &decisions, |unit| unit);
+        let (removed, unqualified_removed) =
+            self.partition_qualified(diff.removed, source, &decisions, |unit| &unit.inner);
+        let (changed, unqualified_changed) =
+            self.partition_qualified(diff.changed, source, &decisions, |changed| &changed.new);
+        let map_link = self.map_link(source);
+        // Extra text from plugins for the notification about the listing with ID `id`.
+        let plugin_text = |id: &str| {
+            decisions
+                .get(id)
+                .and_then(|decision| decision.text.as_deref())
+                .map(|text| format!("\n\n{text}"))
+                .unwrap_or_default()
+        };
+
+        // Units without their own page link to the page they were listed on.
+        let page = |listing: &T| listing.url().unwrap_or_else(|| source.url().to_owned());
+        let link = |listing: &T, text: &dyn Display| color::link(text, &page(listing));
+        // Lines about the cost of renting `listing`, for its notification.
+        let rent_notes = |listing: &T| {
+            let best_deal = listing.best_deal().map(|deal| format!("Best deal: {deal}"));
+            let best_move_in = self
+                .config
+                .move_in
+                .as_ref()
+                .and_then(|window| window.best(listing.lease_options()))
+                .map(|best| format!("Cheapest move-in: {best}"));
+            let affordability = self
+                .config
+                .affordability
+                .as_ref()
+                .zip(listing.rent())
+                .map(|(affordability, rent)| affordability.check(rent).to_string());
+            best_deal
+                .into_iter()
+                .chain(best_move_in)
+                .chain(affordability)
+                .chain(forecast::describe(listing, forecasts))
+                .map(|note| format!("\n{note}"))
+                .collect::<String>()
+        };
+
+        if !unqualified_added.is_empty() {
+            tracing::debug!(
+                target: EVERYTHING,
+                "Newly listed unqualified apartments:\n{}",
+                to_bullet_list(unqualified_added.iter().map(|unit| link(unit, unit)))
+            );
+        }
+
+        if !unqualified_removed.is_empty() {
+            tracing::debug!(
+                target: EVERYTHING,
+                "Unlisted unqualified apartments:\n{}",
+                to_bullet_list(unqualified_removed.iter().map(|unit| link(&unit.inner, unit)))
+            );
+        }
+
+        if !unqualified_changed.is_empty() {
+            tracing::debug!(
+                target: EVERYTHING,
+                "Changed unqualified apartments:\n{}",
+                to_bullet_list(unqualified_changed.iter().map(|c| link(&c.new, &c.new)))
+            );
+        }
+
+        let weights = &self.config.score;
+        let today = Utc::now().naive_utc().date();
+        let score = |unit: &T| {
+            decisions
+                .get(unit.id())
+                .and_then(|decision| decision.score)
+                .unwrap_or_else(|| weights.score(unit, today))
+        };
+        score::sort_by(&mut added, score);
+
+        // Watched units are notified about any change; others only about price drops.
+        let notify_change = |changed: &ChangedApartment<T>| {
+            changed.price_drop(&self.config.price_drop).is_some()
+                || self.store.watched.contains(changed.new.id())
+        };
+        // Units going to `watch-to` always get their own emails, so they're left out of bursts.
+        let to_main = |id: &str| self.watch_recipient(id).is_none();
+        let burst_added: Vec<_> = added.iter().filter(|unit| to_main(unit.id())).collect();
+        let burst_removed: Vec<_> = removed.iter().filter(|unit| to_main(unit.id())).collect();
+        let burst_changed: Vec<_> = changed
+            .iter()
+            .filter(|changed| notify_change(changed) && to_main(changed.new.id()))
+            .collect();
+        let burst_size = burst_added.len() + burst_removed.len() + burst_changed.len();
+        let burst = self
+            .config
+            .burst_threshold
+            .map_or(false, |threshold| burst_size > threshold);
+        if burst {
+            tracing::info!(burst_size, "Summarizing burst of notifications");
+            let section = |title: &str, lines: Vec<String>| {
+                if lines.is_empty() {
+                    String::new()
+                } else {
+                    format!("{title}:\n{}\n\n", itertools::join(lines, "\n"))
                 }
+            };
+            let bullet = |text: &dyn Display, listing: &T| {
+                format!(
+                    "• {}\n  {}",
+                    text.to_string().replace('\n', "\n  "),
+                    page(listing)
+                )
+            };
+            let subject = [
+                (burst_added.len(), "new"),
+                (burst_removed.len(), "unlisted"),
+                (burst_changed.len(), "changed"),
+            ]
+            .into_iter()
+            .filter(|(count, _)| *count > 0)
+            .map(|(count, what)| format!("{count} {what}"));
+            // Price drops are urgent; the rest can wait for the morning.
+            let any_drops = burst_changed
+                .iter()
+                .any(|changed| changed.price_drop(&self.config.price_drop).is_some());
+            self.send(&jmap::Email {
+                to: self.config.to.clone(),
+                subject: format!("Listing updates: {}", itertools::join(subject, ", ")),
+                body: format!(
+                    "{}{}{}",
+                    section(
+                        "New",
+                        burst_added.iter().map(|unit| bullet(unit, unit)).collect()
+                    ),
+                    section(
+                        "Unlisted",
+                        burst_removed
+                            .iter()
+                            .map(|unit| bullet(unit, &unit.inner))
+                            .collect()
+                    ),
+                    section(
+                        "Changed",
+                        burst_changed
+                            .iter()
+                            .map(|changed| bullet(changed, &changed.new))
+                            .collect()
+                    ),
+                )
+                .trim_end()
+                .to_owned(),
+                attachments: Vec::new(),
+                unit: None,
+                cc: self.config.cc.clone(),
+                bcc: self.config.bcc.clone(),
+                reply_to: self.config.reply_to.clone(),
+                send_at: if any_drops {
+                    None
+                } else {
+                    self.quiet_send_at()
+                },
+            })
+            .await?;
+        }
+
+        if !added.is_empty() {
+            tracing::info!(
+                "Newly listed apartments:\n{}",
+                to_bullet_list(added.iter().map(|unit| link(unit, unit)))
+            );
+
+            let (watched, added): (Vec<_>, Vec<_>) = added
+                .into_iter()
+                .partition(|unit| self.store.watched.contains(unit.id()));
+
+            let send_digest = !burst && self.config.digest && watched.len() + added.len() > 1;
+            if send_digest {
+                let section = |title: &str, units: &[T]| {
+                    if units.is_empty() {
+                        return String::new();
+                    }
+                    let units = itertools::join(
+                        units.iter().map(|unit| {
+                            let virtual_tour = match unit.virtual_tour_url() {
+                                Some(url) => format!("\n  Virtual tour: {url}"),
+                                None => String::new(),
+                            };
+                            let typical = match days_on_market.typical(unit) {
+                                Some(typical) => format!("\n  Typical time on market: {typical}"),
+                                None => String::new(),
+                            };
+                            let rent_notes = rent_notes(unit).replace('\n', "\n  ");
+                            let text = match decisions.get(unit.id()).and_then(|d| d.text.as_ref())
+                            {
+                                Some(text) => format!("\n  {}", text.replace('\n', "\n  ")),
+                                None => String::new(),
+                            };
+                            format!(
+                                "• {unit}\n  {}\n  Score: {:.1}{rent_notes}{typical}{virtual_tour}{text}",
+                                page(unit),
+                                score(unit)
+                            )
+                        }),
+                        "\n",
+                    );
+                    format!("{title}:\n{units}\n\n")
+                };
+                self.send(&jmap::Email {
+                    to: self.config.to.clone(),
+                    subject: format!("{} new listings", watched.len() + added.len()),
+                    body: format!("{}{}", section("Watched", &watched), section("New", &added))
+                        .trim_end()
+                        .to_owned(),
+                    attachments: Vec::new(),
+                    unit: None,
+                    cc: self.config.cc.clone(),
+                    bcc: self.config.bcc.clone(),
+                    reply_to: self.config.reply_to.clone(),
+                    send_at: self.quiet_send_at(),
+                })
+                .await?;
             }
 
-            // Update our data.
-            self.known_apartments.insert(apt.id().to_owned(), apt);
+            for unit in watched.iter().chain(&added) {
+                self.post_social(EventKind::Listed, unit, source).await;
+                // Units in the digest only get their own email if they're going somewhere else.
+                let to = match self.watch_recipient(unit.id()) {
+                    Some(watch_to) => watch_to.clone(),
+                    None if send_digest || burst => continue,
+                    None => self.config.to.clone(),
+                };
+                self.send(&jmap::Email {
+                    to,
+                    subject: unit.listed_subject(),
+                    body: format!(
+                        "{}{unit}\n{}\nScore: {:.1}{}{map_link}{}{}{}",
+                        unit.virtual_tour_url()
+                            .map(|url| format!("Actual-unit virtual tour available: {url}\n\n"))
+                            .unwrap_or_default(),
+                        page(unit),
+                        score(unit),
+                        rent_notes(unit),
+                        days_on_market
+                            .typical(unit)
+                            .map(|typical| format!("\nTypical time on market: {typical}"))
+                            .unwrap_or_default(),
+                        price_range::describe(unit, &self.store.events, floor_plan_prices)
+                            .map(|prices| format!("\n\n{prices}"))
+                            .unwrap_or_default(),
+                        plugin_text(unit.id()),
+                    ),
+                    attachments: Vec::new(),
+                    unit: Some(unit.id().to_owned()),
+                    cc: self.config.cc.clone(),
+                    bcc: self.config.bcc.clone(),
+                    reply_to: self.config.reply_to.clone(),
+                    send_at: None,
+                })
+                .await?;
+            }
         }
 
-        for (_, mut unit) in removed.iter_mut() {
-            unit.unlisted = Some(Utc::now());
+        if !removed.is_empty() {
+            tracing::info!(
+                "Unlisted apartments:\n{}",
+                to_bullet_list(removed.iter().map(|unit| link(&unit.inner, unit)))
+            );
+
+            for unit in removed {
+                self.post_social(EventKind::Unlisted, &unit.inner, source)
+                    .await;
+                if burst && to_main(unit.id()) {
+                    continue;
+                }
+                self.send(&jmap::Email {
+                    to: self.recipient(unit.id()),
+                    subject: unit.inner.unlisted_subject(),
+                    body: format!(
+                        "{unit}\n{}\nTracked since: {}{map_link}{}",
+                        page(&unit.inner),
+                        self.config
+                            .timezone
+                            .format(unit.listed, "%b %e %Y %H:%M %Z"),
+                        plugin_text(unit.id()),
+                    ),
+                    attachments: Vec::new(),
+                    unit: Some(unit.id().to_owned()),
+                    cc: self.config.cc.clone(),
+                    bcc: self.config.bcc.clone(),
+                    reply_to: self.config.reply_to.clone(),
+                    send_at: self.quiet_send_at(),
+                })
+                .await?;
+            }
         }
 
-        diff.removed
-            .extend(removed.iter().map(|(_, unit)| unit.clone()));
+        if !changed.is_empty() {
+            tracing::info!(
+                "Changed apartments:\n{}",
+                to_bullet_list(changed.iter().map(|c| link(&c.new, &c.new)))
+            );
+
+            for changed in changed {
+                self.post_social(EventKind::Changed, &changed.new, source)
+                    .await;
+                let drop = changed.price_drop(&self.config.price_drop);
+                if let Some(drop) = drop {
+                    tracing::info!(%drop, "Rent dropped: {}", link(&changed.new, &changed.new));
+                }
+                if !notify_change(&changed) || burst && to_main(changed.new.id()) {
+                    continue;
+                }
+                let subject = match drop {
+                    Some(drop) => changed.new.price_drop_subject(&drop),
+                    None => changed.new.changed_subject(),
+                };
+                // Price drops are urgent; other changes can wait for the morning.
+                let send_at = match drop {
+                    Some(_) => None,
+                    None => self.quiet_send_at(),
+                };
+                self.send(&jmap::Email {
+                    to: self.recipient(changed.new.id()),
+                    subject,
+                    body: match price_range::describe(
+                        &changed.new,
+                        &self.store.events,
+                        floor_plan_prices,
+                    ) {
+                        Some(prices) => format!(
+                            "{changed}\n{}{}{map_link}\n\n{prices}",
+                            page(&changed.new),
+                            rent_notes(&changed.new)
+                        ),
+                        None => format!(
+                            "{changed}\n{}{}{map_link}",
+                            page(&changed.new),
+                            rent_notes(&changed.new)
+                        ),
+                    } + &plugin_text(changed.new.id()),
+                    attachments: chart::attachment(
+                        changed.new.id(),
+                        &events::price_history(&self.store.events, changed.new.id()),
+                    )
+                    .into_iter()
+                    .collect(),
+                    unit: Some(changed.new.id().to_owned()),
+                    cc: self.config.cc.clone(),
+                    bcc: self.config.bcc.clone(),
+                    reply_to: self.config.reply_to.clone(),
+                    send_at,
+                })
+                .await?;
+            }
+        }
 
-        // Note when each apartment was unlisted.
-        self.unlisted_apartments.extend(removed.into_iter());
+        Ok(())
+    }
+}
 
-        Ok(diff)
+/// Write `store` to `path`, unless it's the same as when it was last written, according to the
+/// hash in `last_saved`. Returns whether it was written.
+fn save_if_changed(
+    store: &ApartmentStore,
+    path: &Path,
+    last_saved: &std::sync::Mutex<Option<u64>>,
+) -> eyre::Result<bool> {
+    let data = store.to_json()?;
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    data.hash(&mut hasher);
+    let hash = hasher.finish();
+    let mut last_saved = last_saved
+        .lock()
+        .map_err(|_| eyre!("DB write lock poisoned"))?;
+    if *last_saved == Some(hash) && path.exists() {
+        tracing::debug!(?path, "DB unchanged, not writing");
+        return Ok(false);
     }
+    store::write(&data, path)?;
+    *last_saved = Some(hash);
+    Ok(true)
+}
+
+/// `listing` as a [`color::link`] to its page, or the page it was listed on if it doesn't
+/// have its own.
+fn listing_link<T: Listing + Display>(listing: &api::Apartment<T>) -> String {
+    color::link(
+        listing,
+        &listing
+            .inner
+            .url()
+            .unwrap_or_else(|| listing.source.clone()),
+    )
 }
 
 fn to_bullet_list(iter: impl Iterator<Item = impl Display>) -> String {
     itertools::join(iter.map(|unit| format!("• {unit}")), "\n")
 }
+
+#[cfg(test)]
+mod tests {
+    use api::fixtures::apartment_731;
+    use api::fixtures::apartment_731_at;
+    use api::fixtures::apartment_731_available;
+    use chrono::TimeZone;
+    use expect_test::expect;
+    use expect_test::Expect;
+
+    use super::*;
+
+    /// Fetch a recorded Craigslist search, diff it against an empty DB, and send the
+    /// notifications to a mock JMAP server.
+    #[tokio::test]
+    async fn test_notify_pipeline() {
+        let server = mock_jmap::MockJmap::start().await;
+        let mut app = App {
+            config: Config {
+                communities: Vec::new(),
+                craigslist: vec![
+                    "https://seattle.craigslist.org/search/apa?query=capitol+hill".to_owned(),
+                ],
+                fixtures: Some(http::Fixtures::Replay(
+                    concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures").into(),
+                )),
+                jmap: server.config(),
+                notify_all: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        app.connect().await.unwrap();
+
+        for source in app.config.sources() {
+            let listings = source.fetch(&app.http, &app.config).await.unwrap();
+            app.update(&source, listings).await.unwrap();
+        }
+
+        assert_eq!(app.store.known_posts.len(), 3);
+        let mut subjects = server
+            .emails()
+            .iter()
+            .map(|email| email["subject"].as_str().unwrap().to_owned())
+            .collect::<Vec<_>>();
+        subjects.sort();
+        assert_eq!(subjects.len(), 3, "{subjects:?}");
+        assert!(subjects
+            .iter()
+            .all(|subject| subject.contains("Capitol Hill")));
+        assert_eq!(server.submissions().len(), 3);
+    }
+
+    /// Start tracking unit 731, listed three days ago and watched so any change to it is
+    /// notified about, then update it to `listings` and check the one email that's sent.
+    ///
+    /// Times which change from run to run are replaced with `[listed]` and `[today]`. Run the
+    /// tests with `UPDATE_EXPECT=1` to update the snapshots after changing an email.
+    async fn check_notification(listings: Vec<api::ApiApartment>, expected: Expect) {
+        let server = mock_jmap::MockJmap::start().await;
+        let source = Source::Avalon(AVA_URL.to_owned());
+        let listed = Utc::now() - chrono::Duration::days(3);
+        let mut known = api::Apartment::new(AVA_URL, apartment_731());
+        known.listed = listed;
+        let mut app = App {
+            config: Config {
+                jmap: server.config(),
+                ..Default::default()
+            },
+            store: ApartmentStore {
+                known_apartments: [(known.id().to_owned(), known)].into_iter().collect(),
+                watched: [apartment_731().unit_id].into_iter().collect(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        app.connect().await.unwrap();
+
+        let listings = listings
+            .into_iter()
+            .map(|apartment| api::Apartment::new(AVA_URL, apartment))
+            .collect();
+        app.update(&source, Listings::Avalon(listings))
+            .await
+            .unwrap();
+
+        let emails = server.emails();
+        assert_eq!(emails.len(), 1, "{emails:#?}");
+        let email = &emails[0];
+        let body = format!(
+            "Subject: {}\n\n{}",
+            email["subject"].as_str().unwrap(),
+            email["bodyValues"]["text"]["value"].as_str().unwrap()
+        )
+        .replace(
+            &timezone::DisplayTimeZone::Local.format(listed, "%b %e %Y %H:%M %Z"),
+            "[listed]",
+        )
+        .replace(&Utc::now().format("%b %e %Y").to_string(), "[today]");
+        expected.assert_eq(&body);
+    }
+
+    /// With a burst threshold of 1, a new unit and a change to a watched unit are summarized in
+    /// one email.
+    #[tokio::test]
+    async fn test_burst_email() {
+        let server = mock_jmap::MockJmap::start().await;
+        let source = Source::Avalon(AVA_URL.to_owned());
+        let known = api::Apartment::new(AVA_URL, apartment_731());
+        let mut app = App {
+            config: Config {
+                jmap: server.config(),
+                notify_all: true,
+                burst_threshold: Some(1),
+                ..Default::default()
+            },
+            store: ApartmentStore {
+                known_apartments: [(known.id().to_owned(), known)].into_iter().collect(),
+                watched: [apartment_731().unit_id].into_iter().collect(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        app.connect().await.unwrap();
+
+        let mut new = apartment_731();
+        new.unit_id = "AVB-WA026-001-732".to_owned();
+        new.number = "732".to_owned();
+        let listings = [apartment_731_at(4100.0), new]
+            .into_iter()
+            .map(|apartment| api::Apartment::new(AVA_URL, apartment))
+            .collect();
+        app.update(&source, Listings::Avalon(listings))
+            .await
+            .unwrap();
+
+        let emails = server.emails();
+        assert_eq!(emails.len(), 1, "{emails:#?}");
+        let email = &emails[0];
+        let body = format!(
+            "Subject: {}\n\n{}",
+            email["subject"].as_str().unwrap(),
+            email["bodyValues"]["text"]["value"].as_str().unwrap()
+        );
+        expect![[r#"
+            Subject: Listing updates: 1 new, 1 changed
+
+            New:
+            • Apartment 732 (7th floor, 2 bed 2 bath, $4,260, 1268sq/ft ($3.36/sq/ft), avail. Oct 21 2022, plan f-b4v)
+              https://new.avaloncommunities.com/washington/seattle-apartments/ava-capitol-hill/
+
+            Changed:
+            • Apartment 731 (7th floor, 2 bed 2 bath, $4,100, 1268sq/ft ($3.23/sq/ft), avail. Oct 21 2022, plan f-b4v)
+              • rent: $4,260 → $4,100
+              https://new.avaloncommunities.com/washington/seattle-apartments/ava-capitol-hill/"#]].assert_eq(&body);
+    }
+
+    #[test]
+    fn test_save_if_changed() {
+        let path = std::env::temp_dir().join(format!("ava_db_save_{}.json", std::process::id()));
+        let last_saved = Default::default();
+        let mut store = ApartmentStore::default();
+        assert!(save_if_changed(&store, &path, &last_saved).unwrap());
+        assert!(!save_if_changed(&store, &path, &last_saved).unwrap());
+        store.ignored.insert(apartment_731().unit_id);
+        assert!(save_if_changed(&store, &path, &last_saved).unwrap());
+        assert_eq!(ApartmentStore::load(&path).unwrap().ignored, store.ignored);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_snapshot_shares_unchanged_state() {
+        let mut app = App::default();
+        let first = app.snapshot(None);
+        let second = app.snapshot(Some(Utc::now()));
+        assert!(Arc::ptr_eq(
+            &first.known_apartments,
+            &second.known_apartments
+        ));
+        assert!(Arc::ptr_eq(&first.events, &second.events));
+        assert!(second.last_tick.is_some());
+
+        app.store_mut().ignored.insert(apartment_731().unit_id);
+        let third = app.snapshot(None);
+        assert!(!Arc::ptr_eq(&second.events, &third.events));
+    }
+
+    #[tokio::test]
+    async fn test_price_change_email() {
+        check_notification(vec![apartment_731_at(4100.0)], expect![[r#"
+            Subject: Apartment 731 changed
+
+            Apartment 731 (7th floor, 2 bed 2 bath, $4,100, 1268sq/ft ($3.23/sq/ft), avail. Oct 21 2022, plan f-b4v)
+            • rent: $4,260 → $4,100
+            https://new.avaloncommunities.com/washington/seattle-apartments/ava-capitol-hill/"#]]).await;
+    }
+
+    #[tokio::test]
+    async fn test_availability_change_email() {
+        let available = Utc.ymd(2022, 11, 15).and_hms_opt(4, 0, 0).unwrap();
+        check_notification(vec![apartment_731_available(available)], expect![[r#"
+            Subject: Apartment 731 changed
+
+            Apartment 731 (7th floor, 2 bed 2 bath, $4,260, 1268sq/ft ($3.36/sq/ft), avail. Nov 15 2022, plan f-b4v)
+            • available: Oct 21 2022 → Nov 15 2022
+            https://new.avaloncommunities.com/washington/seattle-apartments/ava-capitol-hill/"#]]).await;
+    }
+
+    #[tokio::test]
+    async fn test_unlisted_email() {
+        check_notification(Vec::new(), expect![[r#"
+            Subject: Apartment 731 no longer available!
+
+            Unlisted after 3 days 0 hrs 0 mins: Apartment 731 (7th floor, 2 bed 2 bath, $4,260, 1268sq/ft ($3.36/sq/ft), avail. Oct 21 2022, plan f-b4v)
+            https://new.avaloncommunities.com/washington/seattle-apartments/ava-capitol-hill/
+            Tracked since: [listed]"#]]).await;
+    }
+}
@@ -0,0 +1,314 @@
+//! A weekly summary of the market, sent on a schedule.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use chrono::DateTime;
+use chrono::Datelike;
+use chrono::Duration;
+use chrono::NaiveDateTime;
+use chrono::Utc;
+use chrono::Weekday;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::api::Apartment;
+use crate::chart;
+use crate::events;
+use crate::events::Event;
+use crate::events::EventKind;
+use crate::filter::Value;
+use crate::jmap::Attachment;
+use crate::listing::Listing;
+use crate::money::Money;
+
+/// How many units to list in the "biggest price drops" and "longest listed" sections.
+const TOP_N: usize = 5;
+
+/// When to send the weekly report, in local time, like `{ day = "mon", hour = 8 }`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Schedule {
+    pub day: Weekday,
+    #[serde(default)]
+    pub hour: u32,
+}
+
+impl Schedule {
+    /// The most recent scheduled time at or before `now`.
+    pub fn last_scheduled(&self, now: NaiveDateTime) -> NaiveDateTime {
+        let days_back =
+            (7 + now.weekday().num_days_from_monday() - self.day.num_days_from_monday()) % 7;
+        let scheduled = (now.date() - Duration::days(days_back.into()))
+            .and_hms_opt(self.hour.min(23), 0, 0)
+            .expect("Hour is in range");
+        if scheduled > now {
+            scheduled - Duration::weeks(1)
+        } else {
+            scheduled
+        }
+    }
+
+    /// Is a report due at `now`, if the last one was sent at `last_sent`?
+    pub fn is_due(&self, last_sent: Option<NaiveDateTime>, now: NaiveDateTime) -> bool {
+        last_sent.map_or(true, |last_sent| last_sent < self.last_scheduled(now))
+    }
+}
+
+/// A summary of the listed apartments and the past week of events, rendered as text by
+/// [`render`], or printed as JSON by `report --output json`.
+#[derive(Debug, Serialize)]
+pub struct MarketReport {
+    /// How many units are listed.
+    pub units: usize,
+    /// Rents of the listed units, by number of bedrooms, fewest first.
+    pub by_bedrooms: Vec<BedroomRents>,
+    /// How many units were listed, unlisted, and changed in the past week.
+    pub this_week: WeekCounts,
+    /// The listed units whose rents dropped the most in the past week, biggest drop first.
+    pub price_drops: Vec<PriceDrop>,
+    /// The units which have been listed the longest, longest first.
+    pub longest_listed: Vec<LongestListed>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BedroomRents {
+    pub bedrooms: u64,
+    pub units: usize,
+    pub average: Money,
+    pub median: Money,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WeekCounts {
+    pub listed: usize,
+    pub unlisted: usize,
+    pub changed: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PriceDrop {
+    pub id: String,
+    pub summary: String,
+    pub drop: Money,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LongestListed {
+    pub id: String,
+    pub summary: String,
+    pub days: i64,
+}
+
+/// Summarize the listed `apartments` and the past week of `events`.
+pub fn summarize(
+    apartments: &BTreeMap<String, Apartment>,
+    events: &[Event],
+    now: DateTime<Utc>,
+) -> MarketReport {
+    let week_ago = now - Duration::weeks(1);
+
+    let mut by_bedrooms: BTreeMap<u64, Vec<f64>> = BTreeMap::new();
+    for apt in apartments.values() {
+        let bedrooms = match apt.inner.field("bedrooms") {
+            Some(Value::Number(bedrooms)) => bedrooms as u64,
+            _ => continue,
+        };
+        by_bedrooms
+            .entry(bedrooms)
+            .or_default()
+            .extend(apt.inner.rent().map(Money::dollars));
+    }
+    let by_bedrooms = by_bedrooms
+        .into_iter()
+        .map(|(bedrooms, mut rents)| {
+            rents.sort_by(f64::total_cmp);
+            BedroomRents {
+                bedrooms,
+                units: rents.len(),
+                average: Money::from_dollars(mean(&rents)),
+                median: Money::from_dollars(median(&rents)),
+            }
+        })
+        .collect();
+
+    let this_week = events
+        .iter()
+        .filter(|event| event.time > week_ago)
+        .collect::<Vec<_>>();
+    let count = |kind| this_week.iter().filter(|event| event.kind == kind).count();
+
+    let price_drops = price_drops(apartments, events, week_ago)
+        .into_iter()
+        .take(TOP_N)
+        .map(|(apt, drop)| PriceDrop {
+            id: apt.id().to_owned(),
+            summary: apt.inner.to_string(),
+            drop,
+        })
+        .collect();
+
+    let mut longest_listed = apartments.values().collect::<Vec<_>>();
+    longest_listed.sort_by_key(|apt| apt.listed);
+    let longest_listed = longest_listed
+        .into_iter()
+        .take(TOP_N)
+        .map(|apt| LongestListed {
+            id: apt.id().to_owned(),
+            summary: apt.inner.to_string(),
+            days: (now - apt.listed).num_days(),
+        })
+        .collect();
+
+    MarketReport {
+        units: apartments.len(),
+        by_bedrooms,
+        this_week: WeekCounts {
+            listed: count(EventKind::Listed),
+            unlisted: count(EventKind::Unlisted),
+            changed: count(EventKind::Changed),
+        },
+        price_drops,
+        longest_listed,
+    }
+}
+
+/// Summarize the listed `apartments` and the past week of `events`, as text.
+pub fn render(
+    apartments: &BTreeMap<String, Apartment>,
+    events: &[Event],
+    now: DateTime<Utc>,
+) -> String {
+    let summary = summarize(apartments, events, now);
+    let mut report = String::new();
+
+    let _ = writeln!(report, "{} units listed.\n", summary.units);
+
+    for rents in &summary.by_bedrooms {
+        let _ = writeln!(
+            report,
+            "{} bed: {} units, average {}, median {}",
+            rents.bedrooms,
+            rents.units,
+            rents.average.round(),
+            rents.median.round(),
+        );
+    }
+
+    let _ = writeln!(
+        report,
+        "\nThis week: {} listed, {} unlisted, {} changed.",
+        summary.this_week.listed, summary.this_week.unlisted, summary.this_week.changed,
+    );
+
+    if !summary.price_drops.is_empty() {
+        let _ = writeln!(report, "\nBiggest price drops this week:");
+        for drop in &summary.price_drops {
+            let _ = writeln!(report, "• -{}: {}", drop.drop, drop.summary);
+        }
+    }
+
+    if !summary.longest_listed.is_empty() {
+        let _ = writeln!(report, "\nLongest listed:");
+        for apt in &summary.longest_listed {
+            let _ = writeln!(report, "• {} days: {}", apt.days, apt.summary);
+        }
+    }
+
+    report
+}
+
+/// Price history charts for the apartments with the biggest price drops in the past week, to
+/// attach to the report.
+pub fn charts(
+    apartments: &BTreeMap<String, Apartment>,
+    events: &[Event],
+    now: DateTime<Utc>,
+) -> Vec<Attachment> {
+    price_drops(apartments, events, now - Duration::weeks(1))
+        .into_iter()
+        .take(TOP_N)
+        .filter_map(|(apt, _)| {
+            chart::attachment(apt.id(), &events::price_history(events, apt.id()))
+        })
+        .collect()
+}
+
+/// How much each apartment's rent has dropped since `since`, biggest drop first.
+fn price_drops<'a>(
+    apartments: &'a BTreeMap<String, Apartment>,
+    events: &[Event],
+    since: DateTime<Utc>,
+) -> Vec<(&'a Apartment, Money)> {
+    let mut drops: Vec<_> = apartments
+        .values()
+        .filter_map(|apt| {
+            let rent = apt.inner.rent()?;
+            let history = events
+                .iter()
+                .filter(|event| event.id == apt.id())
+                .filter(|event| event.rent.is_some());
+            // The rent as of `since`, or when it was first listed if that's later.
+            let before = history
+                .clone()
+                .take_while(|event| event.time <= since)
+                .last()
+                .or_else(|| history.clone().next())?
+                .rent?;
+            let drop = before - rent;
+            (drop > Money::ZERO).then_some((apt, drop))
+        })
+        .collect();
+    drops.sort_by(|(_, a), (_, b)| b.cmp(a));
+    drops
+}
+
+pub fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// The median of sorted `values`.
+pub fn median(values: &[f64]) -> f64 {
+    let mid = values.len() / 2;
+    if values.len() % 2 == 0 {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    #[test]
+    fn test_schedule() {
+        let schedule = Schedule {
+            day: Weekday::Mon,
+            hour: 8,
+        };
+        let at = |day, hour| {
+            NaiveDate::from_ymd_opt(2022, 10, day)
+                .unwrap()
+                .and_hms_opt(hour, 0, 0)
+                .unwrap()
+        };
+
+        // Oct 17 2022 was a Monday.
+        assert_eq!(schedule.last_scheduled(at(17, 8)), at(17, 8));
+        assert_eq!(schedule.last_scheduled(at(17, 7)), at(10, 8));
+        assert_eq!(schedule.last_scheduled(at(21, 12)), at(17, 8));
+
+        assert!(schedule.is_due(None, at(21, 12)));
+        assert!(schedule.is_due(Some(at(16, 12)), at(17, 9)));
+        assert!(!schedule.is_due(Some(at(17, 9)), at(21, 12)));
+    }
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(&[1.0, 2.0, 10.0]), 2.0);
+        assert_eq!(median(&[1.0, 2.0, 4.0, 10.0]), 3.0);
+    }
+}
@@ -0,0 +1,399 @@
+//! A minimal in-process JMAP server, for tests and offline `simulate` runs.
+//!
+//! Implements just enough for [`crate::jmap::Mailer`]: the session, `Mailbox/query`,
+//! `Mailbox/get`, `Identity/get`, `Email/set`, `Email/import`, `EmailSubmission/set`, and blob
+//! uploads. Created emails are checked for the mistakes a real server would reject, like
+//! malformed `Message-ID`s or addresses, and recorded for tests to inspect.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use axum::body::Bytes;
+use axum::http::header;
+use axum::http::HeaderMap;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::routing::post;
+use axum::Extension;
+use axum::Json;
+use axum::Router;
+use serde_json::json;
+use serde_json::Map;
+use serde_json::Value;
+use tokio::task::JoinHandle;
+
+use crate::jmap::JmapConfig;
+
+/// The environment variable holding the mock server's token, for [`JmapConfig::token_env`].
+const TOKEN_ENV: &str = "AVA_MOCK_JMAP_TOKEN";
+const TOKEN: &str = "mock-token";
+const ACCOUNT_ID: &str = "account";
+const IDENTITY_ID: &str = "identity";
+const SESSION_STATE: &str = "session";
+
+pub struct MockJmap {
+    url: String,
+    state: Arc<Mutex<State>>,
+    server: JoinHandle<()>,
+}
+
+/// What the server has been sent.
+#[derive(Debug, Default)]
+struct State {
+    /// Created or imported emails, as sent.
+    emails: Vec<Value>,
+    /// Created submissions, as sent.
+    submissions: Vec<Value>,
+    /// Uploaded blobs.
+    blobs: Vec<Vec<u8>>,
+}
+
+impl MockJmap {
+    /// Start the server on a free port.
+    pub async fn start() -> Self {
+        std::env::set_var(TOKEN_ENV, TOKEN);
+        let state = Arc::new(Mutex::new(State::default()));
+        let router = Router::new()
+            .route("/.well-known/jmap", get(session))
+            .route("/api", post(api))
+            .route("/upload/:account_id", post(upload))
+            .layer(Extension(state.clone()));
+        let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+            .serve(router.into_make_service());
+        let url = format!("http://{}", server.local_addr());
+        let server = tokio::spawn(async move {
+            if let Err(err) = server.await {
+                tracing::error!("Mock JMAP server failed: {err}");
+            }
+        });
+        Self { url, state, server }
+    }
+
+    /// A config for sending from the mock server's identity.
+    pub fn config(&self) -> JmapConfig {
+        JmapConfig {
+            session_url: self.url.clone(),
+            token_env: TOKEN_ENV.to_owned(),
+            from: ("Ava Apartment Finder", "ava@example.com").into(),
+            retries: 0,
+            ..Default::default()
+        }
+    }
+
+    /// The emails created so far, as JMAP `Email` objects.
+    pub fn emails(&self) -> Vec<Value> {
+        self.state.lock().unwrap().emails.clone()
+    }
+
+    /// The submissions created so far, as JMAP `EmailSubmission` objects.
+    #[cfg(test)]
+    pub fn submissions(&self) -> Vec<Value> {
+        self.state.lock().unwrap().submissions.clone()
+    }
+
+    /// The blobs uploaded so far.
+    #[cfg(test)]
+    pub fn blobs(&self) -> Vec<Vec<u8>> {
+        self.state.lock().unwrap().blobs.clone()
+    }
+}
+
+impl Drop for MockJmap {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+fn authorize(headers: &HeaderMap) -> Result<(), StatusCode> {
+    let expected = format!("Bearer {TOKEN}");
+    match headers.get(header::AUTHORIZATION) {
+        Some(value) if value.as_bytes() == expected.as_bytes() => Ok(()),
+        _ => Err(StatusCode::UNAUTHORIZED),
+    }
+}
+
+async fn session(headers: HeaderMap) -> Result<Json<Value>, StatusCode> {
+    authorize(&headers)?;
+    let host = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    let url = format!("http://{host}");
+    let event_source_url =
+        format!("{url}/events?types={{types}}&closeafter={{closeafter}}&ping={{ping}}");
+    Ok(Json(json!({
+        "capabilities": {
+            "urn:ietf:params:jmap:core": {
+                "maxSizeUpload": 50_000_000,
+                "maxConcurrentUpload": 4,
+                "maxSizeRequest": 10_000_000,
+                "maxConcurrentRequests": 4,
+                "maxCallsInRequest": 16,
+                "maxObjectsInGet": 500,
+                "maxObjectsInSet": 500,
+                "collationAlgorithms": [],
+            },
+            "urn:ietf:params:jmap:mail": {},
+            "urn:ietf:params:jmap:submission": {},
+        },
+        "accounts": {
+            ACCOUNT_ID: {
+                "name": "ava@example.com",
+                "isPersonal": true,
+                "isReadOnly": false,
+                "accountCapabilities": {
+                    "urn:ietf:params:jmap:mail": {
+                        "maxMailboxesPerEmail": null,
+                        "maxMailboxDepth": 10,
+                        "maxSizeMailboxName": 255,
+                        "maxSizeAttachmentsPerEmail": 50_000_000,
+                        "emailQuerySortOptions": ["receivedAt"],
+                        "mayCreateTopLevelMailbox": true,
+                    },
+                    "urn:ietf:params:jmap:submission": {
+                        "maxDelayedSend": 0,
+                        "submissionExtensions": [],
+                    },
+                },
+            },
+        },
+        "primaryAccounts": {
+            "urn:ietf:params:jmap:mail": ACCOUNT_ID,
+            "urn:ietf:params:jmap:submission": ACCOUNT_ID,
+        },
+        "username": "ava@example.com",
+        "apiUrl": format!("{url}/api"),
+        "downloadUrl": format!("{url}/download/{{accountId}}/{{blobId}}/{{name}}?type={{type}}"),
+        "uploadUrl": format!("{url}/upload/{{accountId}}"),
+        "eventSourceUrl": event_source_url,
+        "state": SESSION_STATE,
+    })))
+}
+
+async fn upload(
+    Extension(state): Extension<Arc<Mutex<State>>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<Value>, StatusCode> {
+    authorize(&headers)?;
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("application/octet-stream");
+    let mut state = state.lock().unwrap();
+    state.blobs.push(body.to_vec());
+    Ok(Json(json!({
+        "accountId": ACCOUNT_ID,
+        "blobId": format!("blob-{}", state.blobs.len()),
+        "type": content_type,
+        "size": body.len(),
+    })))
+}
+
+async fn api(
+    Extension(state): Extension<Arc<Mutex<State>>>,
+    headers: HeaderMap,
+    Json(request): Json<Value>,
+) -> Result<Json<Value>, StatusCode> {
+    authorize(&headers)?;
+    let calls = request["methodCalls"]
+        .as_array()
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    let mut state = state.lock().unwrap();
+    let responses = calls
+        .iter()
+        .map(|call| {
+            let (method, args, call_id) = (&call[0], &call[1], &call[2]);
+            match method.as_str().and_then(|method| state.call(method, args)) {
+                Some(response) => json!([method, response, call_id]),
+                None => json!(["error", { "type": "unknownMethod" }, call_id]),
+            }
+        })
+        .collect::<Vec<_>>();
+    Ok(Json(json!({
+        "methodResponses": responses,
+        "sessionState": SESSION_STATE,
+    })))
+}
+
+impl State {
+    /// Respond to a method call, or `None` if the method isn't supported.
+    fn call(&mut self, method: &str, args: &Value) -> Option<Value> {
+        let get = |list: Value| {
+            json!({
+                "accountId": ACCOUNT_ID,
+                "state": "0",
+                "list": list,
+                "notFound": [],
+            })
+        };
+        Some(match method {
+            "Mailbox/query" => json!({
+                "accountId": ACCOUNT_ID,
+                "queryState": "0",
+                "canCalculateChanges": false,
+                "position": 0,
+                "ids": ["inbox", "sent"],
+            }),
+            "Mailbox/get" => get(json!([
+                { "id": "inbox", "name": "Inbox", "parentId": null, "role": "inbox" },
+                { "id": "sent", "name": "Sent", "parentId": null, "role": "sent" },
+            ])),
+            "Identity/get" => get(json!([{
+                "id": IDENTITY_ID,
+                "name": "Ava Apartment Finder",
+                "email": "ava@example.com",
+                "replyTo": null,
+            }])),
+            // No replies to process.
+            "Email/query" => json!({
+                "accountId": ACCOUNT_ID,
+                "queryState": "0",
+                "canCalculateChanges": false,
+                "position": 0,
+                "ids": [],
+            }),
+            "Email/get" => get(json!([])),
+            "Email/set" | "Email/import" => {
+                let objects = if method == "Email/set" {
+                    &args["create"]
+                } else {
+                    &args["emails"]
+                };
+                let (created, not_created) = self.create(objects, |state, email| {
+                    check_email(email)?;
+                    tracing::info!(
+                        subject = email["subject"].as_str(),
+                        "Mock JMAP server received an email, not sending it"
+                    );
+                    state.emails.push(email.clone());
+                    Ok(json!({ "id": format!("email-{}", state.emails.len()) }))
+                });
+                let mut response = json!({
+                    "accountId": ACCOUNT_ID,
+                    "newState": "1",
+                    "created": created,
+                    "notCreated": not_created,
+                });
+                // Keyword updates, like marking replies as processed.
+                if let Some(update) = args["update"].as_object() {
+                    response["updated"] =
+                        update.keys().map(|id| (id.clone(), Value::Null)).collect();
+                }
+                response
+            }
+            "EmailSubmission/set" => {
+                let (created, not_created) = self.create(&args["create"], |state, submission| {
+                    check_submission(submission)?;
+                    state.submissions.push(submission.clone());
+                    Ok(json!({
+                        "id": format!("submission-{}", state.submissions.len()),
+                        "undoStatus": "final",
+                    }))
+                });
+                json!({
+                    "accountId": ACCOUNT_ID,
+                    "newState": "1",
+                    "created": created,
+                    "notCreated": not_created,
+                })
+            }
+            "EmailSubmission/get" => get(Value::Array(
+                args["ids"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|id| {
+                        json!({
+                            "id": id,
+                            "undoStatus": "final",
+                            "deliveryStatus": {},
+                        })
+                    })
+                    .collect(),
+            )),
+            _ => return None,
+        })
+    }
+
+    /// Create each of `objects`, keyed by creation ID, returning the `created` and
+    /// `notCreated` maps.
+    fn create(
+        &mut self,
+        objects: &Value,
+        mut create: impl FnMut(&mut Self, &Value) -> Result<Value, String>,
+    ) -> (Value, Value) {
+        let mut created = Map::new();
+        let mut not_created = Map::new();
+        for (create_id, object) in objects.as_object().into_iter().flatten() {
+            match create(self, object) {
+                Ok(object) => {
+                    created.insert(create_id.clone(), object);
+                }
+                Err(description) => {
+                    not_created.insert(
+                        create_id.clone(),
+                        json!({ "type": "invalidProperties", "description": description }),
+                    );
+                }
+            }
+        }
+        (Value::Object(created), Value::Object(not_created))
+    }
+}
+
+/// Check an email's headers the way a real server would.
+fn check_email(email: &Value) -> Result<(), String> {
+    if let Some(message_ids) = email["messageId"].as_array() {
+        for message_id in message_ids {
+            let message_id = message_id.as_str().unwrap_or_default();
+            // The server adds the angle brackets.
+            if message_id.contains(['<', '>'])
+                || message_id.contains(char::is_whitespace)
+                || !message_id.contains('@')
+            {
+                return Err(format!("Invalid Message-ID {message_id:?}"));
+            }
+        }
+    }
+    for field in ["from", "to", "cc", "bcc", "replyTo"] {
+        for address in email[field].as_array().into_iter().flatten() {
+            check_address(&address["email"]).map_err(|err| format!("Invalid {field}: {err}"))?;
+        }
+    }
+    if email["from"].as_array().map_or(true, Vec::is_empty) {
+        return Err("No From address".to_owned());
+    }
+    match email["subject"].as_str() {
+        Some(subject) if subject.contains(['\r', '\n']) => {
+            Err(format!("Subject contains a line break: {subject:?}"))
+        }
+        Some(_) => Ok(()),
+        None => Err("No subject".to_owned()),
+    }
+}
+
+fn check_submission(submission: &Value) -> Result<(), String> {
+    if submission["identityId"] != IDENTITY_ID {
+        return Err(format!("Unknown identity {}", submission["identityId"]));
+    }
+    let envelope = &submission["envelope"];
+    check_address(&envelope["mailFrom"]["email"])
+        .map_err(|err| format!("Invalid mailFrom: {err}"))?;
+    let rcpt_to = envelope["rcptTo"].as_array().map_or(&[][..], Vec::as_slice);
+    if rcpt_to.is_empty() {
+        return Err("No recipients".to_owned());
+    }
+    for address in rcpt_to {
+        check_address(&address["email"]).map_err(|err| format!("Invalid rcptTo: {err}"))?;
+    }
+    Ok(())
+}
+
+fn check_address(email: &Value) -> Result<(), String> {
+    match email.as_str() {
+        Some(email) if email.contains('@') && !email.contains(char::is_whitespace) => Ok(()),
+        _ => Err(format!("Invalid address {email}")),
+    }
+}
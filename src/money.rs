@@ -0,0 +1,189 @@
+//! Amounts of money, like rents, stored in whole cents so that float noise like `4259.999999`
+//! never counts as a change.
+//!
+//! Amounts are shown like `$4,260` or `$4,260.50`, which can be configured like:
+//!
+//! ```toml
+//! currency = { symbol = "€", symbol-after = true, thousands-separator = ".", decimal-separator = "," }
+//! ```
+
+use std::fmt::Display;
+use std::ops::Add;
+use std::ops::Sub;
+use std::sync::RwLock;
+
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+
+/// An amount of money, in cents.
+///
+/// Serialized as a number of dollars (or whatever the currency's main unit is), like the
+/// Avalon API and older DBs use.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Money(i64);
+
+impl Money {
+    pub const ZERO: Self = Self(0);
+
+    pub const fn from_cents(cents: i64) -> Self {
+        Self(cents)
+    }
+
+    /// The amount closest to `dollars`, rounded to the nearest cent.
+    pub fn from_dollars(dollars: f64) -> Self {
+        Self((dollars * 100.0).round() as i64)
+    }
+
+    pub fn cents(self) -> i64 {
+        self.0
+    }
+
+    pub fn dollars(self) -> f64 {
+        self.0 as f64 / 100.0
+    }
+
+    pub fn abs(self) -> Self {
+        Self(self.0.abs())
+    }
+
+    /// `self` rounded to the nearest whole dollar.
+    pub fn round(self) -> Self {
+        Self::from_dollars(self.dollars().round())
+    }
+}
+
+impl Add for Money {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self(self.0 + other.0)
+    }
+}
+
+impl Sub for Money {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self(self.0 - other.0)
+    }
+}
+
+/// Debug-formatted as a number of dollars, so diffs of listings read like the API response.
+impl std::fmt::Debug for Money {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&self.dollars(), f)
+    }
+}
+
+impl Display for Money {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let currency = get();
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let cents = self.0.unsigned_abs();
+
+        let whole = (cents / 100).to_string();
+        let mut amount = String::with_capacity(whole.len() + whole.len() / 3);
+        for (i, digit) in whole.chars().enumerate() {
+            if i > 0 && (whole.len() - i) % 3 == 0 {
+                amount.push_str(&currency.thousands_separator);
+            }
+            amount.push(digit);
+        }
+        if cents % 100 != 0 {
+            amount.push_str(&currency.decimal_separator);
+            amount.push_str(&format!("{:02}", cents % 100));
+        }
+
+        if currency.symbol_after {
+            write!(f, "{sign}{amount} {}", currency.symbol)
+        } else {
+            write!(f, "{sign}{}{amount}", currency.symbol)
+        }
+    }
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.dollars())
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        f64::deserialize(deserializer).map(Self::from_dollars)
+    }
+}
+
+async_graphql::scalar!(Money, "Money", "An amount of money, in dollars.");
+
+/// How to show amounts of money.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Currency {
+    /// The currency symbol, like `$`.
+    pub symbol: String,
+    /// Put the symbol after the amount, like `4.260 €`.
+    pub symbol_after: bool,
+    /// Put between each group of three digits, like `,` in `$4,260`.
+    pub thousands_separator: String,
+    /// Put before the cents, like `.` in `$4,260.50`.
+    pub decimal_separator: String,
+}
+
+impl Default for Currency {
+    fn default() -> Self {
+        Self {
+            symbol: "$".to_owned(),
+            symbol_after: false,
+            thousands_separator: ",".to_owned(),
+            decimal_separator: ".".to_owned(),
+        }
+    }
+}
+
+/// The configured currency, or `None` for the default.
+static CURRENCY: RwLock<Option<Currency>> = RwLock::new(None);
+
+/// Set how to show amounts of money from now on.
+pub fn set(currency: Currency) {
+    *CURRENCY.write().expect("Currency lock poisoned") = Some(currency);
+}
+
+/// The configured currency.
+pub fn get() -> Currency {
+    CURRENCY
+        .read()
+        .expect("Currency lock poisoned")
+        .clone()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_money() {
+        assert_eq!(
+            Money::from_dollars(4259.999999),
+            Money::from_dollars(4260.0)
+        );
+        assert_eq!(Money::from_dollars(4260.0).cents(), 426000);
+        assert_eq!(
+            Money::from_dollars(4033.33).round(),
+            Money::from_cents(403300)
+        );
+
+        assert_eq!(Money::from_dollars(4260.0).to_string(), "$4,260");
+        assert_eq!(Money::from_dollars(4260.5).to_string(), "$4,260.50");
+        assert_eq!(Money::from_dollars(999.0).to_string(), "$999");
+        assert_eq!(Money::from_dollars(1234567.0).to_string(), "$1,234,567");
+        assert_eq!(Money::from_dollars(-150.0).to_string(), "-$150");
+
+        let money: Money = serde_json::from_str("4259.999999").unwrap();
+        assert_eq!(money, Money::from_dollars(4260.0));
+        assert_eq!(serde_json::to_string(&money).unwrap(), "4260.0");
+    }
+}
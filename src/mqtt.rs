@@ -0,0 +1,262 @@
+//! Publish listing events to an MQTT broker, for home automation like Home Assistant.
+//!
+//! Configured like:
+//!
+//! ```toml
+//! [mqtt]
+//! host = "homeassistant.local"
+//! port = 1883
+//! username = "ava"
+//! password = "..."
+//! topic-prefix = "ava-apartment-finder"
+//! ```
+//!
+//! Each event is published to `<prefix>/<community>/<unit>`, like
+//! `ava-apartment-finder/ava-capitol-hill/AVB-WA026-002-731`, with a JSON payload including
+//! whether the listing is qualified and how many bedrooms it has. The currently listed
+//! qualified units are published as a JSON array to the retained topic `<prefix>/state`.
+
+use std::time::Duration;
+
+use chrono::NaiveDate;
+use color_eyre::eyre;
+use rumqttc::AsyncClient;
+use rumqttc::MqttOptions;
+use rumqttc::Outgoing;
+use rumqttc::QoS;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::task::JoinHandle;
+
+use crate::api::Apartment;
+use crate::events::Event;
+use crate::filter::Value;
+use crate::listing::Listing;
+use crate::money::Money;
+use crate::secret;
+use crate::server::Snapshot;
+
+/// How many messages to buffer while the broker is unreachable. Past that, messages are
+/// dropped rather than holding up ticks.
+const CAPACITY: usize = 100;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct MqttConfig {
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    pub username: Option<String>,
+    /// See [`crate::secret`].
+    #[serde(default, deserialize_with = "secret::deserialize_option")]
+    pub password: Option<String>,
+    #[serde(default = "default_topic_prefix")]
+    pub topic_prefix: String,
+}
+
+fn default_port() -> u16 {
+    1883
+}
+
+fn default_topic_prefix() -> String {
+    "ava-apartment-finder".to_owned()
+}
+
+/// What we know about a listing, published with its events and in the state topic.
+#[derive(Debug, Serialize)]
+struct ListingState<'a> {
+    id: &'a str,
+    summary: String,
+    /// The listing's own page, or the page it was listed on.
+    url: String,
+    rent: Option<Money>,
+    bedrooms: Option<f64>,
+    available_date: Option<NaiveDate>,
+    qualified: bool,
+}
+
+impl<'a> ListingState<'a> {
+    fn new<T: Listing>(snapshot: &Snapshot, apt: &'a Apartment<T>) -> Self {
+        Self {
+            id: apt.id(),
+            summary: apt.inner.to_string(),
+            url: apt.inner.url().unwrap_or_else(|| apt.source.clone()),
+            rent: apt.inner.rent(),
+            bedrooms: match apt.inner.field("bedrooms") {
+                Some(Value::Number(bedrooms)) => Some(bedrooms),
+                _ => None,
+            },
+            available_date: apt.inner.available_date(),
+            qualified: apt.inner.meets_qualifications(&snapshot.qualifications),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct EventMessage<'a> {
+    #[serde(flatten)]
+    event: &'a Event,
+    qualified: bool,
+    bedrooms: Option<f64>,
+}
+
+pub struct Mqtt {
+    client: AsyncClient,
+    event_loop: JoinHandle<()>,
+    topic_prefix: String,
+    /// The [`Event::seq`] of the next event to publish.
+    next_seq: u64,
+}
+
+impl Mqtt {
+    /// Connect to the broker in the background. Events numbered before `next_seq` aren't
+    /// published, so restarting doesn't replay old events.
+    pub fn new(config: MqttConfig, next_seq: u64) -> Self {
+        let mut options = MqttOptions::new("ava-apartment-finder", &config.host, config.port);
+        options.set_keep_alive(Duration::from_secs(30));
+        if let Some(username) = &config.username {
+            options.set_credentials(username, config.password.clone().unwrap_or_default());
+        }
+
+        let (client, mut event_loop) = AsyncClient::new(options, CAPACITY);
+        // The event loop has to be polled for anything to be sent, and reconnects on its own.
+        let event_loop = tokio::spawn(async move {
+            loop {
+                match event_loop.poll().await {
+                    Ok(rumqttc::Event::Outgoing(Outgoing::Disconnect)) => break,
+                    Ok(_) => {}
+                    Err(err) => {
+                        tracing::warn!("MQTT connection error: {err}");
+                        tokio::time::sleep(Duration::from_secs(30)).await;
+                    }
+                }
+            }
+        });
+
+        Self {
+            client,
+            event_loop,
+            topic_prefix: config.topic_prefix,
+            next_seq,
+        }
+    }
+
+    /// Publish the events we haven't yet and the current state.
+    pub fn publish(&mut self, snapshot: &Snapshot) -> eyre::Result<()> {
+        let new_events = snapshot
+            .events
+            .iter()
+            .filter(|event| event.seq >= self.next_seq);
+        for event in new_events {
+            let state = find(snapshot, &event.id);
+            let message = EventMessage {
+                event,
+                qualified: state.as_ref().map_or(false, |state| state.qualified),
+                bedrooms: state.and_then(|state| state.bedrooms),
+            };
+            let topic = format!(
+                "{}/{}/{}",
+                self.topic_prefix,
+                community(&event.source),
+                event.id
+            );
+            self.client.try_publish(
+                topic,
+                QoS::AtLeastOnce,
+                false,
+                serde_json::to_vec(&message)?,
+            )?;
+        }
+        if let Some(last) = snapshot.events.last() {
+            self.next_seq = self.next_seq.max(last.seq + 1);
+        }
+
+        let state = snapshot
+            .known_apartments
+            .values()
+            .map(|apt| ListingState::new(snapshot, apt))
+            .chain(
+                snapshot
+                    .known_posts
+                    .values()
+                    .map(|post| ListingState::new(snapshot, post)),
+            )
+            .filter(|state| state.qualified)
+            .collect::<Vec<_>>();
+        self.client.try_publish(
+            format!("{}/state", self.topic_prefix),
+            QoS::AtLeastOnce,
+            true,
+            serde_json::to_vec(&state)?,
+        )?;
+        Ok(())
+    }
+
+    /// Send any queued messages and disconnect, giving up after a few seconds.
+    pub async fn disconnect(self) {
+        if let Err(err) = self.client.disconnect().await {
+            tracing::warn!("Failed to disconnect from MQTT broker: {err}");
+            return;
+        }
+        if tokio::time::timeout(Duration::from_secs(5), self.event_loop)
+            .await
+            .is_err()
+        {
+            tracing::warn!("Timed out sending queued MQTT messages");
+        }
+    }
+}
+
+/// The listing with ID `id`, listed or not.
+fn find<'a>(snapshot: &'a Snapshot, id: &str) -> Option<ListingState<'a>> {
+    let apartment = snapshot
+        .known_apartments
+        .get(id)
+        .or_else(|| snapshot.unlisted_apartments.get(id))
+        .map(|apt| ListingState::new(snapshot, apt));
+    apartment.or_else(|| {
+        snapshot
+            .known_posts
+            .get(id)
+            .or_else(|| snapshot.unlisted_posts.get(id))
+            .map(|post| ListingState::new(snapshot, post))
+    })
+}
+
+/// A topic segment for the source with URL `source`: the community name for Avalon pages, like
+/// `ava-capitol-hill`, or the region for Craigslist searches, like `craigslist-seattle`.
+fn community(source: &str) -> String {
+    let url = source.split(['?', '#']).next().unwrap_or_default();
+    let url = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let (host, path) = url.split_once('/').unwrap_or((url, ""));
+    let name = match host.strip_suffix(".craigslist.org") {
+        Some(region) => format!("craigslist-{region}"),
+        None => path
+            .rsplit('/')
+            .find(|segment| !segment.is_empty())
+            .unwrap_or(host)
+            .to_owned(),
+    };
+    // `+` and `#` are wildcards in MQTT topic filters.
+    name.replace(['+', '#'], "-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_community() {
+        assert_eq!(
+            community(
+                "https://www.avaloncommunities.com/washington/seattle-apartments/ava-capitol-hill/"
+            ),
+            "ava-capitol-hill"
+        );
+        assert_eq!(
+            community("https://seattle.craigslist.org/search/apa?format=rss"),
+            "craigslist-seattle"
+        );
+        assert_eq!(community("https://example.com"), "example.com");
+    }
+}
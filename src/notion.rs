@@ -0,0 +1,266 @@
+//! Sync listings to a Notion database, one page per listing, so units can be annotated with tour
+//! notes while we keep the facts up to date.
+//!
+//! Create an [integration], share the database with it, and configure it like:
+//!
+//! ```toml
+//! [notion]
+//! token = "secret_..."
+//! database-id = "668d797c76fa49349b05ad288df2d136"
+//! ```
+//!
+//! The database needs these properties: `Name` (title), `ID` (text), `Rent`, `Bedrooms`, and
+//! `Sq ft` (numbers), `Available` (date), `Status` (select), and `URL` (URL). We only ever
+//! write those properties, so other properties and page contents are left alone.
+//!
+//! Notion only allows a few requests a second, so the first sync can take minutes. It runs in
+//! the background rather than holding up notifications; see [`crate::export`].
+//!
+//! [integration]: https://developers.notion.com/docs/create-a-notion-integration
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use chrono::DateTime;
+use chrono::Utc;
+use color_eyre::eyre;
+use color_eyre::eyre::eyre;
+use color_eyre::eyre::Context;
+use serde::Deserialize;
+use serde_json::json;
+use serde_json::Value as JsonValue;
+
+use crate::api::Apartment;
+use crate::filter::Value;
+use crate::listing::Listing;
+use crate::secret;
+use crate::server::Snapshot;
+
+const API_URL: &str = "https://api.notion.com/v1";
+const NOTION_VERSION: &str = "2022-06-28";
+
+/// Notion allows an average of three requests per second.
+const REQUEST_INTERVAL: Duration = Duration::from_millis(350);
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NotionConfig {
+    /// The integration's secret token; see [`crate::secret`].
+    #[serde(deserialize_with = "secret::deserialize")]
+    pub token: String,
+    /// The ID from the database's URL.
+    pub database_id: String,
+}
+
+#[derive(Deserialize)]
+struct QueryResponse {
+    results: Vec<Page>,
+    has_more: bool,
+    next_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Page {
+    id: String,
+    properties: JsonValue,
+}
+
+impl Page {
+    /// The listing ID in the page's `ID` property.
+    fn listing_id(&self) -> Option<&str> {
+        self.properties
+            .pointer("/ID/rich_text/0/plain_text")
+            .and_then(JsonValue::as_str)
+    }
+}
+
+pub struct Notion {
+    client: reqwest::Client,
+    config: NotionConfig,
+    /// When we last synced successfully. Listings without events since then are skipped.
+    last_synced: Option<DateTime<Utc>>,
+}
+
+impl Notion {
+    pub fn new(config: NotionConfig) -> eyre::Result<Self> {
+        Ok(Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()?,
+            config,
+            last_synced: None,
+        })
+    }
+
+    /// Create pages for new listings and update the properties of listings which have changed
+    /// since the last sync.
+    pub async fn sync(&mut self, snapshot: &Snapshot) -> eyre::Result<()> {
+        let started = Utc::now();
+        let pages = self.pages().await?;
+
+        let last_synced = self.last_synced;
+        let changed = |id: &str| match last_synced {
+            Some(last_synced) => snapshot
+                .events
+                .iter()
+                .any(|event| event.time > last_synced && event.id == id),
+            None => true,
+        };
+
+        let mut created = 0;
+        let mut updated = 0;
+        for (id, properties) in listing_properties(snapshot) {
+            match pages.get(&id) {
+                Some(page_id) => {
+                    if !changed(&id) {
+                        continue;
+                    }
+                    let url = format!("{API_URL}/pages/{page_id}");
+                    self.request(self.client.patch(&url), json!({ "properties": properties }))
+                        .await
+                        .wrap_err_with(|| format!("Failed to update Notion page for {id}"))?;
+                    updated += 1;
+                }
+                None => {
+                    let body = json!({
+                        "parent": { "database_id": self.config.database_id },
+                        "properties": properties,
+                    });
+                    self.request(self.client.post(format!("{API_URL}/pages")), body)
+                        .await
+                        .wrap_err_with(|| format!("Failed to create Notion page for {id}"))?;
+                    created += 1;
+                }
+            }
+            tokio::time::sleep(REQUEST_INTERVAL).await;
+        }
+
+        self.last_synced = Some(started);
+        tracing::debug!(created, updated, "Synced Notion database");
+        Ok(())
+    }
+
+    /// The ID of the page for each listing in the database.
+    async fn pages(&self) -> eyre::Result<BTreeMap<String, String>> {
+        let url = format!("{API_URL}/databases/{}/query", self.config.database_id);
+        let mut pages = BTreeMap::new();
+        let mut cursor = None;
+        loop {
+            let body = match &cursor {
+                Some(cursor) => json!({ "start_cursor": cursor }),
+                None => json!({}),
+            };
+            let response = self
+                .request(self.client.post(&url), body)
+                .await
+                .wrap_err("Failed to query Notion database")?;
+            let response: QueryResponse = serde_json::from_value(response)?;
+            for page in &response.results {
+                if let Some(id) = page.listing_id() {
+                    pages.insert(id.to_owned(), page.id.clone());
+                }
+            }
+            match (response.has_more, response.next_cursor) {
+                (true, Some(next)) => cursor = Some(next),
+                _ => break,
+            }
+        }
+        Ok(pages)
+    }
+
+    async fn request(
+        &self,
+        request: reqwest::RequestBuilder,
+        body: JsonValue,
+    ) -> eyre::Result<JsonValue> {
+        let response = request
+            .bearer_auth(&self.config.token)
+            .header("Notion-Version", NOTION_VERSION)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(&body)?)
+            .send()
+            .await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            return Err(eyre!("{status}: {text}"));
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+}
+
+/// The page properties for each listing, listed or not, by ID.
+fn listing_properties(snapshot: &Snapshot) -> Vec<(String, JsonValue)> {
+    snapshot
+        .known_apartments
+        .values()
+        .chain(snapshot.unlisted_apartments.values())
+        .map(properties)
+        .chain(
+            snapshot
+                .known_posts
+                .values()
+                .chain(snapshot.unlisted_posts.values())
+                .map(properties),
+        )
+        .collect()
+}
+
+fn properties<T: Listing>(apt: &Apartment<T>) -> (String, JsonValue) {
+    let number = |name| match apt.inner.field(name) {
+        Some(Value::Number(number)) => json!(number),
+        _ => JsonValue::Null,
+    };
+    let available = match apt.inner.available_date() {
+        Some(date) => json!({ "start": date.to_string() }),
+        None => JsonValue::Null,
+    };
+    let status = if apt.unlisted.is_some() {
+        "Unlisted"
+    } else {
+        "Listed"
+    };
+    let properties = json!({
+        "Name": { "title": [{ "text": { "content": apt.inner.to_string() } }] },
+        "ID": { "rich_text": [{ "text": { "content": apt.id() } }] },
+        "Rent": { "number": apt.inner.rent() },
+        "Bedrooms": { "number": number("bedrooms") },
+        "Sq ft": { "number": number("sqft") },
+        "Available": { "date": available },
+        "Status": { "select": { "name": status } },
+        "URL": { "url": apt.inner.url().unwrap_or_else(|| apt.source.clone()) },
+    });
+    (apt.id().to_owned(), properties)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::fixtures::craigslist_post;
+    use crate::api::fixtures::CRAIGSLIST_SEARCH;
+
+    #[test]
+    fn test_properties() {
+        let apt = Apartment::new(CRAIGSLIST_SEARCH, craigslist_post());
+        let (id, properties) = properties(&apt);
+        assert_eq!(id, "craigslist-7551234567");
+        assert_eq!(properties["Rent"], json!({ "number": 3000.0 }));
+        assert_eq!(properties["Sq ft"], json!({ "number": null }));
+        assert_eq!(properties["Available"], json!({ "date": null }));
+        assert_eq!(
+            properties["Status"],
+            json!({ "select": { "name": "Listed" } })
+        );
+        assert_eq!(
+            properties["URL"],
+            json!({ "url": "https://seattle.craigslist.org/see/apa/d/7551234567.html" })
+        );
+
+        let page: Page = serde_json::from_value(json!({
+            "id": "59833787-2cf9-4fdf-8782-e53db20768a5",
+            "properties": { "ID": { "rich_text": [{ "plain_text": id }] } },
+        }))
+        .unwrap();
+        assert_eq!(page.listing_id(), Some("craigslist-7551234567"));
+    }
+}
@@ -0,0 +1,133 @@
+//! Machine-readable output for subcommands, with `--output json`, for piping into `jq` and
+//! scripts:
+//!
+//! ```sh
+//! ava-apartment-finder list --output json | jq '.[] | select(.rent < 4000) | .url'
+//! ```
+//!
+//! Each subcommand prints one JSON value:
+//!
+//! - `list`: an array of [`ListingJson`], best first.
+//! - `compare`: an array of [`ListingJson`], in the order given.
+//! - `report`: a [`crate::market_report::MarketReport`].
+//! - `diff-db`: a [`crate::diff_db::DbDiff`].
+//!
+//! Other subcommands don't print any data, and ignore `--output`.
+//!
+//! The JSON is stable: fields may be added, but won't be renamed or removed. Amounts of money
+//! are numbers of dollars, dates are `YYYY-MM-DD`, and times are RFC 3339.
+
+use chrono::DateTime;
+use chrono::NaiveDate;
+use chrono::Utc;
+use color_eyre::eyre;
+use color_eyre::eyre::Context;
+use serde::Serialize;
+
+use crate::api::Apartment;
+use crate::filter::Value;
+use crate::listing::Listing;
+use crate::money::Money;
+use crate::score::ScoreWeights;
+
+/// How subcommands print their results, set with `--output`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text.
+    #[default]
+    Text,
+    /// JSON, as documented in [`crate::output`].
+    Json,
+}
+
+/// A listing in JSON output. Fields a source doesn't know, like the floor of a Craigslist post,
+/// are `null`.
+#[derive(Debug, Serialize)]
+pub struct ListingJson {
+    pub id: String,
+    /// The URL of the source this listing was fetched from.
+    pub source: String,
+    /// A human-readable description of the listing.
+    pub summary: String,
+    /// A link to the listing's own page, if it has one.
+    pub url: Option<String>,
+    pub rent: Option<Money>,
+    /// The highest rent we've seen for this listing.
+    pub max_rent: Option<Money>,
+    /// The monthly rent with promotions amortized over the lease.
+    pub effective_rent: Option<Money>,
+    pub price_per_sqft: Option<Money>,
+    pub floor: Option<u32>,
+    pub available_date: Option<NaiveDate>,
+    /// The listing's rank from the `score` config; higher is better. `null` for listings
+    /// without a rent, which rank last.
+    pub score: f64,
+    pub listed: DateTime<Utc>,
+    pub unlisted: Option<DateTime<Utc>>,
+}
+
+impl ListingJson {
+    /// `apt` as of `now`, scored with `weights`.
+    pub fn new<T: Listing>(apt: &Apartment<T>, weights: &ScoreWeights, now: DateTime<Utc>) -> Self {
+        let number = |name| match apt.inner.field(name) {
+            Some(Value::Number(number)) => Some(number),
+            _ => None,
+        };
+        Self {
+            id: apt.id().to_owned(),
+            source: apt.source.clone(),
+            summary: apt.inner.to_string(),
+            url: apt.inner.url(),
+            rent: apt.inner.rent(),
+            max_rent: apt.max_rent,
+            effective_rent: number("effective_rent").map(Money::from_dollars),
+            price_per_sqft: number("price_per_sqft").map(Money::from_dollars),
+            floor: number("floor").map(|floor| floor as u32),
+            available_date: apt.inner.available_date(),
+            score: weights.score(&apt.inner, now.date_naive()),
+            listed: apt.listed,
+            unlisted: apt.unlisted,
+        }
+    }
+}
+
+/// `value` as pretty-printed JSON, with a trailing newline.
+pub fn json(value: &impl Serialize) -> eyre::Result<String> {
+    let mut json = serde_json::to_string_pretty(value).wrap_err("Failed to serialize output")?;
+    json.push('\n');
+    Ok(json)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use expect_test::expect;
+
+    use super::*;
+    use crate::api::fixtures::apartment_731;
+
+    #[test]
+    fn test_listing_json() {
+        let now = Utc.ymd(2022, 10, 21).and_hms_opt(12, 0, 0).unwrap();
+        let mut apt = Apartment::new(crate::AVA_URL, apartment_731());
+        apt.listed = now - chrono::Duration::days(12);
+        let listing = ListingJson::new(&apt, &ScoreWeights::default(), now);
+        expect![[r#"
+            {
+              "id": "AVB-WA026-001-731",
+              "source": "https://new.avaloncommunities.com/washington/seattle-apartments/ava-capitol-hill/",
+              "summary": "Apartment 731 (7th floor, 2 bed 2 bath, $4,260, 1268sq/ft ($3.36/sq/ft), avail. Oct 21 2022, plan f-b4v)",
+              "url": null,
+              "rent": 4260.0,
+              "max_rent": 4260.0,
+              "effective_rent": 4260.0,
+              "price_per_sqft": 3.36,
+              "floor": 7,
+              "available_date": "2022-10-21",
+              "score": -29.92,
+              "listed": "2022-10-09T12:00:00Z",
+              "unlisted": null
+            }
+        "#]].assert_eq(&json(&listing).unwrap());
+    }
+}
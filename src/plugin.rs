@@ -0,0 +1,290 @@
+//! WASM plugins which decide what to notify about, configured like:
+//!
+//! ```toml
+//! plugins = ["/home/me/.config/ava-apartment-finder/no-ground-floor.wasm"]
+//! ```
+//!
+//! Requires the `plugins` feature.
+//!
+//! Each plugin is a WASM module (with no imports) which exports its `memory` and two
+//! functions:
+//!
+//! - `alloc(len: i32) -> i32` returns a pointer to `len` bytes the host can write to.
+//! - `on_event(ptr: i32, len: i32) -> i64` is called with a JSON event at `ptr`, like
+//!   `{"event": {"kind": "listed", "id": "...", "summary": "...", ...}, "listing": {...}}`,
+//!   and returns a pointer to its JSON [`Decision`] in the high 32 bits and its length in
+//!   the low 32 bits.
+//!
+//! A decision looks like `{"notify": false}`, `{"score": 12.5}`, or `{"text": "..."}`; any
+//! field can be left out to leave it up to the config. Plugins are run in order, each in a
+//! fresh instance, so they can't keep state between events.
+
+use camino::Utf8PathBuf;
+use color_eyre::eyre;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::events::Event;
+use crate::listing::Listing;
+
+/// What a plugin wants done about an event.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Decision {
+    /// Notify about the listing (even if it doesn't meet the qualifications) or don't (even
+    /// if it does). Ignored and snoozed units are never notified about.
+    pub notify: Option<bool>,
+    /// Use this score for the listing instead of the configured score weights.
+    pub score: Option<f64>,
+    /// Extra text to add to the notification.
+    pub text: Option<String>,
+}
+
+impl Decision {
+    /// Combine decisions from plugins run in order: any plugin can suppress a notification,
+    /// later scores win, and text is joined.
+    fn and(self, next: Decision) -> Decision {
+        Decision {
+            notify: match (self.notify, next.notify) {
+                (Some(false), _) | (_, Some(false)) => Some(false),
+                (notify, None) | (None, notify) => notify,
+                (Some(true), Some(true)) => Some(true),
+            },
+            score: next.score.or(self.score),
+            text: match (self.text, next.text) {
+                (Some(text), Some(next)) => Some(format!("{text}\n{next}")),
+                (text, next) => text.or(next),
+            },
+        }
+    }
+}
+
+/// The JSON a plugin's `on_event` is called with.
+#[derive(Serialize)]
+struct Input<'a, T> {
+    event: &'a Event,
+    listing: &'a T,
+}
+
+/// The configured plugins, loaded and compiled.
+#[derive(Default)]
+pub struct Plugins {
+    #[cfg(feature = "plugins")]
+    plugins: Vec<imp::Plugin>,
+}
+
+impl Plugins {
+    #[cfg(feature = "plugins")]
+    pub fn load(paths: &[Utf8PathBuf]) -> eyre::Result<Self> {
+        let engine = imp::engine()?;
+        Ok(Self {
+            plugins: paths
+                .iter()
+                .map(|path| imp::Plugin::load(&engine, path))
+                .collect::<eyre::Result<_>>()?,
+        })
+    }
+
+    #[cfg(not(feature = "plugins"))]
+    pub fn load(paths: &[Utf8PathBuf]) -> eyre::Result<Self> {
+        if paths.is_empty() {
+            Ok(Self::default())
+        } else {
+            Err(eyre::eyre!(
+                "`plugins` are configured, but ava-apartment-finder was built without the \
+                 `plugins` feature"
+            ))
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        #[cfg(feature = "plugins")]
+        return self.plugins.is_empty();
+        #[cfg(not(feature = "plugins"))]
+        return true;
+    }
+
+    /// Ask each plugin what to do about `event`, which happened to `listing`.
+    ///
+    /// A plugin which fails is logged and skipped, so one bad plugin can't stop notifications.
+    pub fn decide<T: Listing>(&self, event: &Event, listing: &T) -> Decision {
+        if self.is_empty() {
+            return Decision::default();
+        }
+        let input = match serde_json::to_vec(&Input { event, listing }) {
+            Ok(input) => input,
+            Err(err) => {
+                tracing::warn!(
+                    id = event.id,
+                    "Failed to serialize event for plugins: {err}"
+                );
+                return Decision::default();
+            }
+        };
+        self.decide_json(&event.id, &input)
+    }
+
+    #[cfg(feature = "plugins")]
+    fn decide_json(&self, id: &str, input: &[u8]) -> Decision {
+        self.plugins
+            .iter()
+            .filter_map(|plugin| match plugin.call(input) {
+                Ok(decision) => {
+                    tracing::debug!(id, plugin = %plugin.path, ?decision, "Plugin decided");
+                    Some(decision)
+                }
+                Err(err) => {
+                    tracing::warn!(id, plugin = %plugin.path, "Plugin failed: {err:?}");
+                    None
+                }
+            })
+            .fold(Decision::default(), Decision::and)
+    }
+
+    #[cfg(not(feature = "plugins"))]
+    fn decide_json(&self, _id: &str, _input: &[u8]) -> Decision {
+        Decision::default()
+    }
+}
+
+#[cfg(feature = "plugins")]
+mod imp {
+    use camino::Utf8Path;
+    use camino::Utf8PathBuf;
+    use color_eyre::eyre;
+    use color_eyre::eyre::eyre;
+    use color_eyre::eyre::Context;
+    use wasmtime::Engine;
+    use wasmtime::Instance;
+    use wasmtime::Module;
+    use wasmtime::Store;
+
+    use super::Decision;
+
+    /// How many WASM instructions (roughly) a plugin gets per event, so a plugin stuck in a
+    /// loop can't hang the tick.
+    const FUEL: u64 = 100_000_000;
+
+    /// `wasmtime` errors are `anyhow` errors, which don't implement `std::error::Error`.
+    fn wasm_err(err: wasmtime::Error) -> eyre::Report {
+        eyre!("{err:#}")
+    }
+
+    pub fn engine() -> eyre::Result<Engine> {
+        let mut config = wasmtime::Config::new();
+        config.consume_fuel(true);
+        Engine::new(&config).map_err(wasm_err)
+    }
+
+    pub struct Plugin {
+        pub path: Utf8PathBuf,
+        engine: Engine,
+        module: Module,
+    }
+
+    impl Plugin {
+        pub fn load(engine: &Engine, path: &Utf8Path) -> eyre::Result<Self> {
+            let module = Module::from_file(engine, path)
+                .map_err(wasm_err)
+                .wrap_err_with(|| format!("Failed to load plugin `{path}`"))?;
+            Ok(Self {
+                path: path.to_owned(),
+                engine: engine.clone(),
+                module,
+            })
+        }
+
+        /// Call the plugin's `on_event` with `input` in a fresh instance.
+        pub fn call(&self, input: &[u8]) -> eyre::Result<Decision> {
+            let mut store = Store::new(&self.engine, ());
+            store.add_fuel(FUEL).map_err(wasm_err)?;
+            let instance = Instance::new(&mut store, &self.module, &[]).map_err(wasm_err)?;
+            let memory = instance
+                .get_memory(&mut store, "memory")
+                .ok_or_else(|| eyre!("Plugin doesn't export `memory`"))?;
+            let alloc = instance
+                .get_typed_func::<i32, i32>(&mut store, "alloc")
+                .map_err(wasm_err)?;
+            let on_event = instance
+                .get_typed_func::<(i32, i32), i64>(&mut store, "on_event")
+                .map_err(wasm_err)?;
+
+            let len = i32::try_from(input.len()).wrap_err("Event is too big for a plugin")?;
+            let ptr = alloc.call(&mut store, len).map_err(wasm_err)?;
+            memory
+                .write(&mut store, ptr as u32 as usize, input)
+                .wrap_err("`alloc` returned an invalid pointer")?;
+
+            let output = on_event.call(&mut store, (ptr, len)).map_err(wasm_err)?;
+            let (ptr, len) = ((output >> 32) as u32 as usize, output as u32 as usize);
+            let output = memory
+                .data(&store)
+                .get(ptr..ptr + len)
+                .ok_or_else(|| eyre!("`on_event` returned an invalid pointer"))?;
+            serde_json::from_slice(output).wrap_err_with(|| {
+                format!(
+                    "Failed to parse decision {:?}",
+                    String::from_utf8_lossy(output)
+                )
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decision_and() {
+        let decision = |json: &str| serde_json::from_str::<Decision>(json).unwrap();
+        assert_eq!(
+            decision(r#"{"notify": true, "score": 1.0, "text": "Corner unit"}"#)
+                .and(decision(r#"{"score": 2.0, "text": "Near the park"}"#)),
+            Decision {
+                notify: Some(true),
+                score: Some(2.0),
+                text: Some("Corner unit\nNear the park".to_owned()),
+            }
+        );
+        assert_eq!(
+            decision(r#"{"notify": false}"#).and(decision(r#"{"notify": true}"#)),
+            decision(r#"{"notify": false}"#)
+        );
+        assert_eq!(
+            Decision::default().and(Decision::default()),
+            Decision::default()
+        );
+    }
+
+    /// A plugin which suppresses every event, returning a decision it keeps at address 0.
+    #[cfg(feature = "plugins")]
+    #[test]
+    fn test_plugin() {
+        let dir = std::env::temp_dir().join(format!("ava-plugin-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = Utf8PathBuf::from_path_buf(dir.join("suppress.wat")).unwrap();
+        std::fs::write(
+            &path,
+            r#"(module
+                (memory (export "memory") 1)
+                (data (i32.const 0) "{\"notify\": false, \"text\": \"nope\"}")
+                (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+                (func (export "on_event") (param i32 i32) (result i64) (i64.const 33)))"#,
+        )
+        .unwrap();
+
+        let plugins = Plugins::load(&[path]).unwrap();
+        let listing = crate::api::fixtures::apartment_731();
+        let event = Event::new(crate::events::EventKind::Listed, crate::AVA_URL, &listing);
+        assert_eq!(
+            plugins.decide(&event, &listing),
+            Decision {
+                notify: Some(false),
+                score: None,
+                text: Some("nope".to_owned()),
+            }
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
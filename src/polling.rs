@@ -0,0 +1,160 @@
+//! Adaptive polling: check more often while listings are changing, and back off when nothing has
+//! happened for a while.
+
+use std::time::Duration;
+
+use chrono::DateTime;
+use chrono::Utc;
+use serde::Deserialize;
+
+use crate::events::Event;
+
+/// How often to poll.
+///
+/// Configured in the `[polling]` table of the config file, like:
+///
+/// ```toml
+/// [polling]
+/// interval-minutes = 5
+/// min-interval-minutes = 2
+/// max-interval-minutes = 30
+/// ```
+///
+/// Set all three to the same value to poll at a fixed rate.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Polling {
+    /// How often to poll normally.
+    pub interval_minutes: f64,
+    /// How often to poll while listings are changing.
+    pub min_interval_minutes: f64,
+    /// The longest to wait between polls when nothing's changed.
+    pub max_interval_minutes: f64,
+    /// Poll at `min-interval-minutes` when at least this many events happened in the last hour.
+    pub busy_events_per_hour: usize,
+    /// Double the interval (up to `max-interval-minutes`) for each this many hours without any
+    /// events.
+    pub quiet_hours: f64,
+}
+
+impl Default for Polling {
+    fn default() -> Self {
+        Self {
+            interval_minutes: 5.0,
+            min_interval_minutes: 2.0,
+            max_interval_minutes: 30.0,
+            busy_events_per_hour: 3,
+            quiet_hours: 2.0,
+        }
+    }
+}
+
+impl Polling {
+    /// The shortest we'll ever wait between polls.
+    pub fn min_interval(&self) -> Duration {
+        minutes(self.min_interval_minutes.min(self.interval_minutes))
+    }
+
+    /// The longest we'll ever wait between polls.
+    pub fn max_interval(&self) -> Duration {
+        minutes(self.max_interval_minutes.max(self.interval_minutes))
+    }
+
+    /// How long to wait before the next poll, given the `events` so far.
+    pub fn next_interval(&self, events: &[Event], now: DateTime<Utc>) -> Duration {
+        let hour_ago = now - chrono::Duration::hours(1);
+        let recent = events
+            .iter()
+            .rev()
+            .take_while(|event| event.time > hour_ago)
+            .count();
+        if recent >= self.busy_events_per_hour {
+            return self.min_interval();
+        }
+
+        let quiet_hours = match events.last() {
+            Some(event) => (now - event.time).num_minutes() as f64 / 60.0,
+            // Nothing has ever happened; we've probably just started tracking.
+            None => 0.0,
+        };
+        let doublings = if self.quiet_hours > 0.0 {
+            (quiet_hours / self.quiet_hours).floor().min(16.0)
+        } else {
+            0.0
+        };
+        let interval = self.interval_minutes * 2_f64.powf(doublings);
+        minutes(interval).min(self.max_interval())
+    }
+
+    /// Back off from `interval` after `failures` failed ticks in a row, doubling it for each
+    /// failure up to the max interval.
+    pub fn backoff(&self, interval: Duration, failures: usize) -> Duration {
+        if failures == 0 {
+            return interval;
+        }
+        let factor = 2_u32.saturating_pow(failures.min(16) as u32);
+        interval
+            .saturating_mul(factor)
+            .min(self.max_interval())
+            .max(interval)
+    }
+}
+
+fn minutes(minutes: f64) -> Duration {
+    Duration::from_secs_f64(minutes.max(0.0) * 60.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::events::EventKind;
+    use crate::money::Money;
+
+    fn event(time: DateTime<Utc>) -> Event {
+        Event {
+            seq: 0,
+            time,
+            id: "AVB-WA026-001-731".to_owned(),
+            source: crate::AVA_URL.to_owned(),
+            url: None,
+            kind: EventKind::Changed,
+            rent: Some(Money::from_dollars(3000.0)),
+            summary: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_next_interval() {
+        let polling = Polling::default();
+        let now = Utc.ymd(2022, 10, 21).and_hms_opt(12, 0, 0).unwrap();
+        let ago = |minutes| event(now - chrono::Duration::minutes(minutes));
+
+        assert_eq!(polling.next_interval(&[], now), minutes(5.0));
+        // Busy.
+        assert_eq!(
+            polling.next_interval(&[ago(50), ago(30), ago(10)], now),
+            minutes(2.0)
+        );
+        // Not busy enough.
+        assert_eq!(
+            polling.next_interval(&[ago(90), ago(30), ago(10)], now),
+            minutes(5.0)
+        );
+        // Quiet.
+        assert_eq!(polling.next_interval(&[ago(150)], now), minutes(10.0));
+        assert_eq!(polling.next_interval(&[ago(250)], now), minutes(20.0));
+        assert_eq!(polling.next_interval(&[ago(60 * 24)], now), minutes(30.0));
+    }
+
+    #[test]
+    fn test_backoff() {
+        let polling = Polling::default();
+        assert_eq!(polling.backoff(minutes(5.0), 0), minutes(5.0));
+        assert_eq!(polling.backoff(minutes(5.0), 1), minutes(10.0));
+        assert_eq!(polling.backoff(minutes(5.0), 2), minutes(20.0));
+        assert_eq!(polling.backoff(minutes(5.0), 3), minutes(30.0));
+        assert_eq!(polling.backoff(minutes(5.0), 100), minutes(30.0));
+    }
+}
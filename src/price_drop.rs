@@ -0,0 +1,160 @@
+//! Alerts for when a tracked listing's rent drops.
+
+use std::fmt::Display;
+
+use serde::Deserialize;
+
+use crate::money::Money;
+
+/// When to alert about a listing's rent dropping.
+///
+/// An alert is sent when the rent drops by more than `amount` dollars _or_ more than `percent`
+/// percent. If neither is set, no alerts are sent.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct PriceDropAlert {
+    /// Alert when rent drops by more than this many dollars.
+    pub amount: Option<Money>,
+
+    /// Alert when rent drops by more than this percentage, like `5` for 5%.
+    pub percent: Option<f64>,
+
+    /// What to measure the drop from.
+    pub since: Baseline,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Baseline {
+    /// The rent the last time we saw the listing.
+    #[default]
+    Previous,
+    /// The highest rent we've ever seen for the listing.
+    High,
+}
+
+impl PriceDropAlert {
+    /// Check if the rent changing from `previous` to `rent` is worth an alert.
+    ///
+    /// `high` is the highest rent seen for the listing before this observation. We only alert when
+    /// the rent has just dropped, so a listing which stays below its high doesn't alert on every
+    /// unrelated change.
+    pub fn check(&self, previous: Money, high: Option<Money>, rent: Money) -> Option<PriceDrop> {
+        if rent >= previous {
+            return None;
+        }
+
+        let from = match self.since {
+            Baseline::Previous => previous,
+            Baseline::High => high.map_or(previous, |high| high.max(previous)),
+        };
+        let drop = PriceDrop { from, to: rent };
+
+        let over_amount = self.amount.map_or(false, |amount| drop.amount() > amount);
+        let over_percent = self
+            .percent
+            .map_or(false, |percent| drop.percent() > percent);
+        (over_amount || over_percent).then_some(drop)
+    }
+}
+
+/// A drop in rent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PriceDrop {
+    pub from: Money,
+    pub to: Money,
+}
+
+impl PriceDrop {
+    pub fn amount(&self) -> Money {
+        self.from - self.to
+    }
+
+    pub fn percent(&self) -> f64 {
+        100.0 * self.amount().dollars() / self.from.dollars()
+    }
+}
+
+impl Display for PriceDrop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({:.1}%) to {}",
+            self.amount(),
+            self.percent(),
+            self.to
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dollars(dollars: f64) -> Money {
+        Money::from_dollars(dollars)
+    }
+
+    #[test]
+    fn test_check() {
+        let alert = PriceDropAlert {
+            amount: Some(dollars(100.0)),
+            percent: Some(5.0),
+            since: Baseline::Previous,
+        };
+
+        // Rent went up.
+        assert_eq!(alert.check(dollars(3000.0), None, dollars(3100.0)), None);
+        // Too small a drop.
+        assert_eq!(alert.check(dollars(3000.0), None, dollars(2950.0)), None);
+        // Over the amount threshold.
+        assert_eq!(
+            alert.check(dollars(3000.0), None, dollars(2890.0)),
+            Some(PriceDrop {
+                from: dollars(3000.0),
+                to: dollars(2890.0)
+            })
+        );
+        // Over the percentage threshold but not the amount threshold.
+        let alert = PriceDropAlert {
+            amount: Some(dollars(1000.0)),
+            ..alert
+        };
+        assert!(alert
+            .check(dollars(3000.0), None, dollars(2800.0))
+            .is_some());
+
+        // Measured from the high, but only when the rent just dropped.
+        let alert = PriceDropAlert {
+            amount: Some(dollars(100.0)),
+            percent: None,
+            since: Baseline::High,
+        };
+        assert_eq!(
+            alert.check(dollars(2950.0), Some(dollars(3000.0)), dollars(2890.0)),
+            Some(PriceDrop {
+                from: dollars(3000.0),
+                to: dollars(2890.0)
+            })
+        );
+        assert_eq!(
+            alert.check(dollars(2890.0), Some(dollars(3000.0)), dollars(2890.0)),
+            None
+        );
+
+        // Disabled by default.
+        assert_eq!(
+            PriceDropAlert::default().check(dollars(3000.0), None, dollars(1000.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let drop = PriceDrop {
+            from: dollars(3000.0),
+            to: dollars(2850.0),
+        };
+        assert_eq!(drop.to_string(), "$150 (5.0%) to $2,850");
+    }
+}
@@ -0,0 +1,173 @@
+//! The lowest and highest rents we've seen, for context in notifications: a unit listed at
+//! $4,260 is a much better deal if it's been as high as $4,600 than if it's been as low as $4,090.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use chrono::DateTime;
+use chrono::Utc;
+
+use crate::api::Apartment;
+use crate::events;
+use crate::events::Event;
+use crate::filter::Value;
+use crate::listing::Listing;
+use crate::money::Money;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PriceRange {
+    pub lowest: (DateTime<Utc>, Money),
+    pub highest: (DateTime<Utc>, Money),
+}
+
+impl PriceRange {
+    /// The range of rents in `history`, or `None` if it's empty. Ties go to the earliest.
+    pub fn new(history: impl IntoIterator<Item = (DateTime<Utc>, Money)>) -> Option<Self> {
+        history.into_iter().fold(None, |range, point| {
+            Some(match range {
+                None => Self {
+                    lowest: point,
+                    highest: point,
+                },
+                Some(Self { lowest, highest }) => Self {
+                    lowest: if point.1 < lowest.1 { point } else { lowest },
+                    highest: if point.1 > highest.1 { point } else { highest },
+                },
+            })
+        })
+    }
+
+    /// Have we seen more than one price?
+    pub fn varies(&self) -> bool {
+        self.lowest.1 != self.highest.1
+    }
+}
+
+impl Display for PriceRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            lowest: (lowest_time, lowest),
+            highest: (highest_time, highest),
+        } = self;
+        write!(
+            f,
+            "lowest seen {lowest} on {}, highest seen {highest} on {}",
+            crate::timezone::format(*lowest_time, "%b %e %Y"),
+            crate::timezone::format(*highest_time, "%b %e %Y"),
+        )
+    }
+}
+
+/// The range of rents seen for each floor plan, across all of `apartments`, from their
+/// [`events::price_histories`].
+pub fn by_floor_plan<'a, T: Listing + 'a>(
+    apartments: impl IntoIterator<Item = &'a Apartment<T>>,
+    histories: &HashMap<&str, Vec<(DateTime<Utc>, Money)>>,
+) -> BTreeMap<String, PriceRange> {
+    let mut by_plan: BTreeMap<String, Vec<(DateTime<Utc>, Money)>> = BTreeMap::new();
+    for apt in apartments {
+        if let Some(Value::String(plan)) = apt.inner.field("plan") {
+            by_plan
+                .entry(plan)
+                .or_default()
+                .extend(histories.get(apt.id()).into_iter().flatten());
+        }
+    }
+    by_plan
+        .into_iter()
+        .filter_map(|(plan, history)| Some((plan, PriceRange::new(history)?)))
+        .collect()
+}
+
+/// Describe `listing`'s current rent in the context of its own history and its floor plan's,
+/// like "Current rent $4,260; lowest seen $4,090 on Aug 3 2022, highest seen ...".
+///
+/// Returns `None` if we haven't seen the price change, so there's nothing interesting to say.
+pub fn describe(
+    listing: &impl Listing,
+    events: &[Event],
+    floor_plans: &BTreeMap<String, PriceRange>,
+) -> Option<String> {
+    let rent = listing.rent()?;
+    let unit = PriceRange::new(events::price_history(events, listing.id()))
+        .filter(PriceRange::varies)
+        .map(|range| format!("\nThis unit: {range}"));
+    let floor_plan = match listing.field("plan") {
+        Some(Value::String(plan)) => floor_plans
+            .get(&plan)
+            .filter(|range| range.varies())
+            .map(|range| format!("\nFloor plan {plan}: {range}")),
+        _ => None,
+    };
+    if unit.is_none() && floor_plan.is_none() {
+        return None;
+    }
+    Some(format!(
+        "Current rent {rent}{}{}",
+        unit.unwrap_or_default(),
+        floor_plan.unwrap_or_default()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_price_range() {
+        let day = |day| Utc.ymd(2022, 8, day).and_hms_opt(12, 0, 0).unwrap();
+        let dollars = Money::from_dollars;
+        assert_eq!(PriceRange::new([]), None);
+
+        let range = PriceRange::new([
+            (day(1), dollars(4260.0)),
+            (day(3), dollars(4090.0)),
+            (day(10), dollars(4400.0)),
+            (day(20), dollars(4090.0)),
+        ])
+        .unwrap();
+        assert_eq!(
+            range,
+            PriceRange {
+                lowest: (day(3), dollars(4090.0)),
+                highest: (day(10), dollars(4400.0)),
+            }
+        );
+        assert!(range.varies());
+        assert_eq!(
+            range.to_string(),
+            "lowest seen $4,090 on Aug  3 2022, highest seen $4,400 on Aug 10 2022"
+        );
+
+        assert!(!PriceRange::new([(day(1), dollars(4260.0))])
+            .unwrap()
+            .varies());
+    }
+
+    #[test]
+    fn test_by_floor_plan() {
+        use crate::api::fixtures::apartment_731;
+        use crate::api::fixtures::apartment_731_at;
+        use crate::events::EventKind;
+
+        let mut other = apartment_731_at(4400.0);
+        other.unit_id = "AVB-WA026-001-732".to_owned();
+        let events: Vec<_> = [apartment_731(), other.clone(), apartment_731_at(4100.0)]
+            .iter()
+            .map(|unit| Event::new(EventKind::Changed, crate::AVA_URL, unit))
+            .collect();
+        let apartments = [
+            Apartment::new(crate::AVA_URL, apartment_731()),
+            Apartment::new(crate::AVA_URL, other),
+        ];
+
+        let ranges = by_floor_plan(&apartments, &events::price_histories(&events));
+        assert_eq!(ranges.keys().collect::<Vec<_>>(), ["f-b4v"]);
+        let range = ranges["f-b4v"];
+        assert_eq!(range.lowest.1, Money::from_dollars(4100.0));
+        assert_eq!(range.highest.1, Money::from_dollars(4400.0));
+    }
+}
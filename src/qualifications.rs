@@ -0,0 +1,213 @@
+//! User-configurable criteria for which apartments we care about.
+
+use std::cmp::Ordering;
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use crate::filter::Filter;
+use crate::filter::Value;
+use crate::geo::Location;
+use crate::money::Money;
+
+/// Criteria an apartment must meet for us to notify about it.
+///
+/// Configured in the `[qualifications]` table of the config file, like:
+///
+/// ```toml
+/// [qualifications]
+/// bedrooms = { min = 2, max = 2 }
+/// rent = { max = 4300 }
+/// use-effective-rent = true
+/// square-feet = { min = 900 }
+/// price-per-square-foot = { max = 3.5 }
+/// floor = { min = 4 }
+/// allow-furnished = false
+/// require-virtual-tour = true
+/// available-after = "2023-01-15"
+/// available-before = "2023-03-01"
+/// floor-plans = ["f-b4v", "f-b2"]
+/// filter = "bathroom >= 2 || sqft / rent > 0.28"
+/// near = { latitude = 47.6062, longitude = -122.3321, within-km = 2 }
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Qualifications {
+    pub bedrooms: Bounds<usize>,
+    pub bathrooms: Bounds<usize>,
+    /// Monthly rent, in dollars.
+    pub rent: Bounds<Money>,
+    /// Check `rent` against the effective rent, with promotions like "1 month free" amortized
+    /// over the lease, rather than the sticker price.
+    pub use_effective_rent: bool,
+    pub square_feet: Bounds<f64>,
+    /// Monthly rent per square foot, in dollars. Uses the effective rent if `use_effective_rent`
+    /// is set.
+    pub price_per_square_foot: Bounds<f64>,
+    /// The floor the apartment is on. Apartments whose floor is unknown aren't checked.
+    pub floor: Bounds<u32>,
+    /// Whether to consider furnished apartments.
+    pub allow_furnished: bool,
+    /// Only consider apartments with a virtual tour of the actual unit, rather than a similar
+    /// one.
+    pub require_virtual_tour: bool,
+    /// Skip apartments available before this date, e.g. long before your current lease ends.
+    ///
+    /// They're still tracked, so their price history is available if they're relisted later.
+    pub available_after: Option<NaiveDate>,
+    /// Skip apartments available after this date.
+    pub available_before: Option<NaiveDate>,
+    /// Only consider apartments with these floor plans, like `f-b4v`. If empty, all floor plans
+    /// are allowed.
+    pub floor_plans: Vec<String>,
+    /// Skip apartments with these floor plans.
+    pub exclude_floor_plans: Vec<String>,
+    /// An expression apartments must match, for criteria the other fields can't express.
+    ///
+    /// See [`crate::filter`] for the syntax.
+    pub filter: Option<Filter>,
+    /// Only consider apartments within some distance of a point, like your office.
+    ///
+    /// Needs `[geocoding]` to know where communities are; listings whose location is unknown
+    /// aren't checked.
+    pub near: Option<Near>,
+}
+
+impl Default for Qualifications {
+    fn default() -> Self {
+        Self {
+            bedrooms: Bounds::exactly(2),
+            bathrooms: Default::default(),
+            rent: Default::default(),
+            use_effective_rent: false,
+            square_feet: Default::default(),
+            price_per_square_foot: Default::default(),
+            floor: Default::default(),
+            allow_furnished: false,
+            require_virtual_tour: false,
+            available_after: None,
+            available_before: None,
+            floor_plans: Vec::new(),
+            exclude_floor_plans: Vec::new(),
+            filter: None,
+            near: None,
+        }
+    }
+}
+
+impl Qualifications {
+    /// Check `available_after` and `available_before` against a listing's availability date.
+    ///
+    /// Returns the reason the listing doesn't match, like [`Bounds::check`].
+    pub fn check_available(&self, available: NaiveDate) -> Option<&'static str> {
+        Bounds {
+            min: self.available_after,
+            max: self.available_before,
+        }
+        .check(&available, "available too soon", "available too late")
+    }
+
+    /// Check `floor_plans` and `exclude_floor_plans` against a listing's floor plan name.
+    ///
+    /// Returns the reason the listing doesn't match, like [`Bounds::check`].
+    pub fn check_floor_plan(&self, floor_plan: &str) -> Option<&'static str> {
+        if self
+            .exclude_floor_plans
+            .iter()
+            .any(|plan| plan == floor_plan)
+        {
+            Some("floor plan excluded")
+        } else if !self.floor_plans.is_empty()
+            && !self.floor_plans.iter().any(|plan| plan == floor_plan)
+        {
+            Some("floor plan not allowed")
+        } else {
+            None
+        }
+    }
+
+    /// Check the `filter` expression, if any, against a listing's fields.
+    ///
+    /// Returns the reason the listing doesn't match, like [`Bounds::check`].
+    pub fn check_filter(&self, field: impl Fn(&str) -> Option<Value>) -> Option<&'static str> {
+        let filter = self.filter.as_ref()?;
+        match filter.matches(field) {
+            Ok(true) => None,
+            Ok(false) => Some("doesn't match filter"),
+            Err(err) => {
+                tracing::warn!(%filter, "Failed to evaluate filter: {err}");
+                Some("filter failed")
+            }
+        }
+    }
+
+    /// Check `near` against the location of a listing's community, if it's known.
+    ///
+    /// Returns the reason the listing doesn't match, like [`Bounds::check`].
+    pub fn check_location(&self, location: Option<&Location>) -> Option<&'static str> {
+        let near = self.near.as_ref()?;
+        let distance = near.location.distance_km(location?);
+        (distance > near.within_km).then_some("too far away")
+    }
+}
+
+/// A point and how far from it apartments can be.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Near {
+    #[serde(flatten)]
+    pub location: Location,
+    /// The maximum distance, in kilometers.
+    pub within_km: f64,
+}
+
+/// Inclusive bounds on a value. Either end may be left open.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Bounds<T> {
+    pub min: Option<T>,
+    pub max: Option<T>,
+}
+
+impl<T> Default for Bounds<T> {
+    fn default() -> Self {
+        Self {
+            min: None,
+            max: None,
+        }
+    }
+}
+
+impl<T: PartialOrd + Clone> Bounds<T> {
+    pub fn exactly(value: T) -> Self {
+        Self {
+            min: Some(value.clone()),
+            max: Some(value),
+        }
+    }
+
+    /// Compare `value` to the bounds: [`Ordering::Less`] if it's below the minimum,
+    /// [`Ordering::Greater`] if it's above the maximum, and [`Ordering::Equal`] if it's within
+    /// the bounds.
+    pub fn compare(&self, value: &T) -> Ordering {
+        match (&self.min, &self.max) {
+            (Some(min), _) if value < min => Ordering::Less,
+            (_, Some(max)) if value > max => Ordering::Greater,
+            _ => Ordering::Equal,
+        }
+    }
+
+    /// If `value` is out of bounds, return `too_low` or `too_high` as appropriate.
+    pub fn check(
+        &self,
+        value: &T,
+        too_low: &'static str,
+        too_high: &'static str,
+    ) -> Option<&'static str> {
+        match self.compare(value) {
+            Ordering::Less => Some(too_low),
+            Ordering::Equal => None,
+            Ordering::Greater => Some(too_high),
+        }
+    }
+}
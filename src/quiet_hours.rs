@@ -0,0 +1,84 @@
+//! Holding non-urgent notifications until morning, configured like:
+//!
+//! ```toml
+//! quiet-hours = { start = 22, end = 8 }
+//! ```
+//!
+//! Hours are in the display [`crate::timezone`]. Emails composed during quiet hours are handed
+//! to the JMAP server right away, but with a `send_at` of the end of quiet hours, so they're
+//! delivered then. New listings, price drops, and alerts are always sent immediately. Servers
+//! which don't support delayed sending (`maxDelayedSend` is 0) deliver everything immediately.
+
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::NaiveDateTime;
+use chrono::Timelike;
+use chrono::Utc;
+use serde::Deserialize;
+
+use crate::timezone::DisplayTimeZone;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct QuietHours {
+    /// The hour quiet hours start at, from 0 to 23.
+    pub start: u32,
+    /// The hour quiet hours end at, like 8 for 8am.
+    pub end: u32,
+}
+
+impl QuietHours {
+    /// When to deliver a non-urgent email composed at `now`, or `None` to deliver it now.
+    pub fn send_at(&self, now: DateTime<Utc>, timezone: DisplayTimeZone) -> Option<DateTime<Utc>> {
+        let end = self.end_after(timezone.naive_local(now))?;
+        timezone.from_naive_local(&end)
+    }
+
+    /// The end of the quiet hours `now` is in, if it's in any.
+    fn end_after(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        let (start, end) = (self.start.min(23), self.end.min(23));
+        let hour = now.hour();
+        let quiet = if start <= end {
+            start <= hour && hour < end
+        } else {
+            // Quiet hours span midnight, like 22 to 8.
+            hour >= start || hour < end
+        };
+        if !quiet {
+            return None;
+        }
+        let end_today = now.date().and_hms_opt(end, 0, 0).expect("Hour is in range");
+        Some(if end_today > now {
+            end_today
+        } else {
+            end_today + Duration::days(1)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2022, 10, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_end_after() {
+        let overnight = QuietHours { start: 22, end: 8 };
+        assert_eq!(overnight.end_after(at(1, 3, 47)), Some(at(1, 8, 0)));
+        assert_eq!(overnight.end_after(at(1, 23, 15)), Some(at(2, 8, 0)));
+        assert_eq!(overnight.end_after(at(1, 22, 0)), Some(at(2, 8, 0)));
+        assert_eq!(overnight.end_after(at(1, 8, 0)), None);
+        assert_eq!(overnight.end_after(at(1, 12, 0)), None);
+
+        let daytime = QuietHours { start: 9, end: 17 };
+        assert_eq!(daytime.end_after(at(1, 12, 30)), Some(at(1, 17, 0)));
+        assert_eq!(daytime.end_after(at(1, 18, 0)), None);
+    }
+}
@@ -0,0 +1,185 @@
+//! Scrubbing secrets, like API tokens and email addresses, from logs and error reports.
+//!
+//! Traces can include whole HTTP responses and error chains, which sometimes echo credentials
+//! back. Everything written through [`Redacting`] has the secrets from [`set_secrets`] and
+//! [`add_secret`] replaced with [`REDACTED`].
+
+use std::io::Write;
+use std::sync::RwLock;
+
+use tracing::Metadata;
+use tracing_subscriber::fmt::MakeWriter;
+
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Shorter strings are too likely to show up by coincidence to scrub.
+const MIN_SECRET_LEN: usize = 6;
+
+/// How many secrets from [`add_secret`] to keep scrubbing. Older ones are forgotten first;
+/// they're usually access tokens which have long since expired.
+const MAX_ADDED_SECRETS: usize = 64;
+
+static SECRETS: RwLock<Secrets> = RwLock::new(Secrets {
+    configured: Vec::new(),
+    added: Vec::new(),
+    all: Vec::new(),
+});
+
+struct Secrets {
+    /// From [`set_secrets`], like the tokens in the config.
+    configured: Vec<String>,
+    /// From [`add_secret`], like access tokens we're issued while running.
+    added: Vec<String>,
+    /// Both, longest first, so a secret containing another is scrubbed whole.
+    all: Vec<String>,
+}
+
+impl Secrets {
+    fn update_all(&mut self) {
+        let mut all: Vec<String> = self
+            .configured
+            .iter()
+            .chain(&self.added)
+            .filter(|secret| secret.len() >= MIN_SECRET_LEN)
+            .cloned()
+            .collect();
+        all.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        all.dedup();
+        self.all = all;
+    }
+}
+
+/// Set the strings to scrub from now on, like when the config is reloaded. Secrets from
+/// [`add_secret`] are still scrubbed too.
+pub fn set_secrets(secrets: impl IntoIterator<Item = String>) {
+    if let Ok(mut guard) = SECRETS.write() {
+        guard.configured = secrets.into_iter().collect();
+        guard.update_all();
+    }
+}
+
+/// Scrub `secret` from now on, like a token we were just issued.
+pub fn add_secret(secret: &str) {
+    if let Ok(mut guard) = SECRETS.write() {
+        if !guard.added.iter().any(|added| added == secret) {
+            if guard.added.len() >= MAX_ADDED_SECRETS {
+                guard.added.remove(0);
+            }
+            guard.added.push(secret.to_owned());
+            guard.update_all();
+        }
+    }
+}
+
+/// Replace any secrets in `text` with [`REDACTED`].
+pub fn redact(text: &str) -> String {
+    let secrets = match SECRETS.read() {
+        Ok(secrets) => secrets,
+        Err(_) => return text.to_owned(),
+    };
+    redact_with(&secrets.all, text)
+}
+
+fn redact_with(secrets: &[String], text: &str) -> String {
+    secrets.iter().fold(text.to_owned(), |text, secret| {
+        if text.contains(secret.as_str()) {
+            text.replace(secret.as_str(), REDACTED)
+        } else {
+            text
+        }
+    })
+}
+
+/// A [`MakeWriter`] which scrubs secrets from everything written to `M`'s writers.
+pub struct Redacting<M>(pub M);
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for Redacting<M> {
+    type Writer = RedactingWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter::new(self.0.make_writer())
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        RedactingWriter::new(self.0.make_writer_for(meta))
+    }
+}
+
+/// Buffers an event's output, so secrets split across writes are still caught, and writes it
+/// scrubbed when dropped.
+pub struct RedactingWriter<W: Write> {
+    inner: W,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> RedactingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            buffer: Vec::new(),
+        }
+    }
+
+    fn write_buffer(&mut self) -> std::io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let text = String::from_utf8_lossy(&self.buffer);
+        let redacted = redact(&text);
+        self.buffer.clear();
+        self.inner.write_all(redacted.as_bytes())
+    }
+}
+
+impl<W: Write> Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.write_buffer()?;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for RedactingWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.write_buffer();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        let secrets = vec![
+            "fmu1-abcdef-0123456789".to_owned(),
+            "rbt@fastmail.com".to_owned(),
+        ];
+        assert_eq!(
+            redact_with(
+                &secrets,
+                "Failed to send to rbt@fastmail.com: 401 Unauthorized (Bearer fmu1-abcdef-0123456789)"
+            ),
+            "Failed to send to [REDACTED]: 401 Unauthorized (Bearer [REDACTED])"
+        );
+        assert_eq!(
+            redact_with(&secrets, "Tracking 3 apartments"),
+            "Tracking 3 apartments"
+        );
+    }
+
+    #[test]
+    fn test_added_secrets() {
+        let mut secrets = Secrets {
+            configured: vec!["configured-token".to_owned()],
+            added: vec!["access-token-123".to_owned(), "short".to_owned()],
+            all: Vec::new(),
+        };
+        secrets.update_all();
+        assert_eq!(secrets.all, ["access-token-123", "configured-token"]);
+    }
+}
@@ -0,0 +1,98 @@
+//! Reminders about deadlines on units, configured with how many days ahead to send them:
+//!
+//! ```toml
+//! promotion-reminder-days = 3
+//! availability-reminder-days = 14
+//! ```
+//!
+//! A promotion's end date is effectively a deadline on the deal, so each promotion on a
+//! qualified or watched unit gets one reminder, like "6 Weeks Free! on apartment 731 ends
+//! Friday".
+//!
+//! Watched units get a reminder as their availability date approaches, with the current rent
+//! and how it's changed since we started watching, so the application window isn't missed.
+
+use chrono::DateTime;
+use chrono::NaiveDate;
+use chrono::Utc;
+
+use crate::money::Money;
+
+/// A key identifying the reminder for the promotion with ID `promotion` ending on `end` on the
+/// unit with ID `unit`, so we only send it once. The same promotion extended to a later date
+/// gets a new reminder.
+pub fn key(unit: &str, promotion: &str, end: NaiveDate) -> String {
+    format!("{unit}/{promotion}/{end}")
+}
+
+/// Should we remind about a promotion ending on `end` today, `days` days ahead?
+pub fn is_due(end: NaiveDate, today: NaiveDate, days: u32) -> bool {
+    let left = (end - today).num_days();
+    (0..=i64::from(days)).contains(&left)
+}
+
+/// When a promotion ending on `end` ends, relative to `today`, like "today", "Friday", or
+/// "Nov 30".
+pub fn ends(end: NaiveDate, today: NaiveDate) -> String {
+    match (end - today).num_days() {
+        0 => "today".to_owned(),
+        1 => "tomorrow".to_owned(),
+        2..=6 => end.format("%A").to_string(),
+        _ => end.format("%b %e").to_string(),
+    }
+}
+
+/// How `rent` compares to `watched_rent`, the rent when we started watching a unit on
+/// `watched`, like "down $160 since you started watching on Oct  5".
+pub fn since_watching(rent: Money, watched_rent: Money, watched: DateTime<Utc>) -> String {
+    let since = format!(
+        "since you started watching on {}",
+        crate::timezone::format(watched, "%b %e")
+    );
+    if rent < watched_rent {
+        format!("down {} {since}", watched_rent - rent)
+    } else if rent > watched_rent {
+        format!("up {} {since}", rent - watched_rent)
+    } else {
+        format!("unchanged {since}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_ends() {
+        let day = |day| NaiveDate::from_ymd_opt(2022, 11, day).unwrap();
+        // Nov 21 2022 was a Monday.
+        assert_eq!(ends(day(21), day(21)), "today");
+        assert_eq!(ends(day(22), day(21)), "tomorrow");
+        assert_eq!(ends(day(25), day(21)), "Friday");
+        assert_eq!(ends(day(30), day(21)), "Nov 30");
+
+        assert!(is_due(day(25), day(21), 4));
+        assert!(!is_due(day(25), day(21), 3));
+        assert!(!is_due(day(20), day(21), 3));
+    }
+
+    #[test]
+    fn test_since_watching() {
+        let watched = Utc.ymd(2022, 10, 15).and_hms_opt(12, 0, 0).unwrap();
+        let dollars = Money::from_dollars;
+        assert_eq!(
+            since_watching(dollars(4100.0), dollars(4260.0), watched),
+            "down $160 since you started watching on Oct 15"
+        );
+        assert_eq!(
+            since_watching(dollars(4300.0), dollars(4260.0), watched),
+            "up $40 since you started watching on Oct 15"
+        );
+        assert_eq!(
+            since_watching(dollars(4260.0), dollars(4260.0), watched),
+            "unchanged since you started watching on Oct 15"
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! Parsing and matching `robots.txt` files.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// The longest `Crawl-delay` we'll honor. Longer delays, up to infinity, are clamped to this.
+const MAX_CRAWL_DELAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The rules from a `robots.txt` file which apply to us.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Robots {
+    rules: Vec<Rule>,
+    pub crawl_delay: Option<Duration>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+impl Robots {
+    /// Parse a `robots.txt` file, keeping the rules for `user_agent` if there are any and the
+    /// rules for `*` otherwise.
+    pub fn parse(text: &str, user_agent: &str) -> Self {
+        let mut groups: HashMap<String, Robots> = HashMap::new();
+        // The user agents the current group applies to.
+        let mut agents: Vec<String> = Vec::new();
+        // Have we seen any rules since the last `User-agent` line? If so, the next `User-agent`
+        // line starts a new group.
+        let mut in_rules = false;
+
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let (key, value) = match line.split_once(':') {
+                Some((key, value)) => (key.trim().to_ascii_lowercase(), value.trim()),
+                None => continue,
+            };
+
+            match key.as_str() {
+                "user-agent" => {
+                    if in_rules {
+                        agents.clear();
+                        in_rules = false;
+                    }
+                    agents.push(value.to_ascii_lowercase());
+                }
+                "allow" | "disallow" | "crawl-delay" => {
+                    in_rules = true;
+                    for agent in &agents {
+                        let group = groups.entry(agent.clone()).or_default();
+                        match key.as_str() {
+                            "crawl-delay" => {
+                                group.crawl_delay =
+                                    value.parse::<f64>().ok().filter(|secs| *secs >= 0.0).map(
+                                        |secs| {
+                                            Duration::try_from_secs_f64(secs)
+                                                .unwrap_or(MAX_CRAWL_DELAY)
+                                                .min(MAX_CRAWL_DELAY)
+                                        },
+                                    );
+                            }
+                            // An empty `Disallow` means everything is allowed.
+                            "disallow" if value.is_empty() => {}
+                            _ => group.rules.push(Rule {
+                                allow: key == "allow",
+                                pattern: value.to_owned(),
+                            }),
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        groups
+            .remove(&user_agent.to_ascii_lowercase())
+            .or_else(|| groups.remove("*"))
+            .unwrap_or_default()
+    }
+
+    /// Are we allowed to fetch `path`, which includes the query string, if any?
+    ///
+    /// The longest matching rule wins, with `Allow` winning ties.
+    pub fn allows(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|rule| matches(&rule.pattern, path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+            .map(|rule| rule.allow)
+            .unwrap_or(true)
+    }
+}
+
+/// Does `pattern` match `path`? Patterns are prefixes which may contain `*` wildcards and may
+/// end in `$` to match the end of the path.
+fn matches(pattern: &str, path: &str) -> bool {
+    match pattern.split_once('*') {
+        Some((prefix, rest)) => {
+            path.starts_with(prefix)
+                && (prefix.len()..=path.len())
+                    .filter(|i| path.is_char_boundary(*i))
+                    .any(|i| matches(rest, &path[i..]))
+        }
+        None => match pattern.strip_suffix('$') {
+            Some(pattern) => path == pattern,
+            None => path.starts_with(pattern),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROBOTS: &str = "\
+        # Comments are ignored.\n\
+        User-agent: *\n\
+        Disallow: /search\n\
+        Allow: /search/apa\n\
+        Disallow: /*.pdf$\n\
+        Crawl-delay: 5\n\
+        \n\
+        User-agent: BadBot\n\
+        User-agent: ava-apartment-finder\n\
+        Disallow: /\n\
+        ";
+
+    #[test]
+    fn test_robots_default_group() {
+        let robots = Robots::parse(ROBOTS, "some-other-bot");
+        assert_eq!(robots.crawl_delay, Some(Duration::from_secs(5)));
+        assert!(robots.allows("/washington/seattle-apartments/"));
+        assert!(!robots.allows("/search?query=seattle"));
+        assert!(robots.allows("/search/apa?format=rss"));
+        assert!(!robots.allows("/floorplans/b4v.pdf"));
+        assert!(robots.allows("/floorplans/b4v.pdf.jpg"));
+    }
+
+    #[test]
+    fn test_robots_specific_group() {
+        let robots = Robots::parse(ROBOTS, "Ava-Apartment-Finder");
+        assert_eq!(robots.crawl_delay, None);
+        assert!(!robots.allows("/washington/seattle-apartments/"));
+    }
+
+    #[test]
+    fn test_robots_crawl_delay() {
+        let crawl_delay = |delay| {
+            Robots::parse(&format!("User-agent: *\nCrawl-delay: {delay}\n"), "*").crawl_delay
+        };
+        assert_eq!(crawl_delay("0.5"), Some(Duration::from_millis(500)));
+        assert_eq!(crawl_delay("1e20"), Some(MAX_CRAWL_DELAY));
+        assert_eq!(crawl_delay("inf"), Some(MAX_CRAWL_DELAY));
+        assert_eq!(crawl_delay("-1"), None);
+        assert_eq!(crawl_delay("NaN"), None);
+    }
+
+    #[test]
+    fn test_robots_empty() {
+        let robots = Robots::parse("User-agent: *\nDisallow:\n", "ava-apartment-finder");
+        assert_eq!(robots, Robots::default());
+        assert!(robots.allows("/anything"));
+    }
+}
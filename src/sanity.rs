@@ -0,0 +1,192 @@
+//! Sanity checks on scraped listings, so a partial page or a broken parser doesn't look like
+//! every unit being unlisted at once.
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use crate::api::Apartment;
+use crate::listing::Listing;
+use crate::money::Money;
+use crate::store::SuspectScrapes;
+
+/// Sources with fewer listings than this are too small for the count-based checks to mean
+/// anything.
+const MIN_LISTINGS: usize = 5;
+
+/// When to treat a scrape as suspect and skip it.
+///
+/// Configured in the `[sanity-checks]` table of the config file, like:
+///
+/// ```toml
+/// [sanity-checks]
+/// max-drop-percent = 50
+/// min-rent = 500
+/// max-rent = 20000
+/// accept-after = 12
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct SanityChecks {
+    /// Skip scrapes where the number of listings drops by more than this percentage since the
+    /// last tick.
+    pub max_drop_percent: f64,
+    /// Rents below this are assumed to be parse errors.
+    pub min_rent: Money,
+    /// Rents above this are assumed to be parse errors.
+    pub max_rent: Money,
+    /// Accept suspect scrapes once this many in a row have returned the same listings, in case
+    /// the source really did change that much, like a community leasing most of its units at
+    /// once. 0 never accepts them.
+    pub accept_after: usize,
+}
+
+impl Default for SanityChecks {
+    fn default() -> Self {
+        Self {
+            max_drop_percent: 50.0,
+            min_rent: Money::from_dollars(100.0),
+            max_rent: Money::from_dollars(50_000.0),
+            accept_after: 12,
+        }
+    }
+}
+
+impl SanityChecks {
+    /// Check the `new` listings from a source against the `known` listings from the same
+    /// source.
+    ///
+    /// Returns the reason the scrape looks wrong, if it does.
+    pub fn check<'a, T: Listing + 'a>(
+        &self,
+        known: impl IntoIterator<Item = &'a Apartment<T>>,
+        new: &[Apartment<T>],
+    ) -> Option<String> {
+        let known: BTreeMap<&str, &Apartment<T>> =
+            known.into_iter().map(|apt| (apt.id(), apt)).collect();
+
+        if known.len() >= MIN_LISTINGS {
+            let drop_percent = 100.0 * (1.0 - new.len() as f64 / known.len() as f64);
+            if drop_percent > self.max_drop_percent {
+                return Some(format!(
+                    "listings dropped from {} to {} ({drop_percent:.0}%)",
+                    known.len(),
+                    new.len()
+                ));
+            }
+
+            if !new.is_empty() && new.iter().all(|apt| !known.contains_key(apt.id())) {
+                return Some(format!(
+                    "all {} listings are new; none of the {} known listings were seen",
+                    new.len(),
+                    known.len()
+                ));
+            }
+        }
+
+        // Craigslist posters put all kinds of nonsense in the price field, so only a majority of
+        // absurd rents indicates a parser problem.
+        let rents: Vec<Money> = new.iter().filter_map(|apt| apt.inner.rent()).collect();
+        let absurd = rents
+            .iter()
+            .filter(|rent| !(self.min_rent..=self.max_rent).contains(*rent))
+            .count();
+        if absurd > 0 && absurd * 2 > rents.len() {
+            return Some(format!(
+                "{absurd} of {} rents are outside {}-{}",
+                rents.len(),
+                self.min_rent,
+                self.max_rent
+            ));
+        }
+
+        None
+    }
+
+    /// Note that a scrape returned the suspect listings in `new`, and decide whether to accept
+    /// them anyway, because `accept_after` scrapes in a row have returned the same listings.
+    pub fn accept<T: Listing>(&self, suspect: &mut SuspectScrapes, new: &[Apartment<T>]) -> bool {
+        let count = suspect.record(new.iter().map(|apt| apt.id().to_owned()).collect());
+        self.accept_after > 0 && count >= self.accept_after
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::fixtures::craigslist_post;
+    use crate::api::fixtures::CRAIGSLIST_SEARCH;
+    use crate::craigslist::Post;
+
+    fn post(id: u64, price: f64) -> Apartment<Post> {
+        Apartment::new(
+            CRAIGSLIST_SEARCH,
+            Post {
+                id: format!("craigslist-{id}"),
+                price: Some(Money::from_dollars(price)),
+                link: format!("https://seattle.craigslist.org/see/apa/d/{id}.html"),
+                ..craigslist_post()
+            },
+        )
+    }
+
+    #[test]
+    fn test_sanity_checks() {
+        let checks = SanityChecks::default();
+        let known: Vec<_> = (0..10).map(|id| post(id, 3000.0)).collect();
+
+        assert_eq!(checks.check(&known, &known), None);
+        assert_eq!(checks.check(&known, &known[..6]), None);
+        assert_eq!(
+            checks.check(&known, &known[..4]),
+            Some("listings dropped from 10 to 4 (60%)".to_owned())
+        );
+        // Small sources can swing wildly.
+        assert_eq!(checks.check(&known[..4], &known[..1]), None);
+
+        let new: Vec<_> = (10..20).map(|id| post(id, 3000.0)).collect();
+        assert_eq!(
+            checks.check(&known, &new),
+            Some("all 10 listings are new; none of the 10 known listings were seen".to_owned())
+        );
+
+        let mut absurd = known.clone();
+        assert_eq!(checks.check(&known, &absurd), None);
+        for apt in &mut absurd[..6] {
+            apt.inner.price = Some(Money::ZERO);
+        }
+        assert_eq!(
+            checks.check(&known, &absurd),
+            Some("6 of 10 rents are outside $100-$50,000".to_owned())
+        );
+        absurd[0].inner.price = known[0].inner.price;
+        absurd[1].inner.price = known[1].inner.price;
+        assert_eq!(checks.check(&known, &absurd), None);
+    }
+
+    #[test]
+    fn test_accept_consistent_suspect_scrapes() {
+        let checks = SanityChecks {
+            accept_after: 3,
+            ..Default::default()
+        };
+        let known: Vec<_> = (0..10).map(|id| post(id, 3000.0)).collect();
+        let shrunk = &known[..4];
+        assert!(checks.check(&known, shrunk).is_some());
+
+        let mut suspect = SuspectScrapes::default();
+        assert!(!checks.accept(&mut suspect, shrunk));
+        assert!(!checks.accept(&mut suspect, shrunk));
+        // A different scrape starts the streak over.
+        assert!(!checks.accept(&mut suspect, &known[..3]));
+        assert!(!checks.accept(&mut suspect, shrunk));
+        assert!(!checks.accept(&mut suspect, shrunk));
+        assert!(checks.accept(&mut suspect, shrunk));
+
+        let never = SanityChecks {
+            accept_after: 0,
+            ..Default::default()
+        };
+        assert!(!never.accept(&mut suspect, shrunk));
+    }
+}
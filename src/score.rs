@@ -0,0 +1,125 @@
+//! Ranking listings by how much we like them.
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use crate::filter::Value;
+use crate::listing::Listing;
+
+/// How much each feature of a listing contributes to its score. Higher scores are better.
+///
+/// Configured in the `[score]` table of the config file, like:
+///
+/// ```toml
+/// [score]
+/// rent = -1.0        # Per $100/month.
+/// square-feet = 1.0  # Per 100 sq/ft.
+/// floor = 0.5        # Per floor.
+/// available = -0.25  # Per week until move-in.
+/// virtual-tour = 1.0 # If the unit has a virtual tour.
+/// ```
+///
+/// Features a listing doesn't have (e.g. square footage for Craigslist posts) contribute
+/// nothing, except rent: listings without one, like Craigslist posts with no price, score
+/// negative infinity so they rank last.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ScoreWeights {
+    pub rent: f64,
+    pub square_feet: f64,
+    pub floor: f64,
+    pub available: f64,
+    pub virtual_tour: f64,
+}
+
+impl Default for ScoreWeights {
+    fn default() -> Self {
+        Self {
+            rent: -1.0,
+            square_feet: 1.0,
+            floor: 0.0,
+            available: 0.0,
+            virtual_tour: 0.0,
+        }
+    }
+}
+
+impl ScoreWeights {
+    /// Score `listing`, measuring availability from `today`.
+    pub fn score(&self, listing: &impl Listing, today: NaiveDate) -> f64 {
+        if self.rent != 0.0 && listing.rent().is_none() {
+            return f64::NEG_INFINITY;
+        }
+        let number = |name| match listing.field(name) {
+            Some(Value::Number(number)) => number,
+            _ => 0.0,
+        };
+        let weeks_until_available = listing
+            .available_date()
+            .map_or(0.0, |date| (date - today).num_days().max(0) as f64 / 7.0);
+        let virtual_tour = match listing.field("virtual_tour") {
+            Some(Value::Bool(true)) => 1.0,
+            _ => 0.0,
+        };
+
+        self.rent * number("rent") / 100.0
+            + self.square_feet * number("sqft") / 100.0
+            + self.floor * number("floor")
+            + self.available * weeks_until_available
+            + self.virtual_tour * virtual_tour
+    }
+}
+
+/// Sort `listings` best-first according to `weights`.
+pub fn sort_by_score<T, L: Listing>(
+    listings: &mut [T],
+    weights: &ScoreWeights,
+    today: NaiveDate,
+    listing: impl Fn(&T) -> &L,
+) {
+    sort_by(listings, |item| weights.score(listing(item), today));
+}
+
+/// Sort `listings` best-first according to `score`.
+pub fn sort_by<T>(listings: &mut [T], score: impl Fn(&T) -> f64) {
+    listings.sort_by(|a, b| score(b).total_cmp(&score(a)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::fixtures::craigslist_post;
+    use crate::craigslist::Post;
+    use crate::money::Money;
+
+    fn post(price: Option<f64>) -> Post {
+        Post {
+            price: price.map(Money::from_dollars),
+            ..craigslist_post()
+        }
+    }
+
+    #[test]
+    fn test_sort_by_score() {
+        let today = NaiveDate::from_ymd_opt(2022, 10, 1).unwrap();
+        let weights = ScoreWeights::default();
+        assert_eq!(weights.score(&post(Some(3000.0)), today), -30.0);
+        assert_eq!(weights.score(&post(None), today), f64::NEG_INFINITY);
+
+        let mut posts = vec![post(Some(3000.0)), post(None), post(Some(2500.0))];
+        sort_by_score(&mut posts, &weights, today, |post| post);
+        assert_eq!(
+            posts
+                .iter()
+                .map(|post| post.price.map(Money::dollars))
+                .collect::<Vec<_>>(),
+            vec![Some(2500.0), Some(3000.0), None]
+        );
+
+        let weights = ScoreWeights {
+            rent: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(weights.score(&post(None), today), 0.0);
+    }
+}
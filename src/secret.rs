@@ -0,0 +1,182 @@
+//! Secrets in the config, like API tokens and passwords, which can be written inline or read
+//! from elsewhere so they don't have to sit in the config or the systemd unit:
+//!
+//! ```toml
+//! [jmap]
+//! # From the OS keyring, under the service `ava-apartment-finder`. Requires the `keyring`
+//! # feature. Store it with e.g. `secret-tool store --label=Fastmail service
+//! # ava-apartment-finder username fastmail` or Keychain Access.
+//! token = { keyring = "fastmail" }
+//!
+//! [notion]
+//! # From a file only its owner can read, like a systemd credential.
+//! token = { file = "/run/credentials/ava-apartment-finder.service/notion" }
+//!
+//! [mqtt]
+//! host = "homeassistant.local"
+//! # From an environment variable.
+//! password = { env = "MQTT_PASSWORD" }
+//! ```
+//!
+//! Secrets are read when the config is loaded, so a missing secret is reported at startup.
+
+use camino::Utf8Path;
+use camino::Utf8PathBuf;
+use color_eyre::eyre;
+use color_eyre::eyre::eyre;
+use color_eyre::eyre::Context;
+use serde::de::Error;
+use serde::Deserialize;
+use serde::Deserializer;
+
+/// The keyring service secrets are stored under.
+#[cfg(feature = "keyring")]
+const KEYRING_SERVICE: &str = "ava-apartment-finder";
+
+/// A secret as written in the config.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Secret {
+    Inline(String),
+    Reference(Reference),
+}
+
+/// Where to read a secret from.
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+enum Reference {
+    /// An environment variable.
+    Env(String),
+    /// A file, which mustn't be readable by other users. A trailing newline is ignored.
+    File(Utf8PathBuf),
+    /// The entry for this username in the OS keyring.
+    Keyring(String),
+}
+
+impl Reference {
+    fn read(&self) -> eyre::Result<String> {
+        match self {
+            Reference::Env(var) => {
+                std::env::var(var).wrap_err_with(|| format!("Couldn't get ${var}"))
+            }
+            Reference::File(path) => read_file(path),
+            Reference::Keyring(username) => read_keyring(username),
+        }
+    }
+}
+
+/// Read a secret from the file at `path`, checking that only its owner can read it.
+fn read_file(path: &Utf8Path) -> eyre::Result<String> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = std::fs::metadata(path)
+            .wrap_err_with(|| format!("Failed to read secret file `{path}`"))?
+            .permissions()
+            .mode();
+        if mode & 0o077 != 0 {
+            return Err(eyre!(
+                "Secret file `{path}` is accessible by other users (mode {:o}); \
+                 run `chmod 600 {path}`",
+                mode & 0o777
+            ));
+        }
+    }
+    let contents = std::fs::read_to_string(path)
+        .wrap_err_with(|| format!("Failed to read secret file `{path}`"))?;
+    Ok(contents.trim_end_matches(['\r', '\n']).to_owned())
+}
+
+#[cfg(feature = "keyring")]
+fn read_keyring(username: &str) -> eyre::Result<String> {
+    keyring::Entry::new(KEYRING_SERVICE, username)
+        .and_then(|entry| entry.get_password())
+        .wrap_err_with(|| {
+            format!("Couldn't get `{username}` from the keyring service `{KEYRING_SERVICE}`")
+        })
+}
+
+#[cfg(not(feature = "keyring"))]
+fn read_keyring(username: &str) -> eyre::Result<String> {
+    Err(eyre!(
+        "Reading `{username}` from the OS keyring requires the `keyring` feature"
+    ))
+}
+
+/// Deserialize a secret, reading it if it's a reference. For `#[serde(deserialize_with)]`.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    match Secret::deserialize(deserializer)? {
+        Secret::Inline(secret) => Ok(secret),
+        Secret::Reference(reference) => reference
+            .read()
+            .map_err(|err| D::Error::custom(format!("{err:#}"))),
+    }
+}
+
+/// Like [`deserialize`], for optional secrets.
+pub fn deserialize_option<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    #[derive(Deserialize)]
+    struct Wrapper(#[serde(deserialize_with = "deserialize")] String);
+
+    Ok(Option::<Wrapper>::deserialize(deserializer)?.map(|Wrapper(secret)| secret))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct Config {
+        #[serde(deserialize_with = "deserialize")]
+        token: String,
+        #[serde(default, deserialize_with = "deserialize_option")]
+        password: Option<String>,
+    }
+
+    #[test]
+    fn test_secrets() {
+        let config: Config = toml::from_str(r#"token = "inline""#).unwrap();
+        assert_eq!(config.token, "inline");
+        assert_eq!(config.password, None);
+
+        std::env::set_var("AVA_TEST_SECRET", "from-env");
+        let config: Config = toml::from_str(
+            r#"
+            token = { env = "AVA_TEST_SECRET" }
+            password = { env = "AVA_TEST_SECRET" }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.token, "from-env");
+        assert_eq!(config.password.as_deref(), Some("from-env"));
+
+        let err = toml::from_str::<Config>(r#"token = { env = "AVA_TEST_MISSING" }"#)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Couldn't get $AVA_TEST_MISSING"), "{err}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_secret_file_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("ava_secret_{}", std::process::id()));
+        std::fs::write(&path, "from-file\n").unwrap();
+        let path = Utf8PathBuf::from_path_buf(path).unwrap();
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let err = read_file(&path).unwrap_err().to_string();
+        assert!(
+            err.contains("accessible by other users (mode 644)"),
+            "{err}"
+        );
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        assert_eq!(read_file(&path).unwrap(), "from-file");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,212 @@
+//! An HTTP API over the tracker's live state, so other tools can query it without reading the
+//! DB file.
+//!
+//! - `GET /`: An HTML dashboard; see [`crate::dashboard`].
+//! - `GET /apartments`: Currently listed apartments and posts.
+//! - `GET /apartments/{unit_id}`: A single listing, listed or not.
+//! - `GET /events?since=2022-10-21T00:00:00Z`: Events after `since`, or all of them.
+//! - `GET /feed.xml?all=true`: An RSS feed of events; see [`crate::feed`].
+//! - `GET /calendar.ics`: Availability and promotion end dates; see [`crate::calendar`].
+//! - `GET /days-on-market`: How long apartments stay listed; see [`crate::days_on_market`].
+//! - `GET /healthz`: When the last successful tick was.
+//! - `POST /graphql`: A GraphQL API over listings and events; see [`crate::graphql`].
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::extract::Path;
+use axum::extract::Query;
+use axum::http::header;
+use axum::http::StatusCode;
+use axum::response::Html;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::routing::post;
+use axum::Extension;
+use axum::Json;
+use axum::Router;
+use chrono::DateTime;
+use chrono::Utc;
+use color_eyre::eyre;
+use color_eyre::eyre::Context;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::watch;
+
+use crate::api::Apartment;
+use crate::calendar;
+use crate::craigslist;
+use crate::dashboard;
+use crate::days_on_market::DaysOnMarket;
+use crate::events::Event;
+use crate::feed;
+use crate::graphql;
+use crate::qualifications::Qualifications;
+
+/// A copy of the tracker's state, published after every tick.
+///
+/// The listings and events are shared between snapshots until they change, so cloning one is
+/// cheap.
+#[derive(Clone, Debug, Default)]
+pub struct Snapshot {
+    pub known_apartments: Arc<BTreeMap<String, Apartment>>,
+    pub unlisted_apartments: Arc<BTreeMap<String, Apartment>>,
+    pub known_posts: Arc<BTreeMap<String, Apartment<craigslist::Post>>>,
+    pub unlisted_posts: Arc<BTreeMap<String, Apartment<craigslist::Post>>>,
+    pub events: Arc<Vec<Event>>,
+    pub last_tick: Option<DateTime<Utc>>,
+    /// For the dashboard's "only qualified listings" filter.
+    pub qualifications: Qualifications,
+}
+
+pub type Snapshots = watch::Receiver<Arc<Snapshot>>;
+
+/// Bind to `addr` and return a future serving the API from the latest of `snapshots`.
+///
+/// Binding happens up front so a bad address is reported at startup.
+pub fn serve(
+    addr: SocketAddr,
+    snapshots: Snapshots,
+) -> eyre::Result<impl Future<Output = eyre::Result<()>>> {
+    let router = Router::new()
+        .route("/", get(dashboard))
+        .route("/apartments", get(apartments))
+        .route("/apartments/:unit_id", get(apartment))
+        .route("/events", get(events))
+        .route("/feed.xml", get(feed))
+        .route("/calendar.ics", get(calendar))
+        .route("/days-on-market", get(days_on_market))
+        .route("/healthz", get(healthz))
+        .route("/graphql", post(graphql))
+        .layer(Extension(graphql::schema(snapshots.clone())))
+        .layer(Extension(snapshots));
+
+    let server = axum::Server::try_bind(&addr)
+        .wrap_err_with(|| format!("Failed to listen on {addr}"))?
+        .serve(router.into_make_service());
+    tracing::info!(%addr, "Serving HTTP API");
+
+    Ok(async move { server.await.wrap_err("HTTP API server failed") })
+}
+
+fn to_json(value: impl Serialize) -> Result<Value, StatusCode> {
+    serde_json::to_value(value).map_err(|err| {
+        tracing::error!("Failed to serialize response: {err}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+#[derive(Deserialize)]
+struct DashboardQuery {
+    #[serde(default)]
+    all: bool,
+}
+
+async fn dashboard(
+    Extension(snapshots): Extension<Snapshots>,
+    Query(DashboardQuery { all }): Query<DashboardQuery>,
+) -> Html<String> {
+    let snapshot = snapshots.borrow().clone();
+    Html(dashboard::render(&snapshot, all, Utc::now()))
+}
+
+async fn feed(
+    Extension(snapshots): Extension<Snapshots>,
+    Query(DashboardQuery { all }): Query<DashboardQuery>,
+) -> impl IntoResponse {
+    let snapshot = snapshots.borrow().clone();
+    (
+        [(header::CONTENT_TYPE, "application/rss+xml; charset=utf-8")],
+        feed::render(&snapshot, all),
+    )
+}
+
+async fn calendar(Extension(snapshots): Extension<Snapshots>) -> impl IntoResponse {
+    let snapshot = snapshots.borrow().clone();
+    (
+        [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+        calendar::render(&snapshot, Utc::now()),
+    )
+}
+
+async fn apartments(
+    Extension(snapshots): Extension<Snapshots>,
+) -> Result<Json<Vec<Value>>, StatusCode> {
+    let snapshot = snapshots.borrow().clone();
+    snapshot
+        .known_apartments
+        .values()
+        .map(to_json)
+        .chain(snapshot.known_posts.values().map(to_json))
+        .collect::<Result<_, _>>()
+        .map(Json)
+}
+
+async fn apartment(
+    Extension(snapshots): Extension<Snapshots>,
+    Path(unit_id): Path<String>,
+) -> Result<Json<Value>, StatusCode> {
+    let snapshot = snapshots.borrow().clone();
+    let apartment = snapshot
+        .known_apartments
+        .get(&unit_id)
+        .or_else(|| snapshot.unlisted_apartments.get(&unit_id))
+        .map(to_json);
+    let post = || {
+        snapshot
+            .known_posts
+            .get(&unit_id)
+            .or_else(|| snapshot.unlisted_posts.get(&unit_id))
+            .map(to_json)
+    };
+    apartment
+        .or_else(post)
+        .ok_or(StatusCode::NOT_FOUND)?
+        .map(Json)
+}
+
+#[derive(Deserialize)]
+struct EventsQuery {
+    since: Option<DateTime<Utc>>,
+}
+
+async fn events(
+    Extension(snapshots): Extension<Snapshots>,
+    Query(EventsQuery { since }): Query<EventsQuery>,
+) -> Json<Vec<Event>> {
+    let snapshot = snapshots.borrow().clone();
+    Json(
+        snapshot
+            .events
+            .iter()
+            .filter(|event| since.map_or(true, |since| event.time > since))
+            .cloned()
+            .collect(),
+    )
+}
+
+async fn days_on_market(Extension(snapshots): Extension<Snapshots>) -> Json<DaysOnMarket> {
+    let snapshot = snapshots.borrow().clone();
+    Json(DaysOnMarket::new(snapshot.unlisted_apartments.values()))
+}
+
+#[derive(Serialize)]
+struct Health {
+    last_tick: Option<DateTime<Utc>>,
+}
+
+async fn healthz(Extension(snapshots): Extension<Snapshots>) -> Json<Health> {
+    Json(Health {
+        last_tick: snapshots.borrow().last_tick,
+    })
+}
+
+async fn graphql(
+    Extension(schema): Extension<graphql::Schema>,
+    Json(request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    Json(schema.execute(request).await)
+}
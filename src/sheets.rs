@@ -0,0 +1,380 @@
+//! Sync listings and events to a Google Sheet, for people who'd rather track everything in a
+//! spreadsheet.
+//!
+//! Authenticates as a [service account]; share the spreadsheet with the account's email
+//! address to give it access. Configured like:
+//!
+//! ```toml
+//! [google-sheets]
+//! spreadsheet-id = "1BxiMVs0XRA5nFMdKvBdBZjgmUUqptlbs74OgvE2upms"
+//! service-account-key = "/home/rebecca/.config/ava-apartment-finder/service-account.json"
+//! ```
+//!
+//! The listings sheet has a row per listing, updated in place by ID, so columns added to the
+//! right of ours are left alone. The events sheet is append-only, so it keeps events after
+//! they're pruned from the DB; see `event-retention-days`.
+//!
+//! [service account]: https://cloud.google.com/iam/docs/service-account-overview
+
+use std::time::Duration;
+
+use camino::Utf8PathBuf;
+use chrono::Utc;
+use color_eyre::eyre;
+use color_eyre::eyre::eyre;
+use color_eyre::eyre::Context;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::json;
+use serde_json::Value as JsonValue;
+
+use crate::api::Apartment;
+use crate::events::Event;
+use crate::filter::Value;
+use crate::listing::Listing;
+use crate::redact;
+use crate::server::Snapshot;
+use crate::timezone;
+
+const SCOPE: &str = "https://www.googleapis.com/auth/spreadsheets";
+const API_URL: &str = "https://sheets.googleapis.com/v4/spreadsheets";
+
+const LISTING_COLUMNS: [&str; 9] = [
+    "ID",
+    "Listing",
+    "Rent",
+    "Sq ft",
+    "Available",
+    "Listed",
+    "Unlisted",
+    "Status",
+    "URL",
+];
+const EVENT_COLUMNS: [&str; 7] = ["Time", "ID", "Event", "Rent", "Listing", "URL", "Seq"];
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SheetsConfig {
+    /// The ID from the spreadsheet's URL.
+    pub spreadsheet_id: String,
+    /// The service account's JSON key file.
+    pub service_account_key: Utf8PathBuf,
+    /// The name of the sheet to write listings to.
+    #[serde(default = "default_listings_sheet")]
+    pub listings_sheet: String,
+    /// The name of the sheet to write events to.
+    #[serde(default = "default_events_sheet")]
+    pub events_sheet: String,
+}
+
+fn default_listings_sheet() -> String {
+    "Listings".to_owned()
+}
+
+fn default_events_sheet() -> String {
+    "Events".to_owned()
+}
+
+/// The fields we need from a service account's JSON key file.
+#[derive(Deserialize)]
+struct ServiceAccountKey {
+    client_email: String,
+    private_key: String,
+    token_uri: String,
+}
+
+#[derive(Serialize)]
+struct Claims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: i64,
+    exp: i64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct ValueRange {
+    #[serde(default)]
+    values: Vec<Vec<String>>,
+}
+
+pub struct Sheets {
+    client: reqwest::Client,
+    config: SheetsConfig,
+    key: ServiceAccountKey,
+}
+
+impl Sheets {
+    /// Read the service account key, so a missing or malformed key is reported at startup.
+    pub fn new(config: SheetsConfig) -> eyre::Result<Self> {
+        let path = &config.service_account_key;
+        let key = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("Failed to read service account key `{path}`"))?;
+        let key: ServiceAccountKey = serde_json::from_str(&key)
+            .wrap_err_with(|| format!("Failed to parse service account key `{path}`"))?;
+        // The PEM is split over lines, so scrub each one in case part of it is logged.
+        for line in key.private_key.lines() {
+            if !line.starts_with("-----") {
+                redact::add_secret(line);
+            }
+        }
+        Ok(Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()?,
+            config,
+            key,
+        })
+    }
+
+    /// Upsert every listing in `snapshot` into the listings sheet and append any new events to
+    /// the events sheet.
+    pub async fn sync(&self, snapshot: &Snapshot) -> eyre::Result<()> {
+        let token = self.access_token().await?;
+
+        // Listings are matched to rows by the ID in the first column.
+        let sheet = &self.config.listings_sheet;
+        let ids = self.get(&token, &format!("{sheet}!A:A")).await?;
+        let mut next_row = ids.len().max(1) + 1;
+        let mut data = vec![json!({
+            "range": format!("{sheet}!A1"),
+            "values": [LISTING_COLUMNS],
+        })];
+        for row in listing_rows(snapshot) {
+            let index = ids
+                .iter()
+                .position(|existing| existing.first() == row.first())
+                .map(|index| index + 1)
+                .unwrap_or_else(|| {
+                    next_row += 1;
+                    next_row - 1
+                });
+            data.push(json!({
+                "range": format!("{sheet}!A{index}"),
+                "values": [row],
+            }));
+        }
+
+        // Events are only ever appended, so the events we haven't written yet are the ones
+        // after the last row's sequence number.
+        let sheet = &self.config.events_sheet;
+        let rows = self.get(&token, &format!("{sheet}!A:G")).await?;
+        let written = rows.len().saturating_sub(1);
+        let next_seq = next_event_seq(&rows);
+        data.push(json!({
+            "range": format!("{sheet}!A1"),
+            "values": [EVENT_COLUMNS],
+        }));
+        let new_events = snapshot
+            .events
+            .iter()
+            .filter(|event| event.seq >= next_seq)
+            .map(event_row);
+        for (offset, row) in new_events.enumerate() {
+            data.push(json!({
+                "range": format!("{sheet}!A{}", written + offset + 2),
+                "values": [row],
+            }));
+        }
+
+        let url = format!(
+            "{API_URL}/{}/values:batchUpdate",
+            self.config.spreadsheet_id
+        );
+        let body = json!({ "valueInputOption": "RAW", "data": data });
+        self.request(self.client.post(&url).bearer_auth(&token), Some(&body))
+            .await
+            .wrap_err("Failed to update spreadsheet")?;
+        tracing::debug!(
+            spreadsheet = self.config.spreadsheet_id,
+            "Synced spreadsheet"
+        );
+        Ok(())
+    }
+
+    /// Get the values in `range`, like `Listings!A:A`.
+    async fn get(&self, token: &str, range: &str) -> eyre::Result<Vec<Vec<String>>> {
+        let url = format!("{API_URL}/{}/values/{range}", self.config.spreadsheet_id);
+        let response = self
+            .request(self.client.get(&url).bearer_auth(token), None)
+            .await
+            .wrap_err_with(|| format!("Failed to read `{range}` from spreadsheet"))?;
+        let range: ValueRange = serde_json::from_value(response)?;
+        Ok(range.values)
+    }
+
+    /// Exchange a JWT signed with the service account's key for an access token.
+    async fn access_token(&self) -> eyre::Result<String> {
+        let now = Utc::now().timestamp();
+        let claims = Claims {
+            iss: &self.key.client_email,
+            scope: SCOPE,
+            aud: &self.key.token_uri,
+            iat: now,
+            exp: now + 60 * 60,
+        };
+        let key = jsonwebtoken::EncodingKey::from_rsa_pem(self.key.private_key.as_bytes())
+            .wrap_err("Failed to parse service account private key")?;
+        let jwt = jsonwebtoken::encode(
+            &jsonwebtoken::Header::new(jsonwebtoken::Algorithm::RS256),
+            &claims,
+            &key,
+        )?;
+        redact::add_secret(&jwt);
+
+        let request = self.client.post(&self.key.token_uri).form(&[
+            ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+            ("assertion", &jwt),
+        ]);
+        let response = self
+            .request(request, None)
+            .await
+            .wrap_err("Failed to get Google access token")?;
+        let response: TokenResponse = serde_json::from_value(response)?;
+        redact::add_secret(&response.access_token);
+        Ok(response.access_token)
+    }
+
+    async fn request(
+        &self,
+        request: reqwest::RequestBuilder,
+        body: Option<&JsonValue>,
+    ) -> eyre::Result<JsonValue> {
+        let request = match body {
+            Some(body) => request
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(serde_json::to_vec(body)?),
+            None => request,
+        };
+        let response = request.send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            return Err(eyre!("{status}: {text}"));
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+}
+
+/// A row for each listing, listed or not.
+fn listing_rows(snapshot: &Snapshot) -> Vec<Vec<String>> {
+    let listed = snapshot
+        .known_apartments
+        .values()
+        .map(listing_row)
+        .chain(snapshot.known_posts.values().map(listing_row));
+    let unlisted = snapshot
+        .unlisted_apartments
+        .values()
+        .map(listing_row)
+        .chain(snapshot.unlisted_posts.values().map(listing_row));
+    listed.chain(unlisted).collect()
+}
+
+fn listing_row<T: Listing>(apt: &Apartment<T>) -> Vec<String> {
+    let square_feet = match apt.inner.field("sqft") {
+        Some(Value::Number(square_feet)) => format!("{square_feet:.0}"),
+        _ => String::new(),
+    };
+    vec![
+        apt.id().to_owned(),
+        apt.inner.to_string(),
+        apt.inner
+            .rent()
+            .map(|rent| rent.dollars().to_string())
+            .unwrap_or_default(),
+        square_feet,
+        apt.inner
+            .available_date()
+            .map(|date| date.to_string())
+            .unwrap_or_default(),
+        timezone::format(apt.listed, "%Y-%m-%d %H:%M"),
+        apt.unlisted
+            .map(|unlisted| timezone::format(unlisted, "%Y-%m-%d %H:%M"))
+            .unwrap_or_default(),
+        if apt.unlisted.is_some() {
+            "unlisted"
+        } else {
+            "listed"
+        }
+        .to_owned(),
+        apt.inner.url().unwrap_or_else(|| apt.source.clone()),
+    ]
+}
+
+/// The sequence number of the first event not in the events sheet's `rows`, header included.
+///
+/// Rows written before the sequence number column was added have an event per row, in order
+/// from the first event.
+fn next_event_seq(rows: &[Vec<String>]) -> u64 {
+    let written = rows.len().saturating_sub(1) as u64;
+    rows.iter()
+        .skip(1)
+        .last()
+        .and_then(|row| row.get(EVENT_COLUMNS.len() - 1)?.parse::<u64>().ok())
+        .map_or(written, |seq| seq + 1)
+}
+
+fn event_row(event: &Event) -> Vec<String> {
+    vec![
+        timezone::format(event.time, "%Y-%m-%d %H:%M"),
+        event.id.clone(),
+        format!("{:?}", event.kind),
+        event
+            .rent
+            .map(|rent| rent.dollars().to_string())
+            .unwrap_or_default(),
+        event.summary.clone(),
+        event.url.clone().unwrap_or_else(|| event.source.clone()),
+        event.seq.to_string(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::fixtures::craigslist_post;
+    use crate::api::fixtures::CRAIGSLIST_SEARCH;
+
+    #[test]
+    fn test_listing_row() {
+        let mut apt = Apartment::new(CRAIGSLIST_SEARCH, craigslist_post());
+        let row = listing_row(&apt);
+        assert_eq!(row.len(), LISTING_COLUMNS.len());
+        assert_eq!(row[0], "craigslist-7551234567");
+        assert_eq!(row[2], "3000");
+        assert_eq!(row[3], "");
+        assert_eq!(row[7], "listed");
+        assert_eq!(
+            row[8],
+            "https://seattle.craigslist.org/see/apa/d/7551234567.html"
+        );
+
+        apt.unlisted = Some(apt.listed);
+        let row = listing_row(&apt);
+        assert_eq!(row[6], row[5]);
+        assert_eq!(row[7], "unlisted");
+    }
+
+    #[test]
+    fn test_next_event_seq() {
+        let header: Vec<String> = EVENT_COLUMNS.iter().map(|&column| column.into()).collect();
+        let row = |seq: &str| {
+            let mut row = vec![String::new(); 6];
+            if !seq.is_empty() {
+                row.push(seq.to_owned());
+            }
+            row
+        };
+        assert_eq!(next_event_seq(&[]), 0);
+        assert_eq!(next_event_seq(&[header.clone()]), 0);
+        // Rows from before the sequence number column.
+        assert_eq!(next_event_seq(&[header.clone(), row(""), row("")]), 2);
+        assert_eq!(next_event_seq(&[header, row(""), row("57"), row("58")]), 59);
+    }
+}
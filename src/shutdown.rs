@@ -0,0 +1,38 @@
+//! Shutting down cleanly on `SIGINT` and `SIGTERM`, so we don't die halfway through a tick.
+
+use color_eyre::eyre;
+use color_eyre::eyre::Context;
+use tokio::sync::watch;
+
+/// Listen for shutdown signals in the background.
+///
+/// The returned receiver's value is the number of signals received so far, so callers can
+/// treat the first signal as "finish up" and a second as "stop now".
+pub fn listen() -> eyre::Result<watch::Receiver<usize>> {
+    #[cfg(unix)]
+    let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+        .wrap_err("Failed to listen for SIGTERM")?;
+
+    let (sender, receiver) = watch::channel(0);
+    tokio::spawn(async move {
+        loop {
+            #[cfg(unix)]
+            let terminated = terminate.recv();
+            #[cfg(not(unix))]
+            let terminated = std::future::pending::<Option<()>>();
+
+            tokio::select! {
+                result = tokio::signal::ctrl_c() => {
+                    if let Err(err) = result {
+                        tracing::error!("Failed to listen for Ctrl-C: {err}");
+                        return;
+                    }
+                    tracing::info!("Received SIGINT");
+                }
+                _ = terminated => tracing::info!("Received SIGTERM"),
+            }
+            sender.send_modify(|signals| *signals += 1);
+        }
+    });
+    Ok(receiver)
+}
@@ -0,0 +1,325 @@
+//! Running a fake community through the real diff, notification, and DB code, for demoing
+//! integrations and filters without scraping anything:
+//!
+//! ```sh
+//! ava-apartment-finder simulate --days 30 --seed 7
+//! ```
+//!
+//! Each tick is a simulated day, in which units are listed, change price, and are unlisted at
+//! random. The listings are generated in the same JSON format as the Avalon API and parsed like
+//! real data. The DB is written to `--db` rather than the real one.
+//!
+//! Emails go to an in-process mock JMAP server, which logs them, and integrations which would
+//! publish the made-up listings, like social accounts, are turned off; see [`offline`]. With
+//! `--live`, notifications go to the configured integrations instead.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use chrono::NaiveDate;
+use chrono::TimeZone;
+use chrono::Utc;
+use color_eyre::eyre;
+use color_eyre::eyre::Context;
+use serde_json::json;
+use serde_json::Value;
+
+use crate::api;
+use crate::config::Config;
+use crate::jmap::JmapConfig;
+use crate::source::Listings;
+use crate::source::Source;
+use crate::App;
+
+/// The source simulated listings are from.
+pub const SOURCE_URL: &str = "https://www.avaloncommunities.com/washington/simulated/";
+
+/// Floor plans: name, bedrooms, bathrooms, square feet, and typical rent.
+const FLOOR_PLANS: [(&str, usize, usize, f64, f64); 4] = [
+    ("s1", 0, 1, 520.0, 1900.0),
+    ("a2", 1, 1, 720.0, 2500.0),
+    ("b4", 2, 2, 1100.0, 3600.0),
+    ("c1", 3, 2, 1400.0, 4700.0),
+];
+
+/// How many units are listed on the first day.
+const INITIAL_UNITS: usize = 8;
+
+/// Turn off everything in `config` which would send simulated listings off this machine, and
+/// send emails with `jmap` instead, like a [`crate::mock_jmap::MockJmap`].
+pub fn offline(config: &mut Config, jmap: JmapConfig) {
+    config.jmap = jmap;
+    config.social.clear();
+    config.geocoding = None;
+    config.google_sheets = None;
+    config.notion = None;
+    config.airtable = None;
+    config.mqtt = None;
+    config.statsd = None;
+    config.healthcheck_url = None;
+    config.feed_path = None;
+    config.calendar_path = None;
+    config.listen = None;
+    config.control_socket = None;
+}
+
+/// Simulate `days` days of `app`'s notifications and DB updates, waiting `delay` between them.
+pub async fn run(app: &mut App, days: u32, seed: u64, delay: Duration) -> eyre::Result<()> {
+    app.connect().await?;
+    let source = Source::Avalon(SOURCE_URL.to_owned());
+    let mut community = Community::new(seed, Utc::now().naive_utc().date());
+    for day in 1..=days {
+        community.step();
+        let listings = community.listings()?;
+        tracing::info!(day, units = listings.len(), "Simulated day");
+        app.update(&source, Listings::Avalon(listings)).await?;
+        app.save()?;
+        if day < days {
+            tokio::time::sleep(delay).await;
+        }
+    }
+    tracing::info!(
+        apartments = app.store.known_apartments.len(),
+        events = app.store.events.len(),
+        "Simulation finished"
+    );
+    Ok(())
+}
+
+/// A fake community, changing a day at a time.
+pub struct Community {
+    rng: Rng,
+    today: NaiveDate,
+    /// Days simulated so far.
+    days: u32,
+    /// The listed units, by number.
+    units: BTreeMap<String, Unit>,
+}
+
+#[derive(Clone, Debug)]
+struct Unit {
+    plan: usize,
+    rent: f64,
+    available: NaiveDate,
+}
+
+impl Community {
+    pub fn new(seed: u64, today: NaiveDate) -> Self {
+        Self {
+            rng: Rng(seed),
+            today,
+            days: 0,
+            units: BTreeMap::new(),
+        }
+    }
+
+    /// Advance a day, listing, repricing, and unlisting units at random.
+    pub fn step(&mut self) {
+        if self.days > 0 {
+            self.today = self.today.succ_opt().expect("Date is in range");
+        }
+        self.days += 1;
+
+        let rng = &mut self.rng;
+        // About one unit is leased every few days.
+        self.units.retain(|_, _| !rng.chance(0.08));
+        for unit in self.units.values_mut() {
+            if rng.chance(0.15) {
+                // Drops are more common than raises, and prices end in 5 or 0.
+                let percent = rng.below(8) as f64 - 5.0;
+                unit.rent = ((unit.rent * (1.0 + percent / 100.0)) / 5.0).round() * 5.0;
+            }
+            if rng.chance(0.05) {
+                unit.available += chrono::Duration::days(rng.below(14) as i64 + 1);
+            }
+        }
+
+        let new_units = if self.days == 1 {
+            INITIAL_UNITS
+        } else {
+            self.rng.below(3) as usize
+        };
+        for _ in 0..new_units {
+            let number = loop {
+                let number = format!("{}{:02}", self.rng.below(11) + 2, self.rng.below(30) + 1);
+                if !self.units.contains_key(&number) {
+                    break number;
+                }
+            };
+            let plan = self.rng.below(FLOOR_PLANS.len() as u64) as usize;
+            let typical_rent = FLOOR_PLANS[plan].4;
+            let rent =
+                (typical_rent * (0.9 + self.rng.below(21) as f64 / 100.0) / 5.0).round() * 5.0;
+            let available = self.today + chrono::Duration::days(self.rng.below(45) as i64);
+            self.units.insert(
+                number,
+                Unit {
+                    plan,
+                    rent,
+                    available,
+                },
+            );
+        }
+    }
+
+    /// Today's listings, generated as Avalon API JSON and parsed like real data.
+    pub fn listings(&self) -> eyre::Result<Vec<api::Apartment>> {
+        let date = |date: NaiveDate| {
+            Utc.from_utc_datetime(&date.and_hms_opt(4, 0, 0).expect("Time is valid"))
+                .format("%m/%d/%Y %I:%M:%S %p %:z")
+                .to_string()
+        };
+        let units: Vec<Value> = self
+            .units
+            .iter()
+            .map(|(number, unit)| {
+                let (plan, bedroom, bathroom, square_feet, _) = FLOOR_PLANS[unit.plan];
+                let price = json!({ "price": unit.rent, "netEffectivePrice": unit.rent });
+                json!({
+                    "unitId": format!("SIM-001-{number}"),
+                    "name": number,
+                    "furnishStatus": "Unfurnished",
+                    "floorPlan": {
+                        "name": plan,
+                        "lowResolution": "",
+                        "highResolution": "",
+                    },
+                    "virtualTour": null,
+                    "bedroom": bedroom,
+                    "bathroom": bathroom,
+                    "squareFeet": square_feet,
+                    "availableDate": date(unit.available),
+                    "unitRentPrice": {
+                        "appliedDiscount": 0.0,
+                        "pricesPerMoveinDate": [{
+                            "moveInDate": date(unit.available),
+                            "pricesPerTerms": { "12": price },
+                        }],
+                    },
+                    "lowestPricePerMoveInDate": {
+                        "date": date(unit.available),
+                        "termLength": "12",
+                        "price": unit.rent,
+                        "netEffectivePrice": unit.rent,
+                    },
+                    "promotions": [],
+                })
+            })
+            .collect();
+        let data: api::ApartmentData = serde_json::from_value(json!({
+            "units": units,
+            "promotions": [],
+            "pricingOverview": [],
+        }))
+        .wrap_err("Failed to parse simulated listings")?;
+
+        Ok(data
+            .apartments
+            .into_iter()
+            .map(|mut apartment| {
+                apartment.source = SOURCE_URL.to_owned();
+                apartment.inner.page_url = apartment.inner.unit_url(SOURCE_URL);
+                apartment
+            })
+            .collect())
+    }
+}
+
+/// A small deterministic random number generator (SplitMix64), so a seed always gives the
+/// same simulation.
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number from 0 up to but not including `n`.
+    fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    /// `true` with probability `p`.
+    fn chance(&mut self, p: f64) -> bool {
+        ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < p
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::listing::Listing;
+
+    fn simulate(seed: u64, days: u32) -> Vec<Vec<(String, f64)>> {
+        let mut community = Community::new(seed, NaiveDate::from_ymd_opt(2022, 10, 1).unwrap());
+        (0..days)
+            .map(|_| {
+                community.step();
+                community
+                    .listings()
+                    .unwrap()
+                    .into_iter()
+                    .map(|apartment| {
+                        let rent = apartment.inner.rent().unwrap().dollars();
+                        (apartment.id().to_owned(), rent)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_simulation() {
+        let days = simulate(7, 30);
+        assert_eq!(days[0].len(), INITIAL_UNITS);
+        assert!(days
+            .iter()
+            .flatten()
+            .all(|(id, rent)| id.starts_with("SIM-001-") && rent % 5.0 == 0.0));
+        // Units come and go, and change price.
+        let ids = |day: &[(String, f64)]| day.iter().map(|(id, _)| id.clone()).collect::<Vec<_>>();
+        assert_ne!(ids(&days[0]), ids(&days[29]));
+        assert!(days.windows(2).any(|pair| {
+            pair[0].iter().any(|unit| {
+                pair[1]
+                    .iter()
+                    .any(|other| other.0 == unit.0 && other.1 != unit.1)
+            })
+        }));
+
+        assert_eq!(simulate(7, 30), days);
+        assert_ne!(simulate(8, 30), days);
+    }
+
+    #[test]
+    fn test_offline() {
+        let mut config: Config = toml::from_str(
+            r#"
+            feed-path = "ava-feed.xml"
+
+            [[social]]
+            service = "mastodon"
+            instance = "https://mastodon.social"
+            access-token = "token"
+
+            [notion]
+            token = "secret_token"
+            database-id = "668d797c76fa49349b05ad288df2d136"
+            "#,
+        )
+        .unwrap();
+        let jmap = JmapConfig {
+            session_url: "http://127.0.0.1:1234".to_owned(),
+            ..Default::default()
+        };
+        offline(&mut config, jmap);
+        assert_eq!(config.jmap.session_url, "http://127.0.0.1:1234");
+        assert!(config.social.is_empty());
+        assert!(config.notion.is_none());
+        assert!(config.feed_path.is_none());
+    }
+}
@@ -0,0 +1,314 @@
+//! Post qualified listings to Mastodon or Bluesky, e.g. for a local housing bot account.
+//!
+//! Configured with a `[[social]]` table per account, like:
+//!
+//! ```toml
+//! [[social]]
+//! service = "mastodon"
+//! instance = "https://mastodon.social"
+//! access-token = "..."
+//!
+//! [[social]]
+//! service = "bluesky"
+//! handle = "seattle-apartments.bsky.social"
+//! app-password = "xxxx-xxxx-xxxx-xxxx"
+//!
+//! [social.templates]
+//! listed = "New: {summary}\n{link}"
+//! changed = "Updated: {summary}\n{link}"
+//! ```
+//!
+//! Templates can use `{summary}`, `{rent}`, `{available}`, `{id}`, and `{link}`. By default
+//! only new listings are posted.
+
+use std::time::Duration;
+
+use chrono::Utc;
+use color_eyre::eyre;
+use color_eyre::eyre::eyre;
+use color_eyre::eyre::Context;
+use serde::Deserialize;
+use serde_json::json;
+use serde_json::Value as JsonValue;
+
+use crate::events::EventKind;
+use crate::listing::Listing;
+use crate::redact;
+use crate::secret;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SocialConfig {
+    #[serde(flatten)]
+    pub account: Account,
+    #[serde(default)]
+    pub templates: Templates,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "service", rename_all = "kebab-case")]
+pub enum Account {
+    #[serde(rename_all = "kebab-case")]
+    Mastodon {
+        /// Like `https://mastodon.social`.
+        instance: String,
+        /// From the instance's Preferences > Development page, with the `write:statuses`
+        /// scope. See [`crate::secret`].
+        #[serde(deserialize_with = "secret::deserialize")]
+        access_token: String,
+    },
+    #[serde(rename_all = "kebab-case")]
+    Bluesky {
+        handle: String,
+        /// From Settings > App passwords. See [`crate::secret`].
+        #[serde(deserialize_with = "secret::deserialize")]
+        app_password: String,
+        #[serde(default = "default_pds")]
+        pds: String,
+    },
+}
+
+fn default_pds() -> String {
+    "https://bsky.social".to_owned()
+}
+
+impl Account {
+    /// The longest post the service accepts, in characters.
+    fn max_length(&self) -> usize {
+        match self {
+            Account::Mastodon { .. } => 500,
+            Account::Bluesky { .. } => 300,
+        }
+    }
+}
+
+/// What to post for each kind of event. Events without a template aren't posted.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Templates {
+    pub listed: Option<String>,
+    pub changed: Option<String>,
+    pub unlisted: Option<String>,
+}
+
+impl Default for Templates {
+    fn default() -> Self {
+        Self {
+            listed: Some("New listing: {summary}\n{link}".to_owned()),
+            changed: None,
+            unlisted: None,
+        }
+    }
+}
+
+impl Templates {
+    fn get(&self, kind: EventKind) -> Option<&str> {
+        match kind {
+            EventKind::Listed => self.listed.as_deref(),
+            EventKind::Changed => self.changed.as_deref(),
+            EventKind::Unlisted => self.unlisted.as_deref(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Poster {
+    client: reqwest::Client,
+    accounts: Vec<SocialConfig>,
+}
+
+impl Poster {
+    pub fn new(accounts: Vec<SocialConfig>) -> eyre::Result<Self> {
+        Ok(Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()?,
+            accounts,
+        })
+    }
+
+    /// Post about `kind` happening to `listing`, fetched from the source with URL `source`, on
+    /// every account with a template for it.
+    ///
+    /// Errors are logged rather than returned, so a social media outage doesn't stop
+    /// notifications.
+    pub async fn post(&self, kind: EventKind, listing: &impl Listing, source: &str) {
+        for config in &self.accounts {
+            let template = match config.templates.get(kind) {
+                Some(template) => template,
+                None => continue,
+            };
+            let link = listing.url().unwrap_or_else(|| source.to_owned());
+            let text = truncate(
+                &render(template, listing, &link),
+                config.account.max_length(),
+            );
+            let result = match &config.account {
+                Account::Mastodon {
+                    instance,
+                    access_token,
+                } => self.post_mastodon(instance, access_token, &text).await,
+                Account::Bluesky {
+                    handle,
+                    app_password,
+                    pds,
+                } => {
+                    self.post_bluesky(pds, handle, app_password, &text, &link)
+                        .await
+                }
+            };
+            match result {
+                Ok(()) => tracing::info!(id = listing.id(), ?kind, "Posted to social media"),
+                Err(err) => tracing::error!("Failed to post to social media: {err:?}"),
+            }
+        }
+    }
+
+    async fn post_mastodon(&self, instance: &str, token: &str, text: &str) -> eyre::Result<()> {
+        let url = format!("{}/api/v1/statuses", instance.trim_end_matches('/'));
+        self.request(
+            self.client.post(&url).bearer_auth(token),
+            json!({ "status": text }),
+        )
+        .await
+        .wrap_err("Failed to post to Mastodon")?;
+        Ok(())
+    }
+
+    async fn post_bluesky(
+        &self,
+        pds: &str,
+        handle: &str,
+        password: &str,
+        text: &str,
+        link: &str,
+    ) -> eyre::Result<()> {
+        let pds = pds.trim_end_matches('/');
+        // We post rarely enough that a session per post is fine.
+        let session = self
+            .request(
+                self.client
+                    .post(format!("{pds}/xrpc/com.atproto.server.createSession")),
+                json!({ "identifier": handle, "password": password }),
+            )
+            .await
+            .wrap_err("Failed to log in to Bluesky")?;
+        let token = session["accessJwt"]
+            .as_str()
+            .ok_or_else(|| eyre!("Bluesky session has no `accessJwt`"))?;
+        redact::add_secret(token);
+        if let Some(refresh_token) = session["refreshJwt"].as_str() {
+            redact::add_secret(refresh_token);
+        }
+        let did = session["did"]
+            .as_str()
+            .ok_or_else(|| eyre!("Bluesky session has no `did`"))?;
+
+        let mut post = json!({
+            "$type": "app.bsky.feed.post",
+            "text": text,
+            "createdAt": Utc::now().to_rfc3339(),
+        });
+        // Bluesky doesn't detect links in the text; they have to be marked up as facets.
+        if let Some(start) = text.find(link) {
+            post["facets"] = json!([{
+                "index": { "byteStart": start, "byteEnd": start + link.len() },
+                "features": [{ "$type": "app.bsky.richtext.facet#link", "uri": link }],
+            }]);
+        }
+        self.request(
+            self.client
+                .post(format!("{pds}/xrpc/com.atproto.repo.createRecord"))
+                .bearer_auth(token),
+            json!({ "repo": did, "collection": "app.bsky.feed.post", "record": post }),
+        )
+        .await
+        .wrap_err("Failed to post to Bluesky")?;
+        Ok(())
+    }
+
+    async fn request(
+        &self,
+        request: reqwest::RequestBuilder,
+        body: JsonValue,
+    ) -> eyre::Result<JsonValue> {
+        let response = request
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(&body)?)
+            .send()
+            .await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            return Err(eyre!("{status}: {text}"));
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+}
+
+/// Fill in `template`'s placeholders for `listing`.
+fn render(template: &str, listing: &impl Listing, link: &str) -> String {
+    let rent = listing
+        .rent()
+        .map(|rent| rent.round().to_string())
+        .unwrap_or_default();
+    let available = listing
+        .available_date()
+        .map(|date| date.format("%b %e %Y").to_string())
+        .unwrap_or_default();
+    template
+        .replace("{summary}", &listing.to_string())
+        .replace("{rent}", &rent)
+        .replace("{available}", &available)
+        .replace("{id}", listing.id())
+        .replace("{link}", link)
+}
+
+/// Shorten `text` to at most `max` characters, marking where it was cut off.
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_owned();
+    }
+    let mut truncated: String = text.chars().take(max - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::fixtures::craigslist_post;
+
+    #[test]
+    fn test_render() {
+        let post = craigslist_post();
+        assert_eq!(
+            render("{rent} {id}{available}: {link}", &post, &post.link),
+            "$3,000 craigslist-7551234567: \
+             https://seattle.craigslist.org/see/apa/d/7551234567.html"
+        );
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("rather long", 7), "rather…");
+    }
+
+    #[test]
+    fn test_config() {
+        let config: SocialConfig = toml::from_str(
+            r#"
+            service = "bluesky"
+            handle = "seattle-apartments.bsky.social"
+            app-password = "xxxx-xxxx-xxxx-xxxx"
+            "#,
+        )
+        .unwrap();
+        assert!(
+            matches!(config.account, Account::Bluesky { ref pds, .. } if pds == "https://bsky.social")
+        );
+        assert!(config.templates.listed.is_some());
+        assert!(config.templates.changed.is_none());
+    }
+}
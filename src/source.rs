@@ -0,0 +1,65 @@
+//! Places we fetch listings from.
+
+use std::fmt::Display;
+use std::sync::Arc;
+
+use color_eyre::eyre;
+
+use crate::api::Apartment;
+use crate::config::Config;
+use crate::craigslist;
+use crate::http;
+use crate::AvalonClient;
+
+#[derive(Clone, Debug)]
+pub enum Source {
+    /// An Avalon community page, like [`crate::AVA_URL`].
+    Avalon(String),
+    /// A Craigslist search URL or RSS feed.
+    Craigslist(String),
+}
+
+impl Source {
+    /// The URL this source is fetched from.
+    ///
+    /// This is recorded on each [`Apartment`] so we know which source is responsible for it.
+    pub fn url(&self) -> &str {
+        match self {
+            Source::Avalon(url) => url,
+            Source::Craigslist(url) => url,
+        }
+    }
+
+    #[tracing::instrument(skip(http, config))]
+    pub async fn fetch(&self, http: &Arc<http::Client>, config: &Config) -> eyre::Result<Listings> {
+        match self {
+            Source::Avalon(url) => {
+                let mut client = AvalonClient::new(http.clone());
+                if config.headless_fallback {
+                    client = client.headless_fallback(config.chrome_executable.clone());
+                }
+                let data = client.get_apartments(url).await?;
+                Ok(Listings::Avalon(data.apartments))
+            }
+            Source::Craigslist(search_url) => Ok(Listings::Craigslist(
+                craigslist::get_posts(http, search_url).await?,
+            )),
+        }
+    }
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Avalon(url) => write!(f, "Avalon community {url}"),
+            Source::Craigslist(url) => write!(f, "Craigslist search {url}"),
+        }
+    }
+}
+
+/// The listings fetched from a [`Source`].
+#[derive(Debug)]
+pub enum Listings {
+    Avalon(Vec<Apartment>),
+    Craigslist(Vec<Apartment<craigslist::Post>>),
+}
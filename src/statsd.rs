@@ -0,0 +1,73 @@
+//! Send metrics to a StatsD server, like Datadog's agent or Telegraf.
+//!
+//! Configured like:
+//!
+//! ```toml
+//! [statsd]
+//! host = "127.0.0.1"
+//! port = 8125
+//! prefix = "ava_apartment_finder"
+//! ```
+//!
+//! Metrics are recorded with the [`metrics`] crate; without a `[statsd]` table they go nowhere.
+//! Sources are tagged by URL, in the Datadog style. The metrics are:
+//!
+//! - `ticks` (counter, tagged with `result`): ticks run, and whether they succeeded.
+//! - `tick.duration` (histogram, seconds): how long each tick took.
+//! - `fetches` (counter, tagged with `source` and `result`): sources fetched.
+//! - `fetch.duration` (histogram, seconds, tagged with `source`): how long each fetch took,
+//!   not counting time spent waiting for a fetch slot.
+//! - `listings` (gauge, tagged with `source`): listings seen in the last successful fetch.
+//! - `changes` (counter, tagged with `kind`): units `added`, `removed`, or `changed`.
+//! - `notifications` (counter): emails sent.
+//! - `email.delivery_failures` (counter): recipients the email server couldn't deliver to.
+
+use color_eyre::eyre;
+use color_eyre::eyre::eyre;
+use color_eyre::eyre::Context;
+use metrics_exporter_statsd::StatsdBuilder;
+use serde::Deserialize;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct StatsdConfig {
+    #[serde(default = "default_host")]
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    #[serde(default = "default_prefix")]
+    pub prefix: String,
+}
+
+fn default_host() -> String {
+    "127.0.0.1".to_owned()
+}
+
+fn default_port() -> u16 {
+    8125
+}
+
+fn default_prefix() -> String {
+    "ava_apartment_finder".to_owned()
+}
+
+/// Start sending metrics to the StatsD server in `config`. Metrics are sent over UDP, so an
+/// unreachable server doesn't cause errors.
+pub fn install(config: &StatsdConfig) -> eyre::Result<()> {
+    let recorder = StatsdBuilder::from(config.host.as_str(), config.port)
+        .build(Some(&config.prefix))
+        .wrap_err_with(|| {
+            format!(
+                "Failed to set up StatsD client for {}:{}",
+                config.host, config.port
+            )
+        })?;
+    metrics::set_global_recorder(recorder)
+        .map_err(|_| eyre!("A metrics recorder is already installed"))?;
+    tracing::info!(
+        host = config.host,
+        port = config.port,
+        "Sending metrics to StatsD"
+    );
+    Ok(())
+}
@@ -0,0 +1,260 @@
+//! The DB of listings we know about and what's happened to them, saved as a JSON file.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use chrono::DateTime;
+use chrono::NaiveDate;
+use chrono::Utc;
+use color_eyre::eyre;
+use color_eyre::eyre::Context;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::api;
+use crate::craigslist;
+use crate::events::Event;
+use crate::geo::Location;
+use crate::lease_term::LeaseOption;
+
+/// Known and unlisted listings are keyed by [`crate::listing::Listing::id`].
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ApartmentStore {
+    pub known_apartments: BTreeMap<String, api::Apartment>,
+    pub unlisted_apartments: BTreeMap<String, api::Apartment>,
+    #[serde(default)]
+    pub known_posts: BTreeMap<String, api::Apartment<craigslist::Post>>,
+    #[serde(default)]
+    pub unlisted_posts: BTreeMap<String, api::Apartment<craigslist::Post>>,
+    /// The number of consecutive times each source has failed to fetch, by URL.
+    #[serde(default)]
+    pub failures: BTreeMap<String, usize>,
+    /// IDs of units to never notify about.
+    #[serde(default)]
+    pub ignored: BTreeSet<String>,
+    /// IDs of units to notify about any change to, regardless of qualifications.
+    #[serde(default)]
+    pub watched: BTreeSet<String>,
+    /// IDs of units not to notify about until a time, from replying `SNOOZE` to an email.
+    #[serde(default)]
+    pub snoozed: BTreeMap<String, DateTime<Utc>>,
+    /// Everything that's happened to every listing, oldest first, until it's pruned; see
+    /// [`ApartmentStore::prune_events`].
+    #[serde(default)]
+    pub events: Vec<Event>,
+    /// The [`Event::seq`] of the next event to be recorded.
+    #[serde(default)]
+    pub next_event_seq: u64,
+    /// When we last sent the weekly market report.
+    #[serde(default)]
+    pub last_weekly_report: Option<DateTime<Utc>>,
+    /// Where each community is, by page URL; see [`crate::geo`].
+    #[serde(default)]
+    pub locations: BTreeMap<String, Location>,
+    /// The cheapest move-in date in the configured window for each qualified unit, by ID; see
+    /// [`crate::lease_term`].
+    #[serde(default)]
+    pub best_move_in: BTreeMap<String, LeaseOption>,
+    /// Promotion reminders we've sent, keyed by [`crate::reminders::key`], with the date each
+    /// promotion ends so they can be forgotten afterwards.
+    #[serde(default)]
+    pub promotion_reminders: BTreeMap<String, NaiveDate>,
+    /// When we started watching each watched unit, by ID.
+    #[serde(default)]
+    pub watched_since: BTreeMap<String, DateTime<Utc>>,
+    /// The availability date we've sent a reminder about for each watched unit, by ID.
+    #[serde(default)]
+    pub availability_reminders: BTreeMap<String, NaiveDate>,
+    /// Sources whose latest scrapes looked wrong, like most of their listings vanishing at once,
+    /// by URL. Their listings aren't updated until the scrapes look right again or are
+    /// accepted.
+    #[serde(default)]
+    pub suspect_scrapes: BTreeMap<String, SuspectScrapes>,
+}
+
+/// A streak of suspect scrapes from one source which all returned the same listings.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct SuspectScrapes {
+    /// The IDs of the listings each scrape returned.
+    pub ids: BTreeSet<String>,
+    /// How many scrapes in a row returned `ids`.
+    pub count: usize,
+}
+
+impl SuspectScrapes {
+    /// Note another suspect scrape returning `ids`, and return how many in a row have returned
+    /// the same listings.
+    pub fn record(&mut self, ids: BTreeSet<String>) -> usize {
+        if self.ids == ids {
+            self.count += 1;
+        } else {
+            self.ids = ids;
+            self.count = 1;
+        }
+        self.count
+    }
+}
+
+impl ApartmentStore {
+    /// Load the DB from `path`, or start a new one if it doesn't exist.
+    pub fn load(path: &Path) -> eyre::Result<Self> {
+        if path.exists() {
+            tracing::info!(?path, "DB path exists, reading");
+            let mut store: Self = serde_json::from_str(
+                &std::fs::read_to_string(path)
+                    .wrap_err_with(|| format!("Failed to read `{path:?}`"))?,
+            )
+            .wrap_err_with(|| format!("Failed to load Apartment data from `{path:?}`"))?;
+            store.fill_derived_fields();
+            store.number_events();
+            Ok(store)
+        } else {
+            tracing::info!(?path, "No DB, initializing");
+            Ok(Self::default())
+        }
+    }
+
+    /// Fill in the fields of saved units which are derived from other fields; see
+    /// [`api::ApiApartment::fill_derived_fields`].
+    fn fill_derived_fields(&mut self) {
+        for apt in self
+            .known_apartments
+            .values_mut()
+            .chain(self.unlisted_apartments.values_mut())
+        {
+            apt.inner.fill_derived_fields(&apt.source);
+            if let Some(reported) = &mut apt.reported {
+                reported.fill_derived_fields(&apt.source);
+            }
+        }
+    }
+
+    /// Number the events in DBs written before events had sequence numbers, in order.
+    fn number_events(&mut self) {
+        if self.next_event_seq == 0 && !self.events.is_empty() {
+            for (seq, event) in self.events.iter_mut().enumerate() {
+                event.seq = seq as u64;
+            }
+            self.next_event_seq = self.events.len() as u64;
+        }
+    }
+
+    /// Add `events` to the end of the event log, numbering them.
+    pub fn record_events(&mut self, events: impl IntoIterator<Item = Event>) {
+        for mut event in events {
+            event.seq = self.next_event_seq;
+            self.next_event_seq += 1;
+            self.events.push(event);
+        }
+    }
+
+    /// Forget events from before `cutoff`, except for listings which are still listed. Returns
+    /// how many were forgotten.
+    pub fn prune_events(&mut self, cutoff: DateTime<Utc>) -> usize {
+        let before = self.events.len();
+        let (known_apartments, known_posts) = (&self.known_apartments, &self.known_posts);
+        self.events.retain(|event| {
+            event.time >= cutoff
+                || known_apartments.contains_key(&event.id)
+                || known_posts.contains_key(&event.id)
+        });
+        before - self.events.len()
+    }
+
+    /// Write the DB to `path`.
+    pub fn save(&self, path: &Path) -> eyre::Result<()> {
+        write(&self.to_json()?, path)
+    }
+
+    /// The DB as it's saved to disk.
+    pub fn to_json(&self) -> eyre::Result<Vec<u8>> {
+        serde_json::to_vec_pretty(self).wrap_err("Failed to serialize DB")
+    }
+}
+
+/// Write `data` from [`ApartmentStore::to_json`] to `path`.
+///
+/// The DB is written to a temporary file and moved into place, so being interrupted halfway
+/// through can't leave a corrupt DB behind.
+pub fn write(data: &[u8], path: &Path) -> eyre::Result<()> {
+    let temp_path = format!("{}.tmp", path.display());
+    let mut data_file =
+        File::create(&temp_path).wrap_err_with(|| format!("Failed to open {temp_path:?}"))?;
+    data_file
+        .write_all(data)
+        .and_then(|()| data_file.sync_all())
+        .wrap_err("Failed to write DB")?;
+    std::fs::rename(&temp_path, path)
+        .wrap_err_with(|| format!("Failed to move {temp_path:?} to {path:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::fixtures::craigslist_post;
+    use crate::api::fixtures::CRAIGSLIST_SEARCH;
+    use crate::events::EventKind;
+
+    #[test]
+    fn test_event_seq() {
+        let event = Event::new(EventKind::Listed, CRAIGSLIST_SEARCH, &craigslist_post());
+        let mut store = ApartmentStore::default();
+        store.record_events([event.clone(), event.clone()]);
+        store.events.remove(0);
+        store.record_events([event.clone()]);
+        let seqs = |store: &ApartmentStore| -> Vec<u64> {
+            store.events.iter().map(|event| event.seq).collect()
+        };
+        assert_eq!(seqs(&store), vec![1, 2]);
+        assert_eq!(store.next_event_seq, 3);
+
+        // DBs from before sequence numbers are numbered in order.
+        let mut old = ApartmentStore {
+            events: vec![event.clone(), event],
+            ..Default::default()
+        };
+        old.number_events();
+        assert_eq!(seqs(&old), vec![0, 1]);
+        assert_eq!(old.next_event_seq, 2);
+    }
+
+    #[test]
+    fn test_prune_events() {
+        let post = craigslist_post();
+        let mut gone = craigslist_post();
+        gone.id = "craigslist-7551234568".to_owned();
+        let old = |listing| Event {
+            time: Utc::now() - chrono::Duration::days(400),
+            ..Event::new(EventKind::Listed, CRAIGSLIST_SEARCH, listing)
+        };
+        let mut store = ApartmentStore {
+            known_posts: [(
+                post.id.clone(),
+                api::Apartment::new(CRAIGSLIST_SEARCH, post.clone()),
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        store.record_events([
+            old(&post),
+            old(&gone),
+            Event::new(EventKind::Listed, CRAIGSLIST_SEARCH, &gone),
+        ]);
+
+        let cutoff = Utc::now() - chrono::Duration::days(365);
+        assert_eq!(store.prune_events(cutoff), 1);
+        assert_eq!(
+            store
+                .events
+                .iter()
+                .map(|event| event.seq)
+                .collect::<Vec<_>>(),
+            [0, 2]
+        );
+        assert_eq!(store.prune_events(cutoff), 0);
+    }
+}
@@ -0,0 +1,45 @@
+//! Integration with systemd's service manager, for running as a `Type=notify` service.
+//!
+//! A unit file might look like:
+//!
+//! ```ini
+//! [Service]
+//! Type=notify
+//! ExecStart=/usr/local/bin/ava-apartment-finder --systemd
+//! WatchdogSec=15min
+//! Restart=on-failure
+//! ```
+//!
+//! We ping the watchdog after every tick, so `WatchdogSec` should be comfortably longer than
+//! the poll interval plus the time a tick takes. Outside of systemd, these are all no-ops.
+
+use std::time::Duration;
+
+use sd_notify::NotifyState;
+
+/// Tell systemd we've started up.
+pub fn ready() {
+    notify(&[NotifyState::Ready]);
+}
+
+/// Tell systemd we're shutting down.
+pub fn stopping() {
+    notify(&[NotifyState::Stopping]);
+}
+
+/// Tell systemd we're still alive, and update the status shown by `systemctl status`.
+pub fn watchdog(status: &str) {
+    notify(&[NotifyState::Watchdog, NotifyState::Status(status)]);
+}
+
+/// The watchdog timeout, if systemd has enabled the watchdog for us.
+pub fn watchdog_timeout() -> Option<Duration> {
+    let mut usec = 0;
+    sd_notify::watchdog_enabled(false, &mut usec).then(|| Duration::from_micros(usec))
+}
+
+fn notify(state: &[NotifyState]) {
+    if let Err(err) = sd_notify::notify(false, state) {
+        tracing::warn!("Failed to notify systemd: {err}");
+    }
+}
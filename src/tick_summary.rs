@@ -0,0 +1,90 @@
+//! One structured event at the end of each tick, so the JSONL logs can be turned into
+//! dashboards without piecing together what happened from scattered debug logs.
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use color_eyre::eyre;
+
+/// Tracing target for the summary event. It's logged at `info`, so it's always written to the
+/// JSONL log files.
+pub const TICK_SUMMARY: &str = "ava_apartment_finder::tick_summary";
+
+/// Counters for a single tick.
+#[derive(Debug, Default)]
+pub struct TickSummary {
+    /// Sources fetched, including ones that failed.
+    pub sources: usize,
+    /// Sources which failed to fetch or failed their sanity checks.
+    pub failed_sources: usize,
+    /// Sources whose changes were recorded but not all reported, like when an email fails to
+    /// send.
+    pub failed_reports: usize,
+    /// The slowest fetch. Time spent waiting for a fetch slot isn't counted.
+    pub max_fetch_latency: Duration,
+    /// All fetches added together.
+    pub total_fetch_latency: Duration,
+    /// Listings seen across all successfully fetched sources.
+    pub units_seen: usize,
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+    /// Emails sent. Atomic because emails are sent through a shared reference.
+    notifications: AtomicUsize,
+}
+
+impl TickSummary {
+    pub fn record_fetch(&mut self, latency: Duration) {
+        self.sources += 1;
+        self.max_fetch_latency = self.max_fetch_latency.max(latency);
+        self.total_fetch_latency += latency;
+    }
+
+    pub fn record_notification(&self) {
+        self.notifications.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn notifications(&self) -> usize {
+        self.notifications.load(Ordering::Relaxed)
+    }
+
+    /// Log the summary for a tick that took `duration` and ended with `error`, if any.
+    pub fn emit(&self, duration: Duration, error: Option<&eyre::Report>) {
+        let error = error.map(|err| format!("{err:#}"));
+        tracing::info!(
+            target: TICK_SUMMARY,
+            duration_ms = duration.as_millis() as u64,
+            sources = self.sources,
+            failed_sources = self.failed_sources,
+            failed_reports = self.failed_reports,
+            max_fetch_latency_ms = self.max_fetch_latency.as_millis() as u64,
+            total_fetch_latency_ms = self.total_fetch_latency.as_millis() as u64,
+            units_seen = self.units_seen,
+            added = self.added,
+            removed = self.removed,
+            changed = self.changed,
+            notifications = self.notifications(),
+            error = error.as_deref(),
+            "Tick summary"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_fetch() {
+        let mut summary = TickSummary::default();
+        summary.record_fetch(Duration::from_millis(300));
+        summary.record_fetch(Duration::from_millis(1200));
+        summary.record_fetch(Duration::from_millis(500));
+        summary.record_notification();
+        assert_eq!(summary.sources, 3);
+        assert_eq!(summary.max_fetch_latency, Duration::from_millis(1200));
+        assert_eq!(summary.total_fetch_latency, Duration::from_millis(2000));
+        assert_eq!(summary.notifications(), 1);
+    }
+}
@@ -0,0 +1,134 @@
+//! The time zone times are shown in, in emails, exports, the dashboard, and console logs,
+//! configured like:
+//!
+//! ```toml
+//! timezone = "America/Los_Angeles"
+//! ```
+//!
+//! Defaults to the system's local time zone. Quiet hours and the weekly report schedule are in
+//! this time zone too. Times are still stored in UTC, and JSON logs are in UTC. Availability
+//! dates are just dates, so they aren't converted.
+
+use std::str::FromStr;
+use std::sync::RwLock;
+
+use chrono::DateTime;
+use chrono::Local;
+use chrono::NaiveDateTime;
+use chrono::TimeZone;
+use chrono::Utc;
+use chrono_tz::Tz;
+use serde::Deserialize;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum DisplayTimeZone {
+    /// The system's time zone.
+    #[default]
+    Local,
+    /// An IANA time zone, like `America/New_York`.
+    Named(Tz),
+}
+
+impl DisplayTimeZone {
+    /// Format `time` in this time zone, with a `chrono` format string like `%b %e %H:%M`.
+    pub fn format(&self, time: DateTime<Utc>, format: &str) -> String {
+        match self {
+            DisplayTimeZone::Local => time.with_timezone(&Local).format(format).to_string(),
+            DisplayTimeZone::Named(tz) => time.with_timezone(tz).format(format).to_string(),
+        }
+    }
+
+    /// The wall-clock time in this time zone at `time`.
+    pub fn naive_local(&self, time: DateTime<Utc>) -> NaiveDateTime {
+        match self {
+            DisplayTimeZone::Local => time.with_timezone(&Local).naive_local(),
+            DisplayTimeZone::Named(tz) => time.with_timezone(tz).naive_local(),
+        }
+    }
+
+    /// The first time the wall clock in this time zone reads `local`, if it ever does; it may
+    /// be skipped by a daylight saving time change.
+    pub fn from_naive_local(&self, local: &NaiveDateTime) -> Option<DateTime<Utc>> {
+        match self {
+            DisplayTimeZone::Local => Local
+                .from_local_datetime(local)
+                .earliest()
+                .map(|time| time.with_timezone(&Utc)),
+            DisplayTimeZone::Named(tz) => tz
+                .from_local_datetime(local)
+                .earliest()
+                .map(|time| time.with_timezone(&Utc)),
+        }
+    }
+}
+
+impl FromStr for DisplayTimeZone {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        if name.eq_ignore_ascii_case("local") {
+            return Ok(DisplayTimeZone::Local);
+        }
+        name.parse().map(DisplayTimeZone::Named).map_err(|_| {
+            format!(
+                "Unknown time zone `{name}`; expected a name like `America/New_York` or `local`"
+            )
+        })
+    }
+}
+
+impl TryFrom<String> for DisplayTimeZone {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        name.parse()
+    }
+}
+
+static TIME_ZONE: RwLock<DisplayTimeZone> = RwLock::new(DisplayTimeZone::Local);
+
+/// Set the time zone to display times in from now on.
+pub fn set(time_zone: DisplayTimeZone) {
+    *TIME_ZONE.write().expect("Time zone lock poisoned") = time_zone;
+}
+
+/// The configured time zone.
+pub fn get() -> DisplayTimeZone {
+    *TIME_ZONE.read().expect("Time zone lock poisoned")
+}
+
+/// Format `time` in the configured time zone; see [`DisplayTimeZone::format`].
+pub fn format(time: DateTime<Utc>, format: &str) -> String {
+    get().format(time, format)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    #[test]
+    fn test_time_zone() {
+        assert_eq!("local".parse(), Ok(DisplayTimeZone::Local));
+        let new_york: DisplayTimeZone = "America/New_York".parse().unwrap();
+        assert_eq!(
+            new_york,
+            DisplayTimeZone::Named(chrono_tz::America::New_York)
+        );
+        assert!("Mars/Olympus_Mons".parse::<DisplayTimeZone>().is_err());
+
+        let time = Utc.ymd(2022, 10, 21).and_hms_opt(4, 0, 0).unwrap();
+        assert_eq!(
+            new_york.format(time, "%b %e %Y %H:%M %Z"),
+            "Oct 21 2022 00:00 EDT"
+        );
+        let local = NaiveDate::from_ymd_opt(2022, 10, 21)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        assert_eq!(new_york.naive_local(time), local);
+        assert_eq!(new_york.from_naive_local(&local), Some(time));
+    }
+}
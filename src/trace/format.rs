@@ -1,10 +1,12 @@
 //! Support for formatting tracing events.
 //!
-//! This is used to output log messages to the console.
+//! This is used to output log messages to the console, in one of a few [`Theme`]s.
 //!
-//! Most of the logic is in the [`fmt::Display`] impl for [`EventVisitor`].
+//! [`EventVisitor`] collects an event's message and fields, which are also used by the
+//! [`journal`](super::journal) formatter.
 
 use std::fmt;
+use std::fmt::Write;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 
@@ -12,6 +14,7 @@ use chrono::Utc;
 use owo_colors::OwoColorize;
 use owo_colors::Stream::Stdout;
 use owo_colors::Style;
+use serde::Deserialize;
 use tap::Tap;
 use tracing::field::Field;
 use tracing::field::Visit;
@@ -23,114 +26,88 @@ use tracing_subscriber::registry::LookupSpan;
 
 use crate::wrap::TextWrapOptionsExt;
 
+/// How [`EventFormatter`] lays out events, configured with `theme` in the `[log]` table.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    /// A timestamp and the message, wrapped to the terminal width, with blank lines around
+    /// multi-line messages.
+    #[default]
+    Pretty,
+    /// Just the message, wrapped to the terminal width.
+    Minimal,
+    /// One line per event, starting with a precise timestamp and the level, for scrolling back
+    /// through a long-running daemon's output.
+    Timestamped,
+}
+
 #[derive(Default)]
 pub struct EventFormatter {
+    theme: Theme,
     /// We print blank lines before and after long log messages to help visually separate them.
     ///
     /// This becomes an issue if two long log messages are printed one after another.
     ///
     /// If this variable is `true`, we skip the blank line before to prevent printing two blank
-    /// lines in a row.
-    ///
-    /// This variable is mutated whenever [`format_event`] is called.
+    /// lines in a row. Only used by [`Theme::Pretty`].
     last_event_was_long: AtomicBool,
 }
 
-impl<S, N> FormatEvent<S, N> for EventFormatter
-where
-    S: Subscriber + for<'a> LookupSpan<'a>,
-    N: for<'a> FormatFields<'a> + 'static,
-{
-    fn format_event(
-        &self,
-        _ctx: &tracing_subscriber::fmt::FmtContext<'_, S, N>,
-        mut writer: tracing_subscriber::fmt::format::Writer<'_>,
-        event: &tracing::Event<'_>,
-    ) -> std::fmt::Result {
-        let visitor = EventVisitor::new(
-            *event.metadata().level(),
-            AtomicBool::new(self.last_event_was_long.load(Ordering::SeqCst)),
-        )
-        .tap_mut(|visitor| event.record(visitor));
-        write!(writer, "{visitor}")?;
-        // Transfer `last_event_was_long` state back into this object.
-        self.last_event_was_long.store(
-            visitor.last_event_was_long.load(Ordering::SeqCst),
-            Ordering::SeqCst,
-        );
-        Ok(())
-    }
-}
-
-#[derive(Debug)]
-pub struct EventVisitor {
-    pub last_event_was_long: AtomicBool,
-    pub level: Level,
-    style: EventStyle,
-    pub message: String,
-    pub fields: Vec<(String, String)>,
-}
-
-impl EventVisitor {
-    pub fn new(level: Level, last_event_was_long: AtomicBool) -> Self {
+impl EventFormatter {
+    pub fn new(theme: Theme) -> Self {
         Self {
-            level,
-            last_event_was_long,
-            style: EventStyle::new(level),
-            message: Default::default(),
-            fields: Default::default(),
+            theme,
+            last_event_was_long: AtomicBool::new(false),
         }
     }
 
-    /// If there's only one field, and it fits on the same line as the message, put it on the
-    /// same line. Otherwise, we use the 'long format' with each field on a separate line.
-    fn use_short_format(&self, term_width: usize) -> bool {
-        self.fields.len() == 1
-            && self.fields[0].0.len() + self.fields[0].1.len() + 2 < term_width - self.message.len()
-    }
-}
-
-impl Visit for EventVisitor {
-    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
-        if field.name() == "message" {
-            self.message = format!("{value:?}");
-        } else {
-            self.fields
-                .push((field.name().to_owned(), format!("{value:?}")));
-        }
-    }
-}
-
-impl fmt::Display for EventVisitor {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let indent_colored = self.style.indent_colored();
+    /// Write `visitor`'s message wrapped to the terminal width, optionally prefixed with a
+    /// timestamp and surrounded by blank lines if it's long.
+    fn write_wrapped(
+        &self,
+        f: &mut impl Write,
+        visitor: &EventVisitor,
+        style: &EventStyle,
+        pretty: bool,
+    ) -> fmt::Result {
+        let indent_colored = style.indent_colored();
 
         let options = crate::wrap::options()
             .initial_indent(&indent_colored)
-            .subsequent_indent(self.style.subsequent_indent);
+            .subsequent_indent(style.subsequent_indent);
 
         // Next, color the message _before_ wrapping it. If you wrap before coloring,
         // `textwrap` prepends the `initial_indent` to the first line. The `initial_indent` is
         // colored, so it has a reset sequence at the end, and the message ends up uncolored.
-        let mut message = format!("{} {}", Utc::now().to_rfc2822().dimmed(), self.message);
+        let message = crate::color::for_terminal(&visitor.message);
+        let mut message = if pretty {
+            format!(
+                "{} {message}",
+                Utc::now()
+                    .to_rfc2822()
+                    .if_supports_color(Stdout, |text| text.dimmed()),
+            )
+        } else {
+            message.into_owned()
+        };
 
         // If there's only one field, and it fits on the same line as the message, put it on the
         // same line. Otherwise, we use the 'long format' with each field on a separate line.
-        let short_format = self.use_short_format(options.width);
+        let short_format = visitor.use_short_format(options.width);
 
         if short_format {
-            for (name, value) in &self.fields {
-                message.push_str(&format!(" {}", self.style.style_field(name, value)));
+            for (name, value) in &visitor.fields {
+                message.push_str(&format!(" {}", style.style_field(name, value)));
             }
         }
 
-        let message_colored = self.style.style_message(&message);
+        let message_colored = style.style_message(&message);
 
         let lines = options.wrap(&message_colored);
 
         // If there's more than one line of message, add a blank line before and after the message.
         // This doesn't account for fields, but I think that's fine?
-        let add_blank_lines = lines.len() > 1;
+        let add_blank_lines = pretty && lines.len() > 1;
         // Store `add_blank_lines` and fetch the previous value:
         let last_event_was_long = self
             .last_event_was_long
@@ -146,12 +123,12 @@ impl fmt::Display for EventVisitor {
 
         // Add fields, one per line, at the end.
         if !short_format {
-            for (name, value) in &self.fields {
+            for (name, value) in &visitor.fields {
                 writeln!(
                     f,
                     "{}{}",
-                    self.style.subsequent_indent,
-                    self.style.style_field(name, value)
+                    style.subsequent_indent,
+                    style.style_field(name, value)
                 )?;
             }
         }
@@ -165,6 +142,85 @@ impl fmt::Display for EventVisitor {
     }
 }
 
+impl<S, N> FormatEvent<S, N> for EventFormatter
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        _ctx: &tracing_subscriber::fmt::FmtContext<'_, S, N>,
+        mut writer: tracing_subscriber::fmt::format::Writer<'_>,
+        event: &tracing::Event<'_>,
+    ) -> std::fmt::Result {
+        let level = *event.metadata().level();
+        let visitor = EventVisitor::new(level).tap_mut(|visitor| event.record(visitor));
+        let style = EventStyle::new(level);
+        match self.theme {
+            Theme::Pretty => self.write_wrapped(&mut writer, &visitor, &style, true),
+            Theme::Minimal => self.write_wrapped(&mut writer, &visitor, &style, false),
+            Theme::Timestamped => write_timestamped(&mut writer, &visitor, &style),
+        }
+    }
+}
+
+/// Write `visitor` on one line, unwrapped, after a timestamp and the level. Multi-line messages
+/// have their later lines indented.
+fn write_timestamped(
+    f: &mut impl Write,
+    visitor: &EventVisitor,
+    style: &EventStyle,
+) -> fmt::Result {
+    let mut message = crate::color::for_terminal(&visitor.message).replace('\n', "\n    ");
+    for (name, value) in &visitor.fields {
+        message.push_str(&format!(" {}", style.style_field(name, value)));
+    }
+    writeln!(
+        f,
+        "{} {} {}",
+        crate::timezone::format(Utc::now(), "%Y-%m-%dT%H:%M:%S%.3f%:z")
+            .if_supports_color(Stdout, |text| text.dimmed()),
+        format!("{:5}", visitor.level).if_supports_color(Stdout, |text| style.indent.style(text)),
+        style.style_message(&message),
+    )
+}
+
+#[derive(Debug)]
+pub struct EventVisitor {
+    pub level: Level,
+    pub message: String,
+    pub fields: Vec<(String, String)>,
+}
+
+impl EventVisitor {
+    pub fn new(level: Level) -> Self {
+        Self {
+            level,
+            message: Default::default(),
+            fields: Default::default(),
+        }
+    }
+
+    /// If there's only one field, and it fits on the same line as the message, put it on the
+    /// same line. Otherwise, we use the 'long format' with each field on a separate line.
+    fn use_short_format(&self, term_width: usize) -> bool {
+        self.fields.len() == 1
+            && self.fields[0].0.len() + self.fields[0].1.len() + 2
+                < term_width.saturating_sub(self.message.len())
+    }
+}
+
+impl Visit for EventVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{value:?}");
+        } else {
+            self.fields
+                .push((field.name().to_owned(), format!("{value:?}")));
+        }
+    }
+}
+
 #[derive(Debug)]
 struct EventStyle {
     /// First-line indent text.
@@ -210,16 +266,16 @@ impl EventStyle {
                 field_value = field_value.dimmed();
             }
             Level::INFO => {
-                indent_text = "• ";
+                indent_text = crate::color::bullet();
                 indent = indent.green();
             }
             Level::WARN => {
-                indent_text = "⚠ ";
+                indent_text = crate::color::warning();
                 indent = indent.yellow();
                 text = text.yellow();
             }
             Level::ERROR => {
-                indent_text = "⚠ ";
+                indent_text = crate::color::warning();
                 indent = indent.red();
                 text = text.red();
             }
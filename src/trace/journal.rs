@@ -0,0 +1,68 @@
+//! Log formatting for the systemd journal: one line per event, prefixed with its syslog
+//! priority, with no colors or wrapping.
+//!
+//! See `sd-daemon(3)` for the `<N>` prefix format.
+
+use tap::Tap;
+use tracing::Level;
+use tracing::Subscriber;
+use tracing_subscriber::fmt::FormatEvent;
+use tracing_subscriber::fmt::FormatFields;
+use tracing_subscriber::registry::LookupSpan;
+
+use super::format::EventVisitor;
+
+#[derive(Default)]
+pub struct JournalFormatter;
+
+impl<S, N> FormatEvent<S, N> for JournalFormatter
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        _ctx: &tracing_subscriber::fmt::FmtContext<'_, S, N>,
+        mut writer: tracing_subscriber::fmt::format::Writer<'_>,
+        event: &tracing::Event<'_>,
+    ) -> std::fmt::Result {
+        let level = *event.metadata().level();
+        let visitor = EventVisitor::new(level).tap_mut(|visitor| event.record(visitor));
+        writeln!(writer, "{}", format_line(&visitor))
+    }
+}
+
+/// The syslog priority for `level`.
+fn priority(level: Level) -> u8 {
+    match level {
+        Level::ERROR => 3,
+        Level::WARN => 4,
+        Level::INFO => 6,
+        Level::DEBUG | Level::TRACE => 7,
+    }
+}
+
+fn format_line(visitor: &EventVisitor) -> String {
+    let mut line = format!("<{}>{}", priority(visitor.level), visitor.message);
+    for (name, value) in &visitor.fields {
+        line.push_str(&format!(" {name}={value}"));
+    }
+    // The journal would record each line as a separate entry.
+    line.lines().collect::<Vec<_>>().join(" | ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_line() {
+        let mut visitor = EventVisitor::new(Level::WARN);
+        visitor.message = "Unlisted apartments:\n• 731\n• 732".to_owned();
+        visitor.fields.push(("failures".to_owned(), "3".to_owned()));
+        assert_eq!(
+            format_line(&visitor),
+            "<4>Unlisted apartments: | • 731 | • 732 failures=3"
+        );
+    }
+}
@@ -1,41 +1,236 @@
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use camino::Utf8Path;
 use camino::Utf8PathBuf;
-use chrono::Utc;
 use color_eyre::eyre;
 use color_eyre::eyre::eyre;
 use color_eyre::eyre::Context;
+use serde::Deserialize;
 use tracing::metadata::LevelFilter;
 use tracing::Level;
 use tracing_subscriber::filter::FilterFn;
 use tracing_subscriber::fmt;
 use tracing_subscriber::fmt::format::JsonFields;
 use tracing_subscriber::prelude::*;
+use tracing_subscriber::reload;
 use tracing_subscriber::EnvFilter;
+
+use crate::redact::Redacting;
 use tracing_subscriber::Layer;
 
 mod format;
+mod journal;
+mod otlp;
+mod rotate;
+
+pub use format::Theme;
+pub use otlp::shutdown;
+
+/// Where and how to log, configured in the `[log]` table of the config file, like:
+///
+/// ```toml
+/// [log]
+/// directory = "/var/log/ava-apartment-finder"
+/// file = true
+/// console-format = "pretty"
+/// theme = "timestamped"
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct LogConfig {
+    /// Filter directives for console output, like `info,ava_apartment_finder=debug`.
+    ///
+    /// Overridden by `--tracing-filter`. Re-read on `reload-config` and `SIGUSR1`, so it can be
+    /// changed without restarting.
+    pub filter: Option<String>,
+    /// Where to write JSONL log files. Defaults to `~/.cache/ava-apartment-finder`.
+    pub directory: Option<Utf8PathBuf>,
+    /// Whether to write JSONL log files at all.
+    pub file: bool,
+    /// How to format console output.
+    pub console_format: ConsoleFormat,
+    /// How to lay out `pretty` console output.
+    pub theme: Theme,
+    /// Extra strings to scrub from logs and error reports, besides the tokens, passwords, and
+    /// email addresses in the config. See [`crate::redact`].
+    pub redact: Vec<String>,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            filter: None,
+            directory: None,
+            file: true,
+            console_format: ConsoleFormat::Pretty,
+            theme: Theme::default(),
+            redact: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConsoleFormat {
+    /// Colorful and wrapped to the terminal width, in the configured [`Theme`]; see [`format`].
+    Pretty,
+    /// One line per event.
+    Compact,
+    /// One JSON object per line.
+    Json,
+    /// Plain text for the systemd journal to capture from stdout; see [`journal`].
+    Journal,
+    /// Send events straight to the systemd journal, with their fields as journal fields,
+    /// instead of printing them. Falls back to `journal` if the journal isn't available.
+    ///
+    /// Unlike the other formats, `redact` isn't applied, since the journal gets fields as-is.
+    ///
+    /// Implied by `--systemd`.
+    Journald,
+}
+
+/// How much to log to files.
+pub struct LogFiles {
+    /// Start a new log file once the current one is this many bytes long.
+    pub max_bytes: u64,
+    /// Delete log files older than this at startup.
+    pub retention: Option<Duration>,
+}
+
+/// Changes the console filter directives after [`install_tracing`].
+#[derive(Clone)]
+pub struct FilterHandle(Arc<dyn Fn(EnvFilter) -> Result<(), reload::Error> + Send + Sync>);
+
+impl FilterHandle {
+    pub fn set(&self, directives: &str) -> eyre::Result<()> {
+        let filter = EnvFilter::try_new(directives)
+            .wrap_err_with(|| format!("Invalid filter directives `{directives}`"))?;
+        (self.0)(filter).wrap_err("Failed to reload filter")?;
+        tracing::info!(directives, "Changed log filter");
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for FilterHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FilterHandle").finish_non_exhaustive()
+    }
+}
 
 /// Initialize the logging framework.
 ///
-/// Returns the path logs are being written to.
-pub fn install_tracing(filter_directives: &str) -> eyre::Result<Utf8PathBuf> {
+/// If `otlp_endpoint` is set, spans are exported there too; see [`otlp`].
+///
+/// Returns the path logs are being written to, if any, and a handle for changing the filter.
+/// Later logs may be written to new files in the same directory; see
+/// [`rotate::RotatingFile`].
+pub fn install_tracing(
+    filter_directives: &str,
+    config: &LogConfig,
+    log_files: &LogFiles,
+    otlp_endpoint: Option<&str>,
+) -> eyre::Result<(Option<Utf8PathBuf>, FilterHandle)> {
     let env_filter = EnvFilter::try_new(filter_directives)
         .or_else(|_| EnvFilter::try_from_default_env())
         .or_else(|_| EnvFilter::try_new("info"))?;
+    let (env_filter, reload_handle) = reload::Layer::new(env_filter);
+    let filter_handle = FilterHandle(Arc::new(move |filter| reload_handle.reload(filter)));
+
+    // Only connect to the journal if we're going to use it.
+    let journald = match config.console_format {
+        ConsoleFormat::Journald => Some(tracing_journald::layer()),
+        _ => None,
+    };
+    let (journald, journald_err) = match journald {
+        Some(Ok(layer)) => (Some(layer), None),
+        Some(Err(err)) => (None, Some(err)),
+        None => (None, None),
+    };
+    let fmt_layer = match (journald, config.console_format) {
+        (Some(journald), _) => journald
+            .with_syslog_identifier("ava-apartment-finder".to_owned())
+            .with_filter(env_filter)
+            .boxed(),
+        (None, ConsoleFormat::Pretty) => fmt::layer()
+            .event_format(format::EventFormatter::new(config.theme))
+            .with_writer(Redacting(std::io::stdout))
+            .with_filter(env_filter)
+            .boxed(),
+        (None, ConsoleFormat::Compact) => fmt::layer()
+            .compact()
+            .with_writer(Redacting(std::io::stdout))
+            .with_filter(env_filter)
+            .boxed(),
+        (None, ConsoleFormat::Json) => fmt::layer()
+            .event_format(fmt::format::json())
+            .fmt_fields(JsonFields::new())
+            .with_writer(Redacting(std::io::stdout))
+            .with_filter(env_filter)
+            .boxed(),
+        (None, ConsoleFormat::Journal | ConsoleFormat::Journald) => fmt::layer()
+            .event_format(journal::JournalFormatter)
+            .with_writer(Redacting(std::io::stdout))
+            .with_filter(env_filter)
+            .boxed(),
+    };
 
-    let fmt_layer = fmt::layer()
-        .event_format(format::EventFormatter::default())
-        .with_filter(env_filter);
+    let (json_layer, log_path, deleted) = if config.file {
+        let log_dir = match &config.directory {
+            Some(directory) => {
+                std::fs::create_dir_all(directory)
+                    .wrap_err_with(|| format!("Failed to create log directory {directory:?}"))?;
+                directory.clone()
+            }
+            None => tracing_log_dir().wrap_err("Failed to create log directory")?,
+        };
+        let deleted = match log_files.retention {
+            Some(retention) => rotate::clean_up(&log_dir, retention),
+            None => Ok(0),
+        };
+        let (json_layer, log_path) = tracing_json_layer(&log_dir, log_files.max_bytes)?;
+        (Some(json_layer), Some(log_path), deleted)
+    } else {
+        (None, None, Ok(0))
+    };
 
-    let (json_layer, log_path) = tracing_json_layer()?;
+    let otlp_layer = otlp_endpoint
+        .map(|endpoint| {
+            otlp::layer(endpoint).map(|layer| {
+                layer.with_filter(
+                    FilterFn::new(|metadata| {
+                        metadata.level() <= &Level::DEBUG && is_our_target(metadata.target())
+                    })
+                    .with_max_level_hint(LevelFilter::DEBUG),
+                )
+            })
+        })
+        .transpose()?;
 
     let registry = tracing_subscriber::registry();
 
-    registry.with(json_layer).with(fmt_layer).init();
+    registry
+        .with(json_layer)
+        .with(fmt_layer)
+        .with(otlp_layer)
+        .with(crate::error_reporting::layer())
+        .init();
+
+    if let Some(err) = journald_err {
+        tracing::warn!("Failed to connect to the systemd journal, printing logs instead: {err}");
+    }
+
+    match deleted {
+        Ok(0) => {}
+        Ok(deleted) => tracing::debug!(deleted, "Deleted old log files"),
+        Err(err) => tracing::warn!("Failed to delete old log files: {err:?}"),
+    }
 
-    Ok(log_path)
+    Ok((log_path, filter_handle))
 }
 
-fn tracing_log_file_path() -> eyre::Result<Utf8PathBuf> {
+fn tracing_log_dir() -> eyre::Result<Utf8PathBuf> {
     let mut path = Utf8PathBuf::from_path_buf(
         dirs::cache_dir().ok_or_else(|| eyre!("Could not locate cache directory"))?,
     )
@@ -43,32 +238,29 @@ fn tracing_log_file_path() -> eyre::Result<Utf8PathBuf> {
     path.push("ava-apartment-finder");
 
     std::fs::create_dir_all(&path)?;
-
-    let format = "ava-apartment-finder-%FT%H_%M_%S%z.jsonl";
-    path.push(&Utc::now().format(&format).to_string());
     Ok(path)
 }
 
-fn tracing_json_layer<S>() -> eyre::Result<(
+fn tracing_json_layer<S>(
+    log_dir: &Utf8Path,
+    max_bytes: u64,
+) -> eyre::Result<(
     Box<dyn tracing_subscriber::Layer<S> + Send + Sync + 'static>,
     Utf8PathBuf,
 )>
 where
     S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
 {
-    let path = tracing_log_file_path().wrap_err("Failed to create log path")?;
-    let file = std::fs::File::create(&path).wrap_err_with(|| format!("Failed to open {path:?}"))?;
+    let file = rotate::RotatingFile::new(log_dir, max_bytes)?;
+    let path = file.path().to_owned();
 
     let layer = fmt::layer()
         .event_format(fmt::format::json())
         .fmt_fields(JsonFields::new())
-        .with_writer(file)
+        .with_writer(Redacting(Mutex::new(file)))
         .with_filter(
             FilterFn::new(|metadata| {
-                metadata.level() <= &Level::DEBUG && {
-                    let target = metadata.target();
-                    target.starts_with("ava_apartment_finder") || target.starts_with("jmap")
-                }
+                metadata.level() <= &Level::DEBUG && is_our_target(metadata.target())
             })
             .with_max_level_hint(LevelFilter::DEBUG),
         )
@@ -76,3 +268,8 @@ where
 
     Ok((layer, path))
 }
+
+/// Whether `target` is from this crate, rather than a dependency like `hyper`.
+fn is_our_target(target: &str) -> bool {
+    target.starts_with("ava_apartment_finder") || target.starts_with("jmap")
+}
@@ -0,0 +1,60 @@
+//! Exporting spans to an OpenTelemetry collector over OTLP, so tick latency can be broken down
+//! in something like Jaeger or Tempo.
+
+use color_eyre::eyre;
+use tracing::Subscriber;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// A layer sending spans to the OTLP/gRPC collector at `endpoint`, like
+/// `http://localhost:4317`.
+///
+/// Must be called from within the Tokio runtime, which exports spans in the background.
+#[cfg(feature = "otlp")]
+pub fn layer<S>(endpoint: &str) -> eyre::Result<Box<dyn Layer<S> + Send + Sync + 'static>>
+where
+    S: Subscriber + Send + Sync + for<'a> LookupSpan<'a>,
+{
+    use color_eyre::eyre::Context;
+    use opentelemetry::sdk::trace;
+    use opentelemetry::sdk::Resource;
+    use opentelemetry::KeyValue;
+    use opentelemetry_otlp::WithExportConfig;
+
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint),
+        )
+        .with_trace_config(trace::config().with_resource(Resource::new([KeyValue::new(
+            "service.name",
+            "ava-apartment-finder",
+        )])))
+        .install_batch(opentelemetry::runtime::Tokio)
+        .wrap_err_with(|| format!("Failed to set up OTLP exporter for {endpoint}"))?;
+
+    Ok(tracing_opentelemetry::layer().with_tracer(tracer).boxed())
+}
+
+#[cfg(not(feature = "otlp"))]
+pub fn layer<S>(_endpoint: &str) -> eyre::Result<Box<dyn Layer<S> + Send + Sync + 'static>>
+where
+    S: Subscriber + Send + Sync + for<'a> LookupSpan<'a>,
+{
+    Err(eyre::eyre!(
+        "`--otlp-endpoint` was given, but ava-apartment-finder was built without the `otlp` \
+         feature"
+    ))
+}
+
+/// Export any spans still buffered. Call before exiting.
+pub async fn shutdown() {
+    #[cfg(feature = "otlp")]
+    if let Err(err) =
+        tokio::task::spawn_blocking(opentelemetry::global::shutdown_tracer_provider).await
+    {
+        tracing::warn!("Failed to flush OTLP spans: {err}");
+    }
+}
@@ -0,0 +1,154 @@
+//! Rotating and cleaning up the JSONL log files, so the cache directory doesn't fill up with
+//! them.
+
+use std::fs::File;
+use std::io::Write;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+
+use camino::Utf8Path;
+use camino::Utf8PathBuf;
+use chrono::Utc;
+use color_eyre::eyre;
+use color_eyre::eyre::Context;
+
+const PREFIX: &str = "ava-apartment-finder-";
+const EXTENSION: &str = "jsonl";
+
+/// Start a new log file after this long, even if it isn't full.
+const MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A log file which moves on to a new file in the same directory once it's `max_bytes` long
+/// or a day old.
+pub struct RotatingFile {
+    dir: Utf8PathBuf,
+    max_bytes: u64,
+    path: Utf8PathBuf,
+    file: File,
+    written: u64,
+    opened: Instant,
+    /// Whether the last write ended a line, so we can rotate without splitting one.
+    at_line_start: bool,
+}
+
+impl RotatingFile {
+    /// Open a new log file in `dir`.
+    pub fn new(dir: &Utf8Path, max_bytes: u64) -> eyre::Result<Self> {
+        let (path, file) = create(dir)?;
+        Ok(Self {
+            dir: dir.to_owned(),
+            max_bytes,
+            path,
+            file,
+            written: 0,
+            opened: Instant::now(),
+            at_line_start: true,
+        })
+    }
+
+    /// The file currently being written to.
+    pub fn path(&self) -> &Utf8Path {
+        &self.path
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        let (path, file) =
+            create(&self.dir).map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+        self.file.flush()?;
+        self.path = path;
+        self.file = file;
+        self.written = 0;
+        self.opened = Instant::now();
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.at_line_start
+            && self.written > 0
+            && (self.written >= self.max_bytes || self.opened.elapsed() >= MAX_AGE)
+        {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        if written > 0 {
+            self.at_line_start = buf[written - 1] == b'\n';
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Create a new log file in `dir`, named for the current time.
+fn create(dir: &Utf8Path) -> eyre::Result<(Utf8PathBuf, File)> {
+    let stem = format!("{PREFIX}{}", Utc::now().format("%FT%H_%M_%S%z"));
+    let mut path = dir.join(format!("{stem}.{EXTENSION}"));
+    // If we rotate twice in a second.
+    let mut suffix = 1;
+    while path.exists() {
+        path = dir.join(format!("{stem}-{suffix}.{EXTENSION}"));
+        suffix += 1;
+    }
+    let file = File::create(&path).wrap_err_with(|| format!("Failed to open {path:?}"))?;
+    Ok((path, file))
+}
+
+/// Delete log files in `dir` last written to more than `retention` ago.
+///
+/// Returns how many were deleted.
+pub fn clean_up(dir: &Utf8Path, retention: Duration) -> eyre::Result<usize> {
+    let now = SystemTime::now();
+    let mut deleted = 0;
+    for entry in dir
+        .read_dir_utf8()
+        .wrap_err_with(|| format!("Failed to list {dir:?}"))?
+    {
+        let entry = entry?;
+        let name = entry.file_name();
+        if !name.starts_with(PREFIX) || entry.path().extension() != Some(EXTENSION) {
+            continue;
+        }
+        let modified = entry.metadata()?.modified()?;
+        if now.duration_since(modified).unwrap_or_default() > retention {
+            std::fs::remove_file(entry.path())
+                .wrap_err_with(|| format!("Failed to delete {:?}", entry.path()))?;
+            deleted += 1;
+        }
+    }
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_files(dir: &Utf8Path) -> usize {
+        dir.read_dir_utf8().unwrap().count()
+    }
+
+    #[test]
+    fn test_rotating_file() {
+        let dir = std::env::temp_dir().join(format!("ava-logs-{}", std::process::id()));
+        let dir = Utf8PathBuf::from_path_buf(dir).unwrap();
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut file = RotatingFile::new(&dir, 10).unwrap();
+        let first = file.path().to_owned();
+        file.write_all(b"{\"partial").unwrap();
+        file.write_all(b"\":\"line\"}\n").unwrap();
+        assert_eq!(file.path(), first, "lines shouldn't be split across files");
+        file.write_all(b"{}\n").unwrap();
+        assert_ne!(file.path(), first);
+        assert_eq!(log_files(&dir), 2);
+
+        assert_eq!(clean_up(&dir, Duration::from_secs(60)).unwrap(), 0);
+        assert_eq!(clean_up(&dir, Duration::ZERO).unwrap(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,304 @@
+//! An interactive terminal UI for browsing tracked listings, run against the DB directly.
+
+use std::io::Stdout;
+
+use chrono::DateTime;
+use chrono::NaiveDate;
+use chrono::Utc;
+use color_eyre::eyre;
+use crossterm::event::Event as TermEvent;
+use crossterm::event::KeyCode;
+use crossterm::event::KeyEventKind;
+use crossterm::execute;
+use crossterm::terminal::disable_raw_mode;
+use crossterm::terminal::enable_raw_mode;
+use crossterm::terminal::EnterAlternateScreen;
+use crossterm::terminal::LeaveAlternateScreen;
+use ratatui::backend::Backend;
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::Constraint;
+use ratatui::layout::Direction;
+use ratatui::layout::Layout;
+use ratatui::style::Modifier;
+use ratatui::style::Style;
+use ratatui::widgets::Block;
+use ratatui::widgets::Borders;
+use ratatui::widgets::Cell;
+use ratatui::widgets::Paragraph;
+use ratatui::widgets::Row as TableRow;
+use ratatui::widgets::Table;
+use ratatui::widgets::TableState;
+use ratatui::widgets::Wrap;
+use ratatui::Frame;
+use ratatui::Terminal;
+
+use crate::api::Apartment;
+use crate::filter::Value;
+use crate::listing::Listing;
+use crate::money::Money;
+use crate::timezone;
+use crate::App;
+
+const HELP: &str = "j/k: move  p/s/a/d: sort by price/sqft/available/days listed  \
+                    w: watch  i: ignore  q: quit";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SortBy {
+    Price,
+    SquareFeet,
+    Available,
+    DaysListed,
+}
+
+/// A listing, flattened for display.
+struct Row {
+    id: String,
+    name: String,
+    rent: Option<Money>,
+    square_feet: Option<f64>,
+    available: Option<NaiveDate>,
+    listed: DateTime<Utc>,
+    summary: String,
+}
+
+impl Row {
+    fn new<T: Listing>(apt: &Apartment<T>) -> Self {
+        let name = match apt
+            .inner
+            .field("number")
+            .or_else(|| apt.inner.field("title"))
+        {
+            Some(Value::String(name)) => name,
+            _ => apt.id().to_owned(),
+        };
+        let square_feet = match apt.inner.field("sqft") {
+            Some(Value::Number(square_feet)) => Some(square_feet),
+            _ => None,
+        };
+        Self {
+            id: apt.id().to_owned(),
+            name,
+            rent: apt.inner.rent(),
+            square_feet,
+            available: apt.inner.available_date(),
+            listed: apt.listed,
+            summary: apt.to_string(),
+        }
+    }
+}
+
+struct Tui<'a> {
+    app: &'a mut App,
+    rows: Vec<Row>,
+    table: TableState,
+    sort_by: SortBy,
+    reverse: bool,
+}
+
+/// Browse the listings in `app` until the user quits, saving any watch/ignore changes.
+pub fn run(app: &mut App) -> eyre::Result<()> {
+    let mut rows: Vec<Row> = app
+        .store
+        .known_apartments
+        .values()
+        .map(Row::new)
+        .chain(app.store.known_posts.values().map(Row::new))
+        .collect();
+    rows.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut tui = Tui {
+        app,
+        rows,
+        table: TableState::default(),
+        sort_by: SortBy::Price,
+        reverse: false,
+    };
+    tui.sort();
+    if !tui.rows.is_empty() {
+        tui.table.select(Some(0));
+    }
+
+    enable_raw_mode()?;
+    let mut stdout = std::io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+
+    let result = tui.event_loop(&mut terminal);
+
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    result?;
+
+    tui.app.save()
+}
+
+impl Tui<'_> {
+    fn event_loop(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    ) -> eyre::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            let key = match crossterm::event::read()? {
+                TermEvent::Key(key) if key.kind == KeyEventKind::Press => key,
+                _ => continue,
+            };
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Char('j') | KeyCode::Down => self.select(1),
+                KeyCode::Char('k') | KeyCode::Up => self.select(-1),
+                KeyCode::Char('p') => self.sort_by(SortBy::Price),
+                KeyCode::Char('s') => self.sort_by(SortBy::SquareFeet),
+                KeyCode::Char('a') => self.sort_by(SortBy::Available),
+                KeyCode::Char('d') => self.sort_by(SortBy::DaysListed),
+                KeyCode::Char('w') => self.toggle(|app| &mut app.store_mut().watched),
+                KeyCode::Char('i') => self.toggle(|app| &mut app.store_mut().ignored),
+                _ => {}
+            }
+        }
+    }
+
+    fn selected(&self) -> Option<&Row> {
+        self.rows.get(self.table.selected()?)
+    }
+
+    fn select(&mut self, offset: isize) {
+        if self.rows.is_empty() {
+            return;
+        }
+        let selected = self.table.selected().unwrap_or(0) as isize + offset;
+        self.table.select(Some(
+            selected.clamp(0, self.rows.len() as isize - 1) as usize
+        ));
+    }
+
+    /// Sort by `sort_by`, or reverse the order if we're already sorted by it.
+    fn sort_by(&mut self, sort_by: SortBy) {
+        if self.sort_by == sort_by {
+            self.reverse = !self.reverse;
+        } else {
+            self.sort_by = sort_by;
+            self.reverse = false;
+        }
+        self.sort();
+    }
+
+    fn sort(&mut self) {
+        let selected = self.selected().map(|row| row.id.clone());
+        // Missing values sort last.
+        let key = |row: &Row| match self.sort_by {
+            SortBy::Price => row.rent.map_or(f64::INFINITY, Money::dollars),
+            SortBy::SquareFeet => row.square_feet.unwrap_or(f64::INFINITY),
+            SortBy::Available => row.available.map_or(f64::INFINITY, |date| {
+                date.and_hms_opt(0, 0, 0).unwrap().timestamp() as f64
+            }),
+            // Longest-listed first.
+            SortBy::DaysListed => row.listed.timestamp() as f64,
+        };
+        self.rows.sort_by(|a, b| key(a).total_cmp(&key(b)));
+        if self.reverse {
+            self.rows.reverse();
+        }
+        if let Some(selected) = selected {
+            self.table
+                .select(self.rows.iter().position(|row| row.id == selected));
+        }
+    }
+
+    /// Add the selected unit to a set of IDs like `watched`, or remove it if it's already there.
+    fn toggle(&mut self, set: impl Fn(&mut App) -> &mut std::collections::BTreeSet<String>) {
+        let id = match self.selected() {
+            Some(row) => row.id.clone(),
+            None => return,
+        };
+        let set = set(self.app);
+        if !set.remove(&id) {
+            set.insert(id);
+        }
+    }
+
+    fn draw<B: Backend>(&mut self, frame: &mut Frame<'_, B>) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Percentage(60),
+                Constraint::Percentage(40),
+                Constraint::Length(1),
+            ])
+            .split(frame.size());
+
+        let now = Utc::now();
+        let header = TableRow::new(["", "Unit", "Rent", "Sq/ft", "Available", "Days listed"])
+            .style(Style::default().add_modifier(Modifier::BOLD));
+        let rows = self.rows.iter().map(|row| {
+            let flags = match (
+                self.app.store.watched.contains(&row.id),
+                self.app.store.ignored.contains(&row.id),
+            ) {
+                (true, _) => "W",
+                (_, true) => "I",
+                _ => "",
+            };
+            TableRow::new(vec![
+                Cell::from(flags),
+                Cell::from(row.name.clone()),
+                Cell::from(row.rent.map(|rent| rent.to_string()).unwrap_or_default()),
+                Cell::from(
+                    row.square_feet
+                        .map(|square_feet| format!("{square_feet:.0}"))
+                        .unwrap_or_default(),
+                ),
+                Cell::from(
+                    row.available
+                        .map(|date| date.format("%b %e %Y").to_string())
+                        .unwrap_or_default(),
+                ),
+                Cell::from((now - row.listed).num_days().to_string()),
+            ])
+        });
+        let widths = [
+            Constraint::Length(1),
+            Constraint::Percentage(30),
+            Constraint::Length(8),
+            Constraint::Length(6),
+            Constraint::Length(12),
+            Constraint::Length(11),
+        ];
+        let table = Table::new(rows)
+            .header(header)
+            .block(Block::default().borders(Borders::ALL).title("Listings"))
+            .widths(&widths)
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, chunks[0], &mut self.table);
+
+        let detail = match self.selected() {
+            Some(row) => {
+                let history = self
+                    .app
+                    .store
+                    .events
+                    .iter()
+                    .filter(|event| event.id == row.id)
+                    .map(|event| {
+                        format!(
+                            "{} {:?}: {}",
+                            timezone::format(event.time, "%b %e %Y %H:%M"),
+                            event.kind,
+                            event.summary
+                        )
+                    });
+                itertools::join(std::iter::once(row.summary.clone()).chain(history), "\n")
+            }
+            None => "No listings".to_owned(),
+        };
+        frame.render_widget(
+            Paragraph::new(detail)
+                .wrap(Wrap { trim: false })
+                .block(Block::default().borders(Borders::ALL).title("History")),
+            chunks[1],
+        );
+        frame.render_widget(Paragraph::new(HELP), chunks[2]);
+    }
+}